@@ -0,0 +1,88 @@
+//! Measures merging multi-MB transcripts, run with `cargo bench`.
+//!
+//! Reports the throughput and how many writes reached the underlying
+//! writer, which is what hurts on network drives.
+//!
+//! With `F4TAPIR_BENCH_MIN_MBPS` set, fails if merging into memory is
+//! slower than that many MB per second, as a gate for regressions.
+use std::convert::TryFrom;
+use std::io::{Result, Write};
+use std::time::Instant;
+
+use f4tapir::transcript::{write_merged_transcript, MergeOptions, Transcript};
+
+const SEGMENTS: usize = 4;
+/// Times the content of the reference transcript is repeated per segment.
+const REPETITIONS: usize = 2000;
+
+/// Discards everything, but counts the calls to `write`.
+#[derive(Default)]
+struct CountingSink {
+    writes: usize,
+    bytes: usize,
+}
+
+impl Write for CountingSink {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.writes += 1;
+        self.bytes += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+fn big_transcript() -> Transcript {
+    let reference = std::fs::read_to_string("testdata/interview-01.rtf").unwrap();
+    let content_start = reference.find("\\jexpand\r\n").unwrap() + "\\jexpand\r\n".len();
+    let content_end = reference.len() - "\r\n}".len();
+    let (preamble, rest) = reference.split_at(content_start);
+    let content = &rest[..content_end - content_start];
+
+    let mut big = String::from(preamble);
+    for _ in 0..REPETITIONS {
+        big.push_str(content);
+        big.push_str("\r\n");
+    }
+    big.truncate(big.len() - 2);
+    big.push_str("\r\n}");
+    Transcript::try_from(big).unwrap()
+}
+
+fn main() {
+    let segment = big_transcript();
+    let segments = vec![segment; SEGMENTS];
+    let options = MergeOptions::default();
+
+    let mut sink = CountingSink::default();
+    let start = Instant::now();
+    write_merged_transcript(&mut sink, segments.clone(), &options).unwrap();
+    let elapsed = start.elapsed();
+    println!(
+        "memory: {:.1} MB in {:?} with {} writes",
+        sink.bytes as f64 / 1_000_000.0,
+        elapsed,
+        sink.writes
+    );
+    let throughput = sink.bytes as f64 / 1_000_000.0 / elapsed.as_secs_f64();
+    if let Ok(min) = std::env::var("F4TAPIR_BENCH_MIN_MBPS") {
+        let min: f64 = min.parse().expect("F4TAPIR_BENCH_MIN_MBPS is not a number");
+        if throughput < min {
+            eprintln!(
+                "regression: merged {:.1} MB/s, expected at least {:.1} MB/s",
+                throughput, min
+            );
+            std::process::exit(1);
+        }
+    }
+
+    let path = std::env::temp_dir().join("f4tapir-bench-merge.rtf");
+    let mut file = std::fs::File::create(&path).unwrap();
+    let start = Instant::now();
+    write_merged_transcript(&mut file, segments, &options).unwrap();
+    file.sync_all().unwrap();
+    println!("file:   {:?} including fsync", start.elapsed());
+    std::fs::remove_file(path).unwrap();
+}
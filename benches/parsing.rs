@@ -0,0 +1,86 @@
+//! Measures the stages of merging on large generated transcripts, run with
+//! `cargo bench --bench parsing`.
+//!
+//! To check a change for regressions, save a baseline before it with
+//! `cargo bench --bench parsing -- --save-baseline before` and compare
+//! after it with `cargo bench --bench parsing -- --baseline before`.
+use std::convert::TryFrom;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use f4tapir::generate::Fixture;
+use f4tapir::timestamp::Timestamp;
+use f4tapir::transcript::{write_merged_transcript, MergeOptions, Rtf, Transcript, Utterance};
+
+const SEGMENTS: usize = 4;
+/// Start of the paragraphs in generated transcripts.
+const LINE_PREAMBLE: &str = "{\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 ";
+const LINE_EPILOGUE: &str = "\\par}";
+
+/// Segments of three hours, about 1 MB each.
+fn fixture() -> Fixture {
+    Fixture {
+        speakers: 3,
+        duration: Timestamp::from_tenths(3 * 60 * 60 * 10).unwrap(),
+        timestamp_every: 5,
+        quirks: vec![],
+        seed: 1,
+    }
+}
+
+fn parsing(c: &mut Criterion) {
+    let fixture = fixture();
+    let rtf = fixture.segment(0);
+    let paragraphs: Vec<&str> = rtf
+        .lines()
+        .filter_map(|line| {
+            line.strip_prefix(LINE_PREAMBLE)?
+                .strip_suffix(LINE_EPILOGUE)
+        })
+        .filter(|paragraph| !paragraph.is_empty())
+        .collect();
+
+    let mut group = c.benchmark_group("parsing");
+    group.throughput(Throughput::Bytes(rtf.len() as u64));
+    group.sample_size(20);
+    group.bench_function("rtf tokens", |b| {
+        b.iter(|| Rtf::from(black_box(rtf.as_str())).count())
+    });
+    group.bench_function("utterances", |b| {
+        b.iter(|| {
+            paragraphs
+                .iter()
+                .filter(|paragraph| Utterance::try_from(black_box(**paragraph)).is_ok())
+                .count()
+        })
+    });
+    group.bench_function("timestamps", |b| {
+        b.iter(|| Timestamp::extract_timestamps(black_box(rtf.as_bytes())).len())
+    });
+    group.bench_function("transcript", |b| {
+        b.iter(|| Transcript::try_from(black_box(rtf.clone())).unwrap())
+    });
+    group.finish();
+}
+
+fn merging(c: &mut Criterion) {
+    let fixture = fixture();
+    let segments: Vec<Transcript> = (0..SEGMENTS)
+        .map(|index| Transcript::try_from(fixture.segment(index)).unwrap())
+        .collect();
+    let bytes: usize = segments.iter().map(|segment| segment.content().len()).sum();
+    let options = MergeOptions::default();
+
+    let mut group = c.benchmark_group("merging");
+    group.throughput(Throughput::Bytes(bytes as u64));
+    group.sample_size(10);
+    group.bench_function("write_merged_transcript", |b| {
+        b.iter(|| {
+            write_merged_transcript(std::io::sink(), black_box(segments.clone()), &options).unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parsing, merging);
+criterion_main!(benches);
@@ -0,0 +1,46 @@
+//! Compares finding timestamps by trying every offset, like earlier
+//! versions did, with jumping between `#` delimiters, run with
+//! `cargo bench --bench timestamps`.
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use f4tapir::timestamp::Timestamp;
+
+/// Times the reference transcript is repeated, about 4 MB.
+const REPETITIONS: usize = 400;
+
+fn big_transcript() -> Vec<u8> {
+    let reference = std::fs::read("testdata/interview-01.rtf").unwrap();
+    reference.repeat(REPETITIONS)
+}
+
+/// Timestamps found by parsing a window at every single offset.
+fn windowed(buf: &[u8]) -> impl DoubleEndedIterator<Item = Timestamp> + '_ {
+    (0..buf.len()).filter_map(move |offset| {
+        let window = &buf[offset..buf.len().min(offset + "#0000:00:00-0#".len())];
+        Timestamp::parse(window).ok()
+    })
+}
+
+fn scanning(c: &mut Criterion) {
+    let transcript = big_transcript();
+    let mut group = c.benchmark_group("timestamps");
+    group.throughput(Throughput::Bytes(transcript.len() as u64));
+    group.sample_size(20);
+
+    group.bench_function("extract/windowed", |b| {
+        b.iter(|| windowed(black_box(&transcript)).count())
+    });
+    group.bench_function("extract/memchr", |b| {
+        b.iter(|| Timestamp::extract_timestamps(black_box(&transcript)).len())
+    });
+    group.bench_function("last/windowed", |b| {
+        b.iter(|| windowed(black_box(&transcript)).next_back())
+    });
+    group.bench_function("last/memchr", |b| {
+        b.iter(|| Timestamp::last_timestamp(black_box(&transcript)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, scanning);
+criterion_main!(benches);
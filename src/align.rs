@@ -0,0 +1,279 @@
+//! Corrects transcript timestamps that lie beyond the end of the audio
+//! segment they were written against, which usually means a transcriber
+//! mistyped the hour.
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::args::Align;
+use crate::atomic::AtomicFile;
+use crate::backup::keep_original;
+use crate::exit::{ExitCode, Failure};
+use crate::find::{collect_interviews, collect_transcripts};
+use crate::pair::{self, pair_by_stem, probe_duration};
+use crate::timestamp::{Timestamp, TimestampSyntax};
+
+use log::warn;
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// An hour in tenths of a second.
+const HOUR: u64 = 60 * 60 * 10;
+
+pub fn align(opts: Align) -> Result<()> {
+    match (&opts.output_dir, opts.in_place) {
+        (Some(_), true) | (None, false) => return Err(Error::OutputOptions),
+        (Some(dir), false) if !dir.is_dir() => return Err(Error::NoOutputDir(dir.clone())),
+        _ => (),
+    }
+    if opts.orig && !opts.in_place {
+        return Err(Error::OrigWithoutInPlace);
+    }
+    let audio = collect_interviews(opts.input_segments.clone(), opts.recursive)?;
+    let transcripts = collect_transcripts(opts.input_segments, opts.recursive)?;
+    if audio.is_empty() || transcripts.is_empty() {
+        return Err(Error::NoSegments);
+    }
+
+    let mut stdout = std::io::stdout().lock();
+    for (stem, pair) in pair_by_stem(audio, transcripts) {
+        let (audio, transcript) = match pair {
+            (Some(audio), Some(transcript)) => (audio, transcript),
+            (_, Some(transcript)) => {
+                warn!("no audio segment for transcript {}", transcript.display());
+                continue;
+            }
+            _ => continue,
+        };
+        let duration = probe_duration(&audio)?;
+        let content = fs::read(&transcript)?;
+        let timestamps = Timestamp::extract_timestamps_with(&content, TimestampSyntax::Strict);
+        let corrections = corrections(&timestamps, duration, opts.rescale);
+        if corrections.is_empty() {
+            writeln!(&mut stdout, "{}  fits {}", stem, duration)?;
+            continue;
+        }
+
+        let output = match &opts.output_dir {
+            Some(dir) => {
+                let output = dir.join(transcript.file_name().unwrap_or_default());
+                if output.exists() && !opts.force {
+                    return Err(Error::OutputExists(output));
+                }
+                output
+            }
+            None => {
+                if opts.orig {
+                    keep_original(&transcript)?;
+                }
+                transcript.clone()
+            }
+        };
+        write_corrected(&output, &content, &corrections)?;
+        writeln!(
+            &mut stdout,
+            "{}  {} timestamps after the audio ends at {}, corrected in {}",
+            stem,
+            corrections.len(),
+            duration,
+            output.display()
+        )?;
+        for correction in &corrections {
+            writeln!(&mut stdout, "    {}", correction)?;
+        }
+    }
+    Ok(())
+}
+
+/// A timestamp at an offset in the transcript that gets replaced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Correction {
+    pub offset: usize,
+    pub from: Timestamp,
+    pub to: Timestamp,
+    pub reason: Reason,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    /// Fits the audio with the hours left out.
+    HourTypo,
+    /// Set to the end of the audio.
+    Clamped,
+    /// Scaled with all others so that the last one is the end of the audio.
+    Rescaled,
+}
+
+impl Display for Correction {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let reason = match self.reason {
+            Reason::HourTypo => "mistyped hour",
+            Reason::Clamped => "clamped to the end of the audio",
+            Reason::Rescaled => "rescaled",
+        };
+        write!(f, "{} -> {} ({})", self.from, self.to, reason)
+    }
+}
+
+/// Corrects the timestamps that are later than the audio duration.
+///
+/// Timestamps that fit without their hours are assumed to be mistyped.
+/// The others are set to the end of the audio, or with `rescale`, all
+/// timestamps are scaled so that the latest one ends with the audio.
+pub fn corrections(
+    timestamps: &[(usize, Timestamp)],
+    duration: Timestamp,
+    rescale: bool,
+) -> Vec<Correction> {
+    let duration_tenths = duration.tenths();
+    let fixed: Vec<(Timestamp, Option<Reason>)> = timestamps
+        .iter()
+        .map(|&(_, timestamp)| {
+            let tenths = timestamp.tenths();
+            if tenths <= duration_tenths {
+                (timestamp, None)
+            } else if tenths % HOUR <= duration_tenths {
+                (from_tenths(tenths % HOUR), Some(Reason::HourTypo))
+            } else if rescale {
+                (timestamp, Some(Reason::Rescaled))
+            } else {
+                (duration, Some(Reason::Clamped))
+            }
+        })
+        .collect();
+
+    let latest = fixed
+        .iter()
+        .map(|(timestamp, _)| timestamp.tenths())
+        .max()
+        .unwrap_or_default();
+    let scale = rescale && latest > duration_tenths;
+
+    timestamps
+        .iter()
+        .zip(fixed)
+        .filter_map(|(&(offset, from), (to, reason))| {
+            let (to, reason) = if scale {
+                let scaled = from_tenths(to.tenths() * duration_tenths / latest);
+                (scaled, Some(Reason::Rescaled))
+            } else {
+                (to, reason)
+            };
+            reason.filter(|_| to != from).map(|reason| Correction {
+                offset,
+                from,
+                to,
+                reason,
+            })
+        })
+        .collect()
+}
+
+/// Timestamp for tenths that are known to fit.
+fn from_tenths(tenths: u64) -> Timestamp {
+    Timestamp::from_tenths(tenths).expect("smaller than an existing timestamp")
+}
+
+/// The content with the corrections applied, which must be in order.
+pub fn corrected(content: &[u8], corrections: &[Correction]) -> Vec<u8> {
+    let mut corrected = Vec::with_capacity(content.len());
+    let mut rest = 0;
+    for correction in corrections {
+        corrected.extend_from_slice(&content[rest..correction.offset]);
+        corrected.extend_from_slice(correction.to.to_string().as_bytes());
+        rest = correction.offset + correction.from.len();
+    }
+    corrected.extend_from_slice(&content[rest..]);
+    corrected
+}
+
+/// Writes the corrected content, replacing the file at the output path
+/// atomically if there is one.
+fn write_corrected(output: &Path, content: &[u8], corrections: &[Correction]) -> Result<()> {
+    let mut file = AtomicFile::create(output)?;
+    file.write_all(&corrected(content, corrections))?;
+    file.commit()?;
+    Ok(())
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("no pairs of audio segments and transcripts found")]
+    NoSegments,
+    #[error("output directory {0} does not exist")]
+    NoOutputDir(PathBuf),
+    #[error("{0} exists, use --force to overwrite")]
+    OutputExists(PathBuf),
+    #[error(transparent)]
+    Pair(#[from] pair::Error),
+    #[error("give either --output-dir or --in-place")]
+    OutputOptions,
+    #[error("--orig only keeps originals with --in-place")]
+    OrigWithoutInPlace,
+}
+
+impl Failure for Error {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Error::IO(_) => ExitCode::IO,
+            Error::NoSegments => ExitCode::NoInputs,
+            Error::NoOutputDir(_) => ExitCode::IO,
+            Error::OutputExists(_) => ExitCode::OutputExists,
+            Error::Pair(err) => err.exit_code(),
+            Error::OutputOptions | Error::OrigWithoutInPlace => ExitCode::Failure,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ts(timestamp: &str) -> Timestamp {
+        Timestamp::parse(timestamp).unwrap()
+    }
+
+    const CONTENT: &str = "I: Ja. #00:01:00-0# B: So. #01:04:10-0# I: Gut. #00:05:30-0#";
+
+    #[test]
+    fn mistyped_hours_are_dropped_and_the_rest_clamped() {
+        let timestamps = Timestamp::extract_timestamps(CONTENT.as_bytes());
+        let corrections = corrections(&timestamps, ts("#00:05:00-0#"), false);
+        assert_eq!(
+            corrections
+                .iter()
+                .map(|c| (c.to, c.reason))
+                .collect::<Vec<_>>(),
+            vec![
+                (ts("#00:04:10-0#"), Reason::HourTypo),
+                (ts("#00:05:00-0#"), Reason::Clamped),
+            ]
+        );
+        assert_eq!(
+            String::from_utf8(corrected(CONTENT.as_bytes(), &corrections)).unwrap(),
+            "I: Ja. #00:01:00-0# B: So. #00:04:10-0# I: Gut. #00:05:00-0#"
+        );
+    }
+
+    #[test]
+    fn timestamps_are_rescaled_to_the_audio() {
+        let content = "I: Ja. #00:01:00-0# B: So. #00:04:00-0# I: Gut. #00:06:00-0#";
+        let timestamps = Timestamp::extract_timestamps(content.as_bytes());
+        let corrections = corrections(&timestamps, ts("#00:03:00-0#"), true);
+        assert_eq!(
+            corrections.iter().map(|c| c.to).collect::<Vec<_>>(),
+            vec![ts("#00:00:30-0#"), ts("#00:02:00-0#"), ts("#00:03:00-0#")]
+        );
+        assert!(corrections.iter().all(|c| c.reason == Reason::Rescaled));
+    }
+
+    #[test]
+    fn fitting_timestamps_are_left_alone() {
+        let timestamps = Timestamp::extract_timestamps(CONTENT.as_bytes());
+        assert!(corrections(&timestamps, ts("#01:10:00-0#"), true).is_empty());
+    }
+}
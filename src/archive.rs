@@ -0,0 +1,192 @@
+//! Transcripts in ZIP archives, as transcription agencies deliver them,
+//! read without unpacking the archive.
+//!
+//! A transcript in an archive has the path of the archive joined with its
+//! name in the archive, e.g. `delivery.zip/interview/interview-000.rtf`,
+//! and is read from the archive when loaded. Archives are only read with
+//! the default `archive` feature, without it they are skipped with a
+//! warning.
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::logging::FIND;
+
+#[cfg(not(feature = "archive"))]
+use log::warn;
+
+/// Checks if the file looks like a ZIP archive by its extension.
+pub fn is_archive(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+}
+
+/// Reads the file, or the transcript in an archive if the path leads
+/// through one.
+pub fn read_to_string(path: &Path) -> io::Result<String> {
+    match split_archive_path(path) {
+        Some((archive, entry)) => read_entry(archive, &entry),
+        None => fs::read_to_string(path),
+    }
+}
+
+/// The archive that the path leads through and the name of the entry in
+/// it, if the path does not exist as it is.
+fn split_archive_path(path: &Path) -> Option<(&Path, String)> {
+    if path.exists() {
+        return None;
+    }
+    let archive = path
+        .ancestors()
+        .skip(1)
+        .find(|ancestor| is_archive(ancestor) && ancestor.is_file())?;
+    let entry = path.strip_prefix(archive).ok()?;
+    let entry: Vec<String> = entry
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    Some((archive, entry.join("/")))
+}
+
+/// Paths of the transcripts in the archive, RTF files with timestamps in
+/// their first 4KiB like [`crate::detect::is_transcript`] expects.
+#[cfg(feature = "archive")]
+pub fn transcripts_in(archive: &Path) -> io::Result<Vec<PathBuf>> {
+    use crate::timestamp::Timestamp;
+    use log::debug;
+    use std::io::Read;
+
+    let mut zip = open(archive)?;
+    let mut found = vec![];
+    for idx in 0..zip.len() {
+        let mut entry = zip.by_index(idx).map_err(zip_error)?;
+        let name = match entry.enclosed_name() {
+            Some(name) => name,
+            None => continue,
+        };
+        let is_rtf = name.extension().is_some_and(|ext| ext == "rtf");
+        // resource forks of archives packed on macOS
+        let is_resource_fork = name.starts_with("__MACOSX");
+        if !entry.is_file() || !is_rtf || is_resource_fork {
+            continue;
+        }
+        let mut head = vec![];
+        entry.by_ref().take(4096).read_to_end(&mut head)?;
+        if Timestamp::looks_timestamped(&head) {
+            debug!(target: FIND, "found {} in {}", name.display(), archive.display());
+            found.push(archive.join(name));
+        }
+    }
+    Ok(found)
+}
+
+#[cfg(not(feature = "archive"))]
+pub fn transcripts_in(archive: &Path) -> io::Result<Vec<PathBuf>> {
+    warn!(
+        target: FIND,
+        "skipping {}, f4tapir was built without the archive feature to read ZIP archives",
+        archive.display()
+    );
+    Ok(vec![])
+}
+
+#[cfg(feature = "archive")]
+fn read_entry(archive: &Path, entry: &str) -> io::Result<String> {
+    use std::io::Read;
+
+    let mut zip = open(archive)?;
+    let mut entry = zip.by_name(entry).map_err(zip_error)?;
+    let mut content = String::new();
+    entry.read_to_string(&mut content)?;
+    Ok(content)
+}
+
+#[cfg(not(feature = "archive"))]
+fn read_entry(archive: &Path, entry: &str) -> io::Result<String> {
+    warn!(
+        target: FIND,
+        "cannot read {} in {}, f4tapir was built without the archive feature",
+        entry,
+        archive.display()
+    );
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "reading ZIP archives needs the archive feature",
+    ))
+}
+
+#[cfg(feature = "archive")]
+fn open(archive: &Path) -> io::Result<zip::ZipArchive<fs::File>> {
+    zip::ZipArchive::new(fs::File::open(archive)?).map_err(zip_error)
+}
+
+#[cfg(feature = "archive")]
+fn zip_error(err: zip::result::ZipError) -> io::Error {
+    match err {
+        zip::result::ZipError::Io(err) => err,
+        zip::result::ZipError::FileNotFound => {
+            io::Error::new(io::ErrorKind::NotFound, "not in the archive")
+        }
+        err => io::Error::new(io::ErrorKind::InvalidData, err),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn paths_through_archives_are_split() {
+        let dir = std::env::temp_dir().join("f4tapir-archive");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let archive = dir.join("delivery.ZIP");
+        fs::write(&archive, b"not really").unwrap();
+
+        assert!(is_archive(&archive));
+        assert_eq!(
+            split_archive_path(&archive.join("a").join("interview-000.rtf")),
+            Some((archive.as_path(), String::from("a/interview-000.rtf")))
+        );
+        assert_eq!(split_archive_path(&archive), None);
+        assert_eq!(split_archive_path(&dir.join("missing.rtf")), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "archive")]
+    #[test]
+    fn transcripts_are_read_from_archives() {
+        use std::io::Write;
+        use zip::write::SimpleFileOptions;
+
+        let dir = std::env::temp_dir().join("f4tapir-zip");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let archive = dir.join("delivery.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&archive).unwrap());
+        for (name, source) in [
+            ("segments/interview-001.rtf", "testdata/interview-01.rtf"),
+            ("segments/interview-002.rtf", "testdata/interview-02.rtf"),
+        ] {
+            zip.start_file(name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(&fs::read(source).unwrap()).unwrap();
+        }
+        zip.start_file("notes.txt", SimpleFileOptions::default())
+            .unwrap();
+        zip.finish().unwrap();
+
+        let found = transcripts_in(&archive).unwrap();
+        assert_eq!(
+            found,
+            vec![
+                archive.join("segments/interview-001.rtf"),
+                archive.join("segments/interview-002.rtf")
+            ]
+        );
+        assert_eq!(
+            read_to_string(&found[1]).unwrap(),
+            fs::read_to_string("testdata/interview-02.rtf").unwrap()
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
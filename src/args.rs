@@ -1,18 +1,72 @@
+use crate::convert::Format;
+use crate::cues::TranscriptCuts;
+use crate::diagnostic::Color;
+use crate::generate::Quirk;
+use crate::inject::Every;
+use crate::timestamp::{parse_dialect, TimestampDialect};
+use crate::transcript::{
+    Language, OutputEncoding, SpeakerInference, SpeakerRename, StripAnnotations,
+};
 use argh::FromArgs;
 use std::path::PathBuf;
 
 /// Slice interviews and merge sliced F4 transcripts into a complete one.
 #[derive(FromArgs)]
+#[argh(
+    error_code(1, "failure without a more specific code, e.g. invalid arguments"),
+    error_code(2, "no interviews, segments or transcripts found"),
+    error_code(3, "ffmpeg could not be started, probably not installed"),
+    error_code(4, "ffmpeg reported an error"),
+    error_code(5, "an output file exists, use --force to overwrite"),
+    error_code(6, "malformed transcript"),
+    error_code(7, "I/O error")
+)]
 pub struct TopLevel {
+    /// log more, -v for progress, -vv for decisions like stitching and
+    /// -vvv for everything
+    #[argh(switch, short = 'v')]
+    pub verbose: u8,
+
+    /// log less, -q for only errors and -qq for nothing
+    #[argh(switch, short = 'q')]
+    pub quiet: u8,
+
+    /// only log messages of this target, one of find, detect, merge, split
+    /// or serve, can be repeated
+    #[argh(option)]
+    pub log_target: Vec<String>,
+
+    /// color errors and log messages, auto (the default), always or never
+    #[argh(option, default = "Color::Auto")]
+    pub color: Color,
+
     #[argh(subcommand)]
     pub invocation: Invocation,
 }
 
 #[derive(FromArgs)]
 #[argh(subcommand)]
+#[allow(clippy::large_enum_variant)] // parsed once, merge just has many options
 pub enum Invocation {
     Split(Split),
     Merge(Merge),
+    Resegment(Resegment),
+    Renumber(Renumber),
+    Pair(Pair),
+    Info(Info),
+    Stats(Stats),
+    Check(Check),
+    Report(Report),
+    Index(Index),
+    Import(Import),
+    Doctor(Doctor),
+    Align(Align),
+    Generate(Generate),
+    RenameSpeakers(RenameSpeakers),
+    Excerpt(Excerpt),
+    Convert(Convert),
+    InjectTimestamps(InjectTimestamps),
+    Serve(Serve),
 }
 
 #[derive(FromArgs)]
@@ -23,17 +77,286 @@ pub struct Merge {
     #[argh(switch, short = 'r')]
     pub recursive: bool,
 
-    /// overwrite the output file if it exists
+    /// overwrite the output file if it exists, after backing it up
     #[argh(switch, short = 'f')]
     pub force: bool,
 
-    /// list of files or directories
+    /// directory for backups of overwritten output files, defaults to
+    /// the directory of the output file
+    #[argh(option)]
+    pub backup_dir: Option<PathBuf>,
+
+    /// list of files or directories, a directory with segments named after
+    /// it like foo/foo-000.rtf only contributes these segments, or https://
+    /// URLs of transcripts to download with the remote feature
     #[argh(positional)]
     pub input_segments: Vec<PathBuf>,
 
-    /// file to write the merged segment to, omit to write to standard output
+    /// also recognize and adjust timestamps with dots or commas as
+    /// separators, e.g. #00.01.23-4#, and write them as #00:01:23-4#
+    #[argh(switch)]
+    pub lenient_timestamps: bool,
+
+    /// dialect of the timestamps in the transcripts, converted into F4
+    /// timestamps: f4, easytranscript for [00:01:23] or hypertranscribe
+    /// for [0:01:23.4], detected per transcript by default
+    #[argh(option, from_str_fn(parse_dialect))]
+    pub timestamp_dialect: Option<&'static dyn TimestampDialect>,
+
+    /// fail instead of warning if lines of a transcript cannot be parsed,
+    /// which would be copied without adjusting their timestamps
+    #[argh(switch)]
+    pub fail_on_unparsed: bool,
+
+    /// fail before writing anything if a transcript has control words that
+    /// are not plain formatting, like fields or revision marks added by a
+    /// word processor, which merging may mangle
+    #[argh(switch)]
+    pub paranoid: bool,
+
+    /// remove deleted text, revision marks and comments left by a word
+    /// processor before merging, keeping the text as it reads with all
+    /// changes accepted
+    #[argh(switch)]
+    pub strip_revisions: bool,
+
+    /// file to write the merged segment to, repeat to write several formats
+    /// in one go, e.g. -o a.rtf -o a.txt -o a.srt, with the format inferred
+    /// from the extension, .csv for an NVivo transcript, .intervals.csv
+    /// for a row per stretch of speech between timestamps, .html for a web
+    /// page and .json for the JSON representation
     #[argh(option, short = 'o')]
-    pub output_file: Option<PathBuf>,
+    pub output_file: Vec<PathBuf>,
+
+    /// write the merged transcript to standard output instead of files
+    #[argh(switch)]
+    pub stdout: bool,
+
+    /// encoding of the merged transcript, "utf-8" by default or "cp1252"
+    /// for older F4 versions, which declares the code page in RTF
+    #[argh(option, default = "OutputEncoding::Utf8")]
+    pub output_encoding: OutputEncoding,
+
+    /// start text, subtitle and CSV outputs in UTF-8 with a byte order
+    /// mark, for programs on Windows that would read them as Windows-1252
+    #[argh(switch)]
+    pub bom: bool,
+
+    /// merge several interviews at once, grouping the transcripts by
+    /// "directory" or by "stem-prefix", the name before the segment number,
+    /// needs --output-dir
+    #[argh(option)]
+    pub group_by: Option<crate::merge::GroupBy>,
+
+    /// directory to write a merged transcript per group to, named after
+    /// the group, e.g. foo.rtf
+    #[argh(option)]
+    pub output_dir: Option<PathBuf>,
+
+    /// also write the summary of applied shifts to this file
+    #[argh(option)]
+    pub shift_log: Option<PathBuf>,
+
+    /// record the merge in this SQLite project store and import the merged
+    /// transcripts into it, for `stats --store` and `report --store`
+    #[argh(option)]
+    pub store: Option<PathBuf>,
+
+    /// stitch utterances of the same speaker at segment boundaries only if
+    /// the timestamps before and after are at most this many seconds apart,
+    /// defaults to 60
+    #[argh(
+        option,
+        default = "crate::transcript::MergeOptions::DEFAULT_STITCH_MAX_GAP_SECS"
+    )]
+    pub stitch_max_gap: u64,
+
+    /// never stitch utterances at segment boundaries, even if the speaker
+    /// is the same, to keep the utterances of the segments as they are
+    #[argh(switch)]
+    pub no_stitch: bool,
+
+    /// insert a paragraph like "--- Segment 2 (shift #00:05:00-0#) ---"
+    /// where segments were joined
+    #[argh(switch)]
+    pub mark_boundaries: bool,
+
+    /// write speaker labels uniformly as "Z: " and collapse repeated spaces
+    /// in speech, for transcripts typed with "Z :", "Z:" or "Z:  "
+    #[argh(switch)]
+    pub tidy: bool,
+
+    /// break utterances into lines of at most this many characters, for
+    /// coding software that cannot handle very long paragraphs
+    #[argh(option)]
+    pub wrap: Option<usize>,
+
+    /// attribute paragraphs without a speaker label to the previous or
+    /// next speaker in text, subtitle, CSV and template outputs, for
+    /// transcripts with unlabeled continuation paragraphs
+    #[argh(option)]
+    pub infer_speakers: Option<SpeakerInference>,
+
+    /// language of the speech, de or en, to write subtitle and NVivo CSV
+    /// outputs with an entry per sentence, knowing its abbreviations
+    #[argh(option)]
+    pub lang: Option<Language>,
+
+    /// leave annotations like "(lacht)", "[unverständlich]" or "(...)" out
+    /// of these outputs, a comma-separated list of txt, srt, csv, intervals,
+    /// html and template, or all; RTF and JSON always keep them
+    #[argh(option, default = "StripAnnotations::default()")]
+    pub strip_annotations: StripAnnotations,
+
+    /// write the speaker labels of the merged RTF in a color per speaker,
+    /// the colors of the speaker list of the first transcript and others
+    /// for speakers not in it
+    #[argh(switch)]
+    pub color_speakers: bool,
+
+    /// write a bookmark at the start of every segment of the merged RTF and
+    /// a table of contents with links to them and the times the segments
+    /// start at the top, for navigating long interviews in Word
+    #[argh(switch)]
+    pub toc: bool,
+
+    /// number the utterances of the merged RTF, with the number before the
+    /// speaker label, and write an index of the numbers and the first
+    /// timestamp of their utterance to the output file with .lines.csv
+    /// appended
+    #[argh(switch)]
+    pub line_numbers: bool,
+
+    /// number of the first utterance with --line-numbers
+    #[argh(option, default = "1")]
+    pub line_number_start: u64,
+
+    /// difference between the numbers of consecutive utterances with
+    /// --line-numbers
+    #[argh(option, default = "1")]
+    pub line_number_step: u64,
+
+    /// move notes after the last utterance of every segment, like
+    /// "Aufnahme endet", to the end of the merged transcript
+    #[argh(switch)]
+    pub move_trailers: bool,
+
+    /// leave out notes after the last utterance of every segment
+    #[argh(switch)]
+    pub strip_trailers: bool,
+
+    /// leave out notes before the first utterance of every segment but the
+    /// first, like the date, pseudonym and segment number
+    #[argh(switch)]
+    pub strip_segment_headers: bool,
+
+    /// leave out the first sentence of a segment if it repeats the last
+    /// sentence of the segment before, which is warned about anyway
+    #[argh(switch)]
+    pub drop_boundary_duplicates: bool,
+
+    /// combine the document info of all transcripts, with the earliest
+    /// creation time, all speakers and a comment listing the segments,
+    /// instead of keeping only that of the first
+    #[argh(switch)]
+    pub merge_info: bool,
+
+    /// write utterances with this template instead of RTF, with placeholders
+    /// {{speaker}}, {{speech}}, {{start}} and {{segment}} and an optional
+    /// {{each}}...{{end}} part that repeats for every utterance
+    #[argh(option)]
+    pub template: Option<PathBuf>,
+
+    /// only merge transcripts matching this pattern, e.g. "interview-*",
+    /// can be repeated
+    #[argh(option)]
+    pub include: Vec<crate::glob::Glob>,
+
+    /// skip transcripts matching this pattern, e.g. "*-draft.rtf" or
+    /// "backup/*", can be repeated
+    #[argh(option)]
+    pub exclude: Vec<crate::glob::Glob>,
+
+    /// warn if the order of the segments contradicts the numbers in their
+    /// names, their first timestamps or their modification times, which
+    /// usually means they were renamed wrongly
+    #[argh(switch)]
+    pub order_check: bool,
+
+    /// save the progress next to the output file and continue an earlier
+    /// merge that stopped at a broken transcript, once it is fixed or left
+    /// out with --exclude, needs a single RTF output file
+    #[argh(switch)]
+    pub resume: bool,
+
+    /// only merge transcripts again from the first one that changed since
+    /// the last incremental merge into the same RTF output file, reusing
+    /// the merged transcript before it from a cache next to the output
+    #[argh(switch)]
+    pub incremental: bool,
+
+    /// leave out transcripts that cannot be loaded instead of failing, and
+    /// report how much time is probably missing after them
+    #[argh(switch)]
+    pub skip_broken: bool,
+
+    /// number of threads to load transcripts with, one per CPU by default
+    #[argh(option, short = 'j')]
+    pub jobs: Option<usize>,
+
+    /// time of day the recording started at, like 14:30:00, to rewrite
+    /// clock times like 14:32:05 noted in the transcripts into timestamps
+    /// relative to it, which are then not shifted per segment
+    #[argh(option)]
+    pub clock_start: Option<String>,
+
+    /// like --clock-start, with the time of day from the creation time in
+    /// the metadata of this recording, as ffprobe reports it
+    #[argh(option)]
+    pub clock_start_from: Option<PathBuf>,
+
+    /// how the end time of every segment that the next one is shifted by is
+    /// found: "round-up" the last timestamp to the minute or hour,
+    /// "exact-last" timestamp, "audio-duration" of the audio next to the
+    /// transcript, or "fixed=hh:mm:ss" for segments of the same length;
+    /// round-up unless there is a segment manifest
+    #[argh(option)]
+    pub shift_strategy: Option<crate::merge::ShiftStrategy>,
+
+    /// file with corrections of the shifts of segments, a line like "4 +12"
+    /// per segment with its number or file name and the seconds it starts
+    /// after the end of the one before it, which also moves later segments
+    #[argh(option)]
+    pub segment_offset: Option<PathBuf>,
+
+    /// infer the order and shifts of the segments from their names and
+    /// timestamps even if split left a manifest like interview.f4tapir.toml
+    #[argh(switch)]
+    pub ignore_manifest: bool,
+
+    /// merge even if segments are missing from a numbered sequence, like
+    /// interview-004.rtf between interview-003.rtf and interview-005.rtf
+    #[argh(switch)]
+    pub allow_gaps: bool,
+
+    /// insert a placeholder utterance for every missing segment, from a
+    /// gap in the numbering or the manifest, covering its time, and shift
+    /// the segments after it by its length from the manifest,
+    /// --shift-strategy fixed or else the length of the segment before it
+    #[argh(switch)]
+    pub insert_placeholders: bool,
+
+    /// order the segments by how the audio next to each transcript
+    /// continues the one of another, for segments with unreliable names,
+    /// warning about segments that cannot be placed
+    #[argh(switch)]
+    pub order_by_audio: bool,
+
+    /// warn if the audio next to a transcript starts with the end of the
+    /// audio of the transcript before, so the overlap may be transcribed
+    /// twice
+    #[argh(switch)]
+    pub check_overlap: bool,
 }
 
 #[derive(FromArgs)]
@@ -52,4 +375,559 @@ pub struct Split {
     /// also split sound files in subdirectories
     #[argh(switch, short = 'r')]
     pub recursive: bool,
+
+    /// continue numbering after the highest existing segment with the same
+    /// name instead of starting at 000 for every interview
+    #[argh(switch)]
+    pub continue_numbering: bool,
+
+    /// overwrite segment files if they exist
+    #[argh(switch, short = 'f')]
+    pub force: bool,
+
+    /// cut at the chapters in an ffmetadata file, the tracks of a CUE
+    /// sheet or the times in a list like 00:12:30 instead of every 5min,
+    /// only for a single interview
+    #[argh(option)]
+    pub cues: Option<PathBuf>,
+
+    /// cut at timestamps of this transcript of the whole interview, so
+    /// that the segments match its passages, only for a single interview
+    #[argh(option)]
+    pub cuts_from_transcript: Option<PathBuf>,
+
+    /// which timestamps of --cuts-from-transcript to cut at,
+    /// "speaker-changes" by default, "all" or every nth like "every-3"
+    #[argh(option, default = "TranscriptCuts::SpeakerChanges")]
+    pub cut_at: TranscriptCuts,
+
+    /// write separate segments like interview-ch1-000.mp3 for every audio
+    /// stream, or every channel of a single stream, e.g. for field
+    /// recorders with a track per speaker
+    #[argh(switch)]
+    pub split_channels: bool,
+
+    /// skip interviews whose segments are up to date according to a state
+    /// file in the output directory, and replace the segments of changed
+    /// interviews
+    #[argh(switch)]
+    pub incremental: bool,
+
+    /// write the segments of every interview into a directory named after
+    /// it, e.g. foo/foo-000.mp3 for foo.mp3, which merge then treats as
+    /// one interview
+    #[argh(switch)]
+    pub per_interview_dirs: bool,
+
+    /// only split sound files matching this pattern, e.g. "*.wav", can be
+    /// repeated
+    #[argh(option)]
+    pub include: Vec<crate::glob::Glob>,
+
+    /// skip sound files matching this pattern, e.g. "*-test.mp3", can be
+    /// repeated
+    #[argh(option)]
+    pub exclude: Vec<crate::glob::Glob>,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "resegment")]
+/// Join split sound segments and cut them into segments of a different length.
+pub struct Resegment {
+    /// segments to join, in lexicographical order of their filenames
+    #[argh(positional)]
+    pub input_segments: Vec<PathBuf>,
+
+    /// length of the new segments, e.g. 00:10:00 for 10min segments
+    #[argh(option, short = 't')]
+    pub segment_time: String,
+
+    /// directory to write the new segments to, default to the same
+    /// directory as the first input segment
+    #[argh(option, short = 'o')]
+    pub output_directory: Option<PathBuf>,
+
+    /// name for the new segments before the segment number, defaults to
+    /// the name of the first input segment without its segment number
+    #[argh(option)]
+    pub name: Option<String>,
+
+    /// also use sound files in subdirectories
+    #[argh(switch, short = 'r')]
+    pub recursive: bool,
+
+    /// overwrite segment files if they exist
+    #[argh(switch, short = 'f')]
+    pub force: bool,
+
+    /// detect segments that start with the end of the segment before, as
+    /// some recorders write them, and join them without the repetition
+    #[argh(switch)]
+    pub trim_overlap: bool,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "renumber")]
+/// Rename segments and transcripts into a zero-padded sequence like interview-000.rtf.
+pub struct Renumber {
+    /// segment files or directories with segments to renumber
+    #[argh(positional)]
+    pub input_segments: Vec<PathBuf>,
+
+    /// name for the renamed segments before the segment number, defaults to
+    /// the name of each segment without its segment number
+    #[argh(option)]
+    pub name: Option<String>,
+
+    /// order transcripts by their first timestamp instead of their filenames
+    #[argh(switch)]
+    pub by_timestamp: bool,
+
+    /// only print the new names without renaming anything
+    #[argh(switch, short = 'n')]
+    pub dry_run: bool,
+
+    /// also renumber segments in subdirectories
+    #[argh(switch, short = 'r')]
+    pub recursive: bool,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "pair")]
+/// Check that each transcript fits the length of the audio segment with the same name.
+pub struct Pair {
+    /// audio segments and transcripts, or directories containing them
+    #[argh(positional)]
+    pub input_segments: Vec<PathBuf>,
+
+    /// seconds a transcript may end before its audio segment without a
+    /// warning, defaults to 60
+    #[argh(option, default = "crate::pair::DEFAULT_MAX_GAP_SECS")]
+    pub max_gap: u64,
+
+    /// also pair segments in subdirectories
+    #[argh(switch, short = 'r')]
+    pub recursive: bool,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "info")]
+/// Print RTF header details, timestamps, utterances and speakers of transcripts.
+pub struct Info {
+    /// transcripts or directories with transcripts
+    #[argh(positional)]
+    pub input_segments: Vec<PathBuf>,
+
+    /// also use transcripts in subdirectories
+    #[argh(switch, short = 'r')]
+    pub recursive: bool,
+
+    /// print a JSON array with an object per transcript instead of text
+    #[argh(switch)]
+    pub json: bool,
+
+    /// count paragraphs without a speaker label for the previous or next
+    /// speaker, and list them as inferred
+    #[argh(option)]
+    pub infer_speakers: Option<SpeakerInference>,
+
+    /// list the annotations like "(lacht)" or "[unverständlich]" in the
+    /// speech, with their lines and speakers, instead of the info
+    #[argh(switch)]
+    pub list_annotations: bool,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "stats")]
+/// Count pause markers like "(...)" or "(3)" and the gaps between
+/// timestamps for every speaker, for pause profiles of interviews.
+pub struct Stats {
+    /// transcripts or directories with transcripts
+    #[argh(positional)]
+    pub input_segments: Vec<PathBuf>,
+
+    /// also use transcripts in subdirectories
+    #[argh(switch, short = 'r')]
+    pub recursive: bool,
+
+    /// print the statistics as JSON instead of tables
+    #[argh(switch)]
+    pub json: bool,
+
+    /// count paragraphs without a speaker label for the previous or next
+    /// speaker
+    #[argh(option)]
+    pub infer_speakers: Option<SpeakerInference>,
+
+    /// read the transcripts imported into this SQLite project store instead
+    /// of parsing them, all of them if no transcripts are given
+    #[argh(option)]
+    pub store: Option<PathBuf>,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "check")]
+/// Score the quality of transcript segments from 0 to 100, by timestamp
+/// density, unparsed lines, unintelligible passages and speaker labels,
+/// worst first.
+pub struct Check {
+    /// transcripts or directories with transcripts
+    #[argh(positional)]
+    pub input_segments: Vec<PathBuf>,
+
+    /// also use transcripts in subdirectories
+    #[argh(switch, short = 'r')]
+    pub recursive: bool,
+
+    /// print a JSON array with an object per segment instead of a table
+    #[argh(switch)]
+    pub json: bool,
+
+    /// only list segments with a score below this
+    #[argh(option)]
+    pub below: Option<u8>,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "report")]
+/// Give an overview of all interviews in a project tree: segments found
+/// and missing, durations, quality scores and if they are ready to merge.
+pub struct Report {
+    /// directories to search for transcripts and segment manifests,
+    /// including subdirectories, the current directory by default
+    #[argh(positional)]
+    pub project_dirs: Vec<PathBuf>,
+
+    /// file to write the overview to, as HTML if it ends in .html and as
+    /// CSV otherwise, replacing an earlier one, CSV on standard output by
+    /// default
+    #[argh(option, short = 'o')]
+    pub output_file: Option<PathBuf>,
+
+    /// take durations, unparsed lines and quality scores of transcripts
+    /// imported into this SQLite project store from it instead of parsing
+    /// them
+    #[argh(option)]
+    pub store: Option<PathBuf>,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "index")]
+/// Index every word of the plain text export of merged transcripts with
+/// its offset, the timestamp before it and its segment, for finding the
+/// audio of quotes cited by their position in the text.
+pub struct Index {
+    /// transcripts or directories with transcripts, merged in
+    /// lexicographical order of their filenames
+    #[argh(positional)]
+    pub input_segments: Vec<PathBuf>,
+
+    /// also use transcripts in subdirectories
+    #[argh(switch, short = 'r')]
+    pub recursive: bool,
+
+    /// file to write the index to, an SQLite database if it ends in
+    /// .sqlite, .sqlite3 or .db and JSON otherwise, replacing an earlier one
+    #[argh(option, short = 'o')]
+    pub output_file: PathBuf,
+
+    /// also write the plain text export that the offsets refer to, the
+    /// same as merging into a .txt file
+    #[argh(option)]
+    pub text: Option<PathBuf>,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "import")]
+/// Import transcripts into an SQLite project store for `stats --store` and
+/// `report --store`, parsing only those that are new or changed since the
+/// last import.
+pub struct Import {
+    /// transcripts or directories with transcripts, the current directory
+    /// by default
+    #[argh(positional)]
+    pub input_segments: Vec<PathBuf>,
+
+    /// also use transcripts in subdirectories
+    #[argh(switch, short = 'r')]
+    pub recursive: bool,
+
+    /// the project store, created if it does not exist yet
+    #[argh(option)]
+    pub store: PathBuf,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "doctor")]
+/// Check that ffmpeg is installed, output directories are writable and
+/// transcripts can be parsed, and print how to fix what is not.
+pub struct Doctor {
+    /// directory that segments or merged transcripts will be written to,
+    /// checked for write permissions, can be repeated
+    #[argh(option, short = 'o')]
+    pub output_directory: Vec<PathBuf>,
+
+    /// transcripts to test-parse, in addition to a built-in sample
+    #[argh(positional)]
+    pub transcripts: Vec<PathBuf>,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "align")]
+/// Correct transcript timestamps after the end of their audio segment.
+pub struct Align {
+    /// audio segments and transcripts, or directories containing them,
+    /// paired by their names like in pair
+    #[argh(positional)]
+    pub input_segments: Vec<PathBuf>,
+
+    /// directory to write the corrected transcripts to, under their
+    /// original names
+    #[argh(option, short = 'o')]
+    pub output_dir: Option<PathBuf>,
+
+    /// correct the transcripts themselves instead of writing copies,
+    /// replacing each one atomically
+    #[argh(switch)]
+    pub in_place: bool,
+
+    /// with --in-place, keep the original of every corrected transcript
+    /// as <name>.orig, unless there is one from an earlier run
+    #[argh(switch)]
+    pub orig: bool,
+
+    /// scale all timestamps so that the last one is the end of the audio,
+    /// instead of setting only those after the end to the end
+    #[argh(switch)]
+    pub rescale: bool,
+
+    /// overwrite corrected transcripts that exist in the output directory
+    #[argh(switch, short = 'f')]
+    pub force: bool,
+
+    /// also align segments in subdirectories
+    #[argh(switch, short = 'r')]
+    pub recursive: bool,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "generate")]
+/// Generate F4 transcripts with made-up speech, for testing merges and
+/// reproducing problems without sharing interviews.
+pub struct Generate {
+    /// directory to write the transcripts to
+    #[argh(option, short = 'o')]
+    pub output_dir: PathBuf,
+
+    /// name of the transcripts before the segment number
+    #[argh(option, default = "String::from(\"interview\")")]
+    pub name: String,
+
+    /// number of segments, 1 by default
+    #[argh(option, default = "1")]
+    pub segments: usize,
+
+    /// length of every segment in seconds, 300 by default
+    #[argh(option, default = "300")]
+    pub duration: u64,
+
+    /// number of speakers including the interviewer, 2 by default
+    #[argh(option, default = "2")]
+    pub speakers: usize,
+
+    /// average seconds between timestamps, 20 by default
+    #[argh(option, default = "20")]
+    pub timestamp_every: u64,
+
+    /// something unusual that real transcripts have, one of pard, lf,
+    /// no-jexpand, spaced-labels, lenient-timestamps, unlabeled or
+    /// formatted, can be repeated
+    #[argh(option)]
+    pub quirk: Vec<Quirk>,
+
+    /// seed for the made-up speech, the same seed generates the same
+    /// transcripts
+    #[argh(option, default = "1")]
+    pub seed: u64,
+
+    /// overwrite transcripts that exist in the output directory
+    #[argh(switch, short = 'f')]
+    pub force: bool,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "rename-speakers")]
+/// Rename speakers in every transcript of a project, e.g. when the
+/// pseudonym of a participant changes.
+pub struct RenameSpeakers {
+    /// transcripts or directories with transcripts
+    #[argh(positional)]
+    pub input_segments: Vec<PathBuf>,
+
+    /// speaker to rename like B=P07, can be repeated
+    #[argh(option)]
+    pub map: Vec<SpeakerRename>,
+
+    /// directory to write the renamed transcripts to, mirroring the
+    /// directories they were found in
+    #[argh(option, short = 'o')]
+    pub output_dir: Option<PathBuf>,
+
+    /// rename speakers in the transcripts themselves, replacing each one
+    /// atomically
+    #[argh(switch)]
+    pub in_place: bool,
+
+    /// with --in-place, keep the original of every changed transcript as
+    /// <name>.orig, unless there is one from an earlier run
+    #[argh(switch)]
+    pub orig: bool,
+
+    /// overwrite transcripts that exist in the output directory
+    #[argh(switch, short = 'f')]
+    pub force: bool,
+
+    /// also rename speakers in transcripts in subdirectories
+    #[argh(switch, short = 'r')]
+    pub recursive: bool,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "excerpt")]
+/// Cut the audio of an utterance out of an interview, to listen to a quote
+/// again before publishing it.
+pub struct Excerpt {
+    /// transcript of the whole interview
+    #[argh(positional)]
+    pub transcript: PathBuf,
+
+    /// recording of the whole interview
+    #[argh(positional)]
+    pub audio: PathBuf,
+
+    /// number of the utterance in the transcript, starting at 1
+    #[argh(option, short = 'u')]
+    pub utterance: Option<usize>,
+
+    /// time in the utterance, like #00:12:30-4# or 00:12:30
+    #[argh(option)]
+    pub at: Option<String>,
+
+    /// file with quotes to locate in the transcript, a quote per line or
+    /// a CSV file with a "quote" column, prints the utterances and ffmpeg
+    /// commands to cut clips of them
+    #[argh(option)]
+    pub quotes: Option<PathBuf>,
+
+    /// with --quotes, cut the clips into this directory instead of
+    /// printing ffmpeg commands, named like quote-001.mp3
+    #[argh(option)]
+    pub clips_dir: Option<PathBuf>,
+
+    /// seconds of audio to add before and after the utterance, 2 by
+    /// default
+    #[argh(option, default = "2.0")]
+    pub padding: f64,
+
+    /// file to write the clip to, defaults to the name of the transcript
+    /// with the number of the utterance, e.g. interview-012.mp3
+    #[argh(option, short = 'o')]
+    pub output_file: Option<PathBuf>,
+
+    /// overwrite the clip if it exists
+    #[argh(switch, short = 'f')]
+    pub force: bool,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "convert")]
+/// Convert a transcript between F4 RTF, plain text and JSON, e.g. to bring
+/// a transcript typed in a text editor into F4.
+pub struct Convert {
+    /// transcript to convert
+    #[argh(positional)]
+    pub input: PathBuf,
+
+    /// format of the transcript, rtf, txt with an utterance per paragraph
+    /// like "I: speech #00:01:23-4#" or json, inferred from the extension
+    /// by default
+    #[argh(option)]
+    pub from: Option<Format>,
+
+    /// format to convert to, rtf, json, txt or another output format of
+    /// merge like srt, csv, intervals or html, inferred from the extension
+    /// of the output file and rtf by default
+    #[argh(option)]
+    pub to: Option<Format>,
+
+    /// file to write the converted transcript to, defaults to the input
+    /// with the extension of the format
+    #[argh(option, short = 'o')]
+    pub output_file: Option<PathBuf>,
+
+    /// overwrite the output file if it exists
+    #[argh(switch, short = 'f')]
+    pub force: bool,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "inject-timestamps")]
+/// Insert estimated timestamps into a transcript that has none, assuming
+/// evenly paced speech, so that it can be merged like F4 transcripts.
+pub struct InjectTimestamps {
+    /// transcript without timestamps
+    #[argh(positional)]
+    pub input: PathBuf,
+
+    /// format of the transcript, rtf, txt or json, inferred from the
+    /// extension by default
+    #[argh(option)]
+    pub from: Option<Format>,
+
+    /// insert a timestamp every so many seconds like 30s or words like
+    /// 50w, and at the end of every utterance
+    #[argh(option, default = "Every::Tenths(300)")]
+    pub every: Every,
+
+    /// length of the recording like 45:30, to spread the words over
+    #[argh(option)]
+    pub duration: Option<String>,
+
+    /// recording to spread the words over, with its length from ffprobe
+    #[argh(option)]
+    pub audio: Option<PathBuf>,
+
+    /// pace of the speech without --duration or --audio
+    #[argh(option, default = "150")]
+    pub words_per_minute: u32,
+
+    /// file to write the F4 RTF to, defaults to the input with -timed
+    /// appended to its name, like interview-timed.rtf
+    #[argh(option, short = 'o')]
+    pub output_file: Option<PathBuf>,
+
+    /// overwrite the output file if it exists
+    #[argh(switch, short = 'f')]
+    pub force: bool,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "serve")]
+/// Serve list, check, merge, split and export as JSON-RPC for graphical
+/// front-ends that keep f4tapir running in the background, or an HTTP API
+/// for a shared server.
+pub struct Serve {
+    /// read requests from stdin and write responses to stdout, one per line
+    #[argh(switch)]
+    pub stdio: bool,
+
+    /// serve an HTTP API on this address, like 0.0.0.0:8080, to upload,
+    /// merge and download files, with the http feature
+    #[argh(option)]
+    pub http: Option<String>,
+
+    /// directory that files for --http are uploaded to and written in,
+    /// the current directory by default
+    #[argh(option)]
+    pub data_dir: Option<PathBuf>,
 }
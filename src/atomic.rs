@@ -0,0 +1,102 @@
+//! Output files that only appear under their final name once they have
+//! been written completely, so that interrupted runs never leave a
+//! truncated file behind.
+use std::fs::{self, File};
+use std::io::{Result, Write};
+use std::path::{Path, PathBuf};
+
+/// A file that is written to a temporary file in the same directory and
+/// renamed to its final path on `commit`.
+///
+/// If it is dropped without committing, the temporary file is removed and
+/// an existing file at the final path stays untouched.
+pub struct AtomicFile {
+    file: File,
+    temporary: PathBuf,
+    path: PathBuf,
+    committed: bool,
+}
+
+impl AtomicFile {
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<AtomicFile> {
+        let path = path.as_ref().to_path_buf();
+        let mut temporary_name = std::ffi::OsString::from(".");
+        temporary_name.push(path.file_name().expect("atomic file without file name"));
+        temporary_name.push(format!(".tmp-{}", std::process::id()));
+        let temporary = path.with_file_name(temporary_name);
+        let file = File::create(&temporary)?;
+        Ok(AtomicFile {
+            file,
+            temporary,
+            path,
+            committed: false,
+        })
+    }
+
+    /// Flushes everything to disk and moves the file to its final path,
+    /// replacing any file that was there before.
+    pub fn commit(mut self) -> Result<()> {
+        self.file.flush()?;
+        self.file.sync_all()?;
+        fs::rename(&self.temporary, &self.path)?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_file(&self.temporary);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn file_appears_on_commit() {
+        let dir = temp_dir("f4tapir-atomic-commit");
+        let path = dir.join("merged.rtf");
+        fs::write(&path, "old").unwrap();
+
+        let mut file = AtomicFile::create(&path).unwrap();
+        file.write_all(b"new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        file.commit().unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn dropping_leaves_no_trace() {
+        let dir = temp_dir("f4tapir-atomic-drop");
+        let path = dir.join("merged.rtf");
+
+        let mut file = AtomicFile::create(&path).unwrap();
+        file.write_all(b"half").unwrap();
+        drop(file);
+
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,269 @@
+//! Ordering of segments by their audio, for `merge --order-by-audio` when
+//! the file names of the segments cannot be trusted.
+//!
+//! Segments cut from one recording continue where the segment before them
+//! stopped, so the sound right before the end of a segment resembles the
+//! sound right after the start of the next one. The loudness and
+//! brightness of the last and first fifth of a second are compared for
+//! every pair of segments, and a segment is placed after another only if
+//! they fit each other clearly better than any other segment. Segments
+//! that fit nowhere are reported and stay in the order of their names.
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::exit::{ExitCode, Failure};
+use crate::logging::MERGE;
+use crate::paths::command_arg;
+
+use log::debug;
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Sample rate the audio is decoded with, enough for loudness and
+/// brightness.
+pub(crate) const SAMPLE_RATE: usize = 8000;
+/// Seconds of audio at either end of a segment that are compared.
+const EDGE_SECONDS: &str = "0.2";
+/// How much better the best fitting segment must fit than the second best
+/// to be placed next to another one.
+const CONFIDENCE: f64 = 2.0;
+
+/// Sound at the start and end of a segment, as the logarithm of its
+/// loudness and of the loudness of its changes, which grows with
+/// brightness.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Edges {
+    pub head: [f64; 2],
+    pub tail: [f64; 2],
+}
+
+/// The segments in the order found, and those that could not be placed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioOrder {
+    pub paths: Vec<PathBuf>,
+    pub unplaced: Vec<PathBuf>,
+}
+
+/// Decodes the start and end of the audio with ffmpeg.
+pub fn edges_of(audio: &Path) -> Result<Edges> {
+    let head = decode(audio, &["-t", EDGE_SECONDS], &[])?;
+    let tail = decode(audio, &[], &["-sseof", &format!("-{}", EDGE_SECONDS)])?;
+    Ok(Edges {
+        head: features(&head),
+        tail: features(&tail),
+    })
+}
+
+/// Mono samples of the audio at [`SAMPLE_RATE`], with ffmpeg options
+/// before and after the input.
+pub(crate) fn decode(
+    audio: &Path,
+    after_input: &[&str],
+    before_input: &[&str],
+) -> Result<Vec<i16>> {
+    let output = Command::new("ffmpeg")
+        .args(["-v", "error", "-nostdin"])
+        .args(before_input)
+        .arg("-i")
+        .arg(command_arg(audio))
+        .args(after_input)
+        .args([
+            "-f",
+            "s16le",
+            "-ac",
+            "1",
+            "-ar",
+            &SAMPLE_RATE.to_string(),
+            "-",
+        ])
+        .stdin(Stdio::null())
+        .output()
+        .map_err(Error::FfmpegIo)?;
+    if !output.status.success() || output.stdout.len() < 2 {
+        return Err(Error::FfmpegStatus(audio.to_path_buf()));
+    }
+    Ok(output
+        .stdout
+        .chunks_exact(2)
+        .map(|sample| i16::from_le_bytes([sample[0], sample[1]]))
+        .collect())
+}
+
+/// Logarithms of the RMS of the samples and of their differences.
+pub fn features(samples: &[i16]) -> [f64; 2] {
+    let rms = |values: &mut dyn Iterator<Item = f64>| {
+        let (sum, count) = values.fold((0.0, 0), |(sum, count), v| (sum + v * v, count + 1));
+        (sum / f64::from(count.max(1))).sqrt()
+    };
+    let level = rms(&mut samples.iter().map(|&s| f64::from(s)));
+    let changes = rms(&mut samples
+        .windows(2)
+        .map(|pair| f64::from(pair[1]) - f64::from(pair[0])));
+    [level.ln_1p(), changes.ln_1p()]
+}
+
+/// How badly the start of `next` continues the end of `previous`.
+fn misfit(previous: &Edges, next: &Edges) -> f64 {
+    let level = previous.tail[0] - next.head[0];
+    let brightness = previous.tail[1] - next.head[1];
+    (level * level + brightness * brightness).sqrt()
+}
+
+/// Orders the segments, given in the order of their names, by how well
+/// their edges fit.
+pub fn order_by_audio(segments: &[(PathBuf, Edges)]) -> AudioOrder {
+    let count = segments.len();
+    let misfits: Vec<Vec<f64>> = segments
+        .iter()
+        .map(|(_, a)| segments.iter().map(|(_, b)| misfit(a, b)).collect())
+        .collect();
+    // the clearly best fitting other segment among the candidates
+    let best = |candidates: &mut dyn Iterator<Item = (usize, f64)>| {
+        let mut sorted: Vec<(usize, f64)> = candidates.collect();
+        sorted.sort_by(|a, b| a.1.total_cmp(&b.1));
+        match sorted.as_slice() {
+            [(idx, _)] => Some(*idx),
+            [(idx, first), (_, second), ..] if first * CONFIDENCE <= *second => Some(*idx),
+            _ => None,
+        }
+    };
+    let mut next: Vec<Option<usize>> = (0..count)
+        .map(|a| {
+            let b = best(&mut (0..count).filter(|&b| b != a).map(|b| (b, misfits[a][b])))?;
+            let previous = best(&mut (0..count).filter(|&p| p != b).map(|p| (p, misfits[p][b])));
+            Some(b).filter(|_| previous == Some(a))
+        })
+        .collect();
+    break_cycles(&mut next, &misfits);
+
+    let mut has_previous = vec![false; count];
+    for &b in next.iter().flatten() {
+        has_previous[b] = true;
+    }
+    let mut order = AudioOrder {
+        paths: Vec::with_capacity(count),
+        unplaced: vec![],
+    };
+    for start in (0..count).filter(|&idx| !has_previous[idx]) {
+        if count > 1 && next[start].is_none() {
+            order.unplaced.push(segments[start].0.clone());
+        }
+        let mut current = Some(start);
+        while let Some(idx) = current {
+            order.paths.push(segments[idx].0.clone());
+            current = next[idx];
+        }
+    }
+    debug!(
+        target: MERGE,
+        "ordered {} segments by their audio, {} could not be placed",
+        count,
+        order.unplaced.len()
+    );
+    order
+}
+
+/// Removes the worst fitting link of every cycle, e.g. from the last
+/// segment back to the first.
+fn break_cycles(next: &mut [Option<usize>], misfits: &[Vec<f64>]) {
+    for start in 0..next.len() {
+        let mut cycle = vec![start];
+        let mut current = next[start];
+        while let Some(idx) = current {
+            if idx == start {
+                let worst = *cycle
+                    .iter()
+                    .max_by(|&&a, &&b| {
+                        let misfit = |idx: usize| next[idx].map_or(0.0, |b| misfits[idx][b]);
+                        misfit(a).total_cmp(&misfit(b))
+                    })
+                    .expect("cycle has a start");
+                next[worst] = None;
+                break;
+            }
+            if cycle.len() > next.len() {
+                break;
+            }
+            cycle.push(idx);
+            current = next[idx];
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("failed to invoke ffmpeg to compare the audio of segments, install it with your favorite package manager: {0}")]
+    FfmpegIo(std::io::Error),
+    #[error("ffmpeg could not decode {0}")]
+    FfmpegStatus(PathBuf),
+}
+
+impl Failure for Error {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Error::FfmpegIo(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                ExitCode::FfmpegMissing
+            }
+            Error::FfmpegIo(_) => ExitCode::IO,
+            Error::FfmpegStatus(_) => ExitCode::FfmpegFailed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn segment(name: &str, head: f64, tail: f64) -> (PathBuf, Edges) {
+        let edges = Edges {
+            head: [head, 1.0],
+            tail: [tail, 1.0],
+        };
+        (PathBuf::from(name), edges)
+    }
+
+    #[test]
+    fn segments_continue_where_the_one_before_ended() {
+        let order = order_by_audio(&[
+            segment("c.mp3", 5.0, 9.0),
+            segment("a.mp3", 1.0, 3.0),
+            segment("b.mp3", 3.0, 5.0),
+        ]);
+        assert_eq!(
+            order.paths,
+            vec![
+                PathBuf::from("a.mp3"),
+                PathBuf::from("b.mp3"),
+                PathBuf::from("c.mp3")
+            ]
+        );
+        assert!(order.unplaced.is_empty());
+
+        // the loud segment fits nowhere and keeps its place by name
+        let order = order_by_audio(&[
+            segment("b.mp3", 3.0, 5.0),
+            segment("x.mp3", 20.0, 20.0),
+            segment("a.mp3", 1.0, 3.0),
+            segment("c.mp3", 5.0, 9.0),
+        ]);
+        assert_eq!(
+            order.paths,
+            vec![
+                PathBuf::from("x.mp3"),
+                PathBuf::from("a.mp3"),
+                PathBuf::from("b.mp3"),
+                PathBuf::from("c.mp3")
+            ]
+        );
+        assert_eq!(order.unplaced, vec![PathBuf::from("x.mp3")]);
+    }
+
+    #[test]
+    fn loud_and_bright_audio_has_larger_features() {
+        let quiet = features(&[10, -10, 10, -10]);
+        let loud = features(&[1000, 1000, 1000, 1000]);
+        assert!(loud[0] > quiet[0]);
+        assert!(quiet[1] > loud[1]);
+    }
+}
@@ -0,0 +1,122 @@
+//! Backups of output files before they are overwritten, so that an
+//! unintended `--force` does not destroy the only merged copy.
+use std::fs;
+use std::io::Result;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Copies the file to `<name>.bak-YYYYMMDDHHMMSS` in the given directory,
+/// or next to the file if there is none, and returns the path of the copy.
+///
+/// The time in the name is UTC.
+pub fn backup(path: &Path, backup_dir: Option<&Path>) -> Result<PathBuf> {
+    let mut name = path
+        .file_name()
+        .expect("backup of a path without file name")
+        .to_os_string();
+    name.push(format!(".bak-{}", utc_suffix(SystemTime::now())));
+    let backup = match backup_dir {
+        Some(dir) => {
+            fs::create_dir_all(dir)?;
+            dir.join(name)
+        }
+        None => path.with_file_name(name),
+    };
+    fs::copy(path, &backup)?;
+    Ok(backup)
+}
+
+/// Copies the file to `<name>.orig` next to it before it is edited in
+/// place, and returns the path of the copy. An existing copy is kept, as
+/// it is older and hence closer to the original.
+pub fn keep_original(path: &Path) -> Result<PathBuf> {
+    let mut name = path
+        .file_name()
+        .expect("original of a path without file name")
+        .to_os_string();
+    name.push(".orig");
+    let original = path.with_file_name(name);
+    if !original.exists() {
+        fs::copy(path, &original)?;
+    }
+    Ok(original)
+}
+
+/// Formats the time as `YYYYMMDDHHMMSS` in UTC.
+fn utc_suffix(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let secs_of_day = secs % 86400;
+    format!(
+        "{:04}{:02}{:02}{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    )
+}
+
+/// Date in the proleptic Gregorian calendar for days since 1970-01-01,
+/// after Howard Hinnant's `civil_from_days`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn suffix_is_utc_date_and_time() {
+        assert_eq!(utc_suffix(UNIX_EPOCH), "19700101000000");
+        let time = UNIX_EPOCH + Duration::from_secs(1_709_210_096);
+        assert_eq!(utc_suffix(time), "20240229123456");
+    }
+
+    #[test]
+    fn backup_is_a_copy_in_the_backup_dir() {
+        let dir = std::env::temp_dir().join("f4tapir-backup");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("merged.rtf");
+        fs::write(&path, "original").unwrap();
+
+        let backup = backup(&path, Some(&dir.join("backups"))).unwrap();
+        fs::write(&path, "new").unwrap();
+
+        assert!(backup.starts_with(dir.join("backups")));
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "original");
+    }
+
+    #[test]
+    fn first_original_is_kept() {
+        let dir = std::env::temp_dir().join("f4tapir-orig");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("interview-001.rtf");
+        fs::write(&path, "original").unwrap();
+
+        let original = keep_original(&path).unwrap();
+        fs::write(&path, "edited").unwrap();
+        keep_original(&path).unwrap();
+
+        assert_eq!(original, dir.join("interview-001.rtf.orig"));
+        assert_eq!(fs::read_to_string(&original).unwrap(), "original");
+    }
+}
@@ -0,0 +1,8 @@
+//! Native window for merging transcripts, built with `--features gui`.
+fn main() {
+    stderrlog::new().verbosity(1).init().unwrap();
+    if let Err(err) = f4tapir::gui::run() {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
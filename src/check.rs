@@ -0,0 +1,235 @@
+//! Heuristic quality scores of transcript segments, to see at a glance
+//! which segments need a review before merging.
+//!
+//! Every segment starts with 100 points and loses up to
+//! * 30 if it has less than one timestamp per minute,
+//! * 30 for lines that could not be parsed, all of them at a tenth of the
+//!   lines,
+//! * 20 for `[unverständlich]` and the like, all of them at two per minute,
+//! * 20 for inconsistent speaker labels, all of them at a tenth of the
+//!   utterances. Labels are inconsistent if they are missing from the
+//!   speaker list of the transcript or, without a list, if they are used
+//!   only once in a segment with at least ten utterances, like a typo.
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::args::Check;
+use crate::exit::{ExitCode, Failure};
+use crate::find::collect_transcripts;
+use crate::transcript::json::Document;
+use crate::transcript::{annotations, AnnotationKind, Error as TranscriptError, Transcript};
+
+use log::warn;
+use serde_json::{json, Value};
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Timestamps per minute that are enough.
+const TARGET_TIMESTAMP_DENSITY: f64 = 1.0;
+/// Share of unparsed lines that costs all points for them.
+const MAX_UNPARSED_RATIO: f64 = 0.1;
+/// Unintelligible passages per minute that cost all points for them.
+const MAX_UNINTELLIGIBLE_DENSITY: f64 = 2.0;
+/// Share of inconsistent labels that costs all points for them.
+const MAX_INCONSISTENT_RATIO: f64 = 0.1;
+/// Utterances a segment without speaker list needs before labels that
+/// are used once are suspicious.
+const MIN_UTTERANCES_FOR_RARE_LABELS: usize = 10;
+
+pub fn check(opts: Check) -> Result<()> {
+    let paths = collect_transcripts(opts.input_segments, opts.recursive)?;
+    if paths.is_empty() {
+        return Err(Error::NoTranscripts);
+    }
+    let mut qualities = vec![];
+    for path in paths {
+        match Quality::of(&path) {
+            Ok(quality) => qualities.push(quality),
+            Err(err) => warn!(
+                "failed to load transcript {}, skipping, cause: {}",
+                path.display(),
+                err
+            ),
+        }
+    }
+    // worst first, they need the review
+    qualities.sort_by_key(|quality| quality.score);
+    if let Some(below) = opts.below {
+        qualities.retain(|quality| quality.score < below);
+    }
+
+    let mut stdout = std::io::stdout().lock();
+    if opts.json {
+        let json: Vec<Value> = qualities.iter().map(Quality::to_json).collect();
+        serde_json::to_writer_pretty(&mut stdout, &json).map_err(std::io::Error::from)?;
+        writeln!(&mut stdout)?;
+    } else {
+        writeln!(
+            &mut stdout,
+            "score  ts/min  unparsed  unintelligible  odd labels  segment"
+        )?;
+        for quality in &qualities {
+            writeln!(
+                &mut stdout,
+                "{:>5}  {:>6.1}  {:>7.0}%  {:>14}  {:>10}  {}",
+                quality.score,
+                quality.timestamp_density,
+                quality.unparsed_ratio * 100.0,
+                quality.unintelligible,
+                quality.inconsistent_labels,
+                quality.path.display()
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Quality of a transcript segment and what it is made of.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quality {
+    pub path: PathBuf,
+    /// From 0 for a mess to 100 for nothing to complain about.
+    pub score: u8,
+    /// Timestamps per minute of the segment.
+    pub timestamp_density: f64,
+    /// Share of the non-blank lines that could not be parsed.
+    pub unparsed_ratio: f64,
+    /// Passages marked as unintelligible, like `[unverständlich]`.
+    pub unintelligible: usize,
+    /// Utterances with a speaker label that does not fit the others.
+    pub inconsistent_labels: usize,
+    pub utterances: usize,
+}
+
+impl Quality {
+    pub fn of(path: &Path) -> std::result::Result<Quality, TranscriptError> {
+        Ok(Quality::of_transcript(path, &Transcript::from_file(path)?))
+    }
+
+    pub(crate) fn of_transcript(path: &Path, transcript: &Transcript) -> Quality {
+        let minutes = transcript.interview_end_time().tenths() as f64 / 600.0;
+        let per_minute = |count: usize| {
+            if minutes > 0.0 {
+                count as f64 / minutes
+            } else {
+                0.0
+            }
+        };
+        let records: Vec<_> = transcript.utterance_records().collect();
+        let timestamps = records.iter().map(|record| record.timestamps.len()).sum();
+        let unintelligible = records
+            .iter()
+            .flat_map(|record| annotations(&record.speech))
+            .filter(|annotation| annotation.kind == AnnotationKind::Unintelligible)
+            .count();
+        let lines = transcript
+            .content()
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .count();
+        let unparsed_ratio = transcript.unparsed_lines().len() as f64 / lines.max(1) as f64;
+
+        let declared: Vec<String> = Document::of(transcript)
+            .metadata
+            .speakers
+            .into_iter()
+            .map(|speaker| speaker.code)
+            .collect();
+        let uses = |speaker: &str| {
+            records
+                .iter()
+                .filter(|record| record.speaker == speaker)
+                .count()
+        };
+        let inconsistent_labels = records
+            .iter()
+            .filter(|record| {
+                if declared.is_empty() {
+                    records.len() >= MIN_UTTERANCES_FOR_RARE_LABELS && uses(&record.speaker) == 1
+                } else {
+                    !declared.contains(&record.speaker)
+                }
+            })
+            .count();
+
+        let timestamp_density = per_minute(timestamps);
+        let penalty = |weight: f64, share: f64| weight * share.clamp(0.0, 1.0);
+        let penalties = penalty(30.0, 1.0 - timestamp_density / TARGET_TIMESTAMP_DENSITY)
+            + penalty(30.0, unparsed_ratio / MAX_UNPARSED_RATIO)
+            + penalty(
+                20.0,
+                per_minute(unintelligible) / MAX_UNINTELLIGIBLE_DENSITY,
+            )
+            + penalty(
+                20.0,
+                inconsistent_labels as f64 / records.len().max(1) as f64 / MAX_INCONSISTENT_RATIO,
+            );
+        Quality {
+            path: path.to_path_buf(),
+            score: (100.0 - penalties).round().clamp(0.0, 100.0) as u8,
+            timestamp_density,
+            unparsed_ratio,
+            unintelligible,
+            inconsistent_labels,
+            utterances: records.len(),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "path": self.path.display().to_string(),
+            "score": self.score,
+            "timestamps_per_minute": self.timestamp_density,
+            "unparsed_ratio": self.unparsed_ratio,
+            "unintelligible": self.unintelligible,
+            "inconsistent_labels": self.inconsistent_labels,
+            "utterances": self.utterances,
+        })
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("no transcripts found")]
+    NoTranscripts,
+}
+
+impl Failure for Error {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Error::IO(_) => ExitCode::IO,
+            Error::NoTranscripts => ExitCode::NoInputs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn sloppy_segments_score_lower() {
+        let original = std::fs::read_to_string("testdata/interview-01.rtf").unwrap();
+        let clean = Quality::of(Path::new("testdata/interview-01.rtf")).unwrap();
+        assert_eq!(clean.score, 100, "{:?}", clean);
+
+        let sloppy = original
+            .replace("So daheim", "[unverständlich] So daheim")
+            .replace("Z:}{\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0  Wir", "X:}{ Wir")
+            .replacen(
+                "{\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 \\par}",
+                "{\\b I: Versteh ich nicht.}{\\par}",
+                1,
+            );
+        let transcript = Transcript::try_from(sloppy).unwrap();
+        let quality = Quality::of_transcript(Path::new("sloppy.rtf"), &transcript);
+        assert_eq!(quality.unintelligible, 1);
+        assert_eq!(quality.inconsistent_labels, 1);
+        assert!(quality.unparsed_ratio > 0.0);
+        assert!(quality.score < clean.score, "{:?}", quality);
+    }
+}
@@ -0,0 +1,176 @@
+//! Converts transcripts between F4 RTF, plain text and the JSON
+//! representation, e.g. to bring transcripts typed in a text editor into
+//! the F4 workflow, and writes them in the other output formats of merge,
+//! like subtitles or HTML.
+use std::fmt::{self, Display, Formatter};
+use std::fs::read_to_string;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::args::Convert;
+use crate::atomic::AtomicFile;
+use crate::exit::{ExitCode, Failure};
+use crate::transcript::json::Document;
+use crate::transcript::{
+    format_named, write_transcript_as, Error as TranscriptError, OutputFormat, Text, Transcript,
+    FORMATS,
+};
+
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Format of a transcript to convert.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// F4 RTF.
+    Rtf,
+    /// Plain text with a paragraph per utterance like `I: speech`.
+    Txt,
+    /// The JSON representation.
+    Json,
+    /// One of the other output formats of merge, which can only be
+    /// written.
+    Lines(&'static dyn OutputFormat),
+}
+
+impl Format {
+    /// The format for the extension of the path, if it is one we know.
+    pub(crate) fn of(path: &Path) -> Option<Format> {
+        let extension = path.extension()?.to_string_lossy().to_ascii_lowercase();
+        extension.parse().ok()
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Rtf => "rtf",
+            Format::Txt => "txt",
+            Format::Json => "json",
+            Format::Lines(format) => format.extensions()[0],
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "rtf" => Ok(Format::Rtf),
+            "txt" | "text" => Ok(Format::Txt),
+            "json" => Ok(Format::Json),
+            name => match format_named(name) {
+                Some(format) => Ok(Format::Lines(format)),
+                None => {
+                    let names: Vec<_> = FORMATS
+                        .iter()
+                        .filter(|format| !format.made_from_rtf() && format.name() != Text.name())
+                        .map(|format| format.name())
+                        .collect();
+                    Err(format!(
+                        "unknown format {}, expected rtf, txt, json, {}",
+                        s,
+                        names.join(", ")
+                    ))
+                }
+            },
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Format::Lines(format) => write!(f, "{}", format.name()),
+            _ => write!(f, "{}", self.extension()),
+        }
+    }
+}
+
+pub fn convert(opts: Convert) -> Result<()> {
+    let output_file = convert_to_file(opts)?;
+    println!("{}", output_file.display());
+    Ok(())
+}
+
+/// Converts the transcript and returns the file it was written to.
+pub fn convert_to_file(opts: Convert) -> Result<PathBuf> {
+    let from = match opts.from.or_else(|| Format::of(&opts.input)) {
+        Some(from) => from,
+        None => return Err(Error::UnknownFormat(opts.input)),
+    };
+    let to = opts
+        .to
+        .or_else(|| opts.output_file.as_deref().and_then(Format::of))
+        .unwrap_or(Format::Rtf);
+    if matches!(from, Format::Lines(_)) || from == to {
+        return Err(Error::Unsupported(from, to));
+    }
+    let output_file = opts
+        .output_file
+        .clone()
+        .unwrap_or_else(|| opts.input.with_extension(to.extension()));
+    if output_file.exists() && !opts.force {
+        return Err(Error::OutputExists(output_file));
+    }
+
+    let broken = |err| Error::BrokenTranscript(opts.input.clone(), err);
+    let document = match from {
+        Format::Rtf => Document::of(&Transcript::from_file(&opts.input).map_err(broken)?),
+        Format::Txt => Document::from_text(&read_to_string(&opts.input)?),
+        Format::Json => serde_json::from_str(&read_to_string(&opts.input)?)
+            .map_err(|err| Error::MalformedJson(opts.input.clone(), err))?,
+        Format::Lines(_) => unreachable!("only written"),
+    };
+    let mut file = AtomicFile::create(&output_file)?;
+    match to {
+        Format::Rtf => {
+            // parse what we built so that only valid transcripts are written
+            let transcript = document.to_transcript().map_err(broken)?;
+            write!(&mut file, "{}", transcript)?;
+        }
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut file, &document).map_err(std::io::Error::from)?;
+            writeln!(&mut file)?;
+        }
+        Format::Txt => {
+            let transcript = document.to_transcript().map_err(broken)?;
+            write_transcript_as(&mut file, transcript, &Text).map_err(broken)?;
+        }
+        Format::Lines(format) => {
+            let transcript = document.to_transcript().map_err(broken)?;
+            write_transcript_as(&mut file, transcript, format).map_err(broken)?;
+        }
+    }
+    file.commit()?;
+    Ok(output_file)
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("cannot tell the format of {0}, give it with --from")]
+    UnknownFormat(PathBuf),
+    #[error("cannot convert from {0} to {1}, convert from rtf, txt or json to another format")]
+    Unsupported(Format, Format),
+    #[error("{0} exists, use --force to overwrite")]
+    OutputExists(PathBuf),
+    #[error("{0} is not a transcript in JSON: {1}")]
+    MalformedJson(PathBuf, serde_json::Error),
+    #[error("could not convert {0}: {1}")]
+    BrokenTranscript(PathBuf, TranscriptError),
+}
+
+impl Failure for Error {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Error::IO(_) => ExitCode::IO,
+            Error::UnknownFormat(_) | Error::Unsupported(_, _) => ExitCode::Failure,
+            Error::OutputExists(_) => ExitCode::OutputExists,
+            Error::MalformedJson(_, _) => ExitCode::MalformedTranscript,
+            Error::BrokenTranscript(_, err) => err.exit_code(),
+        }
+    }
+}
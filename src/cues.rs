@@ -0,0 +1,346 @@
+//! Cut points for splitting an interview at its natural sections instead
+//! of at fixed intervals.
+//!
+//! Three formats are understood:
+//! * ffmetadata files as written by `ffmpeg -f ffmetadata`, every chapter
+//!   starts a segment,
+//! * CUE sheets, every `INDEX 01` starts a segment,
+//! * plain lists with one time per line, like `00:12:30`, `750.5` or
+//!   `#00:12:30-0#`, optionally followed by a label.
+//!
+//! Cut points can also be taken from the timestamps of a transcript of the
+//! whole interview, see [`transcript_cues`].
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::exit::{ExitCode, Failure};
+use crate::timestamp::Timestamp;
+use crate::transcript::Transcript;
+
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+const FFMETADATA_HEADER: &str = ";FFMETADATA";
+/// Frames per second in the `mm:ss:ff` times of CUE sheets.
+const CUE_FRAMES_PER_SECOND: u64 = 75;
+
+/// Parses the cut points in the source, in any of the supported formats.
+///
+/// Returns them sorted and without duplicates. A cut point at the very
+/// start is dropped, it would only produce an empty segment.
+pub fn parse_cues(source: &str) -> Result<Vec<Duration>> {
+    let cuts = if source.trim_start().starts_with(FFMETADATA_HEADER) {
+        parse_ffmetadata(source)?
+    } else if source.lines().any(is_cue_sheet_line) {
+        parse_cue_sheet(source)?
+    } else {
+        parse_list(source)?
+    };
+    sorted(cuts)
+}
+
+/// Which timestamps of a transcript to cut at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranscriptCuts {
+    /// The last timestamp of every utterance followed by one of another
+    /// speaker, so that every segment holds the turn of a speaker.
+    SpeakerChanges,
+    /// Every nth timestamp, every one for 1.
+    Every(usize),
+}
+
+impl FromStr for TranscriptCuts {
+    type Err = String;
+
+    /// Parses `speaker-changes`, `all` or `every-N`, e.g. `every-3`.
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let every = s
+            .strip_prefix("every-")
+            .and_then(|n| n.parse().ok())
+            .filter(|&n| n > 0);
+        match (s, every) {
+            ("speaker-changes", _) => Ok(TranscriptCuts::SpeakerChanges),
+            ("all", _) => Ok(TranscriptCuts::Every(1)),
+            (_, Some(n)) => Ok(TranscriptCuts::Every(n)),
+            _ => Err(format!(
+                "unknown cut points {}, expected speaker-changes, all or every-N like every-3",
+                s
+            )),
+        }
+    }
+}
+
+/// Cut points at timestamps of the transcript of a whole interview, so
+/// that the segments correspond to passages of the transcript.
+///
+/// Returns them sorted and without duplicates, like [`parse_cues`].
+pub fn transcript_cues(transcript: &Transcript, cut_at: TranscriptCuts) -> Result<Vec<Duration>> {
+    let records: Vec<_> = transcript.utterance_records().collect();
+    let timestamps: Vec<Timestamp> = match cut_at {
+        TranscriptCuts::SpeakerChanges => records
+            .windows(2)
+            .filter(|pair| pair[0].speaker != pair[1].speaker)
+            .filter_map(|pair| pair[0].timestamps.last().copied())
+            .collect(),
+        TranscriptCuts::Every(n) => records
+            .iter()
+            .flat_map(|record| record.timestamps.iter().copied())
+            .skip(n - 1)
+            .step_by(n)
+            .collect(),
+    };
+    sorted(
+        timestamps
+            .into_iter()
+            .map(|timestamp| Duration::from_millis(timestamp.tenths() * 100))
+            .collect(),
+    )
+}
+
+/// Sorts and deduplicates the cut points and drops one at the very start.
+fn sorted(mut cuts: Vec<Duration>) -> Result<Vec<Duration>> {
+    cuts.sort_unstable();
+    cuts.dedup();
+    cuts.retain(|cut| !cut.is_zero());
+    if cuts.is_empty() {
+        return Err(Error::NoCues);
+    }
+    Ok(cuts)
+}
+
+/// Formats cut points as the comma-separated seconds expected by the
+/// `-segment_times` option of ffmpeg.
+pub fn segment_times(cuts: &[Duration]) -> String {
+    cuts.iter()
+        .map(|cut| format!("{}.{:03}", cut.as_secs(), cut.subsec_millis()))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn is_cue_sheet_line(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("FILE ") || line.starts_with("TRACK ")
+}
+
+fn parse_ffmetadata(source: &str) -> Result<Vec<Duration>> {
+    let mut cuts = vec![];
+    // timebase and start of the chapter that is currently parsed
+    let mut chapter: Option<((u64, u64), Option<u64>)> = None;
+    for (idx, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            if let Some(chapter) = chapter.take() {
+                cuts.push(chapter_start(chapter, idx)?);
+            }
+            // without TIMEBASE, ffmpeg counts in nanoseconds
+            if line == "[CHAPTER]" {
+                chapter = Some(((1, 1_000_000_000), None));
+            }
+            continue;
+        }
+        let chapter = match chapter.as_mut() {
+            Some(chapter) => chapter,
+            None => continue,
+        };
+        let (key, value) = match line.split_once('=') {
+            Some(key_value) => key_value,
+            None => continue,
+        };
+        match key {
+            "TIMEBASE" => {
+                chapter.0 = value
+                    .split_once('/')
+                    .and_then(|(num, den)| Some((num.parse().ok()?, den.parse().ok()?)))
+                    .filter(|&(_, den)| den != 0)
+                    .ok_or_else(|| Error::malformed(idx, line))?
+            }
+            "START" => chapter.1 = Some(value.parse().map_err(|_| Error::malformed(idx, line))?),
+            _ => (),
+        }
+    }
+    if let Some(chapter) = chapter {
+        cuts.push(chapter_start(chapter, source.lines().count())?);
+    }
+    Ok(cuts)
+}
+
+/// Start of a chapter that ended before the line with index `idx`.
+fn chapter_start(((num, den), start): ((u64, u64), Option<u64>), idx: usize) -> Result<Duration> {
+    let start = start.ok_or_else(|| Error::malformed(idx, "[CHAPTER] without START"))?;
+    let millis = u128::from(start) * u128::from(num) * 1000 / u128::from(den);
+    Ok(Duration::from_millis(millis as u64))
+}
+
+fn parse_cue_sheet(source: &str) -> Result<Vec<Duration>> {
+    let mut cuts = vec![];
+    for (idx, line) in source.lines().enumerate() {
+        let mut words = line.split_whitespace();
+        if words.next() != Some("INDEX") || words.next() != Some("01") {
+            continue;
+        }
+        let cut = words
+            .next()
+            .and_then(parse_cue_time)
+            .ok_or_else(|| Error::malformed(idx, line))?;
+        cuts.push(cut);
+    }
+    Ok(cuts)
+}
+
+/// Parses `mm:ss:ff` with frames of 1/75 seconds.
+fn parse_cue_time(time: &str) -> Option<Duration> {
+    let mut parts = time.split(':').map(|part| part.parse::<u64>().ok());
+    let (minutes, seconds, frames) = (parts.next()??, parts.next()??, parts.next()??);
+    if parts.next().is_some() || seconds >= 60 || frames >= CUE_FRAMES_PER_SECOND {
+        return None;
+    }
+    let millis = (minutes * 60 + seconds) * 1000 + frames * 1000 / CUE_FRAMES_PER_SECOND;
+    Some(Duration::from_millis(millis))
+}
+
+fn parse_list(source: &str) -> Result<Vec<Duration>> {
+    let mut cuts = vec![];
+    for (idx, line) in source.lines().enumerate() {
+        let time = match line.split_whitespace().next() {
+            Some(time) => time,
+            None => continue,
+        };
+        let cut = parse_time(time).ok_or_else(|| Error::malformed(idx, line))?;
+        cuts.push(cut);
+    }
+    Ok(cuts)
+}
+
+/// Parses an F4 timestamp or `[[hh:]mm:]ss[.fff]`.
+pub fn parse_time(time: &str) -> Option<Duration> {
+    if time.starts_with('#') {
+        let timestamp = Timestamp::parse(time).ok()?;
+        return Some(Duration::from_millis(timestamp.tenths() * 100));
+    }
+    let (clock, fraction) = match time.split_once('.') {
+        Some((clock, fraction)) => (clock, fraction),
+        None => (time, ""),
+    };
+    let mut seconds = 0u64;
+    for (idx, part) in clock.split(':').enumerate() {
+        let value: u64 = part.parse().ok()?;
+        if idx > 2 || (idx > 0 && value >= 60) {
+            return None;
+        }
+        seconds = seconds * 60 + value;
+    }
+    if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let millis: u64 = format!("{:0<3}", fraction)
+        .get(..3)
+        .and_then(|millis| millis.parse().ok())?;
+    Some(Duration::from_secs(seconds) + Duration::from_millis(millis))
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("unrecognized cue on line {0}: {1}")]
+    Malformed(usize, String),
+    #[error("no cut points found")]
+    NoCues,
+}
+
+impl Error {
+    fn malformed(idx: usize, line: &str) -> Self {
+        Self::Malformed(idx + 1, line.to_string())
+    }
+}
+
+impl Failure for Error {
+    fn exit_code(&self) -> ExitCode {
+        ExitCode::Failure
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn chapters_of_ffmetadata() {
+        let source = ";FFMETADATA1\ntitle=Interview\n\n\
+                      [CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=300000\ntitle=warm-up\n\
+                      [CHAPTER]\nTIMEBASE=1/10\nSTART=3000\nEND=24000\ntitle=main\n\
+                      [CHAPTER]\nTIMEBASE=1/1000\nSTART=2400500\nEND=2700000\ntitle=debrief\n";
+        assert_eq!(
+            parse_cues(source).unwrap(),
+            vec![secs(300), Duration::from_millis(2_400_500)]
+        );
+    }
+
+    #[test]
+    fn chapters_without_timebase_are_in_nanoseconds() {
+        let source = ";FFMETADATA1\n[CHAPTER]\nSTART=0\nEND=90000000000\n\
+                      [CHAPTER]\nSTART=90000000000\nEND=120500000000\n\
+                      [CHAPTER]\nSTART=120500000000\n";
+        assert_eq!(
+            parse_cues(source).unwrap(),
+            vec![secs(90), Duration::from_millis(120_500)]
+        );
+    }
+
+    #[test]
+    fn index_01_of_cue_sheet_tracks() {
+        let source = "FILE \"interview.wav\" WAVE\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n\
+                      \x20 TRACK 02 AUDIO\n    INDEX 00 04:58:00\n    INDEX 01 05:00:15\n";
+        assert_eq!(
+            parse_cues(source).unwrap(),
+            vec![Duration::from_millis(300_200)]
+        );
+    }
+
+    #[test]
+    fn plain_list_with_labels() {
+        let source = "00:00 warm-up\n12:30 main part\n\n#00:40:00-5# debrief\n3000.25\n";
+        assert_eq!(
+            parse_cues(source).unwrap(),
+            vec![
+                secs(750),
+                Duration::from_millis(2_400_500),
+                Duration::from_millis(3_000_250)
+            ]
+        );
+        assert!(matches!(
+            parse_cues("12:30\n12:75 oops\n"),
+            Err(Error::Malformed(2, _))
+        ));
+        assert!(matches!(parse_cues("00:00:00\n"), Err(Error::NoCues)));
+    }
+
+    #[test]
+    fn segment_times_for_ffmpeg() {
+        assert_eq!(
+            segment_times(&[secs(750), Duration::from_millis(2_400_500)]),
+            "750.000,2400.500"
+        );
+    }
+
+    #[test]
+    fn cut_points_from_transcript_timestamps() {
+        let transcript = Transcript::from_file("testdata/interview-02.rtf").unwrap();
+        let tenths = |cuts: Vec<Duration>| -> Vec<u128> {
+            cuts.iter().map(|cut| cut.as_millis() / 100).collect()
+        };
+        assert_eq!(
+            tenths(transcript_cues(&transcript, TranscriptCuts::SpeakerChanges).unwrap()),
+            vec![316, 586, 589, 1106]
+        );
+        assert_eq!(
+            tenths(transcript_cues(&transcript, "every-2".parse().unwrap()).unwrap()),
+            vec![316, 589, 1169]
+        );
+        assert_eq!("all".parse(), Ok(TranscriptCuts::Every(1)));
+        assert!("every-0".parse::<TranscriptCuts>().is_err());
+    }
+}
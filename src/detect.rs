@@ -1,5 +1,7 @@
 //! deals with detection of f4 files and sound files
+use crate::logging::DETECT;
 use crate::timestamp::Timestamp;
+use log::debug;
 use std::ffi::OsStr;
 use std::io;
 use std::path::Path;
@@ -9,7 +11,7 @@ use std::path::Path;
 /// Includes mostly audio formats, but also some video formats.
 ///
 /// There are upper-case and lower-case versions for each.
-const ACCEPTED_FILE_ENDINGS: [&'static str; 16] = [
+const ACCEPTED_FILE_ENDINGS: [&str; 16] = [
     "3gp", "aac", "act", "amr", "avi", "flac", "m4a", "m4b", "mp3", "mp4", "oga", "oog", "vox",
     "wav", "webm", "wma",
 ];
@@ -21,9 +23,18 @@ const ACCEPTED_FILE_ENDINGS: [&'static str; 16] = [
 /// `#00:00:17-5#` in the first 4KiB. This is the default formatting of an
 /// F4 time stamp, other formats are not supported (yet) in f4merge.
 pub fn is_transcript(candidate: &Path) -> Result<bool, io::Error> {
-    Ok(candidate.is_file()
-        && has_rtf_extension(candidate)
-        && Timestamp::contains_timestamps(candidate)?)
+    if !candidate.is_file() || !has_rtf_extension(candidate) {
+        return Ok(false);
+    }
+    let has_timestamps = Timestamp::contains_timestamps(candidate)?;
+    if !has_timestamps {
+        debug!(
+            target: DETECT,
+            "{} is not a transcript, there are no timestamps in the first 4KiB",
+            candidate.display()
+        );
+    }
+    Ok(has_timestamps)
 }
 
 fn has_rtf_extension(candidate: &Path) -> bool {
@@ -0,0 +1,243 @@
+//! Diagnostics that show where a transcript could not be parsed, with the
+//! file, line and a caret under the offending RTF, like compiler errors.
+use std::fmt::{self, Display, Formatter};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::transcript::{Error as TranscriptError, FormatError};
+
+/// Characters of a source line to show at most.
+const MAX_SOURCE_LEN: usize = 80;
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const RESET: &str = "\x1b[0m";
+
+static COLORED: AtomicBool = AtomicBool::new(false);
+
+/// When to color diagnostics and log messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    /// Only if standard error is a terminal and `NO_COLOR` is not set.
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Color::Auto),
+            "always" => Ok(Color::Always),
+            "never" => Ok(Color::Never),
+            _ => Err(format!(
+                "unknown color choice {}, expected auto, always or never",
+                s
+            )),
+        }
+    }
+}
+
+impl Color {
+    /// Decides if diagnostics are colored from now on.
+    pub fn init(self) {
+        let colored = match self {
+            Color::Auto => {
+                std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+            }
+            Color::Always => true,
+            Color::Never => false,
+        };
+        COLORED.store(colored, Ordering::Relaxed);
+    }
+}
+
+/// Wraps the text in the color, if diagnostics are colored.
+pub fn paint(color: &str, text: &str) -> String {
+    if COLORED.load(Ordering::Relaxed) {
+        format!("{}{}{}", color, text, RESET)
+    } else {
+        text.to_string()
+    }
+}
+
+/// `error` in red, if colored.
+pub fn error_label() -> String {
+    paint(RED, "error")
+}
+
+/// A place in a transcript with a note about what is wrong there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    pub path: PathBuf,
+    /// Line, starting at one.
+    pub line: usize,
+    /// Column in characters, starting at one.
+    pub column: usize,
+    /// Number of characters to underline.
+    pub len: usize,
+    /// The whole line.
+    pub source: String,
+    pub note: String,
+}
+
+impl Annotation {
+    /// Points at the part of the transcript that made loading it fail, if
+    /// the error is about a part of it.
+    pub fn of_error(path: &Path, err: &TranscriptError) -> Option<Annotation> {
+        let format = match err {
+            TranscriptError::Format(format) => format,
+            _ => return None,
+        };
+        let content = std::fs::read_to_string(path).ok()?;
+        match format {
+            FormatError::MalformedPreamble => {
+                let first = content.lines().next().unwrap_or_default();
+                Some(Annotation::new(
+                    path,
+                    1,
+                    1,
+                    first.chars().count(),
+                    first,
+                    "no `\\jexpand` line and no paragraph after the RTF header",
+                ))
+            }
+            FormatError::MalformedEpilogue => {
+                let (idx, last) = content
+                    .lines()
+                    .enumerate()
+                    .filter(|(_, line)| !line.trim().is_empty())
+                    .last()?;
+                let trimmed = last.trim_end();
+                let column = if trimmed == "}" {
+                    1
+                } else {
+                    trimmed.chars().count()
+                };
+                Some(Annotation::new(
+                    path,
+                    idx + 1,
+                    column,
+                    1,
+                    last,
+                    "expected the transcript to end with a line break and a line with only `}`",
+                ))
+            }
+            FormatError::NoTimestampsFound => None,
+        }
+    }
+
+    pub fn new(
+        path: &Path,
+        line: usize,
+        column: usize,
+        len: usize,
+        source: &str,
+        note: &str,
+    ) -> Annotation {
+        Annotation {
+            path: path.to_path_buf(),
+            line,
+            column,
+            len,
+            source: source.trim_end_matches(['\r', '\n']).to_string(),
+            note: note.to_string(),
+        }
+    }
+}
+
+/// Renders the location and source line on lines of their own, starting
+/// with a line break so that it can follow a message.
+impl Display for Annotation {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let number = self.line.to_string();
+        let gutter = " ".repeat(number.len());
+        let bar = paint(BLUE, "|");
+        // shorten long lines around the column
+        let skip = self.column.saturating_sub(MAX_SOURCE_LEN / 2);
+        let shown: String = self
+            .source
+            .chars()
+            .skip(skip)
+            .take(MAX_SOURCE_LEN)
+            .collect();
+        let prefix = if skip > 0 { "..." } else { "" };
+        let caret_indent = prefix.len() + self.column - 1 - skip;
+        let len = self.len.clamp(1, MAX_SOURCE_LEN);
+
+        write!(
+            f,
+            "\n{}{} {}:{}:{}",
+            gutter,
+            paint(BLUE, "-->"),
+            self.path.display(),
+            self.line,
+            self.column
+        )?;
+        write!(f, "\n{} {}", gutter, bar)?;
+        write!(f, "\n{} {} {}{}", paint(BLUE, &number), bar, prefix, shown)?;
+        write!(
+            f,
+            "\n{} {} {}{}",
+            gutter,
+            bar,
+            " ".repeat(caret_indent),
+            paint(RED, &format!("{} {}", "^".repeat(len), self.note))
+        )
+    }
+}
+
+/// An annotation if there is one, nothing otherwise.
+#[derive(Debug)]
+pub struct MaybeAnnotation(pub Option<Box<Annotation>>);
+
+impl Display for MaybeAnnotation {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match &self.0 {
+            Some(annotation) => annotation.fmt(f),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn caret_points_at_the_column() {
+        let annotation = Annotation::new(
+            Path::new("a.rtf"),
+            12,
+            5,
+            3,
+            "{\\f0 Z: Hallo\\par\r\n",
+            "here",
+        );
+        assert_eq!(
+            annotation.to_string(),
+            "\n  --> a.rtf:12:5\n   |\n12 | {\\f0 Z: Hallo\\par\n   |     ^^^ here"
+        );
+    }
+
+    #[test]
+    fn broken_epilogue_is_located() {
+        let path = std::env::temp_dir().join("f4tapir-diagnostic.rtf");
+        let original = std::fs::read_to_string("testdata/interview-01.rtf").unwrap();
+        let broken = original.trim_end().trim_end_matches('}').to_string() + "\\par x\r\n";
+        std::fs::write(&path, &broken).unwrap();
+
+        let err = crate::transcript::Transcript::from_file(&path)
+            .err()
+            .unwrap();
+        let annotation = Annotation::of_error(&path, &err).unwrap();
+        assert_eq!(annotation.line, broken.lines().count());
+        assert_eq!(annotation.source, "\\par x");
+        assert_eq!(annotation.column, 6);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
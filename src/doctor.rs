@@ -0,0 +1,215 @@
+//! Checks of the environment f4tapir runs in, with hints how to fix what
+//! is wrong, for the usual "it does not work" before anything was split.
+use std::convert::TryFrom;
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+
+use crate::args::Doctor;
+use crate::atomic::AtomicFile;
+use crate::exit::{ExitCode, Failure};
+use crate::transcript::Transcript;
+
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Transcript that is known to parse, to check the parser itself.
+const SAMPLE_TRANSCRIPT: &str = include_str!("../testdata/interview-01.rtf");
+
+/// Outcome of a single check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: String,
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// Fine, with details like a version.
+    Ok(String),
+    /// Not fine, with what is wrong and how to fix it.
+    Problem { cause: String, fix: String },
+}
+
+pub fn doctor(opts: Doctor) -> Result<()> {
+    let mut checks = vec![tool("ffmpeg"), tool("ffprobe")];
+    checks.extend(opts.output_directory.iter().map(|dir| writable(dir)));
+    checks.push(sample_transcript());
+    checks.extend(opts.transcripts.iter().map(|path| transcript(path)));
+
+    let mut stdout = std::io::stdout().lock();
+    for check in &checks {
+        check.write(&mut stdout)?;
+    }
+    let problems = checks
+        .iter()
+        .filter(|check| matches!(check.outcome, Outcome::Problem { .. }))
+        .count();
+    if problems > 0 {
+        return Err(Error::Problems(problems));
+    }
+    Ok(())
+}
+
+impl Check {
+    fn write<W: Write>(&self, mut to: W) -> std::io::Result<()> {
+        match &self.outcome {
+            Outcome::Ok(details) => writeln!(&mut to, "ok       {}: {}", self.name, details),
+            Outcome::Problem { cause, fix } => {
+                writeln!(&mut to, "PROBLEM  {}: {}", self.name, cause)?;
+                writeln!(&mut to, "         fix: {}", fix)
+            }
+        }
+    }
+}
+
+fn ok(name: &str, details: String) -> Check {
+    Check {
+        name: name.to_string(),
+        outcome: Outcome::Ok(details),
+    }
+}
+
+fn problem(name: &str, cause: String, fix: &str) -> Check {
+    Check {
+        name: name.to_string(),
+        outcome: Outcome::Problem {
+            cause,
+            fix: fix.to_string(),
+        },
+    }
+}
+
+/// Checks that the tool can be started, reporting its version.
+fn tool(name: &str) -> Check {
+    let fix = "install ffmpeg, which includes ffprobe, and make sure it is on the PATH, \
+               e.g. with `brew install ffmpeg`, `apt install ffmpeg` or from https://ffmpeg.org";
+    match Command::new(name).arg("-version").output() {
+        Ok(output) if output.status.success() => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            ok(name, version_line(&stdout).to_string())
+        }
+        Ok(output) => problem(
+            name,
+            format!("`{} -version` failed with {}", name, output.status),
+            fix,
+        ),
+        Err(err) => problem(name, format!("could not be started: {}", err), fix),
+    }
+}
+
+/// First line of `-version` output, like `ffmpeg version 6.0 Copyright…`,
+/// without the copyright.
+fn version_line(output: &str) -> &str {
+    let line = output.lines().next().unwrap_or_default();
+    line.split(" Copyright").next().unwrap_or(line).trim()
+}
+
+/// Checks that files can be created in the directory.
+fn writable(dir: &Path) -> Check {
+    let name = format!("output directory {}", dir.display());
+    if !dir.is_dir() {
+        return problem(
+            &name,
+            String::from("does not exist or is not a directory"),
+            "create it or pass another directory with --output-directory",
+        );
+    }
+    // dropped without committing, which removes it again
+    match AtomicFile::create(dir.join(".f4tapir-doctor")) {
+        Ok(_) => ok(&name, String::from("writable")),
+        Err(err) => problem(
+            &name,
+            format!("cannot create files: {}", err),
+            "grant write permissions or choose another output directory",
+        ),
+    }
+}
+
+/// Checks that the built-in sample transcript parses.
+fn sample_transcript() -> Check {
+    let name = "transcript parser";
+    match Transcript::try_from(String::from(SAMPLE_TRANSCRIPT)) {
+        Ok(transcript) => ok(name, utterance_summary(&transcript)),
+        Err(err) => problem(
+            name,
+            format!("could not parse the sample transcript: {}", err),
+            "this is a bug in f4tapir, please report it",
+        ),
+    }
+}
+
+/// Checks that the given transcript parses completely.
+fn transcript(path: &Path) -> Check {
+    let name = format!("transcript {}", path.display());
+    match Transcript::from_file(path) {
+        Ok(transcript) => {
+            let unparsed = transcript.unparsed_lines().len();
+            if unparsed == 0 {
+                ok(&name, utterance_summary(&transcript))
+            } else {
+                problem(
+                    &name,
+                    format!("{} lines could not be parsed", unparsed),
+                    "run `f4tapir merge` on it to see which, and save it again with F4",
+                )
+            }
+        }
+        Err(err) => problem(
+            &name,
+            format!("could not be loaded: {}", err),
+            "check that it is an RTF transcript saved by F4 or f4x",
+        ),
+    }
+}
+
+fn utterance_summary(transcript: &Transcript) -> String {
+    format!(
+        "{} utterances, ends at {}",
+        transcript.utterance_records().count(),
+        transcript.interview_end_time()
+    )
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("found {0} problems")]
+    Problems(usize),
+}
+
+impl Failure for Error {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Error::IO(_) => ExitCode::IO,
+            Error::Problems(_) => ExitCode::Failure,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn version_without_copyright() {
+        let output = "ffmpeg version 6.0 Copyright (c) 2000-2023 the FFmpeg developers\n\
+                      built with gcc 12\n";
+        assert_eq!(version_line(output), "ffmpeg version 6.0");
+    }
+
+    #[test]
+    fn sample_and_missing_directory() {
+        assert!(matches!(sample_transcript().outcome, Outcome::Ok(_)));
+        assert!(matches!(
+            writable(Path::new("testdata/does-not-exist")).outcome,
+            Outcome::Problem { .. }
+        ));
+        assert!(matches!(
+            writable(&std::env::temp_dir()).outcome,
+            Outcome::Ok(_)
+        ));
+    }
+}
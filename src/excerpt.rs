@@ -0,0 +1,356 @@
+//! Cuts the audio of single utterances out of an interview, so that quotes
+//! can be listened to again before they are published.
+//!
+//! F4 timestamps mark where an utterance ends, so the audio of an
+//! utterance runs from the timestamp before it to its last one.
+//!
+//! With a list of quotes, the utterances are located by their text, see
+//! [`crate::quotes`].
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use crate::args::Excerpt;
+use crate::cues::parse_time;
+use crate::exit::{ExitCode, Failure};
+use crate::logging::SPLIT;
+use crate::paths::command_arg;
+use crate::quotes::{locate_quotes, parse_quotes};
+use crate::timestamp::Timestamp;
+use crate::transcript::{Error as TranscriptError, Transcript};
+
+use log::debug;
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// File extension of clips without an explicit output file.
+const CLIP_EXTENSION: &str = "mp3";
+
+pub fn excerpt(opts: Excerpt) -> Result<()> {
+    if !opts.padding.is_finite() || opts.padding < 0.0 {
+        return Err(Error::Padding(opts.padding));
+    }
+    let transcript = Transcript::from_file(&opts.transcript)
+        .map_err(|err| Error::TranscriptLoadFail(opts.transcript.clone(), err))?;
+    let passages = passages(&transcript);
+    let padding = Duration::from_secs_f64(opts.padding);
+    if let Some(quotes) = &opts.quotes {
+        if opts.utterance.is_some() || opts.at.is_some() || opts.output_file.is_some() {
+            return Err(Error::SelectionOptions);
+        }
+        return excerpt_quotes(&opts, quotes, &passages, padding);
+    }
+    if opts.clips_dir.is_some() {
+        return Err(Error::ClipsDirWithoutQuotes);
+    }
+    let passage = match (opts.utterance, &opts.at) {
+        (Some(number), None) => number
+            .checked_sub(1)
+            .and_then(|idx| passages.get(idx))
+            .ok_or(Error::NoUtterance(number, passages.len()))?,
+        (None, Some(at)) => {
+            let time = parse_time(at).ok_or_else(|| Error::Time(at.clone()))?;
+            passages
+                .iter()
+                .find(|passage| time <= passage.end_time())
+                .ok_or_else(|| Error::NoUtteranceAt(at.clone()))?
+        }
+        _ => return Err(Error::SelectionOptions),
+    };
+    let output_file = opts.output_file.clone().unwrap_or_else(|| {
+        let stem = opts.transcript.file_stem().unwrap_or_default();
+        PathBuf::from(format!(
+            "{}-{:03}.{}",
+            stem.to_string_lossy(),
+            passage.number,
+            CLIP_EXTENSION
+        ))
+    });
+    if output_file.exists() && !opts.force {
+        return Err(Error::OutputExists(output_file));
+    }
+
+    cut_clip(
+        &opts.audio,
+        passage.start_time().saturating_sub(padding),
+        passage.end_time() + padding,
+        &output_file,
+    )?;
+    let mut stdout = std::io::stdout().lock();
+    writeln!(
+        &mut stdout,
+        "{} {}-{}  {}",
+        passage.number,
+        passage.start,
+        passage.end,
+        output_file.display()
+    )?;
+    writeln!(&mut stdout, "{}: {}", passage.speaker, passage.speech)?;
+    Ok(())
+}
+
+/// Locates the quotes and prints the utterances they are in, with the
+/// ffmpeg commands to cut clips of them or after cutting the clips.
+fn excerpt_quotes(
+    opts: &Excerpt,
+    quotes: &Path,
+    passages: &[Passage],
+    padding: Duration,
+) -> Result<()> {
+    let source = std::fs::read_to_string(quotes)
+        .map_err(|err| Error::QuotesLoadFail(quotes.to_path_buf(), err))?;
+    let located = locate_quotes(parse_quotes(quotes, &source), passages);
+    if located.is_empty() {
+        return Err(Error::NoQuotes(quotes.to_path_buf()));
+    }
+    if let Some(dir) = &opts.clips_dir {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let mut stdout = std::io::stdout().lock();
+    let mut missing = 0;
+    for (idx, located) in located.iter().enumerate() {
+        let range = match &located.passages {
+            Some(range) => range,
+            None => {
+                missing += 1;
+                writeln!(
+                    &mut stdout,
+                    "quote {}: not found: {}",
+                    idx + 1,
+                    located.quote
+                )?;
+                writeln!(&mut stdout)?;
+                continue;
+            }
+        };
+        let (first, last) = (&passages[*range.start()], &passages[*range.end()]);
+        let name = format!("quote-{:03}.{}", idx + 1, CLIP_EXTENSION);
+        let output_file = match &opts.clips_dir {
+            Some(dir) => dir.join(name),
+            None => PathBuf::from(name),
+        };
+        writeln!(
+            &mut stdout,
+            "quote {}: utterances {}-{}, {}-{}",
+            idx + 1,
+            first.number,
+            last.number,
+            first.start,
+            last.end
+        )?;
+        for passage in &passages[range.clone()] {
+            writeln!(&mut stdout, "  {}: {}", passage.speaker, passage.speech)?;
+        }
+        let start = first.start_time().saturating_sub(padding);
+        let end = last.end_time() + padding;
+        if opts.clips_dir.is_some() {
+            if output_file.exists() && !opts.force {
+                return Err(Error::OutputExists(output_file));
+            }
+            cut_clip(&opts.audio, start, end, &output_file)?;
+            writeln!(&mut stdout, "  {}", output_file.display())?;
+        } else {
+            let command: Vec<String> = std::iter::once(OsString::from("ffmpeg"))
+                .chain(clip_args(&opts.audio, start, end, &output_file))
+                .map(|arg| shell_quoted(&arg.to_string_lossy()))
+                .collect();
+            writeln!(&mut stdout, "  {}", command.join(" "))?;
+        }
+        writeln!(&mut stdout)?;
+    }
+    if missing > 0 {
+        return Err(Error::QuotesNotFound(missing, located.len()));
+    }
+    Ok(())
+}
+
+/// The argument in single quotes for POSIX shells, unless it is safe
+/// without.
+fn shell_quoted(arg: &str) -> String {
+    let safe = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=+,".contains(c));
+    if safe {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// An utterance with the time span of its audio.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Passage {
+    /// Number of the utterance in the transcript, starting at 1.
+    pub number: usize,
+    pub speaker: String,
+    /// Plain text of the speech.
+    pub speech: String,
+    /// The timestamp before the utterance, or zero for the first one.
+    pub start: Timestamp,
+    /// The last timestamp of the utterance, or the next timestamp after
+    /// it if it has none.
+    pub end: Timestamp,
+}
+
+impl Passage {
+    pub fn start_time(&self) -> Duration {
+        Duration::from_millis(self.start.tenths() * 100)
+    }
+
+    pub fn end_time(&self) -> Duration {
+        Duration::from_millis(self.end.tenths() * 100)
+    }
+}
+
+/// The utterances of the transcript with the time spans of their audio.
+/// Utterances after the last timestamp end where they start.
+pub fn passages(transcript: &Transcript) -> Vec<Passage> {
+    let records: Vec<_> = transcript.utterance_records().collect();
+    let mut passages = Vec::with_capacity(records.len());
+    let mut start = Timestamp::default();
+    for (idx, record) in records.iter().enumerate() {
+        let end = records[idx..]
+            .iter()
+            .find_map(|record| record.timestamps.last().copied())
+            .unwrap_or(start);
+        passages.push(Passage {
+            number: idx + 1,
+            speaker: record.speaker.clone(),
+            speech: record.speech.clone(),
+            start,
+            end,
+        });
+        if let Some(&last) = record.timestamps.last() {
+            start = last;
+        }
+    }
+    passages
+}
+
+/// Arguments for ffmpeg to cut the audio from `start` to `end` into the
+/// output file, replacing it if it exists.
+fn clip_args(audio: &Path, start: Duration, end: Duration, output_file: &Path) -> Vec<OsString> {
+    let duration = end.saturating_sub(start);
+    vec![
+        OsString::from("-hide_banner"),
+        OsString::from("-nostdin"),
+        OsString::from("-y"),
+        OsString::from("-ss"),
+        OsString::from(format!("{:.3}", start.as_secs_f64())),
+        OsString::from("-i"),
+        command_arg(audio).into_owned(),
+        OsString::from("-t"),
+        OsString::from(format!("{:.3}", duration.as_secs_f64())),
+        OsString::from("-vn"),
+        command_arg(output_file).into_owned(),
+    ]
+}
+
+/// Cuts the audio from `start` to `end` into the output file with ffmpeg,
+/// replacing it if it exists.
+pub fn cut_clip(audio: &Path, start: Duration, end: Duration, output_file: &Path) -> Result<()> {
+    let args = clip_args(audio, start, end, output_file);
+    debug!(
+        target: SPLIT,
+        "running ffmpeg {}",
+        args.iter()
+            .map(|arg| arg.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+    let status = Command::new("ffmpeg")
+        .args(args)
+        .status()
+        .map_err(Error::FfmpegIo)?;
+    if !status.success() {
+        return Err(Error::FfmpegStatus);
+    }
+    Ok(())
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("failed to load transcript {0}: {1}")]
+    TranscriptLoadFail(PathBuf, TranscriptError),
+    #[error("give one of --utterance, --at or --quotes, and --output-file only without --quotes")]
+    SelectionOptions,
+    #[error("--clips-dir only applies to --quotes")]
+    ClipsDirWithoutQuotes,
+    #[error("failed to read quotes from {0}: {1}")]
+    QuotesLoadFail(PathBuf, std::io::Error),
+    #[error("no quotes in {0}")]
+    NoQuotes(PathBuf),
+    #[error("{0} of {1} quotes were not found in the transcript")]
+    QuotesNotFound(usize, usize),
+    #[error("there is no utterance {0}, the transcript has {1}")]
+    NoUtterance(usize, usize),
+    #[error("no utterance at {0}, it is after the last timestamp")]
+    NoUtteranceAt(String),
+    #[error("unrecognized time {0}, expected one like #00:12:30-4# or 00:12:30")]
+    Time(String),
+    #[error("padding must be zero or more seconds, got {0}")]
+    Padding(f64),
+    #[error("clip {0} exists, use --force to overwrite")]
+    OutputExists(PathBuf),
+    #[error("failed to run ffmpeg: {0}")]
+    FfmpegIo(std::io::Error),
+    #[error("ffmpeg failed to cut the clip")]
+    FfmpegStatus,
+}
+
+impl Failure for Error {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Error::IO(_) | Error::QuotesLoadFail(_, _) => ExitCode::IO,
+            Error::TranscriptLoadFail(_, err) => err.exit_code(),
+            Error::SelectionOptions
+            | Error::ClipsDirWithoutQuotes
+            | Error::NoQuotes(_)
+            | Error::QuotesNotFound(_, _)
+            | Error::NoUtterance(_, _)
+            | Error::NoUtteranceAt(_)
+            | Error::Time(_)
+            | Error::Padding(_) => ExitCode::Failure,
+            Error::OutputExists(_) => ExitCode::OutputExists,
+            Error::FfmpegIo(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                ExitCode::FfmpegMissing
+            }
+            Error::FfmpegIo(_) => ExitCode::IO,
+            Error::FfmpegStatus => ExitCode::FfmpegFailed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn passages_span_from_the_previous_timestamp() {
+        let transcript = Transcript::from_file("testdata/interview-02.rtf").unwrap();
+        let passages = passages(&transcript);
+        let spans: Vec<(u64, u64)> = passages
+            .iter()
+            .map(|passage| (passage.start.tenths(), passage.end.tenths()))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![(0, 316), (316, 586), (586, 589), (589, 1106), (1106, 1169)]
+        );
+        assert_eq!(passages[1].number, 2);
+        assert_eq!(passages[1].speaker, "I");
+    }
+
+    #[test]
+    fn printed_commands_are_quoted_for_the_shell() {
+        assert_eq!(shell_quoted("quote-001.mp3"), "quote-001.mp3");
+        assert_eq!(shell_quoted("it's here.mp3"), "'it'\\''s here.mp3'");
+    }
+}
@@ -0,0 +1,37 @@
+//! Process exit codes for the different classes of failures, so
+//! wrapper scripts can branch on the failure instead of parsing
+//! error messages.
+
+/// Exit code of the process, by class of failure.
+///
+/// Keep in sync with the `error_code` attributes in `args`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExitCode {
+    /// Any failure that has no more specific code, including
+    /// invalid command line arguments.
+    Failure = 1,
+    /// No interviews, segments or transcripts were found in the
+    /// input paths.
+    NoInputs = 2,
+    /// ffmpeg could not be started, probably it is not installed.
+    FfmpegMissing = 3,
+    /// ffmpeg was started, but reported an error.
+    FfmpegFailed = 4,
+    /// An output file exists and `--force` was not given.
+    OutputExists = 5,
+    /// A transcript does not have the expected F4 RTF format.
+    MalformedTranscript = 6,
+    /// Reading or writing files failed.
+    IO = 7,
+}
+
+impl ExitCode {
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+}
+
+/// Errors that can tell which class of failure they belong to.
+pub trait Failure {
+    fn exit_code(&self) -> ExitCode;
+}
@@ -1,33 +1,226 @@
+use crate::archive::{is_archive, transcripts_in};
 use crate::detect::{is_sound_file, is_transcript};
+use crate::glob::Glob;
+use crate::logging::FIND;
+use crate::renumber::{segment_name, segment_number};
+use log::{debug, trace};
+use std::collections::BTreeMap;
 use std::env::current_dir;
+use std::fmt::{self, Display, Formatter};
 use std::io::Result;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// Examines input files and directories and returns the relevant ones,
 /// in lexicographical order of filenames.
 ///
-/// Only transcripts in F4 RTF format are considered relevant.
+/// Only transcripts in F4 RTF format are considered relevant, including
+/// those in ZIP archives, see [`crate::archive`].
 pub fn collect_transcripts(from: Vec<PathBuf>, recursive: bool) -> Result<Vec<PathBuf>> {
-    find(from, recursive, |p| is_transcript(p))
+    find(from, recursive, true, |p| is_transcript(p))
+}
+
+/// Like `collect_transcripts`, but treats a directory with segments named
+/// after it, as written by `split --per-interview-dirs`, as one interview.
+///
+/// Only the segments of such a directory are collected, e.g.
+/// `foo/foo-000.rtf` and `foo/foo-001.rtf`, but not a merged `foo/foo.rtf`
+/// or other transcripts next to them.
+pub fn collect_interview_transcripts(from: Vec<PathBuf>, recursive: bool) -> Result<Vec<PathBuf>> {
+    let from = if from.is_empty() {
+        vec![current_dir()?]
+    } else {
+        from
+    };
+    let mut found = vec![];
+    for input in from {
+        let mut transcripts = collect_transcripts(vec![input.clone()], recursive)?;
+        let name = input
+            .file_name()
+            .filter(|_| input.is_dir())
+            .and_then(|name| name.to_str());
+        if let Some(name) = name {
+            let directly_in_input = |path: &PathBuf| path.parent() == Some(input.as_path());
+            let is_segment = |path: &PathBuf| directly_in_input(path) && is_segment_of(path, name);
+            if transcripts.iter().any(is_segment) {
+                debug!(
+                    target: FIND,
+                    "{} has segments named after it, ignoring other transcripts in it",
+                    input.display()
+                );
+                transcripts.retain(|path| !directly_in_input(path) || is_segment(path));
+            }
+        }
+        found.extend(transcripts);
+    }
+    found.sort_unstable();
+    Ok(found)
+}
+
+/// Checks if the file is named like a segment of the interview with the
+/// given name, e.g. `foo-000.rtf` for `foo`.
+fn is_segment_of(path: &Path, interview: &str) -> bool {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| stem.strip_prefix(interview))
+        .and_then(|rest| rest.strip_prefix('-'))
+        .map(|number| number.len() >= 3 && number.bytes().all(|b| b.is_ascii_digit()))
+        .unwrap_or(false)
+}
+
+/// Segments that are missing from a numbered sequence of transcripts,
+/// like `interview-004.rtf` between `interview-003.rtf` and
+/// `interview-005.rtf`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentGap {
+    /// The transcript before the missing segments.
+    pub after: PathBuf,
+    /// The transcript after them.
+    pub before: PathBuf,
+    /// Numbers of the missing segments.
+    pub missing: Vec<u32>,
+}
+
+impl SegmentGap {
+    /// Paths the missing segments would have, numbered like the
+    /// transcript before them, e.g. `a/foo-003.rtf` after `a/foo-002.rtf`.
+    pub fn missing_paths(&self) -> Vec<PathBuf> {
+        let stem = self
+            .after
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let name = stem.trim_end_matches(|c: char| c.is_ascii_digit());
+        let width = stem.len() - name.len();
+        let extension = self
+            .after
+            .extension()
+            .map(|ext| format!(".{}", ext.to_string_lossy()))
+            .unwrap_or_default();
+        self.missing
+            .iter()
+            .map(|number| {
+                self.after.with_file_name(format!(
+                    "{}{:0width$}{}",
+                    name,
+                    number,
+                    extension,
+                    width = width
+                ))
+            })
+            .collect()
+    }
+}
+
+impl Display for SegmentGap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let numbers: Vec<String> = self.missing.iter().map(u32::to_string).collect();
+        write!(
+            f,
+            "{} {} missing between {} and {}",
+            if self.missing.len() == 1 {
+                "segment"
+            } else {
+                "segments"
+            },
+            numbers.join(", "),
+            self.after.display(),
+            self.before.display()
+        )
+    }
+}
+
+/// Finds the holes in the numbering of the segments of every interview,
+/// that is of transcripts in the same directory with the same name before
+/// their segment number.
+///
+/// Only numbers between the lowest and the highest found are missing,
+/// segments before the first or after the last one cannot be told apart
+/// from numbering that starts at one or an interview that ends there.
+pub fn segment_gaps(paths: &[PathBuf]) -> Vec<SegmentGap> {
+    let mut interviews: BTreeMap<(PathBuf, String), Vec<(u32, &PathBuf)>> = BTreeMap::new();
+    for path in paths {
+        let stem = match path.file_stem() {
+            Some(stem) => stem.to_string_lossy(),
+            None => continue,
+        };
+        if let Some(number) = segment_number(&stem) {
+            let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+            interviews
+                .entry((dir, segment_name(&stem).to_string()))
+                .or_default()
+                .push((number, path));
+        }
+    }
+    let mut gaps = vec![];
+    for mut segments in interviews.into_values() {
+        segments.sort_unstable();
+        for pair in segments.windows(2) {
+            let ((after, after_path), (before, before_path)) = (pair[0], pair[1]);
+            if before > after + 1 {
+                gaps.push(SegmentGap {
+                    after: after_path.clone(),
+                    before: before_path.clone(),
+                    missing: (after + 1..before).collect(),
+                });
+            }
+        }
+    }
+    gaps
+}
+
+/// Which of the found files to keep, by `--include` and `--exclude`.
+#[derive(Debug, Clone, Default)]
+pub struct PathFilter {
+    /// If not empty, only files matching one of these are kept.
+    pub include: Vec<Glob>,
+    /// Files matching any of these are skipped.
+    pub exclude: Vec<Glob>,
+}
+
+impl PathFilter {
+    pub fn new(include: Vec<Glob>, exclude: Vec<Glob>) -> Self {
+        PathFilter { include, exclude }
+    }
+
+    pub fn accepts(&self, path: &Path) -> bool {
+        if let Some(glob) = self.exclude.iter().find(|glob| glob.matches(path)) {
+            debug!(target: FIND, "excluding {}, it matches {}", path.display(), glob);
+            return false;
+        }
+        let included =
+            self.include.is_empty() || self.include.iter().any(|glob| glob.matches(path));
+        if !included {
+            debug!(target: FIND, "excluding {}, it matches no --include", path.display());
+        }
+        included
+    }
+
+    /// Removes the paths that are not accepted.
+    pub fn apply(&self, paths: &mut Vec<PathBuf>) {
+        paths.retain(|path| self.accepts(path));
+    }
 }
 
 /// Collect files that sound like interview filenames, e.g. mp3 files.
 pub fn collect_interviews(from: Vec<PathBuf>, recursive: bool) -> Result<Vec<PathBuf>> {
-    find(from, recursive, |p| Ok(is_sound_file(p)))
+    find(from, recursive, false, |p| Ok(is_sound_file(p)))
 }
 
+/// Finds the files matching the predicate, and the transcripts in ZIP
+/// archives if `archives` is set.
 fn find<F: Fn(&PathBuf) -> Result<bool>>(
     from: Vec<PathBuf>,
     recursive: bool,
+    archives: bool,
     mut predicate: F,
 ) -> Result<Vec<PathBuf>> {
     let mut found = vec![];
     if from.is_empty() {
         // default to current working directory if no paths specified
-        add_where(&mut found, current_dir()?, recursive, predicate)?;
+        add_where(&mut found, current_dir()?, recursive, archives, predicate)?;
     } else {
         for input in from {
-            predicate = add_where(&mut found, input, recursive, predicate)?;
+            predicate = add_where(&mut found, input, recursive, archives, predicate)?;
         }
     }
     found.sort_unstable();
@@ -38,18 +231,97 @@ fn add_where<F: Fn(&PathBuf) -> Result<bool>>(
     into: &mut Vec<PathBuf>,
     input: PathBuf,
     recursive: bool,
+    archives: bool,
     mut predicate: F,
 ) -> Result<F> {
     if input.is_dir() {
         for entry in input.read_dir()? {
             let entry = entry?.path();
             if entry.is_file() || (entry.is_dir() && recursive) {
-                predicate = add_where(into, entry, recursive, predicate)?
+                predicate = add_where(into, entry, recursive, archives, predicate)?
             }
         }
+    } else if archives && input.is_file() && is_archive(&input) {
+        into.extend(transcripts_in(&input)?);
     } else if input.is_file() && predicate(&input)? {
+        debug!(target: FIND, "found {}", input.display());
         into.push(input)
+    } else {
+        trace!(target: FIND, "skipping {}", input.display());
     }
 
     Ok(predicate)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn interview_directory_contributes_only_its_segments() {
+        let dir = std::env::temp_dir().join("f4tapir-find").join("foo");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for name in &["foo-000.rtf", "foo-001.rtf", "foo.rtf", "notes.rtf"] {
+            fs::copy("testdata/interview-01.rtf", dir.join(name)).unwrap();
+        }
+
+        let found = collect_interview_transcripts(vec![dir.clone()], false).unwrap();
+
+        assert_eq!(
+            found,
+            vec![dir.join("foo-000.rtf"), dir.join("foo-001.rtf")]
+        );
+        assert_eq!(collect_transcripts(vec![dir], false).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn holes_in_segment_numbers_are_gaps() {
+        let paths: Vec<PathBuf> = [
+            "a/foo-001.rtf",
+            "a/foo-002.rtf",
+            "a/foo-005.rtf",
+            "a/bar-000.rtf",
+            "b/foo-004.rtf",
+            "a/foo.rtf",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+        assert_eq!(
+            segment_gaps(&paths),
+            vec![SegmentGap {
+                after: PathBuf::from("a/foo-002.rtf"),
+                before: PathBuf::from("a/foo-005.rtf"),
+                missing: vec![3, 4],
+            }]
+        );
+        assert_eq!(
+            segment_gaps(&paths)[0].to_string(),
+            "segments 3, 4 missing between a/foo-002.rtf and a/foo-005.rtf"
+        );
+        assert_eq!(
+            segment_gaps(&paths)[0].missing_paths(),
+            vec![
+                PathBuf::from("a/foo-003.rtf"),
+                PathBuf::from("a/foo-004.rtf")
+            ]
+        );
+    }
+
+    #[test]
+    fn excludes_win_over_includes() {
+        let filter = PathFilter::new(
+            vec!["interview-*".parse().unwrap()],
+            vec!["*-draft.rtf".parse().unwrap()],
+        );
+        let mut paths = vec![
+            PathBuf::from("a/interview-000.rtf"),
+            PathBuf::from("a/interview-000-draft.rtf"),
+            PathBuf::from("a/notes.rtf"),
+        ];
+        filter.apply(&mut paths);
+        assert_eq!(paths, vec![PathBuf::from("a/interview-000.rtf")]);
+    }
+}
@@ -0,0 +1,425 @@
+//! Synthesizes F4 transcripts with made-up speech, for testing merges at
+//! scale and for reproducing bug reports without sharing confidential
+//! interviews.
+//!
+//! The same options and seed always generate the same transcripts.
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::args::Generate;
+use crate::atomic::AtomicFile;
+use crate::exit::{ExitCode, Failure};
+use crate::timestamp::Timestamp;
+use crate::transcript::{Dialect, TranscriptBuilder};
+
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+const PREAMBLE: &str = "{\\rtf1\\ansi{\\fonttbl{\\f0\\fnil Arial;}}\r\n\
+                        {\\colortbl\\red0\\green0\\blue0;}\r\n\
+                        {\\*\\generator f4tapir}\\paperw11906\\paperh16838\
+                        \\margl1417\\margr1417\\margt1417\\margb1134\r\n";
+const JEXPAND: &str = "\\jexpand\r\n";
+const EPILOGUE: &str = "\r\n}";
+
+/// Words that speech is made of, ASCII only so that they need no escaping.
+const WORDS: &str = "also ja nein genau ich wir sie das ist war haben hatten damals immer \
+                     schon noch nicht wirklich eigentlich vielleicht Musik Konzert Band \
+                     Stadt Jahre Leute Szene Platten gespielt gesagt gemacht gedacht \
+                     irgendwie ziemlich oft mal dann so und aber";
+
+pub fn generate(opts: Generate) -> Result<()> {
+    if !opts.output_dir.is_dir() {
+        return Err(Error::NoOutputDir(opts.output_dir));
+    }
+    let fixture = Fixture {
+        speakers: opts.speakers.max(1),
+        duration: Timestamp::from_tenths(opts.duration.saturating_mul(10))
+            .ok_or(Error::Duration(opts.duration))?,
+        timestamp_every: opts.timestamp_every.max(1),
+        quirks: opts.quirk.clone(),
+        seed: opts.seed,
+    };
+
+    let paths: Vec<PathBuf> = (0..opts.segments)
+        .map(|index| {
+            opts.output_dir
+                .join(format!("{}-{:03}.rtf", opts.name, index + 1))
+        })
+        .collect();
+    if !opts.force {
+        if let Some(existing) = paths.iter().find(|path| path.exists()) {
+            return Err(Error::OutputExists(existing.clone()));
+        }
+    }
+    let mut stdout = std::io::stdout().lock();
+    for (index, path) in paths.iter().enumerate() {
+        let mut file = AtomicFile::create(path)?;
+        file.write_all(fixture.segment(index).as_bytes())?;
+        file.commit()?;
+        writeln!(&mut stdout, "{}", path.display())?;
+    }
+    Ok(())
+}
+
+/// Something unusual about generated transcripts that real ones have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quirk {
+    /// Paragraphs like `\pard ... \par` instead of groups.
+    Pard,
+    /// `\n` instead of `\r\n` line breaks.
+    Lf,
+    /// No `\jexpand` at the end of the preamble, like f5transkript.
+    NoJexpand,
+    /// Labels typed like `B :` or `B:  `.
+    SpacedLabels,
+    /// Some timestamps typed like `#00.01.23-4#`.
+    LenientTimestamps,
+    /// Some utterances continue in a paragraph without speaker label.
+    Unlabeled,
+    /// Some words in bold.
+    Formatted,
+}
+
+impl Quirk {
+    /// Quirks in the markup that [`TranscriptBuilder`] does not write, so
+    /// transcripts with them are written by hand.
+    const MARKUP: [Quirk; 3] = [Quirk::Pard, Quirk::SpacedLabels, Quirk::Formatted];
+
+    /// Every quirk, e.g. to generate transcripts with any of them.
+    pub const ALL: [Quirk; 7] = [
+        Quirk::Pard,
+        Quirk::Lf,
+        Quirk::NoJexpand,
+        Quirk::SpacedLabels,
+        Quirk::LenientTimestamps,
+        Quirk::Unlabeled,
+        Quirk::Formatted,
+    ];
+}
+
+impl FromStr for Quirk {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "pard" => Ok(Quirk::Pard),
+            "lf" => Ok(Quirk::Lf),
+            "no-jexpand" => Ok(Quirk::NoJexpand),
+            "spaced-labels" => Ok(Quirk::SpacedLabels),
+            "lenient-timestamps" => Ok(Quirk::LenientTimestamps),
+            "unlabeled" => Ok(Quirk::Unlabeled),
+            "formatted" => Ok(Quirk::Formatted),
+            _ => Err(format!(
+                "unknown quirk {}, expected pard, lf, no-jexpand, spaced-labels, \
+                 lenient-timestamps, unlabeled or formatted",
+                s
+            )),
+        }
+    }
+}
+
+/// What generated transcripts look like.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fixture {
+    /// Number of speakers, the interviewer `I` included.
+    pub speakers: usize,
+    /// Length of every segment.
+    pub duration: Timestamp,
+    /// Average seconds between timestamps.
+    pub timestamp_every: u64,
+    pub quirks: Vec<Quirk>,
+    pub seed: u64,
+}
+
+impl Fixture {
+    /// Transcript of the segment with the zero-based index, with
+    /// timestamps from the start of the segment to its end.
+    ///
+    /// Transcripts are built like any other with [`TranscriptBuilder`],
+    /// unless they have quirks in their markup, which it never writes.
+    pub fn segment(&self, index: usize) -> String {
+        let mut rng = Rng::new(self.seed ^ (index as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        let paragraphs = self.paragraphs(&mut rng);
+        let rtf = if Quirk::MARKUP.iter().any(|&quirk| self.has(quirk)) {
+            self.marked_up(&paragraphs)
+        } else {
+            built(&paragraphs)
+        };
+        let rtf = if self.has(Quirk::NoJexpand) {
+            rtf.replacen(JEXPAND, "", 1)
+        } else {
+            rtf
+        };
+        if self.has(Quirk::Lf) {
+            rtf.replace("\r\n", "\n")
+        } else {
+            rtf
+        }
+    }
+
+    /// Utterances up to the end of the segment, some followed by a
+    /// paragraph without label if `Quirk::Unlabeled` is set.
+    fn paragraphs(&self, rng: &mut Rng) -> Vec<Paragraph> {
+        let speakers = self.speaker_codes();
+        let mut paragraphs = vec![];
+        let end = self.duration.tenths();
+        let every = self.timestamp_every * 10;
+        let mut time = 0;
+        let mut turn = 0;
+        while time < end {
+            time = (time + rng.between(every / 2, every * 3 / 2).max(1)).min(end);
+            let speaker = if turn % 2 == 0 || speakers.len() == 1 {
+                &speakers[0]
+            } else {
+                &speakers[1 + rng.below(speakers.len() as u64 - 1) as usize]
+            };
+            turn += 1;
+            let label = if self.has(Quirk::SpacedLabels) && rng.below(3) == 0 {
+                [" :", ":  "][rng.below(2) as usize]
+            } else {
+                ":"
+            };
+            let timestamp = Timestamp::from_tenths(time).expect("within the duration");
+            let timestamp = if self.has(Quirk::LenientTimestamps) && rng.below(4) == 0 {
+                timestamp.to_string().replace(':', ".")
+            } else {
+                timestamp.to_string()
+            };
+            paragraphs.push(Paragraph {
+                speaker: Some((speaker.clone(), label)),
+                text: format!("{} {}", self.speech(rng), timestamp),
+            });
+            if self.has(Quirk::Unlabeled) && rng.below(4) == 0 {
+                paragraphs.push(Paragraph {
+                    speaker: None,
+                    text: self.speech(rng),
+                });
+            }
+        }
+        paragraphs
+    }
+
+    /// The paragraphs written by hand, with an empty paragraph after
+    /// every utterance like F4 does.
+    fn marked_up(&self, paragraphs: &[Paragraph]) -> String {
+        let dialect = if self.has(Quirk::Pard) {
+            Dialect::Pard
+        } else {
+            Dialect::Group
+        };
+        let mut rtf = vec![];
+        rtf.extend_from_slice(PREAMBLE.as_bytes());
+        rtf.extend_from_slice(JEXPAND.as_bytes());
+        for paragraph in paragraphs {
+            let text = match &paragraph.speaker {
+                Some((speaker, label)) => format!("{}{} {}", speaker, label, paragraph.text),
+                None => paragraph.text.clone(),
+            };
+            dialect
+                .write_paragraph(&mut rtf, &text)
+                .expect("writing to memory");
+            if paragraph.speaker.is_some() {
+                dialect
+                    .write_paragraph(&mut rtf, "")
+                    .expect("writing to memory");
+            }
+        }
+        rtf.extend_from_slice(EPILOGUE.as_bytes());
+        String::from_utf8(rtf).expect("only ASCII is written")
+    }
+
+    fn has(&self, quirk: Quirk) -> bool {
+        self.quirks.contains(&quirk)
+    }
+
+    /// `I` and `B`, or `I`, `B1`, `B2` and so on for more speakers.
+    fn speaker_codes(&self) -> Vec<String> {
+        let mut codes = vec![String::from("I")];
+        match self.speakers {
+            0 | 1 => (),
+            2 => codes.push(String::from("B")),
+            speakers => codes.extend((1..speakers).map(|n| format!("B{}", n))),
+        }
+        codes
+    }
+
+    /// One to three sentences of made-up words.
+    fn speech(&self, rng: &mut Rng) -> String {
+        let vocabulary: Vec<&str> = WORDS.split_whitespace().collect();
+        let sentences: Vec<String> = (0..rng.between(1, 3))
+            .map(|_| {
+                let words: Vec<String> = (0..rng.between(3, 12))
+                    .map(|_| {
+                        let word = vocabulary[rng.below(vocabulary.len() as u64) as usize];
+                        if self.has(Quirk::Formatted) && rng.below(20) == 0 {
+                            format!("{{\\b {}}}", word)
+                        } else {
+                            word.to_string()
+                        }
+                    })
+                    .collect();
+                let mut sentence = words.join(" ");
+                sentence[..1].make_ascii_uppercase();
+                sentence.push(['.', '?', '.'][rng.below(3) as usize]);
+                sentence
+            })
+            .collect();
+        sentences.join(" ")
+    }
+}
+
+/// A generated paragraph, an utterance if it has a speaker and the label
+/// after the speaker code.
+struct Paragraph {
+    speaker: Option<(String, &'static str)>,
+    text: String,
+}
+
+/// The paragraphs as utterances and notes of a transcript, which has
+/// plain labels, groups per paragraph and `\r\n` line breaks.
+fn built(paragraphs: &[Paragraph]) -> String {
+    let mut builder = TranscriptBuilder::new();
+    for paragraph in paragraphs {
+        builder = match &paragraph.speaker {
+            Some((speaker, _)) => builder.utterance(speaker, &paragraph.text),
+            None => builder.note(&paragraph.text),
+        };
+    }
+    // not built, which fails if all timestamps are lenient ones
+    builder.document().to_rtf()
+}
+
+/// Small xorshift generator, so that fixtures do not depend on a crate
+/// whose output could change between versions.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        Rng(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Number in `0..n`, `n` must not be zero.
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    /// Number in `low..=high`.
+    fn between(&mut self, low: u64, high: u64) -> u64 {
+        low + self.below(high.saturating_sub(low) + 1)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("output directory {0} does not exist")]
+    NoOutputDir(PathBuf),
+    #[error("{0} exists, use --force to overwrite")]
+    OutputExists(PathBuf),
+    #[error("segments of {0} seconds are too long")]
+    Duration(u64),
+}
+
+impl Failure for Error {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Error::IO(_) => ExitCode::IO,
+            Error::NoOutputDir(_) => ExitCode::IO,
+            Error::OutputExists(_) => ExitCode::OutputExists,
+            Error::Duration(_) => ExitCode::Failure,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::transcript::{write_merged_transcript, MergeOptions, Transcript};
+    use std::convert::TryFrom;
+
+    fn fixture(quirks: Vec<Quirk>) -> Fixture {
+        Fixture {
+            speakers: 3,
+            duration: Timestamp::from_tenths(3000).unwrap(),
+            timestamp_every: 20,
+            quirks,
+            seed: 7,
+        }
+    }
+
+    #[test]
+    fn generated_segments_parse_and_merge() {
+        let fixture = fixture(vec![]);
+        assert_eq!(fixture.segment(0), fixture.segment(0));
+        assert_ne!(fixture.segment(0), fixture.segment(1));
+
+        let transcripts: Vec<Transcript> = (0..2)
+            .map(|index| Transcript::try_from(fixture.segment(index)).unwrap())
+            .collect();
+        assert_eq!(transcripts[0].interview_end_time(), fixture.duration);
+        assert!(transcripts[0].unparsed_lines().is_empty());
+        let speakers: Vec<String> = transcripts[0]
+            .utterance_records()
+            .map(|record| record.speaker)
+            .collect();
+        assert!(speakers.iter().any(|speaker| speaker == "B2"));
+
+        let mut merged = vec![];
+        write_merged_transcript(&mut merged, transcripts, &MergeOptions::default()).unwrap();
+        assert!(String::from_utf8(merged).unwrap().contains("#00:10:00-0#"));
+    }
+
+    #[test]
+    fn quirks_end_up_in_the_transcript() {
+        let all = vec![
+            Quirk::Pard,
+            Quirk::Lf,
+            Quirk::NoJexpand,
+            Quirk::SpacedLabels,
+            Quirk::LenientTimestamps,
+            Quirk::Unlabeled,
+        ];
+        let rtf = fixture(all).segment(0);
+        assert!(!rtf.contains('\r'));
+        assert!(!rtf.contains("\\jexpand"));
+
+        let transcript = Transcript::try_from(rtf).unwrap();
+        assert_eq!(transcript.dialect(), Dialect::Pard);
+        assert!(transcript.has_lenient_timestamps());
+        assert!(transcript.content().contains(" :"));
+    }
+
+    #[test]
+    fn quirks_outside_the_markup_apply_to_built_transcripts() {
+        let plain = fixture(vec![]).segment(0);
+        assert!(plain.contains("\\jexpand\r\n"));
+        assert!(plain.contains("#SPEAKER_LIST_START#"));
+
+        let quirks = vec![
+            Quirk::Lf,
+            Quirk::NoJexpand,
+            Quirk::LenientTimestamps,
+            Quirk::Unlabeled,
+        ];
+        let rtf = fixture(quirks).segment(0);
+        assert!(rtf.contains("#SPEAKER_LIST_START#"));
+        assert!(!rtf.contains('\r'));
+        assert!(!rtf.contains("\\jexpand"));
+
+        let transcript = Transcript::try_from(rtf).unwrap();
+        assert_eq!(transcript.dialect(), Dialect::Group);
+        assert!(transcript.has_lenient_timestamps());
+        assert_eq!(transcript.interview_end_time(), fixture(vec![]).duration);
+    }
+}
@@ -0,0 +1,155 @@
+//! Shell-like wildcard patterns for `--include` and `--exclude`.
+//!
+//! `*` matches any run of characters except `/`, `**` also across `/`,
+//! `?` a single character and `[abc]`, `[a-z]` or `[!abc]` one of a set
+//! of characters. Patterns without a `/` are matched against the file
+//! name only, others against the whole path with `/` as separator.
+use std::fmt::{self, Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+    pattern: Vec<char>,
+    /// Matched against the whole path, not just the file name.
+    whole_path: bool,
+}
+
+impl Glob {
+    pub fn matches(&self, path: &Path) -> bool {
+        let candidate = if self.whole_path {
+            path.to_string_lossy().replace('\\', "/")
+        } else {
+            match path.file_name() {
+                Some(name) => name.to_string_lossy().into_owned(),
+                None => return false,
+            }
+        };
+        let candidate: Vec<char> = candidate.chars().collect();
+        matches(&self.pattern, &candidate)
+    }
+}
+
+fn matches(pattern: &[char], candidate: &[char]) -> bool {
+    match pattern.split_first() {
+        None => candidate.is_empty(),
+        Some(('*', rest)) => {
+            let (crosses_separators, rest) = match rest.split_first() {
+                Some(('*', rest)) => (true, rest),
+                _ => (false, rest),
+            };
+            for skip in 0..=candidate.len() {
+                if matches(rest, &candidate[skip..]) {
+                    return true;
+                }
+                if !crosses_separators && candidate.get(skip) == Some(&'/') {
+                    return false;
+                }
+            }
+            false
+        }
+        Some(('?', rest)) => match candidate.split_first() {
+            Some((&c, candidate)) if c != '/' => matches(rest, candidate),
+            _ => false,
+        },
+        Some(('[', rest)) => {
+            let end = rest
+                .iter()
+                .skip(1)
+                .position(|&c| c == ']')
+                .map(|end| end + 1)
+                .expect("unclosed set in validated glob");
+            let (set, rest) = (&rest[..end], &rest[end + 1..]);
+            match candidate.split_first() {
+                Some((&c, candidate)) if in_set(set, c) => matches(rest, candidate),
+                _ => false,
+            }
+        }
+        Some((&literal, rest)) => match candidate.split_first() {
+            Some((&c, candidate)) if c == literal => matches(rest, candidate),
+            _ => false,
+        },
+    }
+}
+
+/// Checks if the character is in a set like `abc`, `a-z` or `!abc`.
+fn in_set(set: &[char], c: char) -> bool {
+    let (negated, set) = match set.split_first() {
+        Some(('!', set)) => (true, set),
+        _ => (false, set),
+    };
+    let mut found = false;
+    let mut idx = 0;
+    while idx < set.len() {
+        if idx + 2 < set.len() && set[idx + 1] == '-' {
+            found |= set[idx] <= c && c <= set[idx + 2];
+            idx += 3;
+        } else {
+            found |= set[idx] == c;
+            idx += 1;
+        }
+    }
+    found != negated
+}
+
+impl FromStr for Glob {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let pattern: Vec<char> = s.chars().collect();
+        let mut idx = 0;
+        while idx < pattern.len() {
+            if pattern[idx] == '[' {
+                // a ] right after the [ is part of the set
+                match pattern[idx + 1..].iter().skip(1).position(|&c| c == ']') {
+                    Some(end) => idx += end + 2,
+                    None => return Err(format!("unclosed [ in pattern {}", s)),
+                }
+            }
+            idx += 1;
+        }
+        Ok(Glob {
+            whole_path: s.contains('/'),
+            pattern,
+        })
+    }
+}
+
+impl Display for Glob {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.pattern.iter().collect::<String>())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn glob(pattern: &str) -> Glob {
+        pattern.parse().unwrap()
+    }
+
+    #[test]
+    fn wildcards_match_file_names() {
+        assert!(glob("*-draft.rtf").matches(Path::new("dir/interview-draft.rtf")));
+        assert!(!glob("*-draft.rtf").matches(Path::new("dir/interview.rtf")));
+        assert!(glob("interview-00?.rtf").matches(Path::new("interview-001.rtf")));
+        assert!(glob("*.[Bb][Aa][Kk]").matches(Path::new("a.Bak")));
+        assert!(glob("[!.]*").matches(Path::new("visible.rtf")));
+        assert!(!glob("[!.]*").matches(Path::new(".hidden.rtf")));
+        assert!(glob("seg-[0-4]*").matches(Path::new("seg-3.rtf")));
+    }
+
+    #[test]
+    fn patterns_with_slashes_match_whole_paths() {
+        assert!(glob("old/*").matches(Path::new("old/a.rtf")));
+        assert!(!glob("old/*").matches(Path::new("old/sub/a.rtf")));
+        assert!(glob("**/backup/**").matches(Path::new("x/backup/y/a.rtf")));
+    }
+
+    #[test]
+    fn unclosed_sets_are_rejected() {
+        assert!("a[bc".parse::<Glob>().is_err());
+        assert!("a[]]".parse::<Glob>().is_ok());
+    }
+}
@@ -0,0 +1,298 @@
+//! Minimal native window for merging transcripts, for people that
+//! never touch a terminal.
+//!
+//! Segments are added via drag and drop, then merged in the background
+//! with the same library functions the `merge` subcommand uses.
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use eframe::egui;
+
+use crate::atomic::AtomicFile;
+use crate::find::collect_transcripts;
+use crate::manifest::plan_from_manifests;
+use crate::merge::Timing;
+use crate::timestamp::Timestamp;
+use crate::transcript::{segment_shifts, write_merged_transcript, MergeOptions, Transcript};
+
+/// Opens the merge window and blocks until it is closed.
+pub fn run() -> eframe::Result<()> {
+    let options = eframe::NativeOptions::default();
+    eframe::run_native(
+        "f4tapir",
+        options,
+        Box::new(|_| Ok(Box::new(MergeApp::default()))),
+    )
+}
+
+/// A dropped transcript, or the reason why it could not be loaded.
+struct Segment {
+    path: PathBuf,
+    transcript: Result<Transcript, String>,
+}
+
+/// Progress of a merge running on a background thread.
+#[derive(Default)]
+struct Progress {
+    /// Number of transcripts already written.
+    done: AtomicUsize,
+    total: usize,
+    /// Set when the merge thread has finished.
+    outcome: Mutex<Option<Result<(), String>>>,
+}
+
+#[derive(Default)]
+struct MergeApp {
+    segments: Vec<Segment>,
+    /// Shift and end time of every loaded segment, or why they cannot be
+    /// found, updated whenever the segments change.
+    preview: Option<Result<Vec<(Timestamp, Timestamp)>, String>>,
+    output_file: String,
+    force: bool,
+    progress: Option<Arc<Progress>>,
+}
+
+impl MergeApp {
+    fn add_dropped(&mut self, dropped: Vec<PathBuf>) {
+        let paths = match collect_transcripts(dropped, true) {
+            Ok(paths) => paths,
+            Err(err) => {
+                self.finish_with(Err(format!("could not read dropped files: {}", err)));
+                return;
+            }
+        };
+        for path in paths {
+            if self.segments.iter().any(|s| s.path == path) {
+                continue;
+            }
+            let transcript = Transcript::from_file(&path).map_err(|e| e.to_string());
+            self.segments.push(Segment { path, transcript });
+        }
+        self.segments.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+        self.update_preview();
+
+        if self.output_file.is_empty() {
+            if let Some(first) = self.segments.first() {
+                self.output_file = first
+                    .path
+                    .with_file_name("merged.rtf")
+                    .display()
+                    .to_string();
+            }
+        }
+    }
+
+    /// The loaded transcripts with the end times and offsets that `merge`
+    /// would give them, from the manifest if `split` left one.
+    fn timed_transcripts(&self) -> Result<Vec<Transcript>, String> {
+        let loaded: Vec<(&PathBuf, &Transcript)> = self
+            .segments
+            .iter()
+            .filter_map(|s| s.transcript.as_ref().ok().map(|t| (&s.path, t)))
+            .collect();
+        let paths: Vec<PathBuf> = loaded.iter().map(|(path, _)| path.to_path_buf()).collect();
+        let manifest = plan_from_manifests(&paths)
+            .map(|plan| plan.end_times)
+            .unwrap_or_default();
+        let timing = Timing::new(&paths, manifest, None, None).map_err(|e| e.to_string())?;
+        loaded
+            .into_iter()
+            .map(|(path, transcript)| {
+                timing
+                    .apply(transcript.clone(), path)
+                    .map_err(|e| e.to_string())
+            })
+            .collect()
+    }
+
+    /// Finds the shifts of the segments the way the merge does.
+    fn update_preview(&mut self) {
+        let preview = self.timed_transcripts().and_then(|transcripts| {
+            let shifts = segment_shifts(&transcripts, &MergeOptions::default())
+                .map_err(|e| e.to_string())?;
+            Ok(shifts
+                .into_iter()
+                .zip(transcripts.iter().map(Transcript::interview_end_time))
+                .collect())
+        });
+        self.preview = Some(preview);
+    }
+
+    fn is_merging(&self) -> bool {
+        self.progress
+            .as_ref()
+            .map(|p| p.outcome.lock().unwrap().is_none())
+            .unwrap_or(false)
+    }
+
+    fn finish_with(&mut self, outcome: Result<(), String>) {
+        let progress = Progress {
+            outcome: Mutex::new(Some(outcome)),
+            ..Progress::default()
+        };
+        self.progress = Some(Arc::new(progress));
+    }
+
+    fn start_merge(&mut self, ctx: &egui::Context) {
+        let transcripts = match self.timed_transcripts() {
+            Ok(transcripts) => transcripts,
+            Err(err) => {
+                self.finish_with(Err(err));
+                return;
+            }
+        };
+        if transcripts.is_empty() {
+            self.finish_with(Err("no transcripts found for merging".into()));
+            return;
+        }
+
+        let output_file = PathBuf::from(self.output_file.trim());
+        if output_file.exists() && !self.force {
+            self.finish_with(Err(format!(
+                "output file {} exists, check overwrite to replace it",
+                output_file.display()
+            )));
+            return;
+        }
+
+        let progress = Arc::new(Progress {
+            total: transcripts.len(),
+            ..Progress::default()
+        });
+        self.progress = Some(Arc::clone(&progress));
+
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let outcome = AtomicFile::create(&output_file)
+                .map_err(|e| e.to_string())
+                .and_then(|mut file| {
+                    let counted = transcripts.into_iter().inspect(|_| {
+                        progress.done.fetch_add(1, Ordering::Relaxed);
+                        ctx.request_repaint();
+                    });
+                    write_merged_transcript(&mut file, counted, &MergeOptions::default())
+                        .map_err(|e| e.to_string())?;
+                    file.commit().map_err(|e| e.to_string())
+                });
+            *progress.outcome.lock().unwrap() = Some(outcome);
+            ctx.request_repaint();
+        });
+    }
+
+    fn boundary_preview(&mut self, ui: &mut egui::Ui) {
+        let mut timed = match &self.preview {
+            Some(Ok(preview)) => preview.iter(),
+            _ => [].iter(),
+        };
+        let mut remove = None;
+        egui::Grid::new("segments").striped(true).show(ui, |ui| {
+            ui.strong("Segment");
+            ui.strong("Shift");
+            ui.strong("End");
+            ui.label("");
+            ui.end_row();
+
+            for (idx, segment) in self.segments.iter().enumerate() {
+                let name = segment
+                    .path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                ui.label(name);
+                match (&segment.transcript, &self.preview) {
+                    (Ok(_), Some(Err(err))) => {
+                        ui.colored_label(egui::Color32::RED, "unknown");
+                        ui.colored_label(egui::Color32::RED, err);
+                    }
+                    (Ok(_), _) => {
+                        let (shift, end_time) = timed.next().copied().unwrap_or_default();
+                        ui.monospace(shift.to_string());
+                        ui.monospace(end_time.to_string());
+                    }
+                    (Err(err), _) => {
+                        ui.colored_label(egui::Color32::RED, "skipped");
+                        ui.colored_label(egui::Color32::RED, err);
+                    }
+                }
+                if ui.small_button("remove").clicked() {
+                    remove = Some(idx);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(idx) = remove {
+            self.segments.remove(idx);
+            self.update_preview();
+        }
+    }
+}
+
+impl eframe::App for MergeApp {
+    fn ui(&mut self, ui: &mut egui::Ui, _frame: &mut eframe::Frame) {
+        let dropped: Vec<PathBuf> = ui.ctx().input(|i| {
+            i.raw
+                .dropped_files
+                .iter()
+                .map(|f| f.path().to_path_buf())
+                .collect()
+        });
+        if !dropped.is_empty() && !self.is_merging() {
+            self.add_dropped(dropped);
+        }
+
+        egui::CentralPanel::default_margins().show(ui, |ui| {
+            ui.heading("Merge F4 transcripts");
+            ui.label("Drop transcript segments or folders into this window.");
+            ui.separator();
+
+            egui::ScrollArea::vertical()
+                .max_height(ui.available_height() - 120.0)
+                .show(ui, |ui| self.boundary_preview(ui));
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                ui.label("Output file:");
+                ui.text_edit_singleline(&mut self.output_file);
+            });
+            ui.checkbox(&mut self.force, "overwrite the output file if it exists");
+
+            let merging = self.is_merging();
+            ui.horizontal(|ui| {
+                let can_merge = !merging && !self.output_file.trim().is_empty();
+                if ui
+                    .add_enabled(can_merge, egui::Button::new("Merge"))
+                    .clicked()
+                {
+                    self.start_merge(ui.ctx());
+                }
+                if ui
+                    .add_enabled(!merging, egui::Button::new("Clear"))
+                    .clicked()
+                {
+                    self.segments.clear();
+                    self.preview = None;
+                    self.progress = None;
+                }
+            });
+
+            if let Some(progress) = &self.progress {
+                if progress.total > 0 {
+                    let done = progress.done.load(Ordering::Relaxed);
+                    let fraction = done as f32 / progress.total as f32;
+                    ui.add(egui::ProgressBar::new(fraction).show_percentage());
+                }
+                match &*progress.outcome.lock().unwrap() {
+                    Some(Ok(())) => {
+                        ui.label(format!("Merged into {}", self.output_file.trim()));
+                    }
+                    Some(Err(err)) => {
+                        ui.colored_label(egui::Color32::RED, format!("error: {}", err));
+                    }
+                    None => (),
+                }
+            }
+        });
+    }
+}
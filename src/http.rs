@@ -0,0 +1,398 @@
+//! HTTP API of `serve --http`, for a shared machine with ffmpeg that the
+//! team uploads segments to, merges them on and downloads the results
+//! from. All files are in a single data directory:
+//!
+//! * `GET /files`: the names of the files, as a JSON array.
+//! * `PUT /files/NAME`: uploads a file, replacing one with the same name.
+//! * `GET /files/NAME`: downloads a file.
+//! * `POST /merge` with `{"inputs": [NAME…], "output": NAME, "args": […]}`:
+//!   merges the transcripts, with further switches of `merge`.
+//! * `POST /split` with `{"inputs": [NAME…], "args": […]}`: splits the
+//!   interviews into segments in the data directory, with further
+//!   switches of `split`.
+//!
+//! Only the switches in [`MERGE_SWITCHES`] and [`SPLIT_SWITCHES`] are
+//! accepted as `args`, no options with values or further inputs, so that
+//! requests cannot read or write files outside of the data directory.
+//!
+//! Failures are answered like `{"error": {"code": 5, "message": "…"}}`
+//! with the exit code of the command. Requests are not authenticated, so
+//! only serve on a network whose users are trusted.
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::args::{Merge, Split};
+use crate::atomic::AtomicFile;
+use crate::exit::{ExitCode, Failure};
+use crate::logging::SERVE;
+use crate::remote::percent_decoded;
+use crate::{merge, split};
+
+use argh::FromArgs;
+use log::{info, warn};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tiny_http::{Header, Response, Server};
+
+/// Answers requests on the address, like `0.0.0.0:8080`, until the
+/// process is stopped.
+pub fn serve_http(address: &str, data_dir: &Path) -> Result<(), String> {
+    let server = Server::http(address).map_err(|err| err.to_string())?;
+    info!(
+        target: SERVE,
+        "serving HTTP on {} for files in {}",
+        address,
+        data_dir.display()
+    );
+    for mut request in server.incoming_requests() {
+        let method = request.method().as_str().to_string();
+        let url = request.url().to_string();
+        let reply = handle(&method, &url, request.as_reader(), data_dir);
+        info!(target: SERVE, "{} {} {}", method, url, reply.status);
+        let responded = match reply.body {
+            Body::Json(json) => request.respond(
+                Response::from_string(json.to_string())
+                    .with_status_code(reply.status)
+                    .with_header(json_header()),
+            ),
+            Body::File(file) => request.respond(Response::from_file(file)),
+        };
+        if let Err(err) = responded {
+            warn!(target: SERVE, "could not answer {} {}: {}", method, url, err);
+        }
+    }
+    Ok(())
+}
+
+fn json_header() -> Header {
+    "Content-Type: application/json"
+        .parse()
+        .expect("header is valid")
+}
+
+/// What to answer a request with.
+#[derive(Debug)]
+pub struct Reply {
+    pub status: u16,
+    pub body: Body,
+}
+
+#[derive(Debug)]
+pub enum Body {
+    Json(Value),
+    File(File),
+}
+
+impl Reply {
+    fn json(status: u16, json: Value) -> Reply {
+        Reply {
+            status,
+            body: Body::Json(json),
+        }
+    }
+
+    fn error(status: u16, code: ExitCode, message: impl ToString) -> Reply {
+        Reply::json(
+            status,
+            json!({"error": {"code": code.code(), "message": message.to_string()}}),
+        )
+    }
+
+    /// The failure of a command, with a status by its exit code.
+    fn failed<E: Failure + ToString>(err: E) -> Reply {
+        let code = err.exit_code();
+        let status = match code {
+            ExitCode::Failure | ExitCode::NoInputs => 400,
+            ExitCode::OutputExists => 409,
+            ExitCode::MalformedTranscript => 422,
+            ExitCode::FfmpegMissing | ExitCode::FfmpegFailed | ExitCode::IO => 500,
+        };
+        Reply::error(status, code, err)
+    }
+}
+
+/// Body of `POST /merge` and `POST /split`.
+#[derive(Deserialize)]
+struct CommandBody {
+    inputs: Vec<String>,
+    #[serde(default)]
+    output: Option<String>,
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+/// Answers the request for the URL with the body, for files in the data
+/// directory.
+pub fn handle(method: &str, url: &str, body: &mut dyn Read, data_dir: &Path) -> Reply {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let result = match (method, path) {
+        ("GET", "/files") => list(data_dir),
+        ("GET", _) | ("PUT", _) if path.starts_with("/files/") => {
+            file_in(data_dir, &path["/files/".len()..]).and_then(|file| {
+                if method == "GET" {
+                    download(&file)
+                } else {
+                    upload(&file, body)
+                }
+            })
+        }
+        ("POST", "/merge") => command_body(body).and_then(|body| run_merge(body, data_dir)),
+        ("POST", "/split") => command_body(body).and_then(|body| run_split(body, data_dir)),
+        _ => Err(Reply::error(
+            404,
+            ExitCode::Failure,
+            format!("no endpoint {} {}", method, path),
+        )),
+    };
+    result.unwrap_or_else(|reply| reply)
+}
+
+/// The file with the name in the data directory, if the name does not
+/// lead out of it.
+fn file_in(data_dir: &Path, name: &str) -> Result<PathBuf, Reply> {
+    let name = percent_decoded(name);
+    let unsafe_name = name.is_empty()
+        || name == "."
+        || name == ".."
+        || name.contains(['/', '\\'])
+        || name.chars().any(char::is_control);
+    if unsafe_name {
+        return Err(Reply::error(
+            400,
+            ExitCode::Failure,
+            format!("{} is not a file name", name),
+        ));
+    }
+    Ok(data_dir.join(name))
+}
+
+fn list(data_dir: &Path) -> Result<Reply, Reply> {
+    let io_failure = |err: io::Error| Reply::error(500, ExitCode::IO, err);
+    let mut names = vec![];
+    for entry in fs::read_dir(data_dir).map_err(io_failure)? {
+        let entry = entry.map_err(io_failure)?;
+        if entry.file_type().map_err(io_failure)?.is_file() {
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    names.sort_unstable();
+    Ok(Reply::json(200, json!(names)))
+}
+
+fn download(file: &Path) -> Result<Reply, Reply> {
+    match File::open(file) {
+        Ok(opened) if file.is_file() => Ok(Reply {
+            status: 200,
+            body: Body::File(opened),
+        }),
+        _ => Err(Reply::error(
+            404,
+            ExitCode::NoInputs,
+            format!("no file {}", file.display()),
+        )),
+    }
+}
+
+fn upload(file: &Path, body: &mut dyn Read) -> Result<Reply, Reply> {
+    let io_failure = |err: io::Error| Reply::error(500, ExitCode::IO, err);
+    let mut atomic = AtomicFile::create(file).map_err(io_failure)?;
+    io::copy(body, &mut atomic).map_err(io_failure)?;
+    atomic.commit().map_err(io_failure)?;
+    let name = file.file_name().unwrap_or_default().to_string_lossy();
+    Ok(Reply::json(201, json!({ "file": name })))
+}
+
+fn command_body(body: &mut dyn Read) -> Result<CommandBody, Reply> {
+    serde_json::from_reader(body).map_err(|err| Reply::error(400, ExitCode::Failure, err))
+}
+
+/// Switches of `merge` that requests may give, none of them takes a
+/// value or reads or writes files other than the inputs and the output.
+pub const MERGE_SWITCHES: &[&str] = &[
+    "--force",
+    "--lenient-timestamps",
+    "--fail-on-unparsed",
+    "--paranoid",
+    "--strip-revisions",
+    "--bom",
+    "--no-stitch",
+    "--mark-boundaries",
+    "--tidy",
+    "--color-speakers",
+    "--toc",
+    "--line-numbers",
+    "--move-trailers",
+    "--strip-trailers",
+    "--strip-segment-headers",
+    "--drop-boundary-duplicates",
+    "--merge-info",
+    "--order-check",
+    "--skip-broken",
+    "--allow-gaps",
+    "--insert-placeholders",
+];
+
+/// Switches of `split` that requests may give.
+pub const SPLIT_SWITCHES: &[&str] = &["--force", "--continue-numbering", "--split-channels"];
+
+/// Arguments of the command: the given switches, if all of them are
+/// allowed, then the options added by the server and the paths of the
+/// inputs.
+fn command_args(
+    body: &CommandBody,
+    switches: &[&str],
+    data_dir: &Path,
+    options: &[&Path],
+) -> Result<Vec<String>, Reply> {
+    if let Some(arg) = body
+        .args
+        .iter()
+        .find(|arg| !switches.contains(&arg.as_str()))
+    {
+        return Err(Reply::error(
+            400,
+            ExitCode::Failure,
+            format!(
+                "{} is not allowed over HTTP, only the switches {}",
+                arg,
+                switches.join(", ")
+            ),
+        ));
+    }
+    let mut args = body.args.clone();
+    args.extend(options.iter().map(|option| option.display().to_string()));
+    for input in &body.inputs {
+        args.push(file_in(data_dir, input)?.display().to_string());
+    }
+    Ok(args)
+}
+
+fn parse_command<T: FromArgs>(command: &str, args: &[String]) -> Result<T, Reply> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    T::from_args(&[command], &args)
+        .map_err(|early_exit| Reply::error(400, ExitCode::Failure, early_exit.output.trim()))
+}
+
+fn run_merge(body: CommandBody, data_dir: &Path) -> Result<Reply, Reply> {
+    let output = match &body.output {
+        Some(output) => output.clone(),
+        None => {
+            return Err(Reply::error(
+                400,
+                ExitCode::Failure,
+                "give the name of the merged transcript as output",
+            ))
+        }
+    };
+    let output_file = file_in(data_dir, &output)?;
+    let args = command_args(
+        &body,
+        MERGE_SWITCHES,
+        data_dir,
+        &[Path::new("-o"), &output_file],
+    )?;
+    let opts: Merge = parse_command("merge", &args)?;
+    if opts.stdout {
+        return Err(Reply::error(
+            400,
+            ExitCode::Failure,
+            "cannot merge to standard output of the server",
+        ));
+    }
+    merge::merge(opts).map_err(Reply::failed)?;
+    Ok(Reply::json(200, json!({ "file": output })))
+}
+
+fn run_split(body: CommandBody, data_dir: &Path) -> Result<Reply, Reply> {
+    let args = command_args(
+        &body,
+        SPLIT_SWITCHES,
+        data_dir,
+        &[Path::new("-o"), data_dir],
+    )?;
+    split::split(parse_command::<Split>("split", &args)?).map_err(Reply::failed)?;
+    list(data_dir)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn request(method: &str, url: &str, body: &str, data_dir: &Path) -> (u16, Value) {
+        let reply = handle(method, url, &mut body.as_bytes(), data_dir);
+        let json = match reply.body {
+            Body::Json(json) => json,
+            Body::File(mut file) => {
+                let mut content = String::new();
+                file.read_to_string(&mut content).unwrap();
+                Value::String(content)
+            }
+        };
+        (reply.status, json)
+    }
+
+    #[test]
+    fn uploaded_segments_are_merged_and_downloaded() {
+        let dir = std::env::temp_dir().join("f4tapir-http");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for (name, source) in [
+            ("a.rtf", "testdata/interview-01.rtf"),
+            ("b.rtf", "testdata/interview-02.rtf"),
+        ] {
+            let content = fs::read_to_string(source).unwrap();
+            let url = format!("/files/{}", name);
+            assert_eq!(request("PUT", &url, &content, &dir).0, 201);
+        }
+        assert_eq!(
+            request("GET", "/files", "", &dir),
+            (200, json!(["a.rtf", "b.rtf"]))
+        );
+
+        let merge = r#"{"inputs": ["a.rtf", "b.rtf"], "output": "merged.rtf"}"#;
+        assert_eq!(
+            request("POST", "/merge", merge, &dir),
+            (200, json!({"file": "merged.rtf"}))
+        );
+        let (status, merged) = request("GET", "/files/merged.rtf", "", &dir);
+        assert_eq!(status, 200);
+        assert!(merged.as_str().unwrap().starts_with("{\\rtf1"));
+        let (status, error) = request("POST", "/merge", merge, &dir);
+        assert_eq!(status, 409);
+        assert_eq!(error["error"]["code"], ExitCode::OutputExists.code());
+
+        let forced = r#"{"inputs": ["a.rtf", "b.rtf"], "output": "merged.rtf", "args": ["--force", "--tidy"]}"#;
+        assert_eq!(request("POST", "/merge", forced, &dir).0, 200);
+
+        assert_eq!(request("GET", "/files/..%2Fsecret", "", &dir).0, 400);
+        assert_eq!(request("GET", "/files/missing.rtf", "", &dir).0, 404);
+        assert_eq!(request("DELETE", "/files/a.rtf", "", &dir).0, 404);
+    }
+
+    #[test]
+    fn arguments_other_than_allowed_switches_are_refused() {
+        let dir = std::env::temp_dir().join("f4tapir-http-args");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let outside = std::env::temp_dir().join("f4tapir-http-outside.rtf");
+        let outside = outside.display().to_string();
+        for args in [
+            json!(["-o", outside]),
+            json!(["--backup-dir", "/tmp"]),
+            json!(["--segment-offset", "/etc/passwd"]),
+            json!(["--stdout"]),
+            json!(["/etc/passwd"]),
+            json!(["https://example.com/a.rtf"]),
+        ] {
+            let merge = json!({"inputs": ["a.rtf"], "output": "merged.rtf", "args": args});
+            let (status, error) = request("POST", "/merge", &merge.to_string(), &dir);
+            assert_eq!(status, 400, "{}", args);
+            let message = error["error"]["message"].as_str().unwrap();
+            assert!(message.contains("not allowed over HTTP"), "{}", message);
+        }
+        let split = json!({"inputs": ["a.mp3"], "args": ["-o", "/tmp"]});
+        assert_eq!(request("POST", "/split", &split.to_string(), &dir).0, 400);
+        assert!(!Path::new(&outside).exists());
+    }
+}
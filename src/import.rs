@@ -0,0 +1,40 @@
+//! Keeps the project store up to date with the transcripts of a study,
+//! see [`crate::store`].
+use crate::args::Import;
+use crate::exit::{ExitCode, Failure};
+use crate::find::collect_transcripts;
+use crate::store::{import_transcripts, Error as StoreError};
+
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+pub fn import(opts: Import) -> Result<()> {
+    let paths = collect_transcripts(opts.input_segments, opts.recursive)?;
+    if paths.is_empty() {
+        return Err(Error::NoTranscripts);
+    }
+    let imported = import_transcripts(&opts.store, &paths)?;
+    println!("{}", imported);
+    Ok(())
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("no transcripts found")]
+    NoTranscripts,
+    #[error("failed to import into the project store: {0}")]
+    Store(#[from] StoreError),
+}
+
+impl Failure for Error {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Error::IO(_) => ExitCode::IO,
+            Error::NoTranscripts => ExitCode::NoInputs,
+            Error::Store(err) => err.exit_code(),
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! State of earlier `split --incremental` runs, so that interviews whose
+//! segments are up to date are not split again.
+//!
+//! Every output directory gets a small state file with a line per set
+//! of segments: the segment pattern, a fingerprint of the interview and
+//! the split options, and the segment files that were produced.
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::atomic::AtomicFile;
+
+/// Name of the state file in the output directory.
+pub const STATE_FILE_NAME: &str = ".f4tapir-split";
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// What an earlier run produced for a segment pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub fingerprint: u64,
+    /// File names of the segments, in the directory of the state.
+    pub segments: Vec<String>,
+}
+
+/// The state file of a single output directory.
+#[derive(Debug)]
+pub struct SplitState {
+    dir: PathBuf,
+    path: PathBuf,
+    entries: BTreeMap<String, Entry>,
+}
+
+impl SplitState {
+    /// Loads the state of the directory, or an empty state if there is
+    /// no state file yet. Malformed lines are ignored.
+    pub fn load(dir: &Path) -> io::Result<SplitState> {
+        let path = dir.join(STATE_FILE_NAME);
+        let entries = match fs::read_to_string(&path) {
+            Ok(content) => content.lines().filter_map(parse_line).collect(),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => return Err(err),
+        };
+        Ok(SplitState {
+            dir: dir.to_path_buf(),
+            path,
+            entries,
+        })
+    }
+
+    pub fn entry(&self, pattern: &str) -> Option<&Entry> {
+        self.entries.get(pattern)
+    }
+
+    /// Paths of the segments recorded for the pattern.
+    pub fn segment_paths(&self, pattern: &str) -> Vec<PathBuf> {
+        self.entry(pattern)
+            .map(|entry| {
+                entry
+                    .segments
+                    .iter()
+                    .map(|segment| self.dir.join(segment))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Checks if the segments of the pattern were produced from an
+    /// interview with the same fingerprint and still exist.
+    pub fn is_up_to_date(&self, pattern: &str, fingerprint: u64) -> bool {
+        match self.entry(pattern) {
+            Some(entry) => {
+                entry.fingerprint == fingerprint
+                    && !entry.segments.is_empty()
+                    && entry
+                        .segments
+                        .iter()
+                        .all(|segment| self.dir.join(segment).is_file())
+            }
+            None => false,
+        }
+    }
+
+    /// Records the segments and writes the state file.
+    pub fn record(&mut self, pattern: &str, entry: Entry) -> io::Result<()> {
+        self.entries.insert(pattern.to_string(), entry);
+        let mut file = AtomicFile::create(&self.path)?;
+        for (pattern, entry) in &self.entries {
+            write!(&mut file, "{}\t{:016x}", pattern, entry.fingerprint)?;
+            for segment in &entry.segments {
+                write!(&mut file, "\t{}", segment)?;
+            }
+            writeln!(&mut file)?;
+        }
+        file.commit()
+    }
+}
+
+fn parse_line(line: &str) -> Option<(String, Entry)> {
+    let mut fields = line.split('\t');
+    let pattern = fields.next()?.to_string();
+    let fingerprint = u64::from_str_radix(fields.next()?, 16).ok()?;
+    let segments = fields.map(String::from).collect();
+    Some((
+        pattern,
+        Entry {
+            fingerprint,
+            segments,
+        },
+    ))
+}
+
+/// FNV-1a hash of the content of the file and the given options, which
+/// unlike the hashers of the standard library is stable across versions.
+pub fn fingerprint(path: &Path, options: &str) -> io::Result<u64> {
+    let mut hash = fnv1a(FNV_OFFSET_BASIS, options.as_bytes());
+    let mut file = File::open(path)?;
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let read = match file.read(&mut buf) {
+            Ok(0) => return Ok(hash),
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        hash = fnv1a(hash, &buf[..read]);
+    }
+}
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &byte in bytes {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fnv1a_of_known_input() {
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b""), FNV_OFFSET_BASIS);
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn recorded_segments_are_up_to_date_until_removed() {
+        let dir = std::env::temp_dir().join("f4tapir-incremental");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let interview = dir.join("interview.mp3");
+        let segment = dir.join("interview-000.mp3");
+        fs::write(&interview, "sound").unwrap();
+        fs::write(&segment, "sound").unwrap();
+        let fingerprint = fingerprint(&interview, "").unwrap();
+
+        let mut state = SplitState::load(&dir).unwrap();
+        let entry = Entry {
+            fingerprint,
+            segments: vec![String::from("interview-000.mp3")],
+        };
+        state.record("interview-%03d.mp3", entry).unwrap();
+
+        let state = SplitState::load(&dir).unwrap();
+        assert!(state.is_up_to_date("interview-%03d.mp3", fingerprint));
+        assert!(!state.is_up_to_date("interview-%03d.mp3", fingerprint + 1));
+        fs::remove_file(&segment).unwrap();
+        assert!(!state.is_up_to_date("interview-%03d.mp3", fingerprint));
+    }
+}
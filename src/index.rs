@@ -0,0 +1,181 @@
+//! Index of the words of a merged interview, for finding the audio of a
+//! quote that a paper cites by its position in the plain text export.
+//!
+//! The index is JSON or, with the `sqlite` feature, an SQLite database
+//! with the tables `segments(id, path)` and
+//! `words(offset, word, timestamp, tenths, segment)`.
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::args::Index;
+use crate::atomic::AtomicFile;
+use crate::exit::{ExitCode, Failure};
+use crate::find::collect_transcripts;
+use crate::transcript::{
+    index_words, Error as TranscriptError, IndexedWord, MergeOptions, Transcript,
+};
+
+use serde_json::{json, Value};
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+pub fn index(opts: Index) -> Result<()> {
+    let paths = collect_transcripts(opts.input_segments, opts.recursive)?;
+    if paths.is_empty() {
+        return Err(Error::NoTranscripts);
+    }
+    let transcripts = paths
+        .iter()
+        .map(|path| {
+            Transcript::from_file(path).map_err(|err| Error::TranscriptLoadFail(path.clone(), err))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let (text, words) = index_words(transcripts, &MergeOptions::default()).map_err(Error::Merge)?;
+
+    if let Some(text_file) = &opts.text {
+        let mut file = AtomicFile::create(text_file)?;
+        file.write_all(text.as_bytes())?;
+        file.commit()?;
+    }
+    if is_sqlite(&opts.output_file) {
+        write_sqlite(&opts.output_file, &paths, &words)
+    } else {
+        let mut file = AtomicFile::create(&opts.output_file)?;
+        serde_json::to_writer_pretty(&mut file, &to_json(&paths, &words))
+            .map_err(std::io::Error::from)?;
+        writeln!(&mut file)?;
+        file.commit()?;
+        Ok(())
+    }
+}
+
+fn is_sqlite(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            ["sqlite", "sqlite3", "db"]
+                .iter()
+                .any(|sqlite| ext.eq_ignore_ascii_case(sqlite))
+        })
+}
+
+/// The index as JSON, with the segments by their index.
+fn to_json(paths: &[PathBuf], words: &[IndexedWord]) -> Value {
+    let segments: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+    let words: Vec<Value> = words
+        .iter()
+        .map(|word| {
+            json!({
+                "offset": word.offset,
+                "word": word.word,
+                "timestamp": word.timestamp.to_string(),
+                "segment": word.segment,
+            })
+        })
+        .collect();
+    json!({
+        "segments": segments,
+        "words": words,
+    })
+}
+
+/// Writes the index to a new database, replacing an earlier one.
+#[cfg(feature = "sqlite")]
+fn write_sqlite(path: &Path, paths: &[PathBuf], words: &[IndexedWord]) -> Result<()> {
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    let mut db = rusqlite::Connection::open(path)?;
+    let tx = db.transaction()?;
+    tx.execute_batch(
+        "CREATE TABLE segments (id INTEGER PRIMARY KEY, path TEXT NOT NULL);
+         CREATE TABLE words (
+             offset INTEGER PRIMARY KEY,
+             word TEXT NOT NULL,
+             timestamp TEXT NOT NULL,
+             tenths INTEGER NOT NULL,
+             segment INTEGER NOT NULL REFERENCES segments(id)
+         );
+         CREATE INDEX words_by_word ON words (word COLLATE NOCASE);",
+    )?;
+    {
+        let mut insert = tx.prepare("INSERT INTO segments (id, path) VALUES (?1, ?2)")?;
+        for (id, segment) in paths.iter().enumerate() {
+            insert.execute(rusqlite::params![id, segment.display().to_string()])?;
+        }
+        let mut insert = tx.prepare(
+            "INSERT INTO words (offset, word, timestamp, tenths, segment) VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        for word in words {
+            insert.execute(rusqlite::params![
+                word.offset,
+                word.word,
+                word.timestamp.to_string(),
+                word.timestamp.tenths(),
+                word.segment
+            ])?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn write_sqlite(path: &Path, _paths: &[PathBuf], _words: &[IndexedWord]) -> Result<()> {
+    Err(Error::SqliteUnsupported(path.to_path_buf()))
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("no transcripts found")]
+    NoTranscripts,
+    #[error("failed to load transcript {0}: {1}")]
+    TranscriptLoadFail(PathBuf, TranscriptError),
+    #[error("failed to merge the transcripts: {0}")]
+    Merge(TranscriptError),
+    #[error("cannot write {0}, f4tapir was built without the sqlite feature")]
+    SqliteUnsupported(PathBuf),
+    #[cfg(feature = "sqlite")]
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+}
+
+impl Failure for Error {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Error::IO(_) => ExitCode::IO,
+            Error::NoTranscripts => ExitCode::NoInputs,
+            Error::TranscriptLoadFail(_, err) => err.exit_code(),
+            Error::Merge(err) => err.exit_code(),
+            Error::SqliteUnsupported(_) => ExitCode::Failure,
+            #[cfg(feature = "sqlite")]
+            Error::Sqlite(_) => ExitCode::IO,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn index_lists_segments_and_words_with_timestamps() {
+        let paths = vec![
+            PathBuf::from("testdata/interview-01.rtf"),
+            PathBuf::from("testdata/interview-02.rtf"),
+        ];
+        let transcripts = paths.iter().map(|p| Transcript::from_file(p).unwrap());
+        let (_, words) = index_words(transcripts, &MergeOptions::default()).unwrap();
+        let json = to_json(&paths, &words);
+
+        assert_eq!(json["segments"][1], "testdata/interview-02.rtf");
+        assert_eq!(json["words"][0]["word"], "Was");
+        assert_eq!(json["words"][0]["timestamp"], "#00:00:00-0#");
+        assert_eq!(json["words"][0]["offset"], 3);
+        assert!(is_sqlite(Path::new("study.SQLite")));
+        assert!(!is_sqlite(Path::new("study.json")));
+    }
+}
@@ -0,0 +1,399 @@
+//! Summary of what is in a transcript, for eyeballing incoming files
+//! before merging them.
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::args::Info;
+use crate::exit::{ExitCode, Failure};
+use crate::find::collect_transcripts;
+use crate::timestamp::Timestamp;
+use crate::transcript::{
+    annotations, plain_text, Error as TranscriptError, SpeakerInference, Transcript,
+};
+
+use log::warn;
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+pub fn info(opts: Info) -> Result<()> {
+    let paths = collect_transcripts(opts.input_segments, opts.recursive)?;
+    if paths.is_empty() {
+        return Err(Error::NoTranscripts);
+    }
+
+    if opts.list_annotations {
+        let stdout = std::io::stdout().lock();
+        return list_annotations(stdout, &paths, opts.infer_speakers, opts.json);
+    }
+
+    let mut infos = vec![];
+    for path in paths {
+        match TranscriptInfo::of(&path, opts.infer_speakers) {
+            Ok(info) => infos.push(info),
+            Err(err) => warn!(
+                "failed to load transcript {}, skipping, cause: {}",
+                path.display(),
+                err
+            ),
+        }
+    }
+
+    let mut stdout = std::io::stdout().lock();
+    if opts.json {
+        write_json(&mut stdout, &infos)?;
+    } else {
+        for info in &infos {
+            info.write_text(&mut stdout)?;
+        }
+    }
+    Ok(())
+}
+
+/// What is known about a transcript file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranscriptInfo {
+    pub path: PathBuf,
+    /// Size of the file in bytes.
+    pub file_size: u64,
+    /// Version after `\rtf`, practically always 1.
+    pub rtf_version: Option<u32>,
+    /// Code page from `\ansicpg`, or the character set like `ansi` if
+    /// there is none.
+    pub code_page: Option<String>,
+    /// Names of the fonts in the font table.
+    pub fonts: Vec<String>,
+    pub first_timestamp: Option<Timestamp>,
+    pub last_timestamp: Option<Timestamp>,
+    /// Suspected length of the segment, see
+    /// [`Transcript::interview_end_time`].
+    pub duration: Timestamp,
+    pub utterances: usize,
+    /// Speaker codes in the order they first speak.
+    pub speakers: Vec<String>,
+    /// Lines of paragraphs without a speaker label that were counted as
+    /// utterances of an inferred speaker, starting at one.
+    pub inferred_lines: Vec<usize>,
+}
+
+impl TranscriptInfo {
+    /// Reads the info of the transcript, counting paragraphs without a
+    /// speaker label as utterances if a way to infer their speaker is
+    /// given.
+    pub fn of(
+        path: &Path,
+        inference: Option<SpeakerInference>,
+    ) -> std::result::Result<TranscriptInfo, TranscriptError> {
+        let file_size = std::fs::metadata(path)?.len();
+        let transcript = Transcript::from_file(path)?;
+        let timestamps = Timestamp::extract_timestamps(transcript.content().as_bytes());
+        let mut utterances = 0;
+        let mut speakers: Vec<String> = vec![];
+        let mut inferred_lines = vec![];
+        let first_line = transcript.first_content_line();
+        for record in transcript.utterance_records().infer_speakers(inference) {
+            utterances += 1;
+            if record.inferred {
+                inferred_lines.push(first_line + record.line_index);
+            }
+            if !speakers.contains(&record.speaker) {
+                speakers.push(record.speaker);
+            }
+        }
+        Ok(TranscriptInfo {
+            path: path.to_path_buf(),
+            file_size,
+            rtf_version: rtf_version(transcript.preamble()),
+            code_page: code_page(transcript.preamble()),
+            fonts: fonts(transcript.preamble()),
+            first_timestamp: timestamps.first().map(|&(_, timestamp)| timestamp),
+            last_timestamp: timestamps.last().map(|&(_, timestamp)| timestamp),
+            duration: transcript.interview_end_time(),
+            utterances,
+            speakers,
+            inferred_lines,
+        })
+    }
+
+    /// The info as a JSON object, like an element of `info --json`.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "path": self.path.display().to_string(),
+            "file_size": self.file_size,
+            "rtf_version": self.rtf_version,
+            "code_page": self.code_page,
+            "fonts": self.fonts,
+            "first_timestamp": self.first_timestamp.map(|ts| ts.to_string()),
+            "last_timestamp": self.last_timestamp.map(|ts| ts.to_string()),
+            "duration": self.duration.to_string(),
+            "utterances": self.utterances,
+            "speakers": self.speakers,
+            "inferred_lines": self.inferred_lines,
+        })
+    }
+
+    fn write_text<W: Write>(&self, mut to: W) -> std::io::Result<()> {
+        writeln!(&mut to, "{}", self.path.display())?;
+        writeln!(&mut to, "  file size:       {} bytes", self.file_size)?;
+        writeln!(
+            &mut to,
+            "  rtf version:     {}",
+            or_unknown(self.rtf_version)
+        )?;
+        writeln!(
+            &mut to,
+            "  code page:       {}",
+            or_unknown(self.code_page.as_ref())
+        )?;
+        writeln!(&mut to, "  fonts:           {}", self.fonts.join(", "))?;
+        writeln!(
+            &mut to,
+            "  first timestamp: {}",
+            or_unknown(self.first_timestamp)
+        )?;
+        writeln!(
+            &mut to,
+            "  last timestamp:  {}",
+            or_unknown(self.last_timestamp)
+        )?;
+        writeln!(&mut to, "  duration:        {}", self.duration)?;
+        writeln!(&mut to, "  utterances:      {}", self.utterances)?;
+        writeln!(&mut to, "  speakers:        {}", self.speakers.join(", "))?;
+        if !self.inferred_lines.is_empty() {
+            let lines: Vec<String> = self.inferred_lines.iter().map(usize::to_string).collect();
+            writeln!(&mut to, "  inferred lines:  {}", lines.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes the annotations in the speech of the transcripts, a line like
+/// `a.rtf:12: I: (lacht) (comment)` for each, or a JSON array of them.
+fn list_annotations<W: Write>(
+    mut to: W,
+    paths: &[PathBuf],
+    inference: Option<SpeakerInference>,
+    json: bool,
+) -> Result<()> {
+    let mut listed = vec![];
+    for path in paths {
+        let transcript = match Transcript::from_file(path) {
+            Ok(transcript) => transcript,
+            Err(err) => {
+                warn!(
+                    "failed to load transcript {}, skipping, cause: {}",
+                    path.display(),
+                    err
+                );
+                continue;
+            }
+        };
+        let first_line = transcript.first_content_line();
+        for record in transcript.utterance_records().infer_speakers(inference) {
+            let line = first_line + record.line_index;
+            for annotation in annotations(&record.speech) {
+                if json {
+                    listed.push(serde_json::json!({
+                        "path": path.display().to_string(),
+                        "line": line,
+                        "speaker": record.speaker,
+                        "kind": annotation.kind.to_string(),
+                        "text": annotation.text,
+                    }));
+                } else {
+                    writeln!(
+                        &mut to,
+                        "{}:{}: {}: {} ({})",
+                        path.display(),
+                        line,
+                        record.speaker,
+                        annotation.text,
+                        annotation.kind
+                    )?;
+                }
+            }
+        }
+    }
+    if json {
+        serde_json::to_writer_pretty(&mut to, &listed).map_err(std::io::Error::from)?;
+        writeln!(&mut to)?;
+    }
+    Ok(())
+}
+
+fn or_unknown<T: ToString>(value: Option<T>) -> String {
+    value
+        .map(|value| value.to_string())
+        .unwrap_or_else(|| String::from("unknown"))
+}
+
+/// Writes the infos as a JSON array of objects.
+fn write_json<W: Write>(mut to: W, infos: &[TranscriptInfo]) -> std::io::Result<()> {
+    let optional = |value: Option<String>| value.unwrap_or_else(|| String::from("null"));
+    let strings = |values: &[String]| {
+        let values: Vec<String> = values.iter().map(|value| json_string(value)).collect();
+        format!("[{}]", values.join(","))
+    };
+    writeln!(&mut to, "[")?;
+    for (idx, info) in infos.iter().enumerate() {
+        let separator = if idx + 1 < infos.len() { "," } else { "" };
+        writeln!(
+            &mut to,
+            "  {{\"path\":{},\"file_size\":{},\"rtf_version\":{},\"code_page\":{},\"fonts\":{},\
+             \"first_timestamp\":{},\"last_timestamp\":{},\"duration\":{},\"utterances\":{},\
+             \"speakers\":{},\"inferred_lines\":[{}]}}{}",
+            json_string(&info.path.display().to_string()),
+            info.file_size,
+            optional(info.rtf_version.map(|version| version.to_string())),
+            optional(info.code_page.as_deref().map(json_string)),
+            strings(&info.fonts),
+            optional(info.first_timestamp.map(|ts| json_string(&ts.to_string()))),
+            optional(info.last_timestamp.map(|ts| json_string(&ts.to_string()))),
+            json_string(&info.duration.to_string()),
+            info.utterances,
+            strings(&info.speakers),
+            info.inferred_lines
+                .iter()
+                .map(usize::to_string)
+                .collect::<Vec<_>>()
+                .join(","),
+            separator
+        )?;
+    }
+    writeln!(&mut to, "]")
+}
+
+/// Quotes the string for JSON, escaping quotes, backslashes and control
+/// characters.
+fn json_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if u32::from(c) < 0x20 => quoted.push_str(&format!("\\u{:04x}", u32::from(c))),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Version number directly after `{\rtf`.
+fn rtf_version(preamble: &str) -> Option<u32> {
+    let rest = preamble.trim_start().strip_prefix("{\\rtf")?;
+    let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+    digits.parse().ok()
+}
+
+/// Code page from `\ansicpgN`, or the character set control word.
+fn code_page(preamble: &str) -> Option<String> {
+    if let Some(at) = preamble.find("\\ansicpg") {
+        let digits: String = preamble[at + "\\ansicpg".len()..]
+            .chars()
+            .take_while(char::is_ascii_digit)
+            .collect();
+        if !digits.is_empty() {
+            return Some(digits);
+        }
+    }
+    ["ansi", "mac", "pca", "pc"]
+        .iter()
+        .find(|charset| {
+            let word = format!("\\{}", charset);
+            preamble.match_indices(&word).any(|(at, _)| {
+                !preamble[at + word.len()..].starts_with(|c: char| c.is_ascii_alphanumeric())
+            })
+        })
+        .map(|charset| charset.to_string())
+}
+
+/// Font names in the `\fonttbl` group, e.g. `Arial` for
+/// `{\fonttbl{\f0\fnil Arial;}}`.
+fn fonts(preamble: &str) -> Vec<String> {
+    let start = match preamble.find("{\\fonttbl") {
+        Some(start) => start,
+        None => return vec![],
+    };
+    let mut depth = 0;
+    let mut end = preamble.len();
+    let mut escaped = false;
+    for (at, c) in preamble[start..].char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    end = start + at;
+                    break;
+                }
+            }
+            _ => (),
+        }
+    }
+    let table = &preamble[start + "{\\fonttbl".len()..end];
+    table
+        .split(';')
+        .map(|entry| plain_text(entry).trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("no transcripts found")]
+    NoTranscripts,
+}
+
+impl Failure for Error {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Error::IO(_) => ExitCode::IO,
+            Error::NoTranscripts => ExitCode::NoInputs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn info_of_reference_transcript() {
+        let info = TranscriptInfo::of(Path::new("testdata/interview-01.rtf"), None).unwrap();
+        assert_eq!(info.rtf_version, Some(1));
+        assert_eq!(info.code_page.as_deref(), Some("ansi"));
+        assert_eq!(info.fonts, vec!["Arial".to_string()]);
+        assert_eq!(info.first_timestamp, Timestamp::parse("#00:00:27-8#").ok());
+        assert_eq!(info.last_timestamp, Timestamp::parse("#00:04:50-3#").ok());
+        assert_eq!(info.speakers, vec!["I".to_string(), "Z".to_string()]);
+        assert_eq!(
+            info.file_size,
+            std::fs::metadata("testdata/interview-01.rtf")
+                .unwrap()
+                .len()
+        );
+    }
+
+    #[test]
+    fn header_fields() {
+        let preamble =
+            "{\\rtf1\\ansi\\ansicpg1252{\\fonttbl{\\f0\\fswiss Arial;}{\\f1 Times \\{New\\};}}";
+        assert_eq!(code_page(preamble).as_deref(), Some("1252"));
+        assert_eq!(fonts(preamble), vec!["Arial", "Times {New}"]);
+        assert_eq!(code_page("{\\rtf1\\mac{\\fonttbl}").as_deref(), Some("mac"));
+    }
+
+    #[test]
+    fn json_strings_are_escaped() {
+        assert_eq!(json_string("a \"b\"\\\n"), "\"a \\\"b\\\"\\\\\\n\"");
+    }
+}
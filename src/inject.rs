@@ -0,0 +1,259 @@
+//! Inserts estimated timestamps into transcripts that have none, e.g. old
+//! transcripts typed without F4, so that they can be merged and exported
+//! like the others.
+//!
+//! The timestamps assume that the speech is evenly paced: the time of a
+//! word is either its share of all words in the length of the recording or
+//! follows from a pace in words per minute. A timestamp is inserted every
+//! so many seconds or words and, like F4 does, at the end of every
+//! utterance.
+use std::fs::read_to_string;
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::args::InjectTimestamps;
+use crate::atomic::AtomicFile;
+use crate::convert::Format;
+use crate::cues::parse_time;
+use crate::exit::{ExitCode, Failure};
+use crate::pair::{probe_duration, Error as PairError};
+use crate::timestamp::Timestamp;
+use crate::transcript::json::{Block, Document, Utterance};
+use crate::transcript::{Error as TranscriptError, Transcript};
+
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Where to insert timestamps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Every {
+    /// After every so many words.
+    Words(usize),
+    /// After the word that crosses every so many tenths of a second.
+    Tenths(u64),
+}
+
+impl FromStr for Every {
+    type Err = String;
+
+    /// Parses `30s` or `30seconds` and `50w` or `50words`.
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let unit_start = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (count, unit) = s.split_at(unit_start);
+        let count = count.parse::<u64>().ok().filter(|&count| count > 0);
+        match (count, unit) {
+            (Some(count), "w" | "words") => Ok(Every::Words(count as usize)),
+            (Some(count), "s" | "seconds") => Ok(Every::Tenths(count * 10)),
+            _ => Err(format!(
+                "expected an interval like 30s or 50w for every 30 seconds or 50 words, got {}",
+                s
+            )),
+        }
+    }
+}
+
+/// How fast the speech is assumed to be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pace {
+    /// The words are spread over the whole recording.
+    Duration(Timestamp),
+    WordsPerMinute(u32),
+}
+
+pub fn inject_timestamps(opts: InjectTimestamps) -> Result<()> {
+    let from = match opts.from.or_else(|| Format::of(&opts.input)) {
+        Some(Format::Lines(format)) => {
+            return Err(Error::Unreadable(opts.input, Format::Lines(format)))
+        }
+        Some(from) => from,
+        None => return Err(Error::UnknownFormat(opts.input)),
+    };
+    let output_file = opts.output_file.clone().unwrap_or_else(|| {
+        let stem = opts.input.file_stem().unwrap_or_default().to_string_lossy();
+        opts.input.with_file_name(format!("{}-timed.rtf", stem))
+    });
+    if output_file.exists() && !opts.force {
+        return Err(Error::OutputExists(output_file));
+    }
+    let pace = match (&opts.duration, &opts.audio) {
+        (Some(_), Some(_)) => return Err(Error::PaceOptions),
+        (Some(duration), None) => Pace::Duration(
+            parse_time(duration)
+                .and_then(|duration| Timestamp::from_tenths((duration.as_millis() / 100) as u64))
+                .ok_or_else(|| Error::Duration(duration.clone()))?,
+        ),
+        (None, Some(audio)) => Pace::Duration(
+            probe_duration(audio).map_err(|err| Error::AudioDuration(audio.clone(), err))?,
+        ),
+        (None, None) => Pace::WordsPerMinute(opts.words_per_minute),
+    };
+    if pace == Pace::WordsPerMinute(0) {
+        return Err(Error::PaceOptions);
+    }
+
+    let broken = |err| Error::BrokenTranscript(opts.input.clone(), err);
+    let document = match from {
+        Format::Rtf => {
+            Document::of(&Transcript::parse_untimed(read_to_string(&opts.input)?).map_err(broken)?)
+        }
+        Format::Txt => Document::from_text(&read_to_string(&opts.input)?),
+        Format::Json => serde_json::from_str(&read_to_string(&opts.input)?)
+            .map_err(|err| Error::MalformedJson(opts.input.clone(), err))?,
+        Format::Lines(_) => unreachable!("only written"),
+    };
+    if utterances(&document).any(|utterance| !utterance.timestamps.is_empty()) {
+        return Err(Error::AlreadyTimed(opts.input));
+    }
+    let document = inject(&document, opts.every, pace);
+    // parse what we built so that only valid transcripts are written
+    let transcript = document.to_transcript().map_err(broken)?;
+    let mut file = AtomicFile::create(&output_file)?;
+    write!(&mut file, "{}", transcript)?;
+    file.commit()?;
+    println!("{}", output_file.display());
+    Ok(())
+}
+
+/// The document with timestamps inserted into the utterances.
+pub fn inject(document: &Document, every: Every, pace: Pace) -> Document {
+    let total = utterances(document)
+        .map(|utterance| utterance.speech.split_whitespace().count())
+        .sum::<usize>();
+    let time_after = |words: usize| -> u64 {
+        match pace {
+            Pace::Duration(duration) if total > 0 => {
+                duration.tenths() * words as u64 / total as u64
+            }
+            Pace::Duration(_) => 0,
+            Pace::WordsPerMinute(wpm) => words as u64 * 600 / u64::from(wpm.max(1)),
+        }
+    };
+    let crosses = |words: usize| match every {
+        Every::Words(n) => words.is_multiple_of(n.max(1)),
+        Every::Tenths(tenths) => {
+            time_after(words) / tenths.max(1) > time_after(words - 1) / tenths.max(1)
+        }
+    };
+
+    let mut words = 0;
+    let mut document = document.clone();
+    for block in &mut document.blocks {
+        let utterance = match block {
+            Block::Utterance(utterance) => utterance,
+            Block::Note { .. } => continue,
+        };
+        let count = utterance.speech.split_whitespace().count();
+        let mut speech = String::with_capacity(utterance.speech.len() + count);
+        let mut rest = utterance.speech.as_str();
+        for idx in 0..count {
+            let start = rest.find(|c: char| !c.is_whitespace()).unwrap_or(0);
+            let end = rest[start..]
+                .find(char::is_whitespace)
+                .map_or(rest.len(), |end| start + end);
+            speech.push_str(&rest[..end]);
+            rest = &rest[end..];
+            words += 1;
+            if idx + 1 == count || crosses(words) {
+                let tenths = time_after(words);
+                let timestamp = Timestamp::from_tenths(tenths).unwrap_or_default();
+                speech.push_str(&format!(" {}", timestamp));
+            }
+        }
+        speech.push_str(rest.trim_end());
+        *utterance = Utterance::new(&utterance.speaker, &speech);
+    }
+    document
+}
+
+fn utterances(document: &Document) -> impl Iterator<Item = &Utterance> {
+    document.blocks.iter().filter_map(|block| match block {
+        Block::Utterance(utterance) => Some(utterance),
+        Block::Note { .. } => None,
+    })
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("cannot tell the format of {0}, give it with --from")]
+    UnknownFormat(PathBuf),
+    #[error("cannot read {0} as {1}, give it as rtf, txt or json")]
+    Unreadable(PathBuf, Format),
+    #[error("{0} exists, use --force to overwrite")]
+    OutputExists(PathBuf),
+    #[error("expected a duration like 45:30 or 2730, got {0}")]
+    Duration(String),
+    #[error("give either --duration or --audio, or a --words-per-minute above zero")]
+    PaceOptions,
+    #[error("could not get the duration of {0}: {1}")]
+    AudioDuration(PathBuf, PairError),
+    #[error("{0} is not a transcript in JSON: {1}")]
+    MalformedJson(PathBuf, serde_json::Error),
+    #[error("{0} already has timestamps")]
+    AlreadyTimed(PathBuf),
+    #[error("could not insert timestamps into {0}: {1}")]
+    BrokenTranscript(PathBuf, TranscriptError),
+}
+
+impl Failure for Error {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Error::IO(_) => ExitCode::IO,
+            Error::UnknownFormat(_)
+            | Error::Unreadable(_, _)
+            | Error::Duration(_)
+            | Error::PaceOptions
+            | Error::AlreadyTimed(_) => ExitCode::Failure,
+            Error::OutputExists(_) => ExitCode::OutputExists,
+            Error::AudioDuration(_, err) => err.exit_code(),
+            Error::MalformedJson(_, _) => ExitCode::MalformedTranscript,
+            Error::BrokenTranscript(_, err) => err.exit_code(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn speeches(document: &Document) -> Vec<&str> {
+        utterances(document)
+            .map(|utterance| utterance.speech.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn timestamps_are_spread_over_the_words() {
+        let document = Document::from_text("I: one two three four\n\nB: five six\n");
+        let by_duration = inject(
+            &document,
+            Every::Words(3),
+            Pace::Duration(Timestamp::from_tenths(120).unwrap()),
+        );
+        assert_eq!(
+            speeches(&by_duration),
+            vec![
+                "one two three #00:00:06-0# four #00:00:08-0#",
+                "five six #00:00:12-0#"
+            ]
+        );
+        let by_pace = inject(&document, Every::Tenths(10), Pace::WordsPerMinute(120));
+        assert_eq!(
+            speeches(&by_pace),
+            vec![
+                "one two #00:00:01-0# three four #00:00:02-0#",
+                "five six #00:00:03-0#"
+            ]
+        );
+        assert_eq!(
+            by_pace.to_transcript().unwrap().last_timestamp().tenths(),
+            30
+        );
+        assert_eq!("30s".parse(), Ok(Every::Tenths(300)));
+        assert_eq!("50words".parse(), Ok(Every::Words(50)));
+        assert!("0w".parse::<Every>().is_err());
+    }
+}
@@ -0,0 +1,55 @@
+//! Slice interviews into segments and merge sliced F4 transcripts into a
+//! complete one, adjusting the timestamps along the way.
+#![allow(
+    clippy::too_many_arguments,
+    clippy::enum_variant_names,
+    clippy::module_inception
+)]
+
+pub mod align;
+pub mod archive;
+pub mod args;
+pub mod atomic;
+pub mod audio_order;
+pub mod backup;
+pub mod check;
+pub mod convert;
+pub mod cues;
+pub mod detect;
+pub mod diagnostic;
+pub mod doctor;
+pub mod excerpt;
+pub mod exit;
+pub mod find;
+pub mod generate;
+pub mod glob;
+#[cfg(feature = "gui")]
+pub mod gui;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod import;
+pub mod incremental;
+pub mod index;
+pub mod info;
+pub mod inject;
+pub mod load;
+pub mod logging;
+pub mod manifest;
+pub mod merge;
+pub mod offsets;
+pub mod order;
+pub mod overlap;
+pub mod pair;
+mod paths;
+pub mod quotes;
+pub mod remote;
+pub mod renumber;
+pub mod report;
+pub mod resume;
+pub mod serve;
+pub mod speakers;
+pub mod split;
+pub mod stats;
+pub mod store;
+pub mod timestamp;
+pub mod transcript;
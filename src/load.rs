@@ -0,0 +1,158 @@
+//! Loads transcripts on several threads, for merges of many segments on
+//! slow network shares where reading and parsing dominates.
+//!
+//! Transcripts are handed out in the order of their paths as soon as they
+//! and all before them are loaded, so that merging can start before the
+//! last one is read.
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::sync::Arc;
+use std::thread;
+
+use crate::transcript::{Error, LoadOptions, Result, Transcript};
+
+/// A transcript with the path it was loaded from, or why loading failed.
+type Loaded = (PathBuf, Result<Transcript>);
+
+/// Iterator over loaded transcripts, in the order of their paths.
+///
+/// Threads that are still loading stop after their current transcript
+/// when the iterator is dropped.
+pub struct Loader {
+    paths: Arc<Vec<PathBuf>>,
+    receiver: Receiver<(usize, Loaded)>,
+    /// Transcripts that were loaded before ones with lower indices.
+    early: BTreeMap<usize, Loaded>,
+    next: usize,
+    len: usize,
+    stop: Arc<AtomicBool>,
+}
+
+/// Starts loading the transcripts on up to `jobs` threads, at least one.
+pub fn load_transcripts(paths: Vec<PathBuf>, options: LoadOptions, jobs: usize) -> Loader {
+    let len = paths.len();
+    let paths = Arc::new(paths);
+    let claimed = Arc::new(AtomicUsize::new(0));
+    let stop = Arc::new(AtomicBool::new(false));
+    let jobs = jobs.clamp(1, len.max(1));
+    // bounded, so that fast threads do not load far ahead of the merge
+    let (sender, receiver) = sync_channel(jobs);
+    for _ in 0..jobs {
+        let (paths, claimed, stop, sender) = (
+            Arc::clone(&paths),
+            Arc::clone(&claimed),
+            Arc::clone(&stop),
+            sender.clone(),
+        );
+        thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                let idx = claimed.fetch_add(1, Ordering::Relaxed);
+                let path = match paths.get(idx) {
+                    Some(path) => path.clone(),
+                    None => break,
+                };
+                let transcript = Transcript::load(&path, options);
+                if sender.send((idx, (path, transcript))).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    Loader {
+        paths,
+        receiver,
+        early: BTreeMap::new(),
+        next: 0,
+        len,
+        stop,
+    }
+}
+
+/// Number of threads to load with if not given, one per CPU.
+pub fn default_jobs() -> usize {
+    thread::available_parallelism().map_or(1, usize::from)
+}
+
+impl Iterator for Loader {
+    type Item = Loaded;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next == self.len {
+            return None;
+        }
+        while !self.early.contains_key(&self.next) {
+            // the senders only hang up before sending everything if a
+            // thread panicked, which must not look like the end
+            let Ok((idx, loaded)) = self.receiver.recv() else {
+                let path = self.paths[self.next].clone();
+                self.next += 1;
+                return Some((path, Err(Error::LoadingAborted)));
+            };
+            self.early.insert(idx, loaded);
+        }
+        let loaded = self.early.remove(&self.next);
+        self.next += 1;
+        loaded
+    }
+}
+
+impl Drop for Loader {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn transcripts_come_in_the_order_of_their_paths() {
+        let paths: Vec<PathBuf> = (0..12)
+            .map(|idx| match idx % 3 {
+                0 => PathBuf::from("testdata/interview-01.rtf"),
+                1 => PathBuf::from("testdata/interview-02.rtf"),
+                _ => PathBuf::from("testdata/missing.rtf"),
+            })
+            .collect();
+        let loaded: Vec<Loaded> =
+            load_transcripts(paths.clone(), LoadOptions::default(), 4).collect();
+
+        assert_eq!(loaded.len(), paths.len());
+        for ((path, transcript), expected) in loaded.iter().zip(&paths) {
+            assert_eq!(path, expected);
+            assert_eq!(transcript.is_ok(), !path.ends_with("missing.rtf"));
+        }
+        assert_eq!(
+            load_transcripts(vec![], LoadOptions::default(), 4).count(),
+            0
+        );
+    }
+
+    #[test]
+    fn transcripts_of_panicked_threads_are_failures() {
+        let paths = vec![
+            PathBuf::from("testdata/interview-01.rtf"),
+            PathBuf::from("testdata/interview-02.rtf"),
+        ];
+        // all senders hung up without sending, like after a panic
+        let (_, receiver) = sync_channel(1);
+        let loader = Loader {
+            paths: Arc::new(paths.clone()),
+            receiver,
+            early: BTreeMap::new(),
+            next: 0,
+            len: paths.len(),
+            stop: Arc::new(AtomicBool::new(false)),
+        };
+
+        let loaded: Vec<Loaded> = loader.collect();
+        assert_eq!(loaded.len(), paths.len());
+        for ((path, transcript), expected) in loaded.iter().zip(&paths) {
+            assert_eq!(path, expected);
+            assert!(matches!(transcript, Err(Error::LoadingAborted)));
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! Logging to standard error, with log targets for the parts of f4tapir
+//! that can be selected with `--log-target`.
+//!
+//! Warnings are shown by default, `-v` adds what is being done, `-vv`
+//! decisions like stitching and `-vvv` everything. `-q` leaves only
+//! errors and `-qq` silences the log completely.
+
+use stderrlog::ColorChoice;
+
+use crate::diagnostic::Color;
+
+/// Finding transcripts and interviews in the given paths.
+pub const FIND: &str = "find";
+/// Recognizing transcripts and sound files.
+pub const DETECT: &str = "detect";
+/// Merging transcripts, including stitching decisions.
+pub const MERGE: &str = "merge";
+/// Splitting interviews with ffmpeg.
+pub const SPLIT: &str = "split";
+/// Requests to `serve`.
+pub const SERVE: &str = "serve";
+
+/// All targets, in the order they are documented.
+pub const TARGETS: [&str; 5] = [FIND, DETECT, MERGE, SPLIT, SERVE];
+
+/// Verbosity for `stderrlog` from the number of `-v` and `-q` flags, or
+/// `None` if nothing should be logged.
+pub fn verbosity(verbose: u8, quiet: u8) -> Option<usize> {
+    const DEFAULT: i32 = 1;
+    let verbosity = DEFAULT + i32::from(verbose) - i32::from(quiet);
+    if verbosity < 0 {
+        None
+    } else {
+        Some(verbosity as usize)
+    }
+}
+
+/// Sets up logging to standard error, only for the given targets if any.
+pub fn init(
+    verbose: u8,
+    quiet: u8,
+    targets: &[String],
+    color: Color,
+) -> Result<(), log::SetLoggerError> {
+    let verbosity = verbosity(verbose, quiet);
+    stderrlog::new()
+        .color(match color {
+            Color::Auto => ColorChoice::Auto,
+            Color::Always => ColorChoice::Always,
+            Color::Never => ColorChoice::Never,
+        })
+        .verbosity(verbosity.unwrap_or_default())
+        .quiet(verbosity.is_none())
+        .modules(targets.iter().cloned())
+        .init()
+}
+
+/// Splits repeated short flags like `-vv` into `-v -v`, which the
+/// argument parser does not understand otherwise. Arguments after `--`
+/// are left alone.
+pub fn expand_short_flags<I: IntoIterator<Item = String>>(args: I) -> Vec<String> {
+    let mut expanded = vec![];
+    let mut args = args.into_iter();
+    for arg in &mut args {
+        if arg == "--" {
+            expanded.push(arg);
+            break;
+        }
+        let flags = arg.strip_prefix('-').unwrap_or_default();
+        let repeated = flags.len() > 1
+            && [b'v', b'q']
+                .iter()
+                .any(|&flag| flags.bytes().all(|b| b == flag));
+        if repeated {
+            expanded.extend(flags.chars().map(|flag| format!("-{}", flag)));
+        } else {
+            expanded.push(arg);
+        }
+    }
+    expanded.extend(args);
+    expanded
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn verbosity_from_flags() {
+        assert_eq!(verbosity(0, 0), Some(1));
+        assert_eq!(verbosity(2, 0), Some(3));
+        assert_eq!(verbosity(0, 1), Some(0));
+        assert_eq!(verbosity(0, 2), None);
+    }
+
+    #[test]
+    fn repeated_short_flags_are_split() {
+        let args = ["-vv", "merge", "-qq", "-vq", "--", "-vv"]
+            .iter()
+            .map(|arg| arg.to_string());
+        assert_eq!(
+            expand_short_flags(args),
+            vec!["-v", "-v", "merge", "-q", "-q", "-vq", "--", "-vv"]
+        );
+    }
+}
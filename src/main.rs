@@ -1,28 +1,84 @@
-mod args;
-mod detect;
-mod find;
-mod merge;
-mod paths;
-mod split;
-mod timestamp;
-mod transcript;
-
-use args::{Invocation, TopLevel};
-
-fn main() {
-    stderrlog::new().verbosity(1).init().unwrap();
-    match run(argh::from_env()) {
-        Ok(_) => (),
-        Err(msg) => {
-            eprintln!("error: {}", msg);
-            std::process::exit(1);
-        }
-    }
-}
-
-fn run(invocation: TopLevel) -> Result<(), String> {
-    match invocation.invocation {
-        Invocation::Split(opts) => split::split(opts).map_err(|e| format!("{}", e)),
-        Invocation::Merge(opts) => merge::merge(opts).map_err(|e| format!("{}", e)),
-    }
-}
+use std::fmt::Display;
+
+use argh::FromArgs;
+use f4tapir::args::{Invocation, TopLevel};
+use f4tapir::exit::{ExitCode, Failure};
+use f4tapir::{
+    align, check, convert, diagnostic, doctor, excerpt, generate, import, index, info, inject,
+    logging, merge, pair, renumber, report, serve, speakers, split, stats,
+};
+
+fn main() {
+    let invocation = parse_args();
+    invocation.color.init();
+    if let Some(unknown) = invocation
+        .log_target
+        .iter()
+        .find(|target| !logging::TARGETS.contains(&target.as_str()))
+    {
+        eprintln!(
+            "{}: unknown log target {}, expected one of {}",
+            diagnostic::error_label(),
+            unknown,
+            logging::TARGETS.join(", ")
+        );
+        std::process::exit(ExitCode::Failure.code());
+    }
+    logging::init(
+        invocation.verbose,
+        invocation.quiet,
+        &invocation.log_target,
+        invocation.color,
+    )
+    .unwrap();
+    match run(invocation) {
+        Ok(_) => (),
+        Err((code, msg)) => {
+            eprintln!("{}: {}", diagnostic::error_label(), msg);
+            std::process::exit(code.code());
+        }
+    }
+}
+
+/// Like `argh::from_env`, but understands repeated short flags like `-vv`.
+fn parse_args() -> TopLevel {
+    let mut args = std::env::args();
+    let command = args.next().unwrap_or_default();
+    let args = logging::expand_short_flags(args);
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    TopLevel::from_args(&[&command], &args).unwrap_or_else(|early_exit| {
+        println!("{}", early_exit.output);
+        std::process::exit(match early_exit.status {
+            Ok(()) => 0,
+            Err(()) => 1,
+        })
+    })
+}
+
+fn run(invocation: TopLevel) -> Result<(), (ExitCode, String)> {
+    match invocation.invocation {
+        Invocation::Split(opts) => split::split(opts).map_err(failure),
+        Invocation::Merge(opts) => merge::merge(opts).map_err(failure),
+        Invocation::Resegment(opts) => split::resegment(opts).map_err(failure),
+        Invocation::Renumber(opts) => renumber::renumber(opts).map_err(failure),
+        Invocation::Pair(opts) => pair::pair(opts).map_err(failure),
+        Invocation::Info(opts) => info::info(opts).map_err(failure),
+        Invocation::Stats(opts) => stats::stats(opts).map_err(failure),
+        Invocation::Check(opts) => check::check(opts).map_err(failure),
+        Invocation::Report(opts) => report::report(opts).map_err(failure),
+        Invocation::Index(opts) => index::index(opts).map_err(failure),
+        Invocation::Import(opts) => import::import(opts).map_err(failure),
+        Invocation::Doctor(opts) => doctor::doctor(opts).map_err(failure),
+        Invocation::Align(opts) => align::align(opts).map_err(failure),
+        Invocation::Generate(opts) => generate::generate(opts).map_err(failure),
+        Invocation::RenameSpeakers(opts) => speakers::rename_speakers(opts).map_err(failure),
+        Invocation::Excerpt(opts) => excerpt::excerpt(opts).map_err(failure),
+        Invocation::Convert(opts) => convert::convert(opts).map_err(failure),
+        Invocation::InjectTimestamps(opts) => inject::inject_timestamps(opts).map_err(failure),
+        Invocation::Serve(opts) => serve::serve(opts).map_err(failure),
+    }
+}
+
+fn failure<E: Failure + Display>(err: E) -> (ExitCode, String) {
+    (err.exit_code(), format!("{}", err))
+}
@@ -0,0 +1,295 @@
+//! Manifest of the segments that `split` produced for an interview, like
+//! `interview.f4tapir.toml` next to `interview-000.mp3`, so that `merge`
+//! knows their exact order and lengths instead of inferring them.
+//!
+//! ```toml
+//! interview = "interview.mp3"
+//!
+//! [[segments]]
+//! file = "interview-000.mp3"
+//! number = 0
+//! start = 0.0
+//! duration = 300.024
+//! hash = "8f3c0a9d27e1b460"
+//! ```
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::atomic::AtomicFile;
+use crate::logging::MERGE;
+use crate::timestamp::Timestamp;
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+/// Extension of manifest files, after the interview stem.
+pub const MANIFEST_EXTENSION: &str = "f4tapir.toml";
+
+/// The segments of a single interview, in order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// File name of the split interview.
+    pub interview: String,
+    #[serde(default)]
+    pub segments: Vec<ManifestSegment>,
+}
+
+/// A segment file in the directory of the manifest.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestSegment {
+    pub file: String,
+    pub number: u32,
+    /// Seconds into the interview that the segment starts at.
+    pub start: f64,
+    /// Length of the segment in seconds, as reported by ffprobe.
+    pub duration: f64,
+    /// Fingerprint of the segment file, see [`crate::incremental::fingerprint`].
+    pub hash: String,
+}
+
+impl Manifest {
+    /// Path of the manifest for the interview with the given stem in the
+    /// directory.
+    pub fn path(dir: &Path, interview_stem: &str) -> PathBuf {
+        dir.join(format!("{}.{}", interview_stem, MANIFEST_EXTENSION))
+    }
+
+    pub fn load(path: &Path) -> io::Result<Manifest> {
+        let source = fs::read_to_string(path)?;
+        toml::from_str(&source).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let toml =
+            toml::to_string(self).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let mut file = AtomicFile::create(path)?;
+        file.write_all(toml.as_bytes())?;
+        file.commit()
+    }
+
+    /// The manifests in the directory, ignoring those that cannot be read.
+    pub(crate) fn in_dir(dir: &Path) -> Vec<Manifest> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return vec![],
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.ends_with(&format!(".{}", MANIFEST_EXTENSION)))
+            })
+            .collect();
+        paths.sort_unstable();
+        paths
+            .into_iter()
+            .filter_map(|path| match Manifest::load(&path) {
+                Ok(manifest) => Some(manifest),
+                Err(err) => {
+                    warn!(
+                        target: MERGE,
+                        "ignoring segment manifest {}: {}",
+                        path.display(),
+                        err
+                    );
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+/// Order and exact lengths of transcripts of split segments.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SegmentPlan {
+    /// The transcripts in the order of their segments.
+    pub paths: Vec<PathBuf>,
+    /// Length of the segment of every transcript, rounded so that the
+    /// lengths of the segments before one add up to its start.
+    pub end_times: BTreeMap<PathBuf, Timestamp>,
+    /// Segments of the manifests without a transcript, between segments
+    /// with one.
+    pub missing: Vec<MissingSegment>,
+}
+
+/// A segment in a manifest that has no transcript.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingSegment {
+    /// The transcript of the segment before it.
+    pub after: PathBuf,
+    /// The transcript it would have, like the one before it but named
+    /// after the segment.
+    pub path: PathBuf,
+    /// Length of the segment.
+    pub length: Timestamp,
+}
+
+/// Orders the transcripts by the manifests in their directories, if every
+/// one of them is named after a segment in a manifest, like
+/// `interview-000.rtf` for `interview-000.mp3`.
+pub fn plan_from_manifests(paths: &[PathBuf]) -> Option<SegmentPlan> {
+    let mut dirs: Vec<PathBuf> = paths.iter().map(|path| segment_dir(path)).collect();
+    dirs.sort_unstable();
+    dirs.dedup();
+
+    // the position, start and end of every segment by its directory and
+    // stem, with the position ordering interviews by name first
+    let mut segments = BTreeMap::new();
+    // the stem and length of every segment by its directory and position
+    let mut listed = BTreeMap::new();
+    for dir in dirs {
+        for manifest in Manifest::in_dir(&dir) {
+            for (idx, segment) in manifest.segments.iter().enumerate() {
+                let stem = match Path::new(&segment.file).file_stem() {
+                    Some(stem) => stem.to_string_lossy().into_owned(),
+                    None => continue,
+                };
+                let start = seconds_to_timestamp(segment.start)?;
+                let end = seconds_to_timestamp(segment.start + segment.duration)?;
+                let position = (manifest.interview.clone(), idx);
+                let length = Timestamp::from_tenths(end.tenths().saturating_sub(start.tenths()))?;
+                listed.insert((dir.clone(), position.clone()), (stem.clone(), length));
+                segments.insert((dir.clone(), stem), (position, start, end));
+            }
+        }
+    }
+    if segments.is_empty() {
+        return None;
+    }
+
+    let mut planned = Vec::with_capacity(paths.len());
+    for path in paths {
+        let stem = path.file_stem()?.to_string_lossy().into_owned();
+        match segments.get(&(segment_dir(path), stem)) {
+            Some(segment) => planned.push((path, segment)),
+            None => {
+                debug!(
+                    target: MERGE,
+                    "{} is not in a segment manifest, inferring order and shifts",
+                    path.display()
+                );
+                return None;
+            }
+        }
+    }
+    planned.sort_by(|(_, (a, _, _)), (_, (b, _, _))| a.cmp(b));
+    let end_times = planned
+        .iter()
+        .map(|(path, (_, start, end))| {
+            let length = end.tenths().saturating_sub(start.tenths());
+            Some(((*path).clone(), Timestamp::from_tenths(length)?))
+        })
+        .collect::<Option<_>>()?;
+    let mut missing = vec![];
+    for pair in planned.windows(2) {
+        let ((after, ((interview, first), _, _)), (before, ((next_interview, next), _, _))) =
+            (pair[0], pair[1]);
+        let dir = segment_dir(after);
+        if interview != next_interview || dir != segment_dir(before) {
+            continue;
+        }
+        for idx in first + 1..*next {
+            if let Some((stem, length)) = listed.get(&(dir.clone(), (interview.clone(), idx))) {
+                let mut path = after.with_file_name(stem);
+                if let Some(extension) = after.extension() {
+                    path.set_extension(extension);
+                }
+                missing.push(MissingSegment {
+                    after: after.clone(),
+                    path,
+                    length: *length,
+                });
+            }
+        }
+    }
+    Some(SegmentPlan {
+        paths: planned.into_iter().map(|(path, _)| path.clone()).collect(),
+        end_times,
+        missing,
+    })
+}
+
+fn segment_dir(path: &Path) -> PathBuf {
+    path.parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."))
+        .to_path_buf()
+}
+
+fn seconds_to_timestamp(seconds: f64) -> Option<Timestamp> {
+    if seconds.is_finite() && seconds >= 0.0 {
+        Timestamp::from_tenths((seconds * 10.0).round() as u64)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn segment(file: &str, number: u32, start: f64, duration: f64) -> ManifestSegment {
+        ManifestSegment {
+            file: file.to_string(),
+            number,
+            start,
+            duration,
+            hash: String::from("0000000000000000"),
+        }
+    }
+
+    #[test]
+    fn transcripts_are_ordered_and_shifted_by_the_manifest() {
+        let dir = std::env::temp_dir().join("f4tapir-manifest");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let manifest = Manifest {
+            interview: String::from("interview.mp3"),
+            segments: vec![
+                segment("interview-000.mp3", 0, 0.0, 299.96),
+                segment("interview-001.mp3", 1, 299.96, 300.04),
+                segment("interview-002.mp3", 2, 600.0, 12.5),
+            ],
+        };
+        let path = Manifest::path(&dir, "interview");
+        manifest.write(&path).unwrap();
+        assert_eq!(Manifest::load(&path).unwrap(), manifest);
+
+        let paths = vec![
+            dir.join("interview-002.rtf"),
+            dir.join("interview-000.rtf"),
+            dir.join("interview-001.rtf"),
+        ];
+        let plan = plan_from_manifests(&paths).unwrap();
+        assert_eq!(
+            plan.paths,
+            vec![
+                dir.join("interview-000.rtf"),
+                dir.join("interview-001.rtf"),
+                dir.join("interview-002.rtf"),
+            ]
+        );
+        let tenths: Vec<u64> = plan
+            .paths
+            .iter()
+            .map(|path| plan.end_times[path].tenths())
+            .collect();
+        assert_eq!(tenths, vec![3000, 3000, 125]);
+
+        let gappy = vec![dir.join("interview-000.rtf"), dir.join("interview-002.rtf")];
+        assert_eq!(
+            plan_from_manifests(&gappy).unwrap().missing,
+            vec![MissingSegment {
+                after: dir.join("interview-000.rtf"),
+                path: dir.join("interview-001.rtf"),
+                length: Timestamp::from_tenths(3000).unwrap(),
+            }]
+        );
+
+        let unlisted = vec![dir.join("interview-000.rtf"), dir.join("notes.rtf")];
+        assert_eq!(plan_from_manifests(&unlisted), None);
+    }
+}
@@ -1,64 +1,1486 @@
-use std::fs::File;
-use std::path::PathBuf;
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
 
 use crate::args::Merge;
-use crate::find::collect_transcripts;
-use crate::transcript::{write_merged_transcript, Error as TranscriptError, Transcript};
+use crate::atomic::AtomicFile;
+use crate::audio_order::{edges_of, order_by_audio, Error as AudioOrderError};
+use crate::backup::backup;
+use crate::cues::parse_time;
+use crate::detect::is_sound_file;
+use crate::diagnostic::{Annotation, MaybeAnnotation};
+use crate::exit::{ExitCode, Failure};
+use crate::find::{collect_interview_transcripts, segment_gaps, PathFilter, SegmentGap};
+use crate::incremental::fingerprint;
+use crate::load::{default_jobs, load_transcripts};
+use crate::logging::MERGE;
+use crate::manifest::{plan_from_manifests, MissingSegment};
+use crate::offsets::{parse_offsets, Error as OffsetsError};
+use crate::order::{order_problems, SegmentOrder};
+use crate::overlap::measure_overlap;
+use crate::pair::{probe_duration, Error as PairError};
+use crate::remote;
+use crate::renumber::segment_name;
+use crate::resume::{partial_path, MergeState};
+use crate::split::{probe_clock_start, Error as SplitError};
+use crate::store::{self, Error as StoreError};
+use crate::timestamp::{Timestamp, TimestampSyntax};
+use crate::transcript::json::Document;
+use crate::transcript::{
+    write_merged_outputs, DocumentInfo, Encoder, Error as TranscriptError, LineNumbering,
+    LoadOptions, MergeOptions, Merger, OutputEncoding, OutputKind, SegmentShift, Template,
+    Trailers, Transcript,
+};
 
-use log::warn;
+use log::{debug, info, warn};
 use thiserror::Error;
 
 type Result<T> = std::result::Result<T, Error>;
 
+/// Speaker of the placeholder utterances of missing segments.
+const PLACEHOLDER_SPEAKER: &str = "MISSING";
+
 pub fn merge(opts: Merge) -> Result<()> {
-    let mut transcripts = collect_transcripts(opts.input_segments, opts.recursive)?
-        .into_iter()
-        .filter_map(|path| match Transcript::from_file(&path) {
-            Ok(transcript) => Some(transcript),
-            Err(err) => {
+    let syntax = if opts.lenient_timestamps {
+        TimestampSyntax::Lenient
+    } else {
+        TimestampSyntax::Strict
+    };
+    let template = match &opts.template {
+        Some(path) => {
+            let source = std::fs::read_to_string(path)
+                .map_err(|err| Error::TemplateLoadFail(path.clone(), err))?;
+            Some(Template::parse(&source)?)
+        }
+        None => None,
+    };
+    let clock_start = match (&opts.clock_start, &opts.clock_start_from) {
+        (Some(_), Some(_)) => return Err(Error::ClockStartOptions),
+        (Some(time), None) => Some(
+            parse_time(time)
+                .and_then(|time| Timestamp::from_tenths(time.as_millis() as u64 / 100))
+                .ok_or_else(|| Error::ClockStart(time.clone()))?,
+        ),
+        (None, Some(recording)) => Some(probe_clock_start(recording)?),
+        (None, None) => None,
+    };
+    let segment_offsets = match &opts.segment_offset {
+        Some(_) if opts.group_by.is_some() => return Err(Error::SegmentOffsetGroups),
+        Some(path) => Some(
+            std::fs::read_to_string(path)
+                .map_err(|err| Error::SegmentOffsetLoadFail(path.clone(), err))?,
+        ),
+        None => None,
+    };
+    let stitch_max_gap = Timestamp::from_tenths(opts.stitch_max_gap.saturating_mul(10))
+        .unwrap_or_else(Timestamp::max_value);
+    let format = Format {
+        template,
+        options: MergeOptions {
+            stitch: !opts.no_stitch,
+            stitch_max_gap,
+            mark_boundaries: opts.mark_boundaries,
+            tidy: opts.tidy,
+            wrap: opts.wrap.filter(|&width| width > 0),
+            infer_speakers: opts.infer_speakers,
+            trailers: match (opts.move_trailers, opts.strip_trailers) {
+                (false, false) => Trailers::Keep,
+                (true, false) => Trailers::Move,
+                (false, true) => Trailers::Strip,
+                (true, true) => return Err(Error::TrailerOptions),
+            },
+            strip_segment_headers: opts.strip_segment_headers,
+            absolute_timestamps: clock_start.is_some(),
+            drop_boundary_duplicates: opts.drop_boundary_duplicates,
+            language: opts.lang,
+            strip_annotations: opts.strip_annotations,
+            color_speakers: opts.color_speakers,
+            toc: opts.toc,
+            line_numbers: Some(LineNumbering {
+                start: opts.line_number_start,
+                step: opts.line_number_step,
+            })
+            .filter(|_| opts.line_numbers),
+        },
+    };
+    let output = Output {
+        force: opts.force,
+        backup_dir: opts.backup_dir.as_deref(),
+        load: LoadOptions {
+            syntax,
+            strip_revisions: opts.strip_revisions,
+            clock_start,
+            timestamps: opts.timestamp_dialect,
+        },
+        format: &format,
+        fail_on_unparsed: opts.fail_on_unparsed,
+        paranoid: opts.paranoid,
+        merge_info: opts.merge_info,
+        order_check: opts.order_check,
+        order_by_audio: opts.order_by_audio,
+        check_overlap: opts.check_overlap,
+        resume: opts.resume,
+        skip_broken: opts.skip_broken,
+        encoding: opts.output_encoding,
+        bom: opts.bom,
+        jobs: opts.jobs.unwrap_or_else(default_jobs),
+        incremental: opts.incremental,
+        ignore_manifest: opts.ignore_manifest,
+        allow_gaps: opts.allow_gaps,
+        insert_placeholders: opts.insert_placeholders,
+        shift_strategy: opts.shift_strategy,
+        segment_offsets: segment_offsets.as_deref(),
+    };
+    if opts.resume && opts.skip_broken {
+        return Err(Error::ResumeSkipBroken);
+    }
+    if opts.incremental && (opts.resume || opts.skip_broken) {
+        return Err(Error::IncrementalOptions);
+    }
+    if opts.resume || opts.incremental {
+        // these need all transcripts at once, not only the ones after
+        // the saved progress
+        let not_resumable = [
+            (opts.move_trailers, "--move-trailers"),
+            (opts.merge_info, "--merge-info"),
+            (opts.insert_placeholders, "--insert-placeholders"),
+            (opts.color_speakers, "--color-speakers"),
+            (opts.toc, "--toc"),
+            (opts.line_numbers, "--line-numbers"),
+        ];
+        if let Some(&(_, option)) = not_resumable.iter().find(|(given, _)| *given) {
+            return Err(Error::NotResumable(option));
+        }
+    }
+    if opts.line_numbers && opts.line_number_step == 0 {
+        return Err(Error::LineNumberStep);
+    }
+    if opts.bom && opts.output_encoding != OutputEncoding::Utf8 {
+        return Err(Error::BomEncoding);
+    }
+
+    let inputs = remote::fetch_inputs(opts.input_segments)?;
+    let mut paths = collect_interview_transcripts(inputs, opts.recursive)?;
+    PathFilter::new(opts.include, opts.exclude).apply(&mut paths);
+    let summaries = match (opts.group_by, opts.output_dir) {
+        (None, None) => {
+            if opts.output_file.is_empty() != opts.stdout {
+                return Err(Error::OutputOptions);
+            }
+            vec![merge_paths(paths, opts.output_file, &output)?]
+        }
+        (Some(group_by), Some(output_dir)) if opts.output_file.is_empty() && !opts.stdout => {
+            let extension = match &opts.template {
+                Some(template) => template
+                    .extension()
+                    .map(|ext| ext.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| String::from("txt")),
+                None => String::from("rtf"),
+            };
+            let mut summaries = vec![];
+            for (name, paths) in group_by.group(paths)? {
+                let output_file = output_dir.join(format!("{}.{}", name, extension));
+                summaries.push(merge_paths(paths, vec![output_file], &output)?);
+            }
+            summaries
+        }
+        _ => return Err(Error::GroupOptions),
+    };
+
+    if let Some(shift_log) = opts.shift_log {
+        let mut file = File::create(shift_log).map_err(Error::WriteError)?;
+        for summary in &summaries {
+            summary.write(&mut file).map_err(Error::WriteError)?;
+        }
+    }
+    if let Some(store) = opts.store {
+        for summary in &summaries {
+            store::record_merge(
+                &store,
+                &summary.output,
+                &summary.merged_paths,
+                &summary.shifts,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Where and how merged transcripts are written.
+struct Output<'a> {
+    force: bool,
+    backup_dir: Option<&'a Path>,
+    load: LoadOptions,
+    format: &'a Format,
+    /// Fail before writing anything if a transcript has unparsed lines.
+    fail_on_unparsed: bool,
+    /// Fail before writing anything if a transcript has markup that is
+    /// not known to be harmless.
+    paranoid: bool,
+    /// Combine the document info of all transcripts in the preamble.
+    merge_info: bool,
+    /// Warn if the segments seem to be in the wrong order.
+    order_check: bool,
+    /// Order the segments by their audio instead of their names.
+    order_by_audio: bool,
+    /// Warn if the audio of consecutive segments overlaps.
+    check_overlap: bool,
+    /// Save the progress and continue an earlier merge, see `merge_resumable`.
+    resume: bool,
+    /// Leave out transcripts that fail to load instead of failing.
+    skip_broken: bool,
+    encoding: OutputEncoding,
+    /// Start outputs other than RTF with a byte order mark.
+    bom: bool,
+    /// Number of threads to load transcripts with.
+    jobs: usize,
+    /// Only merge again from the first transcript that changed since the
+    /// last merge, see `merge_incremental`.
+    incremental: bool,
+    /// Infer order and shifts even if `split` left a segment manifest.
+    ignore_manifest: bool,
+    /// Merge even if segments are missing from a numbered sequence.
+    allow_gaps: bool,
+    /// Insert a placeholder utterance for every missing segment.
+    insert_placeholders: bool,
+    /// How the end times of segments are found, if given instead of
+    /// taking them from the manifest.
+    shift_strategy: Option<ShiftStrategy>,
+    /// Source of `--segment-offset`, see [`crate::offsets`].
+    segment_offsets: Option<&'a str>,
+}
+
+/// What was merged into one output.
+struct Summary {
+    /// Name of the output, `<stdout>` for standard output.
+    output: String,
+    /// Paths of the transcripts that were actually merged.
+    merged_paths: Vec<PathBuf>,
+    shifts: Vec<SegmentShift>,
+    skipped: Vec<Skipped>,
+}
+
+/// A transcript that failed to load and was left out.
+struct Skipped {
+    path: PathBuf,
+    /// Number of transcripts merged before it.
+    after: usize,
+    cause: String,
+}
+
+impl Summary {
+    /// Writes the shifts and the skipped transcripts.
+    fn write<W: Write>(&self, mut to: W) -> std::io::Result<()> {
+        write_shift_summary(&mut to, &self.merged_paths, &self.shifts)?;
+        write_skipped(&mut to, &self.skipped, &self.shifts)
+    }
+}
+
+/// Merges the transcripts into the output files or to standard output if
+/// there are none.
+///
+/// Fails on the first transcript that cannot be loaded, unless it should
+/// skip broken transcripts, which are then listed in the summary.
+fn merge_paths(
+    paths: Vec<PathBuf>,
+    output_files: Vec<PathBuf>,
+    output: &Output,
+) -> Result<Summary> {
+    let (paths, manifest, missing) =
+        match plan_from_manifests(&paths).filter(|_| !output.ignore_manifest) {
+            Some(plan) => {
+                info!(
+                    target: MERGE,
+                    "ordering {} transcripts by their segment manifest",
+                    plan.paths.len()
+                );
+                (plan.paths, plan.end_times, plan.missing)
+            }
+            None => (paths, BTreeMap::new(), vec![]),
+        };
+    let paths = if output.order_by_audio {
+        order_paths_by_audio(paths)?
+    } else {
+        paths
+    };
+    let gaps = segment_gaps(&paths);
+    if !gaps.is_empty() && !output.allow_gaps && !output.insert_placeholders {
+        return Err(Error::SegmentGaps(gaps[0].clone(), gaps.len()));
+    }
+    for gap in &gaps {
+        warn!(target: MERGE, "{}", gap);
+    }
+    let placeholders = if output.insert_placeholders {
+        placeholders(&gaps, &missing, output.shift_strategy)
+    } else {
+        for segment in &missing {
+            warn!(
+                target: MERGE,
+                "segment {} of the manifest has no transcript",
+                segment.path.display()
+            );
+        }
+        BTreeMap::new()
+    };
+    let timing = Timing::new(
+        &paths,
+        manifest,
+        output.shift_strategy,
+        output.segment_offsets,
+    )?;
+    if output.fail_on_unparsed {
+        ensure_parsed(&paths, output.load)?;
+    }
+    if output.paranoid {
+        ensure_known_markup(&paths, output.load)?;
+    }
+    if output.check_overlap {
+        warn_overlaps(&paths)?;
+    }
+    if output.order_check {
+        let segments: Vec<SegmentOrder> = paths.iter().map(|path| SegmentOrder::of(path)).collect();
+        for problem in order_problems(&segments) {
+            warn!(target: MERGE, "suspicious order: {}", problem);
+        }
+    }
+    info!(
+        target: MERGE,
+        "merging {} transcripts into {}",
+        paths.len(),
+        if output_files.is_empty() {
+            String::from("<stdout>")
+        } else {
+            output_files
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+    );
+
+    if output.resume || output.incremental {
+        let output_file = match output_files.as_slice() {
+            [output_file] if output.format.of(output_file) == OutputKind::RTF => output_file,
+            _ => return Err(Error::ResumeOutput),
+        };
+        if output.encoding != OutputEncoding::Utf8 {
+            return Err(Error::ResumeEncoding);
+        }
+        let shifts = if output.resume {
+            merge_resumable(&paths, &timing, output_file, output)?
+        } else {
+            merge_incremental(&paths, &timing, output_file, output)?
+        };
+        let output_name = output_file.display().to_string();
+        warn_unadjusted(&output_name, &paths, &shifts);
+        let summary = Summary {
+            output: output_name,
+            merged_paths: paths,
+            shifts,
+            skipped: vec![],
+        };
+        summary.write(std::io::stderr().lock())?;
+        return Ok(summary);
+    }
+
+    // paths of the transcripts that were actually merged
+    let mut merged_paths = vec![];
+    let mut skipped = vec![];
+    // first transcript that failed to load, which ends the merge
+    let mut broken = None;
+    let info = if output.merge_info {
+        Some(combined_info(&paths, output.load))
+    } else {
+        None
+    };
+    let mut transcripts = load_transcripts(paths, output.load, output.jobs)
+        .map_while(|(path, loaded)| match loaded {
+            Ok(transcript) => {
+                let transcript = match timing.apply(transcript, &path) {
+                    Ok(transcript) => transcript,
+                    Err(err) => {
+                        broken = Some(err);
+                        return None;
+                    }
+                };
+                debug!(
+                    target: MERGE,
+                    "loaded {}, ending at {}",
+                    path.display(),
+                    transcript.interview_end_time()
+                );
+                if transcript.has_lenient_timestamps() {
+                    warn!(
+                        target: MERGE,
+                        "transcript {} has timestamps like #00.01.23-4# that will not be adjusted, use --lenient-timestamps",
+                        path.display()
+                    );
+                }
+                warn_unparsed(&path, &transcript);
+                let mut merged = vec![];
+                for (missing, length) in placeholders.get(&path).into_iter().flatten() {
+                    let length = length.unwrap_or_else(|| transcript.interview_end_time());
+                    info!(
+                        target: MERGE,
+                        "inserting a placeholder of {} for missing segment {}",
+                        length,
+                        missing.display()
+                    );
+                    match placeholder(missing, length) {
+                        Ok(placeholder) => merged.push((placeholder_path(missing), placeholder)),
+                        Err(err) => {
+                            broken = Some(err);
+                            return None;
+                        }
+                    }
+                }
+                merged.insert(0, (path, transcript));
+                let (paths, transcripts): (Vec<_>, Vec<_>) = merged.into_iter().unzip();
+                merged_paths.extend(paths);
+                Some(transcripts)
+            }
+            Err(err) if output.skip_broken => {
                 warn!(
+                    target: MERGE,
                     "failed to load transcript {}, skipping, cause: {}",
                     path.display(),
                     err
                 );
+                skipped.push(Skipped {
+                    path,
+                    after: merged_paths.len(),
+                    cause: err.to_string(),
+                });
+                Some(vec![])
+            }
+            Err(err) => {
+                broken = Some(Error::broken(path, err));
                 None
             }
         })
+        .flatten()
+        .enumerate()
+        .map(|(idx, transcript)| match &info {
+            Some(info) if idx == 0 => transcript.with_info(info),
+            _ => transcript,
+        })
         .peekable();
 
     // need at least on transcript
     if transcripts.peek().is_none() {
-        return Err(Error::NoTranscripts);
+        drop(transcripts);
+        return Err(broken.unwrap_or(Error::NoTranscripts));
     }
 
+    let rtf_output = output_files
+        .iter()
+        .find(|path| output.format.of(path) == OutputKind::RTF)
+        .cloned();
+    let output_name = rtf_output
+        .as_ref()
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|| String::from("<stdout>"));
+
+    let line_index = match &rtf_output {
+        _ if output.format.options.line_numbers.is_none() => None,
+        Some(rtf) => Some(line_index_path(rtf)),
+        None => {
+            warn!(
+                target: MERGE,
+                "the index of the line numbers is only written next to an RTF output file"
+            );
+            None
+        }
+    };
+
     // write merged transcript while lazily loading them
-    match opts.output_file {
-        Some(output_file) => write_to_file(transcripts, output_file, opts.force),
-        None => write_to_stdout(transcripts),
+    let (files, shifts) = if output_files.is_empty() {
+        (vec![], write_to_stdout(transcripts, output)?)
+    } else {
+        write_to_files(transcripts, output_files, line_index, output)?
+    };
+    if let Some(err) = broken {
+        return Err(err);
+    }
+    // only report success once the merged transcripts are on disk
+    for file in files {
+        file.commit().map_err(Error::WriteError)?;
+    }
+    warn_unadjusted(&output_name, &merged_paths, &shifts);
+
+    let summary = Summary {
+        output: output_name,
+        merged_paths,
+        shifts,
+        skipped,
+    };
+    // print to stderr, stdout may be the merged transcript
+    summary.write(std::io::stderr().lock())?;
+    Ok(summary)
+}
+
+/// Merges into a partial file next to the output file and records the
+/// progress after every transcript, so that a merge of the same
+/// transcripts that failed can continue where it stopped. Transcripts
+/// that fail to load stop the merge instead of being skipped.
+///
+/// The output file is only replaced once all transcripts are merged.
+fn merge_resumable(
+    paths: &[PathBuf],
+    timing: &Timing,
+    output_file: &Path,
+    output: &Output,
+) -> Result<Vec<SegmentShift>> {
+    let options = &output.format.options;
+    let load = |path: &PathBuf| -> Result<Transcript> {
+        let transcript =
+            Transcript::load(path, output.load).map_err(|err| Error::broken(path.clone(), err))?;
+        warn_unparsed(path, &transcript);
+        timing.apply(transcript, path)
+    };
+    let first = load(paths.first().ok_or(Error::NoTranscripts)?)?;
+    back_up_existing(
+        &[output_file.to_path_buf()],
+        output.force,
+        output.backup_dir,
+    )?;
+
+    let mut state = MergeState::load(
+        output_file,
+        &format!("{:?} {:?} {:?}", options, output.load, timing),
+    )?;
+    let partial_path = partial_path(output_file);
+    let partial_len = fs::metadata(&partial_path).map_or(0, |metadata| metadata.len());
+    let mut resumed = state.resumable(paths);
+    state.truncate(resumed);
+    if state.last().is_some_and(|last| last.bytes > partial_len) {
+        resumed = 0;
+        state.truncate(0);
+    }
+
+    let mut partial = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(resumed == 0)
+        .open(&partial_path)
+        .map_err(Error::WriteError)?;
+    let mut merger = match state.last() {
+        Some(last) => {
+            info!(
+                target: MERGE,
+                "resuming after {} transcripts, the last was {}",
+                resumed,
+                last.path.display()
+            );
+            partial.set_len(last.bytes).map_err(Error::WriteError)?;
+            partial.seek(SeekFrom::End(0)).map_err(Error::WriteError)?;
+            let last = load(&paths[resumed - 1])?;
+            Merger::resume(&partial, &first, Some(last), state.checkpoint(), options)
+        }
+        None => Merger::new(&partial, &first, options)?,
+    };
+    for path in &paths[resumed..] {
+        merger.add(load(path)?)?;
+        let checkpoint = merger.checkpoint()?;
+        let bytes = partial.metadata().map_err(Error::WriteError)?.len();
+        state.record(path, &checkpoint, bytes)?;
+    }
+    let shifts = merger.finish()?;
+    partial.sync_all().map_err(Error::WriteError)?;
+    fs::rename(&partial_path, output_file).map_err(Error::WriteError)?;
+    state.remove()?;
+    Ok(shifts)
+}
+
+/// Merges only the transcripts from the first one that changed since the
+/// last incremental merge into the output file, and reuses the merged
+/// transcript before it, e.g. when only the last segment changes while it
+/// is being transcribed.
+///
+/// The shifts and positions of every merged transcript are cached next to
+/// the output file. The cache is only used if the output file is the one
+/// it was recorded for, otherwise everything is merged again.
+fn merge_incremental(
+    paths: &[PathBuf],
+    timing: &Timing,
+    output_file: &Path,
+    output: &Output,
+) -> Result<Vec<SegmentShift>> {
+    let options = &output.format.options;
+    let load = |path: &PathBuf| -> Result<Transcript> {
+        let transcript =
+            Transcript::load(path, output.load).map_err(|err| Error::broken(path.clone(), err))?;
+        warn_unparsed(path, &transcript);
+        timing.apply(transcript, path)
+    };
+    let first = load(paths.first().ok_or(Error::NoTranscripts)?)?;
+
+    let mut state = MergeState::load_cache(
+        output_file,
+        &format!("{:?} {:?} {:?}", options, output.load, timing),
+    )?;
+    // the cache is only for the output file as it was last merged
+    let existing = fingerprint(output_file, "").ok();
+    let ours = existing.is_some() && existing == state.output_fingerprint();
+    let reused = if ours { state.resumable(paths) } else { 0 };
+    if reused == paths.len() && state.transcripts() == paths.len() {
+        info!(target: MERGE, "{} is up to date", output_file.display());
+        return Ok(state.checkpoint().shifts);
+    }
+    if !ours {
+        back_up_existing(
+            &[output_file.to_path_buf()],
+            output.force,
+            output.backup_dir,
+        )?;
+    }
+    // the last reused transcript is merged again since its last line is
+    // only written after the next one
+    let reused = reused.saturating_sub(1);
+    state.truncate(reused);
+
+    let written = Cell::new(0);
+    let mut file = AtomicFile::create(output_file).map_err(Error::WriteError)?;
+    let mut merger = match state.last() {
+        Some(last) => {
+            info!(
+                target: MERGE,
+                "reusing the merge of {} unchanged transcripts, up to {}",
+                reused,
+                last.path.display()
+            );
+            let mut reader = File::open(output_file)?.take(last.bytes);
+            std::io::copy(&mut reader, &mut file).map_err(Error::WriteError)?;
+            written.set(last.bytes);
+            let last = load(&paths[reused - 1])?;
+            let to = Counting::new(&mut file, &written);
+            Merger::resume(to, &first, Some(last), state.checkpoint(), options)
+        }
+        None => Merger::new(Counting::new(&mut file, &written), &first, options)?,
+    };
+    for path in &paths[reused..] {
+        merger.add(load(path)?)?;
+        let checkpoint = merger.checkpoint()?;
+        state.record(path, &checkpoint, written.get())?;
+    }
+    let shifts = merger.finish()?;
+    file.commit().map_err(Error::WriteError)?;
+    state.record_output(fingerprint(output_file, "")?)?;
+    Ok(shifts)
+}
+
+/// The missing segments to insert placeholders for after each transcript,
+/// with their lengths if they are known before merging.
+fn placeholders(
+    gaps: &[SegmentGap],
+    missing: &[MissingSegment],
+    strategy: Option<ShiftStrategy>,
+) -> BTreeMap<PathBuf, Vec<(PathBuf, Option<Timestamp>)>> {
+    let fixed = match strategy {
+        Some(ShiftStrategy::Fixed(length)) => Some(length),
+        _ => None,
+    };
+    let mut placeholders: BTreeMap<PathBuf, Vec<(PathBuf, Option<Timestamp>)>> = BTreeMap::new();
+    for segment in missing {
+        placeholders
+            .entry(segment.after.clone())
+            .or_default()
+            .push((segment.path.clone(), fixed.or(Some(segment.length))));
+    }
+    for gap in gaps {
+        let inserted = placeholders.entry(gap.after.clone()).or_default();
+        for path in gap.missing_paths() {
+            if !inserted.iter().any(|(known, _)| *known == path) {
+                inserted.push((path, fixed));
+            }
+        }
     }
+    placeholders
 }
 
-fn write_to_file<I>(merged: I, output_file: PathBuf, force: bool) -> Result<()>
+/// A transcript of a missing segment with a single utterance, which
+/// covers the segment from its start to its end.
+fn placeholder(missing: &Path, length: Timestamp) -> Result<Transcript> {
+    let name = missing
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let text = format!(
+        "{}: {} [missing segment {}] {}",
+        PLACEHOLDER_SPEAKER,
+        Timestamp::zero(),
+        name,
+        length
+    );
+    let transcript = Document::from_text(&text).to_transcript()?;
+    Ok(transcript.with_interview_end_time(length))
+}
+
+/// How a placeholder is listed among the merged transcripts.
+fn placeholder_path(missing: &Path) -> PathBuf {
+    PathBuf::from(format!("{} (placeholder)", missing.display()))
+}
+
+/// How the shift of every segment is found.
+#[derive(Debug)]
+pub struct Timing {
+    end_times: EndTimes,
+    /// Start offsets of segments in tenths of a second, see
+    /// [`Transcript::with_start_offset`].
+    offsets: BTreeMap<PathBuf, i64>,
+}
+
+impl Timing {
+    /// Times the transcripts at the given paths by the shift strategy if
+    /// given, else by the end times in their manifest if any, else by
+    /// rounding up their last timestamps, and by the offsets in the source
+    /// of `--segment-offset`.
+    pub fn new(
+        paths: &[PathBuf],
+        manifest: BTreeMap<PathBuf, Timestamp>,
+        shift_strategy: Option<ShiftStrategy>,
+        segment_offsets: Option<&str>,
+    ) -> Result<Timing> {
+        let end_times = match shift_strategy {
+            Some(strategy) => EndTimes::Strategy(strategy),
+            None if !manifest.is_empty() => EndTimes::Manifest(manifest),
+            None => EndTimes::Strategy(ShiftStrategy::RoundUp),
+        };
+        let offsets = match segment_offsets {
+            Some(source) => parse_offsets(source, paths)?,
+            None => BTreeMap::new(),
+        };
+        Ok(Timing { end_times, offsets })
+    }
+
+    /// The transcript at the given path, with the end time and start
+    /// offset that its shift and the one of the next segment follow from.
+    pub fn apply(&self, transcript: Transcript, path: &Path) -> Result<Transcript> {
+        let transcript = self.end_times.apply(transcript, path)?;
+        Ok(match self.offsets.get(path) {
+            Some(&offset) => transcript.with_start_offset(offset),
+            None => transcript,
+        })
+    }
+}
+
+/// Where the end times of segments come from, which the segments after
+/// them are shifted by.
+#[derive(Debug)]
+enum EndTimes {
+    Strategy(ShiftStrategy),
+    /// The lengths of the segments in their manifest.
+    Manifest(BTreeMap<PathBuf, Timestamp>),
+}
+
+impl EndTimes {
+    /// The transcript at the given path, ending at the end time found for
+    /// it.
+    fn apply(&self, transcript: Transcript, path: &Path) -> Result<Transcript> {
+        let (end_time, by) = match self {
+            EndTimes::Strategy(ShiftStrategy::RoundUp) => return Ok(transcript),
+            EndTimes::Strategy(ShiftStrategy::ExactLast) => {
+                (transcript.last_timestamp(), "its last timestamp")
+            }
+            EndTimes::Strategy(ShiftStrategy::AudioDuration) => {
+                let audio = audio_of(path).ok_or_else(|| Error::NoAudio(path.to_path_buf()))?;
+                let duration = probe_duration(&audio)
+                    .map_err(|err| Error::AudioDuration(path.to_path_buf(), err))?;
+                (duration, "its audio")
+            }
+            EndTimes::Strategy(ShiftStrategy::Fixed(length)) => (*length, "--shift-strategy"),
+            EndTimes::Manifest(end_times) => match end_times.get(path) {
+                Some(&end_time) => (end_time, "the manifest"),
+                None => return Ok(transcript),
+            },
+        };
+        debug!(
+            target: MERGE,
+            "{} ends at {} by {}, rounding up its last timestamp suggests {}",
+            path.display(),
+            end_time,
+            by,
+            transcript.interview_end_time()
+        );
+        Ok(transcript.with_interview_end_time(end_time))
+    }
+}
+
+/// Orders the transcripts by the audio next to them, warning about those
+/// that cannot be placed.
+fn order_paths_by_audio(paths: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+    let mut segments = Vec::with_capacity(paths.len());
+    for path in paths {
+        let audio = audio_of(&path).ok_or_else(|| Error::NoAudio(path.clone()))?;
+        let edges = edges_of(&audio)?;
+        segments.push((path, edges));
+    }
+    let order = order_by_audio(&segments);
+    for path in &order.unplaced {
+        warn!(
+            target: MERGE,
+            "could not place {} by its audio, keeping it in the order of the names",
+            path.display()
+        );
+    }
+    info!(
+        target: MERGE,
+        "ordered {} transcripts by their audio",
+        order.paths.len()
+    );
+    Ok(order.paths)
+}
+
+/// Warns about consecutive transcripts whose audio overlaps, so that the
+/// transcripts may repeat the sentences in the overlap.
+fn warn_overlaps(paths: &[PathBuf]) -> Result<()> {
+    for (idx, pair) in paths.windows(2).enumerate() {
+        let (previous, next) = match (audio_of(&pair[0]), audio_of(&pair[1])) {
+            (Some(previous), Some(next)) => (previous, next),
+            _ => continue,
+        };
+        if let Some(overlap) = measure_overlap(&previous, &next)? {
+            warn!(
+                target: MERGE,
+                "the audio of {} starts with the last {:.1}s of {}, the transcripts may repeat \
+                 what was said then, and a --segment-offset of \"{} -{:.1}\" corrects the shift",
+                pair[1].display(),
+                overlap.as_secs_f64(),
+                pair[0].display(),
+                idx + 2,
+                overlap.as_secs_f64()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// The audio file next to the transcript with the same stem, e.g.
+/// `interview-000.mp3` for `interview-000.rtf`.
+fn audio_of(transcript: &Path) -> Option<PathBuf> {
+    let stem = transcript.file_stem()?;
+    let dir = transcript
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let mut candidates: Vec<PathBuf> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.file_stem() == Some(stem) && is_sound_file(path))
+        .collect();
+    candidates.sort_unstable();
+    candidates.into_iter().next()
+}
+
+/// Counts the bytes written through it.
+struct Counting<'a, W> {
+    inner: W,
+    written: &'a Cell<u64>,
+}
+
+impl<'a, W: Write> Counting<'a, W> {
+    fn new(inner: W, written: &'a Cell<u64>) -> Self {
+        Counting { inner, written }
+    }
+}
+
+impl<W: Write> Write for Counting<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.written.set(self.written.get() + len as u64);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Warns about every line of the transcript that could not be parsed and
+/// is hence copied without adjusting its timestamps, and returns how many
+/// there are.
+fn warn_unparsed(path: &Path, transcript: &Transcript) -> usize {
+    let unparsed = transcript.unparsed_lines();
+    for line in &unparsed {
+        warn!(
+            target: MERGE,
+            "{}:{}: could not parse line, copying it with {} unadjusted timestamps: {}",
+            path.display(),
+            line.line,
+            line.timestamps,
+            line.snippet
+        );
+    }
+    if unparsed.len() > 1 {
+        warn!(
+            target: MERGE,
+            "{} lines of {} could not be parsed",
+            unparsed.len(),
+            path.display()
+        );
+    }
+    unparsed.len()
+}
+
+/// Document info of the transcripts that can be loaded, combined to
+/// replace that of the first one.
+fn combined_info(paths: &[PathBuf], options: LoadOptions) -> DocumentInfo {
+    let mut info = DocumentInfo::default();
+    for path in paths {
+        if let Ok(transcript) = Transcript::load(path, options) {
+            let name = path.file_name().unwrap_or(path.as_os_str());
+            info.add(&name.to_string_lossy(), &transcript);
+        }
+    }
+    info
+}
+
+/// Fails on the first transcript with control words that are not known
+/// to be harmless, after reporting all of them in that transcript.
+fn ensure_known_markup(paths: &[PathBuf], options: LoadOptions) -> Result<()> {
+    for path in paths {
+        if let Ok(transcript) = Transcript::load(path, options) {
+            let unknown = transcript.unknown_control_words();
+            for word in &unknown {
+                warn!(
+                    target: MERGE,
+                    "{}:{}:{}: unknown control word {}",
+                    path.display(),
+                    word.line,
+                    word.column,
+                    word.word
+                );
+            }
+            if let Some(first) = unknown.into_iter().next() {
+                return Err(Error::UnknownMarkup(
+                    path.clone(),
+                    first.line,
+                    first.column,
+                    first.word,
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Fails on the first transcript with lines that could not be parsed,
+/// after warning about them. Transcripts that fail to load are left for
+/// merging to report.
+fn ensure_parsed(paths: &[PathBuf], options: LoadOptions) -> Result<()> {
+    for path in paths {
+        if let Ok(transcript) = Transcript::load(path, options) {
+            let unparsed = warn_unparsed(path, &transcript);
+            if unparsed > 0 {
+                return Err(Error::Unparsed(path.clone(), unparsed));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// How the end time of a segment, which the segments after it are
+/// shifted by, is found with `--shift-strategy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShiftStrategy {
+    /// Rounds up the last timestamp, see [`Timestamp::round_up`].
+    RoundUp,
+    /// Takes the last timestamp as it is.
+    ExactLast,
+    /// Asks ffprobe for the length of the audio next to the transcript.
+    AudioDuration,
+    /// Every segment has the same length.
+    Fixed(Timestamp),
+}
+
+impl std::str::FromStr for ShiftStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.split_once('=') {
+            None if s == "round-up" => Ok(ShiftStrategy::RoundUp),
+            None if s == "exact-last" => Ok(ShiftStrategy::ExactLast),
+            None if s == "audio-duration" => Ok(ShiftStrategy::AudioDuration),
+            Some(("fixed", length)) => parse_time(length)
+                .and_then(|length| Timestamp::from_tenths(length.as_millis() as u64 / 100))
+                .map(ShiftStrategy::Fixed)
+                .ok_or_else(|| format!("expected a length like fixed=00:05:00, got {}", s)),
+            _ => Err(format!(
+                "unknown shift strategy {}, expected round-up, exact-last, audio-duration or fixed=hh:mm:ss",
+                s
+            )),
+        }
+    }
+}
+
+/// How transcripts are grouped into interviews with `--group-by`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    /// Transcripts in the same directory form an interview named after
+    /// the directory.
+    Directory,
+    /// Transcripts with the same name before the segment number form an
+    /// interview, e.g. `foo-000.rtf` and `foo-001.rtf`.
+    StemPrefix,
+}
+
+impl std::str::FromStr for GroupBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "directory" => Ok(GroupBy::Directory),
+            "stem-prefix" => Ok(GroupBy::StemPrefix),
+            _ => Err(format!(
+                "unknown grouping {}, expected directory or stem-prefix",
+                s
+            )),
+        }
+    }
+}
+
+impl GroupBy {
+    /// Groups the transcripts, keeping their order within each group, and
+    /// returns the groups in order of their names.
+    fn group(self, paths: Vec<PathBuf>) -> Result<Vec<(String, Vec<PathBuf>)>> {
+        let mut groups: BTreeMap<PathBuf, (String, Vec<PathBuf>)> = BTreeMap::new();
+        for path in paths {
+            let (key, name) = match self {
+                GroupBy::Directory => {
+                    let dir = match path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                        Some(dir) => dir.to_path_buf(),
+                        None => std::env::current_dir()?,
+                    };
+                    let name = dir
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_else(|| String::from("merged"));
+                    (dir, name)
+                }
+                GroupBy::StemPrefix => {
+                    let stem = path
+                        .file_stem()
+                        .map(|stem| stem.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    let name = segment_name(&stem).to_string();
+                    (PathBuf::from(&name), name)
+                }
+            };
+            groups
+                .entry(key)
+                .or_insert_with(|| (name, vec![]))
+                .1
+                .push(path);
+        }
+
+        let mut groups: Vec<(String, Vec<PathBuf>)> = groups.into_values().collect();
+        groups.sort_by(|(a, _), (b, _)| a.cmp(b));
+        for pair in groups.windows(2) {
+            if pair[0].0 == pair[1].0 {
+                return Err(Error::GroupNameCollision(pair[0].0.clone()));
+            }
+        }
+        Ok(groups)
+    }
+}
+
+/// Writes the merged transcript to the files in the formats of their
+/// extensions, and the index of the line numbers to its path, if any,
+/// backing up existing files before replacing them.
+///
+/// The files replace existing ones only when they are committed, a failed
+/// merge leaves existing files untouched.
+fn write_to_files<I>(
+    merged: I,
+    output_files: Vec<PathBuf>,
+    line_index: Option<PathBuf>,
+    output: &Output,
+) -> Result<(Vec<AtomicFile>, Vec<SegmentShift>)>
 where
     I: IntoIterator<Item = Transcript>,
 {
-    if output_file.exists() && !force {
-        return Err(Error::OutputFileExists(output_file));
+    let mut checked = output_files.clone();
+    checked.extend(line_index.clone());
+    back_up_existing(&checked, output.force, output.backup_dir)?;
+
+    let formats: Vec<OutputKind> = output_files
+        .iter()
+        .map(|file| output.format.of(file))
+        .collect();
+    let mut files = output_files
+        .iter()
+        .map(AtomicFile::create)
+        .collect::<std::io::Result<Vec<_>>>()
+        .map_err(Error::WriteError)?;
+    let mut encoders: Vec<Encoder<&mut AtomicFile>> = files
+        .iter_mut()
+        .zip(&formats)
+        .map(|(file, format)| {
+            Encoder::new(
+                file,
+                output.encoding,
+                *format == OutputKind::RTF,
+                output.bom,
+            )
+        })
+        .collect();
+    let outputs = encoders.iter_mut().zip(&formats).collect();
+    let shifts = write_merged_outputs(outputs, merged, &output.format.options)?;
+    for encoder in encoders {
+        encoder.finish().map_err(Error::WriteError)?;
+    }
+    if let Some(line_index) = line_index {
+        let mut file = AtomicFile::create(line_index).map_err(Error::WriteError)?;
+        write_line_index(std::io::BufWriter::new(&mut file), &shifts).map_err(Error::WriteError)?;
+        files.push(file);
     }
+    Ok((files, shifts))
+}
 
-    let file = File::create(output_file).map_err(Error::WriteError)?;
-    write_merged_transcript(file, merged)?;
+/// Backs up the output files that exist, or fails if there are any and
+/// `force` is not set.
+fn back_up_existing(
+    output_files: &[PathBuf],
+    force: bool,
+    backup_dir: Option<&Path>,
+) -> Result<()> {
+    if !force {
+        if let Some(existing) = output_files.iter().find(|file| file.exists()) {
+            return Err(Error::OutputFileExists(existing.clone()));
+        }
+    }
+    for output_file in output_files.iter().filter(|file| file.exists()) {
+        let backup = backup(output_file, backup_dir).map_err(Error::BackupFail)?;
+        warn!(
+            target: MERGE,
+            "overwriting {}, backed up to {}",
+            output_file.display(),
+            backup.display()
+        );
+    }
     Ok(())
 }
 
-fn write_to_stdout<I>(merged: I) -> Result<()>
+fn write_to_stdout<I>(merged: I, output: &Output) -> Result<Vec<SegmentShift>>
 where
     I: IntoIterator<Item = Transcript>,
 {
-    write_merged_transcript(std::io::stdout().lock(), merged)?;
+    let format = output.format.of_stdout();
+    let mut encoder = Encoder::new(
+        std::io::stdout().lock(),
+        output.encoding,
+        format == OutputKind::RTF,
+        output.bom,
+    );
+    let outputs = vec![(&mut encoder, &format)];
+    let shifts = write_merged_outputs(outputs, merged, &output.format.options)?;
+    drop(encoder.finish().map_err(Error::WriteError)?);
+    Ok(shifts)
+}
+
+/// How the merged transcript is written.
+struct Format {
+    /// Template to write instead of RTF, if any.
+    template: Option<Template>,
+    options: MergeOptions,
+}
+
+impl Format {
+    /// Format of the output file, inferred from its extension.
+    fn of(&self, output_file: &Path) -> OutputKind {
+        OutputKind::for_path(output_file, self.template.as_ref())
+    }
+
+    /// Format of the standard output, the template if any or RTF.
+    fn of_stdout(&self) -> OutputKind {
+        self.template
+            .clone()
+            .map_or(OutputKind::RTF, OutputKind::Template)
+    }
+}
+
+/// Warns about timestamps in the merged transcript that are earlier than
+/// the shift of their segment, with their position in the output.
+fn warn_unadjusted(output_name: &str, paths: &[PathBuf], shifts: &[SegmentShift]) {
+    for (path, shift) in paths.iter().zip(shifts) {
+        for unadjusted in &shift.unadjusted {
+            warn!(
+                target: MERGE,
+                "{}:{}:{}: timestamp {} from {} is earlier than the shift {} of its segment, it was probably not adjusted",
+                output_name,
+                unadjusted.line,
+                unadjusted.column,
+                unadjusted.timestamp,
+                path.display(),
+                shift.shift
+            );
+        }
+    }
+}
+
+/// The index of the line numbers of the merged transcript at the given
+/// path, e.g. `interview.rtf.lines.csv`.
+fn line_index_path(output_file: &Path) -> PathBuf {
+    let mut path = output_file.as_os_str().to_owned();
+    path.push(".lines.csv");
+    PathBuf::from(path)
+}
+
+/// Writes the line numbers of the merged transcript as CSV, with the
+/// first timestamp of their utterance or nothing if it has none.
+fn write_line_index<W: Write>(mut to: W, shifts: &[SegmentShift]) -> std::io::Result<()> {
+    writeln!(&mut to, "line,timestamp")?;
+    for numbered in shifts.iter().flat_map(|shift| &shift.numbered_lines) {
+        let timestamp = numbered
+            .timestamp
+            .map(|timestamp| timestamp.to_string())
+            .unwrap_or_default();
+        writeln!(&mut to, "{},{}", numbered.number, timestamp)?;
+    }
+    to.flush()
+}
+
+/// Writes a table with the segment paths, their detected end times,
+/// the shift applied to their timestamps and how many timestamps were
+/// adjusted, for cross-checking against the lengths of the audio segments.
+fn write_shift_summary<W: Write>(
+    mut to: W,
+    paths: &[PathBuf],
+    shifts: &[SegmentShift],
+) -> std::io::Result<()> {
+    const SEGMENT_HEADER: &str = "segment";
+    let width = paths
+        .iter()
+        .map(|p| p.display().to_string().chars().count())
+        .chain(std::iter::once(SEGMENT_HEADER.len()))
+        .max()
+        .unwrap_or_default();
+
+    writeln!(
+        &mut to,
+        "{:width$}  {:12}  {:12}  timestamps",
+        SEGMENT_HEADER,
+        "end time",
+        "shift",
+        width = width
+    )?;
+    for (path, shift) in paths.iter().zip(shifts) {
+        writeln!(
+            &mut to,
+            "{:width$}  {}  {}  {}",
+            path.display().to_string(),
+            shift.end_time,
+            shift.shift,
+            shift.adjusted_timestamps,
+            width = width
+        )?;
+    }
     Ok(())
 }
 
+/// Writes a line for every skipped transcript, with the time that is
+/// probably missing in the merged transcript.
+///
+/// The missing time is unknown, the longest merged segment is taken as an
+/// estimate, since the segments of a split interview have the same length
+/// except for the last one.
+fn write_skipped<W: Write>(
+    mut to: W,
+    skipped: &[Skipped],
+    shifts: &[SegmentShift],
+) -> std::io::Result<()> {
+    let gap = shifts
+        .iter()
+        .map(|shift| shift.end_time)
+        .max()
+        .unwrap_or_default();
+    for skipped in skipped {
+        writeln!(
+            &mut to,
+            "skipped {} after {} merged segments, later timestamps are about {} too early: {}",
+            skipped.path.display(),
+            skipped.after,
+            gap,
+            skipped.cause
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::timestamp::Timestamp;
+
+    #[test]
+    fn malformed_transcripts_have_their_own_exit_code() {
+        let err = Error::from(TranscriptError::malformed_preamble());
+        assert_eq!(err.exit_code(), ExitCode::MalformedTranscript);
+        assert_eq!(Error::NoTranscripts.exit_code().code(), 2);
+    }
+
+    #[test]
+    fn shift_strategies_are_parsed() {
+        assert_eq!("round-up".parse(), Ok(ShiftStrategy::RoundUp));
+        assert_eq!("exact-last".parse(), Ok(ShiftStrategy::ExactLast));
+        assert_eq!(
+            "fixed=00:05:00".parse(),
+            Ok(ShiftStrategy::Fixed(Timestamp::from_tenths(3000).unwrap()))
+        );
+        assert!("fixed=soon".parse::<ShiftStrategy>().is_err());
+        assert!("round-down".parse::<ShiftStrategy>().is_err());
+    }
+
+    #[test]
+    fn segments_are_grouped_by_stem_prefix_or_directory() {
+        let paths = vec![
+            PathBuf::from("a/bar-000.rtf"),
+            PathBuf::from("a/foo-000.rtf"),
+            PathBuf::from("b/foo-001.rtf"),
+        ];
+
+        let by_stem = GroupBy::StemPrefix.group(paths.clone()).unwrap();
+        assert_eq!(
+            by_stem,
+            vec![
+                ("bar".to_string(), vec![paths[0].clone()]),
+                ("foo".to_string(), vec![paths[1].clone(), paths[2].clone()]),
+            ]
+        );
+
+        let by_dir = GroupBy::Directory.group(paths.clone()).unwrap();
+        assert_eq!(by_dir[0], ("a".to_string(), paths[..2].to_vec()));
+        assert!(matches!(
+            GroupBy::Directory.group(vec![
+                PathBuf::from("x/foo/a-000.rtf"),
+                PathBuf::from("y/foo/a-000.rtf")
+            ]),
+            Err(Error::GroupNameCollision(_))
+        ));
+    }
+
+    #[test]
+    fn missing_segments_get_placeholders_of_their_length() {
+        let gap = SegmentGap {
+            after: PathBuf::from("a/foo-001.rtf"),
+            before: PathBuf::from("a/foo-004.rtf"),
+            missing: vec![2, 3],
+        };
+        let listed = MissingSegment {
+            after: PathBuf::from("a/foo-001.rtf"),
+            path: PathBuf::from("a/foo-002.rtf"),
+            length: Timestamp::from_tenths(2995).unwrap(),
+        };
+        let placeholders = placeholders(&[gap], &[listed], None);
+        assert_eq!(
+            placeholders[Path::new("a/foo-001.rtf")],
+            vec![
+                (PathBuf::from("a/foo-002.rtf"), Timestamp::from_tenths(2995)),
+                (PathBuf::from("a/foo-003.rtf"), None),
+            ]
+        );
+
+        let length = Timestamp::from_tenths(3000).unwrap();
+        let transcript = placeholder(Path::new("a/foo-003.rtf"), length).unwrap();
+        let records: Vec<_> = transcript.utterance_records().collect();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].speaker, PLACEHOLDER_SPEAKER);
+        assert_eq!(records[0].timestamps, vec![Timestamp::zero(), length]);
+        assert_eq!(transcript.interview_end_time(), length);
+    }
+
+    #[test]
+    fn shift_summary_is_aligned() {
+        let paths = vec![PathBuf::from("a.rtf"), PathBuf::from("segment-b.rtf")];
+        let five_minutes = Timestamp::parse("#00:05:00-0#").unwrap();
+        let shifts = vec![
+            SegmentShift {
+                end_time: five_minutes,
+                shift: Timestamp::zero(),
+                adjusted_timestamps: 8,
+                unadjusted: vec![],
+                numbered_lines: vec![],
+            },
+            SegmentShift {
+                end_time: five_minutes,
+                shift: five_minutes,
+                adjusted_timestamps: 12,
+                unadjusted: vec![],
+                numbered_lines: vec![],
+            },
+        ];
+
+        let mut buf = vec![];
+        write_shift_summary(&mut buf, &paths, &shifts).unwrap();
+
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "segment        end time      shift         timestamps\n\
+             a.rtf          #00:05:00-0#  #00:00:00-0#  8\n\
+             segment-b.rtf  #00:05:00-0#  #00:05:00-0#  12\n"
+        );
+    }
+
+    #[test]
+    fn skipped_transcripts_are_reported_with_the_missing_time() {
+        let five_minutes = Timestamp::from_tenths(3000).unwrap();
+        let shifts = vec![SegmentShift {
+            end_time: five_minutes,
+            shift: Timestamp::zero(),
+            adjusted_timestamps: 8,
+            unadjusted: vec![],
+            numbered_lines: vec![],
+        }];
+        let skipped = vec![Skipped {
+            path: PathBuf::from("b.rtf"),
+            after: 1,
+            cause: String::from("malformed"),
+        }];
+
+        let mut buf = vec![];
+        write_skipped(&mut buf, &skipped, &shifts).unwrap();
+
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "skipped b.rtf after 1 merged segments, later timestamps are about #00:05:00-0# too early: malformed\n"
+        );
+    }
+
+    #[test]
+    fn existing_line_indexes_are_only_replaced_with_force() {
+        use argh::FromArgs;
+
+        let dir = std::env::temp_dir().join("f4tapir-merge-line-index");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let output_file = dir.join("merged.rtf");
+        let line_index = line_index_path(&output_file);
+        fs::write(&line_index, "kept").unwrap();
+        let opts = |force: &[&str]| {
+            let mut args = vec![
+                "testdata/interview-01.rtf",
+                "testdata/interview-02.rtf",
+                "--line-numbers",
+                "-o",
+                output_file.to_str().unwrap(),
+            ];
+            args.extend(force);
+            Merge::from_args(&["merge"], &args).unwrap()
+        };
+
+        let err = merge(opts(&[])).unwrap_err();
+        assert!(matches!(err, Error::OutputFileExists(path) if path == line_index));
+        assert_eq!(fs::read_to_string(&line_index).unwrap(), "kept");
+        assert!(!output_file.exists());
+
+        merge(opts(&["--force"])).unwrap();
+        let written = fs::read_to_string(&line_index).unwrap();
+        assert!(written.starts_with("line,timestamp\n1,"), "{}", written);
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("I/O error: {0}")]
@@ -71,4 +1493,109 @@ pub enum Error {
     WriteError(std::io::Error),
     #[error("could not load transcript: {0}")]
     TranscriptLoadFail(#[from] TranscriptError),
+    #[error("could not back up the existing output file: {0}")]
+    BackupFail(std::io::Error),
+    #[error("could not read template {0}: {1}")]
+    TemplateLoadFail(PathBuf, std::io::Error),
+    #[error("--group-by and --output-dir need each other and replace --output-file")]
+    GroupOptions,
+    #[error("several groups of transcripts would be merged into {0}")]
+    GroupNameCollision(String),
+    #[error("transcript {0} has {1} lines that could not be parsed, their timestamps would not be adjusted")]
+    Unparsed(PathBuf, usize),
+    #[error(
+        "could not load transcript {0}, fix it or leave it out with --exclude and merge again: {1}{2}"
+    )]
+    BrokenTranscript(PathBuf, TranscriptError, MaybeAnnotation),
+    #[error("--resume and --incremental need a single RTF output file")]
+    ResumeOutput,
+    #[error("--resume stops at broken transcripts, leave them out with --exclude instead of --skip-broken")]
+    ResumeSkipBroken,
+    #[error("--resume and --incremental write UTF-8, convert the merged transcript afterwards")]
+    ResumeEncoding,
+    #[error("--incremental cannot be combined with --resume or --skip-broken")]
+    IncrementalOptions,
+    #[error("give output files with --output-file or write to standard output with --stdout")]
+    OutputOptions,
+    #[error("--bom only applies to UTF-8 output")]
+    BomEncoding,
+    #[error("transcript {0} has markup that is not known to be harmless, like {3} at line {1}, column {2}, was it edited in a word processor?")]
+    UnknownMarkup(PathBuf, usize, usize, String),
+    #[error("--move-trailers and --strip-trailers exclude each other")]
+    TrailerOptions,
+    #[error("{0} cannot be combined with --resume or --incremental")]
+    NotResumable(&'static str),
+    #[error("--line-number-step must be at least 1")]
+    LineNumberStep,
+    #[error("could not download transcript: {0}")]
+    Remote(#[from] remote::Error),
+    #[error("could not record the merge in the project store: {0}")]
+    Store(#[from] StoreError),
+    #[error("expected a time of day like 14:30:00 for --clock-start, got {0}")]
+    ClockStart(String),
+    #[error("give either --clock-start or --clock-start-from")]
+    ClockStartOptions,
+    #[error("could not read segment offsets {0}: {1}")]
+    SegmentOffsetLoadFail(PathBuf, std::io::Error),
+    #[error("segment offsets: {0}")]
+    SegmentOffsets(#[from] OffsetsError),
+    #[error("--segment-offset cannot be combined with --group-by")]
+    SegmentOffsetGroups,
+    #[error("no audio next to transcript {0} to take the length of the segment from")]
+    NoAudio(PathBuf),
+    #[error("could not get the length of the audio of {0}: {1}")]
+    AudioDuration(PathBuf, PairError),
+    #[error("could not find when the recording started: {0}")]
+    ClockStartProbe(#[from] SplitError),
+    #[error("could not order the segments by their audio: {0}")]
+    AudioOrder(#[from] AudioOrderError),
+    #[error("{0}{}, merge anyway with --allow-gaps", if *.1 > 1 { format!(" and {} more gaps", .1 - 1) } else { String::new() })]
+    SegmentGaps(SegmentGap, usize),
+}
+
+impl Error {
+    /// A broken transcript, pointing at what is wrong in it if possible.
+    fn broken(path: PathBuf, err: TranscriptError) -> Error {
+        let annotation = MaybeAnnotation(Annotation::of_error(&path, &err).map(Box::new));
+        Error::BrokenTranscript(path, err, annotation)
+    }
+}
+
+impl Failure for Error {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Error::IO(_)
+            | Error::WriteError(_)
+            | Error::BackupFail(_)
+            | Error::TemplateLoadFail(_, _)
+            | Error::SegmentOffsetLoadFail(_, _) => ExitCode::IO,
+            Error::NoTranscripts => ExitCode::NoInputs,
+            Error::GroupOptions
+            | Error::ResumeOutput
+            | Error::ResumeSkipBroken
+            | Error::ResumeEncoding
+            | Error::IncrementalOptions
+            | Error::OutputOptions
+            | Error::BomEncoding
+            | Error::TrailerOptions
+            | Error::NotResumable(_)
+            | Error::LineNumberStep
+            | Error::ClockStart(_)
+            | Error::ClockStartOptions
+            | Error::SegmentOffsetGroups => ExitCode::Failure,
+            Error::SegmentOffsets(err) => err.exit_code(),
+            Error::ClockStartProbe(err) => err.exit_code(),
+            Error::AudioOrder(err) => err.exit_code(),
+            Error::NoAudio(_) | Error::SegmentGaps(_, _) => ExitCode::NoInputs,
+            Error::AudioDuration(_, err) => err.exit_code(),
+            Error::GroupNameCollision(_) => ExitCode::OutputExists,
+            Error::Unparsed(_, _) | Error::UnknownMarkup(_, _, _, _) => {
+                ExitCode::MalformedTranscript
+            }
+            Error::OutputFileExists(_) => ExitCode::OutputExists,
+            Error::TranscriptLoadFail(err) | Error::BrokenTranscript(_, err, _) => err.exit_code(),
+            Error::Remote(err) => err.exit_code(),
+            Error::Store(err) => err.exit_code(),
+        }
+    }
 }
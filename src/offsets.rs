@@ -0,0 +1,113 @@
+//! Manual corrections of the shifts of segments for `merge
+//! --segment-offset`, e.g. when the recorder was restarted and a segment
+//! starts some seconds after the one before it ended.
+//!
+//! Every line names a segment by its number, starting at 1, or by its file
+//! name, followed by the offset in seconds or as `[[hh:]mm:]ss`, with a
+//! sign:
+//!
+//! ```text
+//! # recorder restarted
+//! 4 +12
+//! interview-006.rtf -00:01.5
+//! ```
+//!
+//! The offset of a segment is added to its shift and so also moves all the
+//! segments after it.
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+
+use crate::cues::parse_time;
+use crate::exit::{ExitCode, Failure};
+
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Parses the offsets in the source and finds the transcripts they are for,
+/// in tenths of a second by path.
+pub fn parse_offsets(source: &str, paths: &[PathBuf]) -> Result<BTreeMap<PathBuf, i64>> {
+    let mut offsets = BTreeMap::new();
+    for (idx, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let malformed = || Error::Malformed(idx + 1, line.to_string());
+        let (segment, offset) = line
+            .rsplit_once(char::is_whitespace)
+            .ok_or_else(malformed)?;
+        let offset = parse_offset(offset).ok_or_else(malformed)?;
+        let path = find_segment(segment.trim(), paths)
+            .ok_or_else(|| Error::UnknownSegment(idx + 1, segment.trim().to_string()))?;
+        *offsets.entry(path.clone()).or_insert(0) += offset;
+    }
+    Ok(offsets)
+}
+
+/// Offset like `+12`, `-1.5` or `+00:00:12` in tenths of a second.
+fn parse_offset(offset: &str) -> Option<i64> {
+    let (sign, magnitude) = match offset.as_bytes().first()? {
+        b'+' => (1, &offset[1..]),
+        b'-' => (-1, &offset[1..]),
+        _ => (1, offset),
+    };
+    let tenths = i64::try_from(parse_time(magnitude)?.as_millis() / 100).ok()?;
+    Some(sign * tenths)
+}
+
+/// The segment with the given number, starting at 1, or file name.
+fn find_segment<'a>(segment: &str, paths: &'a [PathBuf]) -> Option<&'a PathBuf> {
+    match segment.parse::<usize>() {
+        Ok(number) => paths.get(number.checked_sub(1)?),
+        Err(_) => paths
+            .iter()
+            .find(|path| path.file_name() == Path::new(segment).file_name()),
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("expected a segment and an offset like \"4 +12\" on line {0}: {1}")]
+    Malformed(usize, String),
+    #[error("no segment {1} to offset on line {0}")]
+    UnknownSegment(usize, String),
+}
+
+impl Failure for Error {
+    fn exit_code(&self) -> ExitCode {
+        ExitCode::Failure
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn offsets_are_found_by_number_or_name() {
+        let paths = vec![
+            PathBuf::from("a/interview-000.rtf"),
+            PathBuf::from("a/interview-001.rtf"),
+            PathBuf::from("a/interview-002.rtf"),
+        ];
+        let offsets = parse_offsets(
+            "# restarted\n2 +12\n\ninterview-002.rtf -00:01.5\n3 1\n",
+            &paths,
+        )
+        .unwrap();
+        assert_eq!(
+            offsets.into_iter().collect::<Vec<_>>(),
+            vec![(paths[1].clone(), 120), (paths[2].clone(), -5)]
+        );
+        assert!(matches!(
+            parse_offsets("4 +12", &paths),
+            Err(Error::UnknownSegment(1, _))
+        ));
+        assert!(matches!(
+            parse_offsets("2 soon", &paths),
+            Err(Error::Malformed(1, _))
+        ));
+    }
+}
@@ -0,0 +1,147 @@
+//! Plausibility checks of the order that segments are merged in, which
+//! catch segments that were renamed wrongly before merging.
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::paths::natural_cmp;
+use crate::timestamp::Timestamp;
+use crate::transcript::Transcript;
+
+/// What is known about a segment to check its position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentOrder {
+    pub path: PathBuf,
+    pub first_timestamp: Option<Timestamp>,
+    pub modified: Option<SystemTime>,
+}
+
+impl SegmentOrder {
+    /// Loads the first timestamp and modification time of the transcript,
+    /// leaving out what cannot be loaded.
+    pub fn of(path: &Path) -> SegmentOrder {
+        let first_timestamp = Transcript::from_file(path).ok().and_then(|transcript| {
+            Timestamp::timestamps_in(transcript.content())
+                .next()
+                .map(|(_, timestamp)| timestamp)
+        });
+        let modified = std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        SegmentOrder {
+            path: path.to_path_buf(),
+            first_timestamp,
+            modified,
+        }
+    }
+}
+
+/// Describes every place where the order of the segments contradicts
+/// natural ordering of their names, their first timestamps or their
+/// modification times.
+///
+/// First timestamps are only compared if they mostly increase, as they
+/// do for segments with timestamps relative to the whole interview.
+/// Timestamps relative to each segment start at zero every time.
+pub fn order_problems(segments: &[SegmentOrder]) -> Vec<String> {
+    let mut problems = vec![];
+    let increasing = segments
+        .windows(2)
+        .filter(
+            |pair| match (pair[0].first_timestamp, pair[1].first_timestamp) {
+                (Some(a), Some(b)) => a < b,
+                _ => false,
+            },
+        )
+        .count();
+    let absolute_timestamps = increasing * 2 > segments.len().saturating_sub(1);
+
+    for pair in segments.windows(2) {
+        let (a, b) = (&pair[0], &pair[1]);
+        let same_dir = a.path.parent() == b.path.parent();
+        if same_dir
+            && natural_cmp(
+                Path::new(&file_name(&a.path)),
+                Path::new(&file_name(&b.path)),
+            ) == Ordering::Greater
+        {
+            problems.push(format!(
+                "{} is merged before {}, but its number is higher, pad numbers with zeros",
+                a.path.display(),
+                b.path.display()
+            ));
+        }
+        if let (true, Some(ta), Some(tb)) =
+            (absolute_timestamps, a.first_timestamp, b.first_timestamp)
+        {
+            if tb < ta {
+                problems.push(format!(
+                    "{} starts at {}, earlier than {} at {}, but is merged after it",
+                    b.path.display(),
+                    tb,
+                    a.path.display(),
+                    ta
+                ));
+            }
+        }
+        if let (Some(ma), Some(mb)) = (a.modified, b.modified) {
+            if mb < ma {
+                problems.push(format!(
+                    "{} was modified before {}, but is merged after it",
+                    b.path.display(),
+                    a.path.display()
+                ));
+            }
+        }
+    }
+    problems
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    fn segment(name: &str, first: Option<&str>, modified_secs: u64) -> SegmentOrder {
+        SegmentOrder {
+            path: PathBuf::from(name),
+            first_timestamp: first.map(|ts| Timestamp::parse(ts).unwrap()),
+            modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(modified_secs)),
+        }
+    }
+
+    #[test]
+    fn contradictions_are_reported() {
+        let segments = vec![
+            segment("seg-10.rtf", Some("#00:00:01-0#"), 1),
+            segment("seg-2.rtf", Some("#00:00:01-0#"), 2),
+        ];
+        let problems = order_problems(&segments);
+        assert_eq!(problems.len(), 1, "{:?}", problems);
+
+        let segments = vec![
+            segment("a-000.rtf", Some("#00:00:10-0#"), 1),
+            segment("a-001.rtf", Some("#00:10:10-0#"), 3),
+            segment("a-002.rtf", Some("#00:05:10-0#"), 2),
+            segment("a-003.rtf", Some("#00:15:10-0#"), 4),
+        ];
+        let problems = order_problems(&segments);
+        assert_eq!(problems.len(), 2, "{:?}", problems);
+        assert!(problems[0].starts_with("a-002.rtf starts at #00:05:10-0#"));
+    }
+
+    #[test]
+    fn relative_timestamps_are_not_compared() {
+        let segments = vec![
+            segment("a-000.rtf", Some("#00:00:20-0#"), 1),
+            segment("a-001.rtf", Some("#00:00:10-0#"), 2),
+        ];
+        assert!(order_problems(&segments).is_empty());
+    }
+}
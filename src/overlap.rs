@@ -0,0 +1,82 @@
+//! Overlap of consecutive segments, as recorders produce that start every
+//! segment with the last seconds of the one before.
+//!
+//! The end of a segment is cross-correlated with the start of the next
+//! one, and they overlap by the longest stretch that matches closely. This
+//! is trimmed by `resegment --trim-overlap`, and `merge --check-overlap`
+//! warns that the transcripts may contain the sentences in it twice.
+use std::path::Path;
+use std::time::Duration;
+
+use crate::audio_order::{decode, Error as DecodeError, SAMPLE_RATE};
+
+/// Seconds at the end and start of segments that are searched for an
+/// overlap.
+const SEARCH_SECONDS: &str = "3";
+/// Shortest overlap that is detected, shorter matches are chance.
+const MIN_OVERLAP: Duration = Duration::from_millis(100);
+/// Normalized cross-correlation of a match.
+const MIN_CORRELATION: f64 = 0.8;
+
+/// How long the start of `next` repeats the end of `previous`, if it does.
+pub fn measure_overlap(previous: &Path, next: &Path) -> Result<Option<Duration>, DecodeError> {
+    let tail = decode(previous, &[], &["-sseof", &format!("-{}", SEARCH_SECONDS)])?;
+    let head = decode(next, &["-t", SEARCH_SECONDS], &[])?;
+    let min_len = (MIN_OVERLAP.as_secs_f64() * SAMPLE_RATE as f64) as usize;
+    Ok(find_overlap(&tail, &head, min_len)
+        .map(|len| Duration::from_secs_f64(len as f64 / SAMPLE_RATE as f64)))
+}
+
+/// Number of samples at the end of `tail` that start `head` again, at
+/// least `min_len`.
+pub(crate) fn find_overlap(tail: &[i16], head: &[i16], min_len: usize) -> Option<usize> {
+    let max_len = tail.len().min(head.len());
+    if min_len == 0 || min_len > max_len {
+        return None;
+    }
+    (min_len..=max_len)
+        .map(|len| (len, correlate(&tail[tail.len() - len..], &head[..len])))
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .filter(|&(_, correlation)| correlation >= MIN_CORRELATION)
+        .map(|(len, _)| len)
+}
+
+/// Normalized cross-correlation of equally long samples, from -1 to 1.
+fn correlate(a: &[i16], b: &[i16]) -> f64 {
+    let (mut ab, mut aa, mut bb) = (0.0, 0.0, 0.0);
+    for (&a, &b) in a.iter().zip(b) {
+        let (a, b) = (f64::from(a), f64::from(b));
+        ab += a * b;
+        aa += a * a;
+        bb += b * b;
+    }
+    if aa == 0.0 || bb == 0.0 {
+        return 0.0;
+    }
+    ab / (aa * bb).sqrt()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Noise that does not repeat itself.
+    fn noise(len: usize) -> Vec<i16> {
+        let mut state: u32 = 12345;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as i16
+            })
+            .collect()
+    }
+
+    #[test]
+    fn overlaps_are_found_to_the_sample() {
+        let recording = noise(2000);
+        let tail = &recording[..1500];
+        assert_eq!(find_overlap(tail, &recording[1199..], 100), Some(301));
+        assert_eq!(find_overlap(tail, &recording[1500..], 100), None);
+        assert_eq!(find_overlap(tail, &recording[1450..], 100), None);
+    }
+}
@@ -0,0 +1,270 @@
+//! Pairs transcripts with the audio segments they were written against
+//! and checks that their timestamps fit the length of the audio.
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::args::Pair;
+use crate::exit::{ExitCode, Failure};
+use crate::find::{collect_interviews, collect_transcripts};
+use crate::paths::command_arg;
+use crate::timestamp::Timestamp;
+use crate::transcript::{Error as TranscriptError, Transcript};
+
+use log::warn;
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Default for how much earlier than the audio a transcript may end
+/// before it is suspicious, in seconds.
+pub const DEFAULT_MAX_GAP_SECS: u64 = 60;
+
+pub fn pair(opts: Pair) -> Result<()> {
+    let audio = collect_interviews(opts.input_segments.clone(), opts.recursive)?;
+    let transcripts = collect_transcripts(opts.input_segments, opts.recursive)?;
+    if audio.is_empty() && transcripts.is_empty() {
+        return Err(Error::NoSegments);
+    }
+
+    let max_gap = opts.max_gap * 10;
+    let mut stdout = std::io::stdout().lock();
+    for (stem, (audio, transcript)) in pair_by_stem(audio, transcripts) {
+        let check = match (audio, transcript) {
+            (Some(audio), Some(transcript)) => {
+                let duration = probe_duration(&audio)?;
+                let last = Transcript::from_file(&transcript)
+                    .map_err(|err| Error::Transcript(transcript.clone(), err))?
+                    .last_timestamp();
+                let check = Check::of(last, duration, max_gap);
+                if check.is_suspicious() {
+                    warn!(
+                        "transcript {} {}, maybe it belongs to another segment than {}",
+                        transcript.display(),
+                        check,
+                        audio.display()
+                    );
+                }
+                check
+            }
+            (Some(audio), None) => {
+                warn!("no transcript for audio segment {}", audio.display());
+                Check::NoTranscript
+            }
+            (None, Some(transcript)) => {
+                warn!("no audio segment for transcript {}", transcript.display());
+                Check::NoAudio
+            }
+            (None, None) => unreachable!("paired without audio or transcript"),
+        };
+        writeln!(&mut stdout, "{}  {}", stem, check)?;
+    }
+    Ok(())
+}
+
+/// Groups audio segments and transcripts with the same file stem in
+/// the same directory, in lexicographical order.
+pub(crate) fn pair_by_stem(
+    audio: Vec<PathBuf>,
+    transcripts: Vec<PathBuf>,
+) -> BTreeMap<String, (Option<PathBuf>, Option<PathBuf>)> {
+    let mut pairs: BTreeMap<String, (Option<PathBuf>, Option<PathBuf>)> = BTreeMap::new();
+    for path in audio {
+        let key = pair_key(&path);
+        pairs.entry(key).or_default().0 = Some(path);
+    }
+    for path in transcripts {
+        let key = pair_key(&path);
+        pairs.entry(key).or_default().1 = Some(path);
+    }
+    pairs
+}
+
+fn pair_key(path: &Path) -> String {
+    path.with_extension("").display().to_string()
+}
+
+/// Outcome of comparing the last timestamp of a transcript with the
+/// length of its audio segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Check {
+    Fits {
+        last: Timestamp,
+        duration: Timestamp,
+    },
+    ExceedsAudio {
+        last: Timestamp,
+        duration: Timestamp,
+    },
+    EndsEarly {
+        last: Timestamp,
+        duration: Timestamp,
+    },
+    NoAudio,
+    NoTranscript,
+}
+
+impl Check {
+    /// Compares the timestamps, allowing the transcript to end at most
+    /// `max_gap` tenths of a second before the audio.
+    fn of(last: Timestamp, duration: Timestamp, max_gap: u64) -> Check {
+        if last > duration {
+            Check::ExceedsAudio { last, duration }
+        } else if duration.tenths() - last.tenths() > max_gap {
+            Check::EndsEarly { last, duration }
+        } else {
+            Check::Fits { last, duration }
+        }
+    }
+
+    fn is_suspicious(self) -> bool {
+        matches!(self, Check::ExceedsAudio { .. } | Check::EndsEarly { .. })
+    }
+}
+
+impl std::fmt::Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Check::Fits { last, duration } => write!(f, "ends at {} of {}", last, duration),
+            Check::ExceedsAudio { last, duration } => {
+                write!(f, "ends at {} after the audio ends at {}", last, duration)
+            }
+            Check::EndsEarly { last, duration } => {
+                write!(
+                    f,
+                    "ends at {} long before the audio ends at {}",
+                    last, duration
+                )
+            }
+            Check::NoAudio => write!(f, "has no audio segment"),
+            Check::NoTranscript => write!(f, "has no transcript"),
+        }
+    }
+}
+
+/// Asks ffprobe for the length of the audio file.
+pub(crate) fn probe_duration(audio: &Path) -> Result<Timestamp> {
+    truncated_to_tenths(probe_seconds(audio)?).ok_or_else(|| Error::FfprobeStatus(audio.into()))
+}
+
+/// Asks ffprobe for the length of the audio file in seconds, not rounded
+/// to tenths.
+pub(crate) fn probe_seconds(audio: &Path) -> Result<f64> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-show_entries",
+            "format=duration",
+            "-of",
+            "default=noprint_wrappers=1:nokey=1",
+        ])
+        .arg(command_arg(audio))
+        .output()
+        .map_err(Error::FfprobeIo)?;
+    if !output.status.success() {
+        return Err(Error::FfprobeStatus(audio.into()));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    parse_seconds(stdout.trim()).ok_or_else(|| Error::FfprobeStatus(audio.into()))
+}
+
+/// Parses a duration in seconds like `300.024000` as printed by ffprobe.
+fn parse_seconds(seconds: &str) -> Option<f64> {
+    let seconds: f64 = seconds.parse().ok()?;
+    Some(seconds).filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+}
+
+/// The duration in seconds truncated to tenths of a second.
+fn truncated_to_tenths(seconds: f64) -> Option<Timestamp> {
+    Timestamp::from_tenths((seconds * 10.0) as u64)
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("no audio segments or transcripts found")]
+    NoSegments,
+    #[error("failed to load transcript {0}: {1}")]
+    Transcript(PathBuf, TranscriptError),
+    #[error("failed to invoke ffprobe to get the length of audio segments, it comes with ffmpeg, install with your favorite package manager or on Windows download from https://ffmpeg.org/download.html#build-windows and add to your \"Path\" environment variable")]
+    FfprobeIo(std::io::Error),
+    #[error("ffprobe could not get the length of {0}")]
+    FfprobeStatus(PathBuf),
+}
+
+impl Failure for Error {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Error::IO(_) => ExitCode::IO,
+            Error::NoSegments => ExitCode::NoInputs,
+            Error::Transcript(_, err) => err.exit_code(),
+            Error::FfprobeIo(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                ExitCode::FfmpegMissing
+            }
+            Error::FfprobeIo(_) => ExitCode::IO,
+            Error::FfprobeStatus(_) => ExitCode::FfmpegFailed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ts(timestamp: &str) -> Timestamp {
+        Timestamp::parse(timestamp).unwrap()
+    }
+
+    #[test]
+    fn ffprobe_duration_is_truncated_to_tenths() {
+        let parse_duration = |seconds| parse_seconds(seconds).and_then(truncated_to_tenths);
+        assert_eq!(parse_duration("300.024000"), Some(ts("#00:05:00-0#")));
+        assert_eq!(parse_duration("61.99"), Some(ts("#00:01:01-9#")));
+        assert_eq!(parse_duration("N/A"), None);
+    }
+
+    #[test]
+    fn transcripts_are_checked_against_audio_length() {
+        let duration = ts("#00:05:00-0#");
+        let max_gap = DEFAULT_MAX_GAP_SECS * 10;
+
+        assert!(!Check::of(ts("#00:04:50-3#"), duration, max_gap).is_suspicious());
+        assert_eq!(
+            Check::of(ts("#00:05:00-1#"), duration, max_gap),
+            Check::ExceedsAudio {
+                last: ts("#00:05:00-1#"),
+                duration
+            }
+        );
+        assert_eq!(
+            Check::of(ts("#00:02:00-0#"), duration, max_gap),
+            Check::EndsEarly {
+                last: ts("#00:02:00-0#"),
+                duration
+            }
+        );
+    }
+
+    #[test]
+    fn audio_and_transcripts_are_paired_by_stem() {
+        let pairs = pair_by_stem(
+            vec![PathBuf::from("a-000.mp3"), PathBuf::from("a-001.mp3")],
+            vec![PathBuf::from("a-001.rtf"), PathBuf::from("a-002.rtf")],
+        );
+        let pairs: Vec<_> = pairs.into_values().collect();
+        assert_eq!(
+            pairs,
+            vec![
+                (Some(PathBuf::from("a-000.mp3")), None),
+                (
+                    Some(PathBuf::from("a-001.mp3")),
+                    Some(PathBuf::from("a-001.rtf"))
+                ),
+                (None, Some(PathBuf::from("a-002.rtf"))),
+            ]
+        );
+    }
+}
@@ -1,7 +1,154 @@
-use std::io::{Error, ErrorKind, Result};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::ffi::OsStr;
 use std::path::Path;
 
-pub fn path_as_str(path: &Path) -> Result<&str> {
-    path.to_str()
-        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Non-UTF-8 filenames not supported"))
+/// Longest path that Windows accepts without the `\\?\` prefix.
+const MAX_PATH: usize = 260;
+
+/// The path as an argument for an external command like ffmpeg, without
+/// requiring it to be UTF-8.
+///
+/// On Windows, long paths are made absolute and get the `\\?\` prefix, or
+/// `\\?\UNC\` for UNC paths, so that they can be opened despite
+/// `MAX_PATH`.
+pub fn command_arg(path: &Path) -> Cow<'_, OsStr> {
+    if cfg!(windows) {
+        // relative paths are resolved against the current directory, which
+        // also resolves `..`, since nothing does behind the prefix
+        let absolute = std::path::absolute(path);
+        let absolute = absolute.as_deref().unwrap_or(path);
+        if let Some(prefixed) = absolute.to_str().and_then(long_path) {
+            return Cow::Owned(prefixed.into());
+        }
+    }
+    Cow::Borrowed(path.as_os_str())
+}
+
+/// Windows path with the prefix for long paths, if it is too long for
+/// the usual APIs, absolute and not prefixed already.
+fn long_path(path: &str) -> Option<String> {
+    if path.len() < MAX_PATH || path.starts_with(r"\\?\") {
+        return None;
+    }
+    // separators are not normalized behind the prefix, only \ works
+    let path = path.replace('/', r"\");
+    if let Some(unc) = path.strip_prefix(r"\\") {
+        return Some(format!(r"\\?\UNC\{}", unc));
+    }
+    let bytes = path.as_bytes();
+    let has_drive =
+        bytes.len() > 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && bytes[2] == b'\\';
+    if has_drive {
+        Some(format!(r"\\?\{}", path))
+    } else {
+        None
+    }
+}
+
+/// Compares file names like a human would, that is, runs of digits
+/// are compared by their numeric value, so `2` comes before `10`, and
+/// fewer leading zeros come first if the values are equal.
+pub(crate) fn natural_cmp(a: &Path, b: &Path) -> Ordering {
+    let a = a.to_string_lossy();
+    let b = b.to_string_lossy();
+    let mut a = a.as_bytes();
+    let mut b = b.as_bytes();
+    loop {
+        match (a.first(), b.first()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (a_digits, a_rest) = split_digits(a);
+                let (b_digits, b_rest) = split_digits(b);
+                let a_trimmed = trim_leading_zeros(a_digits);
+                let b_trimmed = trim_leading_zeros(b_digits);
+                let ordering = a_trimmed
+                    .len()
+                    .cmp(&b_trimmed.len())
+                    .then_with(|| a_trimmed.cmp(b_trimmed))
+                    .then_with(|| a_digits.len().cmp(&b_digits.len()));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a = a_rest;
+                b = b_rest;
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(y);
+                }
+                a = &a[1..];
+                b = &b[1..];
+            }
+        }
+    }
+}
+
+fn split_digits(s: &[u8]) -> (&[u8], &[u8]) {
+    let len = s.iter().take_while(|b| b.is_ascii_digit()).count();
+    s.split_at(len)
+}
+
+fn trim_leading_zeros(digits: &[u8]) -> &[u8] {
+    let zeros = digits.iter().take_while(|&&b| b == b'0').count();
+    &digits[zeros..]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn natural_order_compares_numbers_by_value() {
+        let mut names: Vec<PathBuf> = ["interview-10.mp3", "interview-2.mp3", "interview-01.mp3"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            vec![
+                PathBuf::from("interview-01.mp3"),
+                PathBuf::from("interview-2.mp3"),
+                PathBuf::from("interview-10.mp3"),
+            ]
+        );
+        assert_eq!(
+            natural_cmp(Path::new("seg-002.rtf"), Path::new("seg-010.rtf")),
+            Ordering::Less
+        );
+        assert_eq!(
+            natural_cmp(Path::new("b-1"), Path::new("a-2")),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn long_paths_get_prefixed() {
+        let long_name = "a".repeat(MAX_PATH);
+        assert_eq!(long_path(r"C:\short.mp3"), None);
+        assert_eq!(
+            long_path(&format!("C:/interviews/{}.mp3", long_name)),
+            Some(format!(r"\\?\C:\interviews\{}.mp3", long_name))
+        );
+        assert_eq!(
+            long_path(&format!(r"\\server\share\{}.mp3", long_name)),
+            Some(format!(r"\\?\UNC\server\share\{}.mp3", long_name))
+        );
+        assert_eq!(long_path(&format!(r"relative\{}.mp3", long_name)), None);
+        assert_eq!(long_path(&format!(r"\\?\C:\{}.mp3", long_name)), None);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn long_relative_paths_are_made_absolute_and_prefixed() {
+        let relative = format!(r"segments\{}.mp3", "a".repeat(MAX_PATH));
+        let arg = command_arg(Path::new(&relative));
+        let arg = arg.to_str().unwrap();
+        assert!(arg.starts_with(r"\\?\"), "{}", arg);
+        assert!(arg.ends_with(&relative), "{}", arg);
+    }
 }
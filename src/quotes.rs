@@ -0,0 +1,179 @@
+//! Locates quotes, as they were cited in publications, in the transcript
+//! of an interview, to find the audio to verify them against.
+//!
+//! Quotes are compared by their words only, ignoring case, punctuation,
+//! timestamps and line breaks, so a quote matches even if typographic
+//! quotation marks or dashes were added when citing it.
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use crate::excerpt::Passage;
+use crate::timestamp::Timestamp;
+
+/// A quote and the utterances it was found in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Located {
+    pub quote: String,
+    /// Indices of the first and the last passage the quote is in, or
+    /// `None` if it was not found.
+    pub passages: Option<RangeInclusive<usize>>,
+}
+
+/// Reads the quotes from a CSV file, from the column named `quote` or the
+/// first one, or otherwise from a text file with a quote per line.
+pub fn parse_quotes(path: &Path, source: &str) -> Vec<String> {
+    let is_csv = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+    if !is_csv {
+        return source
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect();
+    }
+    let mut rows = csv_rows(source).into_iter();
+    let first = rows.next().unwrap_or_default();
+    let (column, header) = match first
+        .iter()
+        .position(|field| field.trim().eq_ignore_ascii_case("quote"))
+    {
+        Some(column) => (column, true),
+        None => (0, false),
+    };
+    let first = if header { None } else { Some(first) };
+    first
+        .into_iter()
+        .chain(rows)
+        .filter_map(|mut row| {
+            if column < row.len() {
+                Some(row.swap_remove(column).trim().to_string())
+            } else {
+                None
+            }
+        })
+        .filter(|quote| !quote.is_empty())
+        .collect()
+}
+
+/// Finds every quote in the speech of the passages, possibly spanning
+/// several of them.
+pub fn locate_quotes(quotes: Vec<String>, passages: &[Passage]) -> Vec<Located> {
+    // normalized speech of all passages between spaces, so that quotes
+    // only match whole words, with the passage of every byte
+    let mut text = String::from(" ");
+    let mut owners = vec![0];
+    for (idx, passage) in passages.iter().enumerate() {
+        let speech = normalized(&without_timestamps(&passage.speech));
+        if speech.is_empty() {
+            continue;
+        }
+        text.push_str(&speech);
+        text.push(' ');
+        owners.resize(text.len(), idx);
+    }
+    quotes
+        .into_iter()
+        .map(|quote| {
+            let needle = normalized(&quote);
+            let passages = (!needle.is_empty())
+                .then(|| text.find(&format!(" {} ", needle)))
+                .flatten()
+                .map(|at| owners[at + 1]..=owners[at + needle.len()]);
+            Located { quote, passages }
+        })
+        .collect()
+}
+
+/// Lowercase words separated by single spaces.
+fn normalized(text: &str) -> String {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn without_timestamps(speech: &str) -> String {
+    let mut text = speech.to_string();
+    for (offset, timestamp) in Timestamp::extract_timestamps(speech.as_bytes())
+        .into_iter()
+        .rev()
+    {
+        text.replace_range(offset..offset + timestamp.len(), " ");
+    }
+    text
+}
+
+/// Rows of fields, with fields in double quotes that may contain commas,
+/// line breaks and doubled double quotes.
+fn csv_rows(source: &str) -> Vec<Vec<String>> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => row.push(std::mem::take(&mut field)),
+            ('\r', false) => (),
+            ('\n', false) => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            (c, _) => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows.retain(|row| row.iter().any(|field| !field.trim().is_empty()));
+    rows
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::excerpt::passages;
+    use crate::transcript::Transcript;
+
+    #[test]
+    fn quotes_are_found_across_utterances() {
+        let transcript = Transcript::from_file("testdata/interview-02.rtf").unwrap();
+        let passages = passages(&transcript);
+        let located = locate_quotes(
+            vec![
+                String::from("„versteh ich nicht.“"),
+                String::from("Versteh ich nicht. Sie können"),
+                String::from("nie gesagt"),
+                String::from("ich nich"),
+            ],
+            &passages,
+        );
+        assert_eq!(located[0].passages, Some(1..=1));
+        assert_eq!(located[1].passages, Some(1..=2));
+        assert_eq!(located[2].passages, None);
+        assert_eq!(located[3].passages, None);
+    }
+
+    #[test]
+    fn quotes_from_csv_column() {
+        let source = "page,Quote\r\n12,\"Ja, ja.\"\r\n13,\"Sie sagte \"\"nein\"\"\nund ging\"\r\n";
+        assert_eq!(
+            parse_quotes(Path::new("quotes.csv"), source),
+            vec!["Ja, ja.", "Sie sagte \"nein\"\nund ging"]
+        );
+        assert_eq!(
+            parse_quotes(Path::new("quotes.txt"), "a, b\n\n c \n"),
+            vec!["a, b", "c"]
+        );
+    }
+}
@@ -0,0 +1,212 @@
+//! Transcripts given as `https://` URLs, e.g. files in a Nextcloud share,
+//! downloaded into a cache instead of syncing the whole share first.
+//!
+//! Every URL is downloaded to a path in the cache that mirrors its host
+//! and path, so that the downloaded segments sort like their URLs. Next to
+//! every download, a small file records its ETag and fingerprint. A
+//! download is reused if the server reports it unchanged for the ETag and
+//! the fingerprint still matches, so that a damaged or edited copy in the
+//! cache is downloaded again. Downloading needs the `remote` feature.
+use std::path::{Path, PathBuf};
+
+use crate::exit::{ExitCode, Failure};
+
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Checks if the input is a URL rather than a path.
+pub fn is_url(input: &Path) -> bool {
+    input
+        .to_str()
+        .is_some_and(|input| input.starts_with("https://") || input.starts_with("http://"))
+}
+
+/// Replaces the URLs among the inputs with the paths of their downloads.
+pub fn fetch_inputs(inputs: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+    inputs
+        .into_iter()
+        .map(|input| match input.to_str() {
+            Some(url) if is_url(&input) => fetch(url),
+            _ => Ok(input),
+        })
+        .collect()
+}
+
+/// Directory of the cache, in the temporary directory unless
+/// `F4TAPIR_CACHE_DIR` is set.
+pub fn cache_dir() -> PathBuf {
+    std::env::var_os("F4TAPIR_CACHE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| std::env::temp_dir().join("f4tapir-remote"))
+}
+
+/// Path in the cache for the URL, mirroring its host and path with
+/// characters that are unsafe in file names replaced.
+#[cfg_attr(not(feature = "remote"), allow(dead_code))]
+fn cache_path(url: &str) -> Option<PathBuf> {
+    let rest = url.split_once("://")?.1;
+    let rest = rest.split(['?', '#']).next().unwrap_or_default();
+    let mut path = cache_dir();
+    for component in rest.split('/').filter(|component| !component.is_empty()) {
+        let component = percent_decoded(component);
+        if component == "." || component == ".." {
+            return None;
+        }
+        let safe: String = component
+            .chars()
+            .map(|c| match c {
+                '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | '@' => '_',
+                c if c.is_control() => '_',
+                c => c,
+            })
+            .collect();
+        path.push(safe);
+    }
+    // a decoded component must never leave the cache or replace it
+    if path == cache_dir() || !path.starts_with(cache_dir()) {
+        return None;
+    }
+    Some(path)
+}
+
+/// Decodes `%xx` escapes, leaving malformed ones as they are.
+#[cfg_attr(not(feature = "remote"), allow(dead_code))]
+pub(crate) fn percent_decoded(component: &str) -> String {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let escaped = bytes
+            .get(idx + 1..idx + 3)
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (bytes[idx], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                idx += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                idx += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Path of the file with the ETag and fingerprint of a download.
+#[cfg_attr(not(feature = "remote"), allow(dead_code))]
+fn sum_path(download: &Path) -> PathBuf {
+    let mut name = download.file_name().unwrap_or_default().to_os_string();
+    name.push(".f4tapir-sum");
+    download.with_file_name(name)
+}
+
+#[cfg(feature = "remote")]
+fn fetch(url: &str) -> Result<PathBuf> {
+    use crate::atomic::AtomicFile;
+    use crate::incremental::fingerprint;
+    use crate::logging::FIND;
+    use log::{debug, info};
+    use std::fs;
+    use std::io::Write;
+
+    let path = cache_path(url).ok_or_else(|| Error::Url(url.to_string()))?;
+    let sums = sum_path(&path);
+    // ETag and fingerprint of an earlier download that is still intact
+    let cached = fs::read_to_string(&sums).ok().and_then(|sums| {
+        let (etag, recorded) = sums.trim_end().split_once('\t')?;
+        let recorded = u64::from_str_radix(recorded, 16).ok()?;
+        let intact = fingerprint(&path, "").ok()? == recorded;
+        (intact && !etag.is_empty()).then(|| etag.to_string())
+    });
+
+    let mut request = ureq::get(url);
+    if let Some(etag) = &cached {
+        request = request.set("If-None-Match", etag);
+    }
+    let response = request
+        .call()
+        .map_err(|err| Error::Http(url.to_string(), Box::new(err)))?;
+    if response.status() == 304 && cached.is_some() {
+        debug!(target: FIND, "{} is unchanged, using {}", url, path.display());
+        return Ok(path);
+    }
+    info!(target: FIND, "downloading {} to {}", url, path.display());
+    let etag = response.header("ETag").unwrap_or_default().to_string();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = AtomicFile::create(&path)?;
+    std::io::copy(&mut response.into_reader(), &mut file)?;
+    file.commit()?;
+
+    let mut file = AtomicFile::create(&sums)?;
+    writeln!(&mut file, "{}\t{:016x}", etag, fingerprint(&path, "")?)?;
+    file.commit()?;
+    Ok(path)
+}
+
+#[cfg(not(feature = "remote"))]
+fn fetch(url: &str) -> Result<PathBuf> {
+    Err(Error::Unsupported(url.to_string()))
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("cannot download {0}, f4tapir was built without the remote feature")]
+    Unsupported(String),
+    #[error("{0} does not name a file to download")]
+    Url(String),
+    #[cfg(feature = "remote")]
+    #[error("failed to download {0}: {1}")]
+    Http(String, Box<ureq::Error>),
+}
+
+impl Failure for Error {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Error::IO(_) => ExitCode::IO,
+            Error::Unsupported(_) | Error::Url(_) => ExitCode::Failure,
+            #[cfg(feature = "remote")]
+            Error::Http(_, _) => ExitCode::IO,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn downloads_mirror_their_urls() {
+        assert!(is_url(Path::new("https://cloud.example.org/a.rtf")));
+        assert!(!is_url(Path::new("a/https.rtf")));
+        assert_eq!(
+            cache_path("https://cloud.example.org/s/Interview%20A/a-000.rtf?download=1"),
+            Some(
+                cache_dir()
+                    .join("cloud.example.org")
+                    .join("s")
+                    .join("Interview A")
+                    .join("a-000.rtf")
+            )
+        );
+        assert_eq!(cache_path("https://cloud.example.org/../a.rtf"), None);
+        assert_eq!(cache_path("https://cloud.example.org/%2E%2E/a.rtf"), None);
+        assert_eq!(
+            cache_path("https://h/%2Fhome%2Fu%2F.bashrc"),
+            Some(cache_dir().join("h").join("_home_u_.bashrc"))
+        );
+        assert_eq!(
+            cache_path("https://h/a%2F..%2F..%2Fx"),
+            Some(cache_dir().join("h").join("a_.._.._x"))
+        );
+        assert_eq!(
+            sum_path(Path::new("c/a-000.rtf")),
+            PathBuf::from("c/a-000.rtf.f4tapir-sum")
+        );
+    }
+}
@@ -0,0 +1,225 @@
+//! Renames misnumbered segment files into a clean, zero-padded sequence,
+//! so that their lexicographical order is the order of the interview.
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::args::Renumber;
+use crate::exit::{ExitCode, Failure};
+use crate::find::{collect_interviews, collect_transcripts};
+use crate::paths::natural_cmp;
+use crate::timestamp::Timestamp;
+
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+pub fn renumber(opts: Renumber) -> Result<()> {
+    let mut paths = collect_interviews(opts.input_segments.clone(), opts.recursive)?;
+    paths.extend(collect_transcripts(opts.input_segments, opts.recursive)?);
+    if paths.is_empty() {
+        return Err(Error::NoSegments);
+    }
+
+    let renames = plan_renames(paths, opts.name.as_deref(), opts.by_timestamp)?;
+    let mut stdout = std::io::stdout().lock();
+    for (from, to) in &renames {
+        writeln!(&mut stdout, "{} -> {}", from.display(), to.display())?;
+    }
+    if !opts.dry_run {
+        rename_all(&renames)?;
+    }
+    Ok(())
+}
+
+/// Works out the new name of every segment that needs renaming.
+///
+/// Segments in the same directory with the same name and extension form
+/// a sequence that is numbered from zero, in natural order of their file
+/// names or, if `by_timestamp` is set, by the first timestamp in the
+/// transcript.
+fn plan_renames(
+    paths: Vec<PathBuf>,
+    name: Option<&str>,
+    by_timestamp: bool,
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mut sequences: BTreeMap<(PathBuf, String, String), Vec<PathBuf>> = BTreeMap::new();
+    for path in paths {
+        let stem = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or(Error::EncodingError)?;
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .ok_or(Error::EncodingError)?
+            .to_string();
+        let name = name.unwrap_or_else(|| segment_name(stem)).to_string();
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        sequences
+            .entry((dir, name, extension))
+            .or_default()
+            .push(path);
+    }
+
+    let mut renames = vec![];
+    for ((dir, name, extension), mut segments) in sequences {
+        if by_timestamp {
+            let mut keyed = segments
+                .into_iter()
+                .map(|path| Ok((first_timestamp(&path)?, path)))
+                .collect::<Result<Vec<_>>>()?;
+            keyed.sort_by(|(a_ts, a), (b_ts, b)| a_ts.cmp(b_ts).then_with(|| natural_cmp(a, b)));
+            segments = keyed.into_iter().map(|(_, path)| path).collect();
+        } else {
+            segments.sort_by(|a, b| natural_cmp(a, b));
+        }
+
+        for (number, from) in segments.into_iter().enumerate() {
+            let to = dir.join(format!("{}-{:03}.{}", name, number, extension));
+            if from != to {
+                renames.push((from, to));
+            }
+        }
+    }
+
+    let sources: Vec<&PathBuf> = renames.iter().map(|(from, _)| from).collect();
+    for (_, to) in &renames {
+        if to.exists() && !sources.contains(&to) {
+            return Err(Error::TargetExists(to.clone()));
+        }
+    }
+    Ok(renames)
+}
+
+/// Renames in two steps, so that segments can swap their numbers
+/// without overwriting each other.
+fn rename_all(renames: &[(PathBuf, PathBuf)]) -> Result<()> {
+    let mut staged = Vec::with_capacity(renames.len());
+    for (from, to) in renames {
+        let mut temporary = to.clone().into_os_string();
+        temporary.push(".renumber");
+        let temporary = PathBuf::from(temporary);
+        fs::rename(from, &temporary)?;
+        staged.push((temporary, to));
+    }
+    for (temporary, to) in staged {
+        fs::rename(temporary, to)?;
+    }
+    Ok(())
+}
+
+/// The first timestamp in the file, or `None` for sound files and
+/// transcripts without timestamps, which are ordered first.
+fn first_timestamp(path: &Path) -> Result<Option<Timestamp>> {
+    let is_transcript = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("rtf"));
+    if !is_transcript {
+        return Ok(None);
+    }
+    let content = fs::read(path)?;
+    Ok(Timestamp::extract_timestamps(&content)
+        .first()
+        .map(|&(_, timestamp)| timestamp))
+}
+
+/// Name of the interview without the segment number and separators,
+/// e.g. `interview` for `interview-1` or `interview_002`.
+pub(crate) fn segment_name(stem: &str) -> &str {
+    let without_number = stem.trim_end_matches(|c: char| c.is_ascii_digit());
+    let name = without_number.trim_end_matches(['-', '_', ' ']);
+    if name.is_empty() {
+        stem
+    } else {
+        name
+    }
+}
+
+/// Number of the segment at the end of the stem, e.g. 2 for
+/// `interview-002`, or `None` if the stem has no number after a name.
+pub(crate) fn segment_number(stem: &str) -> Option<u32> {
+    if segment_name(stem).len() == stem.len() {
+        return None;
+    }
+    let name_len = stem.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    stem[name_len..].parse().ok()
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("no segments or transcripts found")]
+    NoSegments,
+    #[error("filename was not valid UTF-8, other encodings are not supported")]
+    EncodingError,
+    #[error("renamed segment {0} exists and is not renumbered itself, use --name")]
+    TargetExists(PathBuf),
+}
+
+impl Failure for Error {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Error::IO(_) => ExitCode::IO,
+            Error::NoSegments => ExitCode::NoInputs,
+            Error::EncodingError => ExitCode::Failure,
+            Error::TargetExists(_) => ExitCode::OutputExists,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn segment_name_strips_number_and_separator() {
+        assert_eq!(segment_name("interview-1"), "interview");
+        assert_eq!(segment_name("interview_002"), "interview");
+        assert_eq!(segment_name("interview"), "interview");
+        assert_eq!(segment_name("007"), "007");
+        assert_eq!(segment_number("interview_002"), Some(2));
+        assert_eq!(segment_number("interview"), None);
+        assert_eq!(segment_number("007"), None);
+    }
+
+    #[test]
+    fn segments_are_renamed_into_a_padded_sequence() {
+        let dir = std::env::temp_dir().join("f4tapir-renumber");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for name in &["interview-2.mp3", "interview-10.mp3", "interview-000.mp3"] {
+            fs::write(dir.join(name), name).unwrap();
+        }
+        let paths = collect_interviews(vec![dir.clone()], false).unwrap();
+
+        let renames = plan_renames(paths, None, false).unwrap();
+        rename_all(&renames).unwrap();
+
+        for (name, content) in &[
+            ("interview-000.mp3", "interview-000.mp3"),
+            ("interview-001.mp3", "interview-2.mp3"),
+            ("interview-002.mp3", "interview-10.mp3"),
+        ] {
+            assert_eq!(&fs::read_to_string(dir.join(name)).unwrap(), content);
+        }
+        assert!(!dir.join("interview-10.mp3").exists());
+    }
+
+    #[test]
+    fn sound_files_have_no_first_timestamp() {
+        let dir = std::env::temp_dir().join("f4tapir-renumber-audio");
+        fs::create_dir_all(&dir).unwrap();
+        let audio = dir.join("interview-1.wav");
+        fs::write(&audio, b"RIFF\0#00:00:17-5#\0").unwrap();
+        assert_eq!(first_timestamp(&audio).unwrap(), None);
+        let transcript = dir.join("interview-1.RTF");
+        fs::write(&transcript, "{\\rtf1 I: Ja. #00:00:17-5#}").unwrap();
+        assert_eq!(
+            first_timestamp(&transcript).unwrap(),
+            Timestamp::from_tenths(175)
+        );
+    }
+}
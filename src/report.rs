@@ -0,0 +1,443 @@
+//! Overview of all interviews of a study, for keeping track of what has
+//! been transcribed and what is ready to merge.
+//!
+//! The project tree is searched for transcripts and segment manifests.
+//! Transcripts in the same directory with the same name before their
+//! segment number, like `foo-000.rtf` and `foo-001.rtf`, are an
+//! interview, and so are the segments listed in a manifest, even before
+//! any of them are transcribed. Transcripts without a segment number next
+//! to numbered ones, like a merged `foo.rtf`, are left out.
+//!
+//! With `--store`, transcripts imported into the project store are not
+//! parsed again, see [`crate::store`].
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::args::Report;
+use crate::atomic::AtomicFile;
+use crate::check::Quality;
+use crate::exit::{ExitCode, Failure};
+use crate::find::collect_transcripts;
+use crate::manifest::Manifest;
+use crate::renumber::{segment_name, segment_number};
+use crate::store::{stored_path, stored_transcripts, Error as StoreError, StoredTranscript};
+use crate::timestamp::Timestamp;
+use crate::transcript::{csv_field, html_escape, Transcript};
+
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+const CSV_HEADER: &str =
+    "interview,directory,segments,missing segments,duration,lowest score,mean score,ready,problems";
+
+pub fn report(opts: Report) -> Result<()> {
+    let dirs = if opts.project_dirs.is_empty() {
+        vec![std::env::current_dir()?]
+    } else {
+        opts.project_dirs
+    };
+    let transcripts = collect_transcripts(dirs.clone(), true)?;
+    let mut manifest_dirs = vec![];
+    for dir in dirs {
+        subdirectories(&dir, &mut manifest_dirs)?;
+    }
+    let stored = match &opts.store {
+        Some(store) => stored_transcripts(store)?,
+        None => BTreeMap::new(),
+    };
+    let reports = interview_reports(transcripts, &manifest_dirs, &stored);
+    if reports.is_empty() {
+        return Err(Error::NoInterviews);
+    }
+
+    match opts.output_file {
+        Some(path) => {
+            let html = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| {
+                    ext.eq_ignore_ascii_case("html") || ext.eq_ignore_ascii_case("htm")
+                });
+            let mut file = AtomicFile::create(&path)?;
+            if html {
+                write_html(&mut file, &reports)?;
+            } else {
+                write_csv(&mut file, &reports)?;
+            }
+            file.commit()?;
+        }
+        None => write_csv(std::io::stdout().lock(), &reports)?,
+    }
+    Ok(())
+}
+
+/// State of the transcription of an interview.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterviewReport {
+    pub name: String,
+    pub dir: PathBuf,
+    /// Number of transcribed segments.
+    pub segments: usize,
+    /// Numbers of segments that are missing between the transcribed ones,
+    /// see [`crate::find::segment_gaps`], or that are in the manifest but
+    /// not transcribed.
+    pub missing: Vec<u32>,
+    /// Length of the audio from the manifest, or else the suspected
+    /// lengths of the transcribed segments added up.
+    pub duration: Timestamp,
+    /// Lowest and mean quality score of the segments, see
+    /// [`crate::check`].
+    pub lowest_score: Option<u8>,
+    pub mean_score: Option<u8>,
+    /// What keeps the interview from being merged, empty if it is ready.
+    pub problems: Vec<String>,
+}
+
+impl InterviewReport {
+    pub fn ready(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    fn missing_list(&self) -> String {
+        let missing: Vec<String> = self.missing.iter().map(u32::to_string).collect();
+        missing.join(" ")
+    }
+}
+
+/// Groups the transcripts into interviews, together with the manifests in
+/// the directories, and reports on each of them in order of directory and
+/// name.
+///
+/// Transcripts in `stored` by their [`stored_path`] are not parsed again.
+pub fn interview_reports(
+    transcripts: Vec<PathBuf>,
+    manifest_dirs: &[PathBuf],
+    stored: &BTreeMap<PathBuf, StoredTranscript>,
+) -> Vec<InterviewReport> {
+    let mut interviews: BTreeMap<(PathBuf, String), Vec<PathBuf>> = BTreeMap::new();
+    for path in transcripts {
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        interviews
+            .entry((dir, segment_name(&stem).to_string()))
+            .or_default()
+            .push(path);
+    }
+    let mut manifests = BTreeMap::new();
+    for dir in manifest_dirs {
+        for manifest in Manifest::in_dir(dir) {
+            let stem = Path::new(&manifest.interview)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            interviews.entry((dir.clone(), stem.clone())).or_default();
+            manifests.insert((dir.clone(), stem), manifest);
+        }
+    }
+
+    interviews
+        .into_iter()
+        .map(|(key, paths)| {
+            let manifest = manifests.get(&key);
+            let (dir, name) = key;
+            interview_report(name, dir, paths, manifest, stored)
+        })
+        .collect()
+}
+
+fn interview_report(
+    name: String,
+    dir: PathBuf,
+    mut paths: Vec<PathBuf>,
+    manifest: Option<&Manifest>,
+    stored: &BTreeMap<PathBuf, StoredTranscript>,
+) -> InterviewReport {
+    let number = |path: &PathBuf| {
+        path.file_stem()
+            .and_then(|stem| segment_number(&stem.to_string_lossy()))
+    };
+    if paths.iter().any(|path| number(path).is_some()) {
+        paths.retain(|path| number(path).is_some());
+    }
+    let mut numbers: Vec<u32> = paths.iter().filter_map(number).collect();
+    numbers.sort_unstable();
+    let expected: Vec<u32> = match (numbers.last(), manifest) {
+        (_, Some(manifest)) => manifest
+            .segments
+            .iter()
+            .map(|segment| segment.number)
+            .collect(),
+        (Some(&last), None) => (numbers[0]..=last).collect(),
+        (None, None) => vec![],
+    };
+    let missing: Vec<u32> = expected
+        .into_iter()
+        .filter(|number| numbers.binary_search(number).is_err())
+        .collect();
+
+    let mut problems = vec![];
+    if !missing.is_empty() {
+        let list: Vec<String> = missing.iter().map(u32::to_string).collect();
+        problems.push(format!("segments {} missing", list.join(", ")));
+    }
+    let mut duration = Timestamp::zero();
+    let mut scores = vec![];
+    for path in &paths {
+        let (end_time, unparsed, score) = match stored.get(&stored_path(path)) {
+            Some(stored) => (stored.end_time, stored.unparsed_lines, stored.score),
+            None => match Transcript::from_file(path) {
+                Ok(transcript) => (
+                    transcript.interview_end_time(),
+                    transcript.unparsed_lines().len(),
+                    Quality::of_transcript(path, &transcript).score,
+                ),
+                Err(err) => {
+                    problems.push(format!("{} cannot be read: {}", file_name(path), err));
+                    continue;
+                }
+            },
+        };
+        if unparsed > 0 {
+            problems.push(format!(
+                "{} unparsed lines in {}",
+                unparsed,
+                file_name(path)
+            ));
+        }
+        duration = duration.saturating_add(end_time);
+        scores.push(score);
+    }
+    if let Some(manifest) = manifest {
+        let seconds: f64 = manifest
+            .segments
+            .iter()
+            .map(|segment| segment.duration)
+            .sum();
+        if let Some(total) = Timestamp::from_tenths((seconds * 10.0).round() as u64) {
+            duration = total;
+        }
+    }
+    if paths.is_empty() {
+        problems.push(String::from("nothing transcribed yet"));
+    }
+
+    InterviewReport {
+        name,
+        dir,
+        segments: paths.len(),
+        missing,
+        duration,
+        lowest_score: scores.iter().copied().min(),
+        mean_score: Some(scores.len())
+            .filter(|&count| count > 0)
+            .map(|count| (scores.iter().map(|&score| score as usize).sum::<usize>() / count) as u8),
+        problems,
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Collects the directory and the directories below it.
+fn subdirectories(dir: &Path, into: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    into.push(dir.to_path_buf());
+    for entry in dir.read_dir()? {
+        let path = entry?.path();
+        if path.is_dir() {
+            subdirectories(&path, into)?;
+        }
+    }
+    Ok(())
+}
+
+/// Length like `01:23:45`.
+fn hms(timestamp: Timestamp) -> String {
+    format!(
+        "{:02}:{:02}:{:02}",
+        timestamp.hours(),
+        timestamp.minutes(),
+        timestamp.seconds()
+    )
+}
+
+fn score(score: Option<u8>) -> String {
+    score.map(|score| score.to_string()).unwrap_or_default()
+}
+
+fn write_csv<W: Write>(mut to: W, reports: &[InterviewReport]) -> std::io::Result<()> {
+    writeln!(&mut to, "{}", CSV_HEADER)?;
+    for report in reports {
+        writeln!(
+            &mut to,
+            "{},{},{},{},{},{},{},{},{}",
+            csv_field(&report.name),
+            csv_field(&report.dir.display().to_string()),
+            report.segments,
+            report.missing_list(),
+            hms(report.duration),
+            score(report.lowest_score),
+            score(report.mean_score),
+            if report.ready() { "yes" } else { "no" },
+            csv_field(&report.problems.join("; "))
+        )?;
+    }
+    Ok(())
+}
+
+fn write_html<W: Write>(mut to: W, reports: &[InterviewReport]) -> std::io::Result<()> {
+    let ready = reports.iter().filter(|report| report.ready()).count();
+    writeln!(
+        &mut to,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Interviews</title>\n<style>\n\
+         body {{ font-family: sans-serif; }}\n\
+         table {{ border-collapse: collapse; }}\n\
+         th, td {{ border: 1px solid #ccc; padding: 0.2em 0.6em; text-align: left; }}\n\
+         tr.ready {{ background: #e6f4e6; }}\n\
+         tr.blocked {{ background: #fbe9e7; }}\n\
+         </style>\n</head>\n<body>\n\
+         <h1>Interviews</h1>\n<p>{} of {} ready to merge</p>\n<table>",
+        ready,
+        reports.len()
+    )?;
+    let header: Vec<String> = CSV_HEADER
+        .split(',')
+        .map(|title| format!("<th>{}</th>", title))
+        .collect();
+    writeln!(&mut to, "<tr>{}</tr>", header.join(""))?;
+    for report in reports {
+        writeln!(
+            &mut to,
+            "<tr class=\"{}\"><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>\
+             <td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            if report.ready() { "ready" } else { "blocked" },
+            html_escape(&report.name),
+            html_escape(&report.dir.display().to_string()),
+            report.segments,
+            report.missing_list(),
+            hms(report.duration),
+            score(report.lowest_score),
+            score(report.mean_score),
+            if report.ready() { "yes" } else { "no" },
+            html_escape(&report.problems.join("; "))
+        )?;
+    }
+    writeln!(&mut to, "</table>\n</body>\n</html>")
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("no transcripts or segment manifests found")]
+    NoInterviews,
+    #[error("failed to read the project store: {0}")]
+    Store(#[from] StoreError),
+}
+
+impl Failure for Error {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Error::IO(_) => ExitCode::IO,
+            Error::NoInterviews => ExitCode::NoInputs,
+            Error::Store(err) => err.exit_code(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::manifest::ManifestSegment;
+    use std::fs;
+
+    #[test]
+    fn interviews_report_missing_segments_and_readiness() {
+        let root = std::env::temp_dir().join("f4tapir-report");
+        let _ = fs::remove_dir_all(&root);
+        let complete = root.join("a");
+        let gappy = root.join("b");
+        let planned = root.join("c");
+        for dir in &[&complete, &gappy, &planned] {
+            fs::create_dir_all(dir).unwrap();
+        }
+        for path in &[
+            complete.join("anna-000.rtf"),
+            complete.join("anna-001.rtf"),
+            complete.join("anna.rtf"),
+            gappy.join("bert-000.rtf"),
+            gappy.join("bert-003.rtf"),
+        ] {
+            fs::copy("testdata/interview-01.rtf", path).unwrap();
+        }
+        let manifest = Manifest {
+            interview: String::from("carl.mp3"),
+            segments: vec![ManifestSegment {
+                file: String::from("carl-000.mp3"),
+                number: 0,
+                start: 0.0,
+                duration: 90.0,
+                hash: String::from("0000000000000000"),
+            }],
+        };
+        manifest.write(&Manifest::path(&planned, "carl")).unwrap();
+
+        let transcripts = collect_transcripts(vec![root.clone()], true).unwrap();
+        let mut dirs = vec![];
+        subdirectories(&root, &mut dirs).unwrap();
+        let bert = stored_path(&gappy.join("bert-000.rtf"));
+        let mut stored = BTreeMap::new();
+        stored.insert(
+            bert.clone(),
+            StoredTranscript {
+                path: bert,
+                end_time: Timestamp::zero(),
+                unparsed_lines: 0,
+                score: 42,
+            },
+        );
+        let reports = interview_reports(transcripts, &dirs, &stored);
+
+        let summary: Vec<(&str, usize, Vec<u32>, bool)> = reports
+            .iter()
+            .map(|report| {
+                (
+                    report.name.as_str(),
+                    report.segments,
+                    report.missing.clone(),
+                    report.ready(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("anna", 2, vec![], true),
+                ("bert", 2, vec![1, 2], false),
+                ("carl", 0, vec![0], false),
+            ]
+        );
+        assert_eq!(reports[0].lowest_score, Some(100));
+        // taken from the store instead of parsing
+        assert_eq!(reports[1].lowest_score, Some(42));
+        assert_eq!(reports[2].duration.tenths(), 900);
+
+        let mut csv = vec![];
+        write_csv(&mut csv, &reports).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert!(csv.contains("bert,"), "{}", csv);
+        assert!(csv.lines().nth(2).unwrap().contains(",1 2,"), "{}", csv);
+    }
+}
@@ -0,0 +1,322 @@
+//! Progress of `merge --resume`, so that a merge that failed on a broken
+//! transcript can continue where it stopped once it was fixed or excluded.
+//!
+//! The merged transcript is written to a partial file next to the output
+//! file. A state file next to it has a line with the merge options and a
+//! line per merged transcript: its fingerprint, shift, end time, number of
+//! adjusted timestamps, the length of the partial file and the number of
+//! lines in it after merging the transcript, and its path.
+//!
+//! `merge --incremental` keeps the same state as a cache next to the
+//! output file, with a last line for the fingerprint of the output, so
+//! that it only merges transcripts again from the first one that changed.
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::atomic::AtomicFile;
+use crate::incremental::fingerprint;
+use crate::timestamp::Timestamp;
+use crate::transcript::{Checkpoint, SegmentShift};
+
+/// Start of the line with the fingerprint of the output.
+const OUTPUT_PREFIX: &str = "output\t";
+
+/// A transcript that was merged completely.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Progress {
+    pub path: PathBuf,
+    pub fingerprint: u64,
+    pub shift: Timestamp,
+    pub end_time: Timestamp,
+    pub adjusted_timestamps: usize,
+    /// Length of the partial file after merging the transcript.
+    pub bytes: u64,
+    /// Number of lines in the partial file after merging the transcript.
+    pub lines: usize,
+}
+
+/// The state of a resumable merge into an output file.
+#[derive(Debug)]
+pub struct MergeState {
+    path: PathBuf,
+    options: String,
+    progress: Vec<Progress>,
+    /// Fingerprint of the complete output, for incremental merges.
+    output: Option<u64>,
+}
+
+impl MergeState {
+    /// Loads the state of a merge into the output file with the given
+    /// options. If there is no state or it was for other options, the
+    /// state is empty. Malformed lines end the progress.
+    pub fn load(output: &Path, options: &str) -> io::Result<MergeState> {
+        MergeState::load_from(sibling(output, ".f4tapir-merge"), options)
+    }
+
+    /// Loads the cache of incremental merges into the output file, like
+    /// [`MergeState::load`].
+    pub fn load_cache(output: &Path, options: &str) -> io::Result<MergeState> {
+        MergeState::load_from(sibling(output, ".f4tapir-cache"), options)
+    }
+
+    fn load_from(path: PathBuf, options: &str) -> io::Result<MergeState> {
+        let (progress, output) = match fs::read_to_string(&path) {
+            Ok(content) => {
+                let mut lines = content.lines();
+                if lines.next() == Some(options) {
+                    let progress = lines.by_ref().map_while(parse_line).collect();
+                    let output = content
+                        .lines()
+                        .last()
+                        .and_then(|line| line.strip_prefix(OUTPUT_PREFIX))
+                        .and_then(|fingerprint| u64::from_str_radix(fingerprint, 16).ok());
+                    (progress, output)
+                } else {
+                    (vec![], None)
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (vec![], None),
+            Err(err) => return Err(err),
+        };
+        Ok(MergeState {
+            path,
+            options: options.to_string(),
+            progress,
+            output,
+        })
+    }
+
+    /// Number of transcripts at the start of `paths` that were merged
+    /// before and have not changed since.
+    pub fn resumable(&self, paths: &[PathBuf]) -> usize {
+        self.progress
+            .iter()
+            .zip(paths)
+            .take_while(|(progress, path)| {
+                progress.path == **path
+                    && fingerprint(path, &self.options).ok() == Some(progress.fingerprint)
+            })
+            .count()
+    }
+
+    /// Number of transcripts with recorded progress.
+    pub fn transcripts(&self) -> usize {
+        self.progress.len()
+    }
+
+    /// Forgets the progress after the given number of transcripts.
+    pub fn truncate(&mut self, transcripts: usize) {
+        self.progress.truncate(transcripts);
+    }
+
+    /// Fingerprint of the output that was recorded after the last
+    /// complete merge.
+    pub fn output_fingerprint(&self) -> Option<u64> {
+        self.output
+    }
+
+    /// Records the fingerprint of the output after a complete merge and
+    /// writes the state.
+    pub fn record_output(&mut self, fingerprint: u64) -> io::Result<()> {
+        self.output = Some(fingerprint);
+        self.save()
+    }
+
+    /// Last progress, if any.
+    pub fn last(&self) -> Option<&Progress> {
+        self.progress.last()
+    }
+
+    /// Checkpoint to resume the merge from.
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            shifts: self
+                .progress
+                .iter()
+                .map(|progress| SegmentShift {
+                    end_time: progress.end_time,
+                    shift: progress.shift,
+                    adjusted_timestamps: progress.adjusted_timestamps,
+                    unadjusted: vec![],
+                    numbered_lines: vec![],
+                })
+                .collect(),
+            lines: self.last().map_or(0, |progress| progress.lines),
+        }
+    }
+
+    /// Records that the transcript at the path was merged, with the
+    /// partial file being `bytes` long afterwards, and writes the state.
+    pub fn record(&mut self, path: &Path, checkpoint: &Checkpoint, bytes: u64) -> io::Result<()> {
+        let shift = checkpoint
+            .shifts
+            .last()
+            .expect("recorded checkpoint without transcripts");
+        self.progress.push(Progress {
+            path: path.to_path_buf(),
+            fingerprint: fingerprint(path, &self.options)?,
+            shift: shift.shift,
+            end_time: shift.end_time,
+            adjusted_timestamps: shift.adjusted_timestamps,
+            bytes,
+            lines: checkpoint.lines,
+        });
+        self.output = None;
+        self.save()
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let mut file = AtomicFile::create(&self.path)?;
+        writeln!(&mut file, "{}", self.options)?;
+        for progress in &self.progress {
+            writeln!(
+                &mut file,
+                "{:016x}\t{}\t{}\t{}\t{}\t{}\t{}",
+                progress.fingerprint,
+                progress.shift.tenths(),
+                progress.end_time.tenths(),
+                progress.adjusted_timestamps,
+                progress.bytes,
+                progress.lines,
+                progress.path.display()
+            )?;
+        }
+        if let Some(output) = self.output {
+            writeln!(&mut file, "{}{:016x}", OUTPUT_PREFIX, output)?;
+        }
+        file.commit()
+    }
+
+    /// Removes the state file after the merge is complete.
+    pub fn remove(self) -> io::Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Path of the partial file that the output is written to.
+pub fn partial_path(output: &Path) -> PathBuf {
+    sibling(output, ".partial")
+}
+
+/// The path with the suffix appended to its file name.
+fn sibling(output: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(output.file_name().unwrap_or_default());
+    name.push(suffix);
+    output.with_file_name(name)
+}
+
+fn parse_line(line: &str) -> Option<Progress> {
+    let mut fields = line.splitn(7, '\t');
+    let fingerprint = u64::from_str_radix(fields.next()?, 16).ok()?;
+    let mut timestamp = || Timestamp::from_tenths(fields.next()?.parse().ok()?);
+    let shift = timestamp()?;
+    let end_time = timestamp()?;
+    let adjusted_timestamps = fields.next()?.parse().ok()?;
+    let bytes = fields.next()?.parse().ok()?;
+    let lines = fields.next()?.parse().ok()?;
+    let path = PathBuf::from(fields.next()?);
+    Some(Progress {
+        path,
+        fingerprint,
+        shift,
+        end_time,
+        adjusted_timestamps,
+        bytes,
+        lines,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn progress_is_resumable_until_a_transcript_changes() {
+        let dir = std::env::temp_dir().join("f4tapir-resume");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("merged.rtf");
+        let paths: Vec<PathBuf> = ["a-000.rtf", "a-001.rtf", "a-002.rtf"]
+            .iter()
+            .map(|name| dir.join(name))
+            .collect();
+        for path in &paths {
+            fs::write(path, "transcript").unwrap();
+        }
+
+        let mut state = MergeState::load(&output, "options").unwrap();
+        let mut checkpoint = Checkpoint::default();
+        for (idx, path) in paths.iter().take(2).enumerate() {
+            checkpoint.shifts.push(SegmentShift {
+                end_time: Timestamp::from_tenths(3000).unwrap(),
+                shift: Timestamp::from_tenths(3000 * idx as u64).unwrap(),
+                adjusted_timestamps: 4,
+                unadjusted: vec![],
+                numbered_lines: vec![],
+            });
+            checkpoint.lines += 10;
+            state
+                .record(path, &checkpoint, 100 * (idx as u64 + 1))
+                .unwrap();
+        }
+
+        let state = MergeState::load(&output, "options").unwrap();
+        assert_eq!(state.resumable(&paths), 2);
+        assert_eq!(state.checkpoint(), checkpoint);
+        assert_eq!(state.last().unwrap().bytes, 200);
+        assert_eq!(
+            MergeState::load(&output, "other")
+                .unwrap()
+                .resumable(&paths),
+            0
+        );
+
+        fs::write(&paths[1], "changed").unwrap();
+        assert_eq!(state.resumable(&paths), 1);
+        state.remove().unwrap();
+        assert_eq!(
+            MergeState::load(&output, "options")
+                .unwrap()
+                .resumable(&paths),
+            0
+        );
+    }
+
+    #[test]
+    fn cache_keeps_the_output_fingerprint() {
+        let dir = std::env::temp_dir().join("f4tapir-cache");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("merged.rtf");
+        let path = dir.join("a-000.rtf");
+        fs::write(&path, "transcript").unwrap();
+
+        let mut state = MergeState::load_cache(&output, "options").unwrap();
+        let checkpoint = Checkpoint {
+            shifts: vec![SegmentShift {
+                end_time: Timestamp::from_tenths(3000).unwrap(),
+                shift: Timestamp::zero(),
+                adjusted_timestamps: 4,
+                unadjusted: vec![],
+                numbered_lines: vec![],
+            }],
+            lines: 10,
+        };
+        state.record(&path, &checkpoint, 100).unwrap();
+        state.record_output(0xf4).unwrap();
+
+        let state = MergeState::load_cache(&output, "options").unwrap();
+        assert_eq!(state.output_fingerprint(), Some(0xf4));
+        assert_eq!(state.resumable(&[path]), 1);
+        assert_eq!(
+            MergeState::load(&output, "options").unwrap().transcripts(),
+            0
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,324 @@
+//! Long-lived backend for graphical front-ends, which drive f4tapir with
+//! JSON-RPC 2.0 requests instead of starting it for every click.
+//!
+//! With `serve --stdio`, every line on stdin is a request and every
+//! response is written as a line to stdout, while logs go to stderr:
+//!
+//! ```text
+//! --> {"jsonrpc":"2.0","id":1,"method":"check","params":{"paths":["interview"]}}
+//! <-- {"jsonrpc":"2.0","id":1,"result":[{"path":"interview/interview-000.rtf","info":{…}}]}
+//! ```
+//!
+//! The methods are:
+//!
+//! * `list` with `paths` and `recursive`: the transcripts and interviews
+//!   found there, like `merge` and `split` would.
+//! * `check` with `paths`, `recursive` and `infer_speakers`: the info of
+//!   every transcript like `info --json`, or why it cannot be loaded.
+//! * `merge`, `split` and `export` with `args`: the arguments of the
+//!   commands `merge`, `split` and `convert` as on the command line.
+//!
+//! Failures of operations are errors with the exit code of the command as
+//! their code, see [`crate::exit`].
+//!
+//! `serve --http` offers similar operations for a shared server instead,
+//! see the `http` module.
+use std::fmt::Display;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use crate::args::{Merge, Serve};
+use crate::exit::{ExitCode, Failure};
+use crate::find::{collect_interviews, collect_transcripts};
+use crate::info::TranscriptInfo;
+use crate::logging::SERVE;
+use crate::transcript::SpeakerInference;
+use crate::{convert, merge, split};
+
+use argh::FromArgs;
+use log::{debug, info};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Error codes defined by JSON-RPC.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+pub fn serve(opts: Serve) -> Result<()> {
+    match (opts.stdio, opts.http) {
+        (true, None) => {
+            info!(target: SERVE, "serving JSON-RPC on stdio");
+            serve_lines(io::stdin().lock(), io::stdout().lock())
+        }
+        (false, Some(address)) => {
+            let data_dir = opts.data_dir.unwrap_or_else(|| PathBuf::from("."));
+            serve_http(&address, &data_dir)
+        }
+        _ => Err(Error::NoTransport),
+    }
+}
+
+#[cfg(feature = "http")]
+fn serve_http(address: &str, data_dir: &Path) -> Result<()> {
+    if !data_dir.is_dir() {
+        return Err(Error::DataDir(data_dir.to_path_buf()));
+    }
+    crate::http::serve_http(address, data_dir)
+        .map_err(|err| Error::Listen(address.to_string(), err))
+}
+
+#[cfg(not(feature = "http"))]
+fn serve_http(_address: &str, _data_dir: &Path) -> Result<()> {
+    Err(Error::HttpUnsupported)
+}
+
+/// Answers the requests on the lines of `input` until it ends.
+pub fn serve_lines<R: BufRead, W: Write>(input: R, mut output: W) -> Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = respond(&line) {
+            writeln!(&mut output, "{}", response)?;
+            output.flush()?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: String,
+    /// Missing for notifications, which get no response.
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// Where to look for transcripts and interviews.
+#[derive(Deserialize)]
+struct FindParams {
+    #[serde(default)]
+    paths: Vec<PathBuf>,
+    #[serde(default)]
+    recursive: bool,
+    #[serde(default)]
+    infer_speakers: Option<String>,
+}
+
+/// Arguments of a command like on the command line.
+#[derive(Deserialize)]
+struct CommandParams {
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+/// The error of a JSON-RPC response.
+#[derive(Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Display) -> RpcError {
+        RpcError {
+            code,
+            message: message.to_string(),
+        }
+    }
+
+    /// The failure of an operation, with the exit code of the command.
+    fn failed<E: Failure + Display>(err: E) -> RpcError {
+        RpcError::new(i64::from(err.exit_code().code()), err)
+    }
+}
+
+/// The response to the request on the line, if it is not a notification.
+fn respond(line: &str) -> Option<Value> {
+    let request: Request = match serde_json::from_str::<Value>(line) {
+        Err(err) => return Some(error_response(Value::Null, RpcError::new(PARSE_ERROR, err))),
+        Ok(value) => {
+            let id = value.get("id").cloned().unwrap_or(Value::Null);
+            match serde_json::from_value(value) {
+                Ok(request) => request,
+                Err(err) => return Some(error_response(id, RpcError::new(INVALID_REQUEST, err))),
+            }
+        }
+    };
+    let id = request.id.clone();
+    let result = if request.jsonrpc != "2.0" {
+        Err(RpcError::new(INVALID_REQUEST, "expected jsonrpc 2.0"))
+    } else {
+        debug!(target: SERVE, "handling {}", request.method);
+        call(&request.method, request.params)
+    };
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err(err) => error_response(id, err),
+    })
+}
+
+fn error_response(id: Value, err: RpcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {"code": err.code, "message": err.message},
+    })
+}
+
+fn call(method: &str, params: Value) -> std::result::Result<Value, RpcError> {
+    match method {
+        "list" => list(params_of(params)?),
+        "check" => check(params_of(params)?),
+        "merge" => {
+            let opts: Merge = command_of("merge", params)?;
+            if opts.stdout {
+                return Err(RpcError::new(
+                    INVALID_PARAMS,
+                    "--stdout is where responses go, give an --output-file",
+                ));
+            }
+            merge::merge(opts).map_err(RpcError::failed)?;
+            Ok(Value::Null)
+        }
+        "split" => {
+            split::split(command_of("split", params)?).map_err(RpcError::failed)?;
+            Ok(Value::Null)
+        }
+        "export" => {
+            let output = convert::convert_to_file(command_of("convert", params)?)
+                .map_err(RpcError::failed)?;
+            Ok(json!({"output_file": output.display().to_string()}))
+        }
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("unknown method {}", method),
+        )),
+    }
+}
+
+fn list(params: FindParams) -> std::result::Result<Value, RpcError> {
+    let io_failure = |err| RpcError::new(i64::from(ExitCode::IO.code()), err);
+    let display = |paths: Vec<PathBuf>| -> Vec<String> {
+        paths
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect()
+    };
+    let transcripts =
+        collect_transcripts(params.paths.clone(), params.recursive).map_err(io_failure)?;
+    let interviews = collect_interviews(params.paths, params.recursive).map_err(io_failure)?;
+    Ok(json!({
+        "transcripts": display(transcripts),
+        "interviews": display(interviews),
+    }))
+}
+
+fn check(params: FindParams) -> std::result::Result<Value, RpcError> {
+    let inference = params
+        .infer_speakers
+        .as_deref()
+        .map(str::parse::<SpeakerInference>)
+        .transpose()
+        .map_err(|err| RpcError::new(INVALID_PARAMS, err))?;
+    let paths = collect_transcripts(params.paths, params.recursive)
+        .map_err(|err| RpcError::new(i64::from(ExitCode::IO.code()), err))?;
+    let checks = paths
+        .iter()
+        .map(|path| match TranscriptInfo::of(path, inference) {
+            Ok(info) => json!({"path": path.display().to_string(), "info": info.to_json()}),
+            Err(err) => json!({
+                "path": path.display().to_string(),
+                "error": {"code": err.exit_code().code(), "message": err.to_string()},
+            }),
+        })
+        .collect();
+    Ok(Value::Array(checks))
+}
+
+fn params_of<T: for<'de> Deserialize<'de>>(params: Value) -> std::result::Result<T, RpcError> {
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|err| RpcError::new(INVALID_PARAMS, err))
+}
+
+/// Parses the `args` of the params like the command line of the command.
+fn command_of<T: FromArgs>(command: &str, params: Value) -> std::result::Result<T, RpcError> {
+    let CommandParams { args } = params_of(params)?;
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    T::from_args(&[command], &args)
+        .map_err(|early_exit| RpcError::new(INVALID_PARAMS, early_exit.output.trim()))
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    IO(#[from] io::Error),
+    #[error("give how to serve, either --stdio or --http with an address")]
+    NoTransport,
+    #[error("serving HTTP needs f4tapir built with the http feature")]
+    HttpUnsupported,
+    #[error("{0} is not a directory to serve files from")]
+    DataDir(PathBuf),
+    #[error("cannot serve HTTP on {0}: {1}")]
+    Listen(String, String),
+}
+
+impl Failure for Error {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Error::IO(_) => ExitCode::IO,
+            Error::NoTransport | Error::HttpUnsupported | Error::DataDir(_) => ExitCode::Failure,
+            Error::Listen(_, _) => ExitCode::IO,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn serve_str(input: &str) -> Vec<Value> {
+        let mut output = vec![];
+        serve_lines(input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn requests_are_answered_line_by_line() {
+        let responses = serve_str(concat!(
+            r#"{"jsonrpc":"2.0","id":1,"method":"check","params":{"paths":["testdata/interview-01.rtf"]}}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","method":"list"}"#,
+            "\n\n",
+            r#"{"jsonrpc":"2.0","id":"b","method":"shred"}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","id":3,"method":"export","params":{"args":["--to"]}}"#,
+            "\n",
+            "{\"jsonrpc\n",
+        ));
+        assert_eq!(responses.len(), 4);
+        assert_eq!(responses[0]["id"], 1);
+        assert_eq!(
+            responses[0]["result"][0]["info"]["speakers"],
+            json!(["I", "Z"])
+        );
+        assert_eq!(responses[1]["id"], "b");
+        assert_eq!(responses[1]["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(responses[2]["error"]["code"], INVALID_PARAMS);
+        assert_eq!(responses[3]["error"]["code"], PARSE_ERROR);
+    }
+}
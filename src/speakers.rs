@@ -0,0 +1,160 @@
+//! Renames speakers in every transcript of a project, in place or into a
+//! mirror of the project directory.
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::args::RenameSpeakers;
+use crate::atomic::AtomicFile;
+use crate::backup::keep_original;
+use crate::exit::{ExitCode, Failure};
+use crate::find::collect_transcripts;
+use crate::transcript::{Error as TranscriptError, SpeakerRename, Transcript};
+
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+pub fn rename_speakers(opts: RenameSpeakers) -> Result<()> {
+    if opts.map.is_empty() {
+        return Err(Error::NoRenames);
+    }
+    if opts.output_dir.is_some() == opts.in_place {
+        return Err(Error::OutputOptions);
+    }
+    if opts.orig && !opts.in_place {
+        return Err(Error::OrigWithoutInPlace);
+    }
+
+    // rename everything before writing anything, so that a broken
+    // transcript does not leave the project half renamed
+    let mut renamed = vec![];
+    for input in &opts.input_segments {
+        for path in collect_transcripts(vec![input.clone()], opts.recursive)? {
+            let transcript = Transcript::from_file(&path)
+                .map_err(|err| Error::BrokenTranscript(path.clone(), err))?;
+            let (transcript, counts) = transcript.with_renamed_speakers(&opts.map);
+            let output = match &opts.output_dir {
+                Some(dir) => dir.join(mirrored(input, &path)),
+                None => path.clone(),
+            };
+            renamed.push((path, output, transcript, counts));
+        }
+    }
+    if renamed.is_empty() {
+        return Err(Error::NoTranscripts);
+    }
+    if opts.output_dir.is_some() && !opts.force {
+        if let Some((_, output, _, _)) = renamed.iter().find(|(_, output, _, _)| output.exists()) {
+            return Err(Error::OutputExists(output.clone()));
+        }
+    }
+
+    let mut stdout = std::io::stdout().lock();
+    let mut total = 0;
+    for (path, output, transcript, counts) in renamed {
+        if opts.in_place && counts.is_empty() {
+            writeln!(&mut stdout, "{}  unchanged", path.display())?;
+            continue;
+        }
+        if opts.orig {
+            keep_original(&path)?;
+        }
+        if let Some(dir) = output.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = AtomicFile::create(&output)?;
+        write!(&mut file, "{}", transcript)?;
+        file.commit()?;
+
+        total += counts.values().sum::<usize>();
+        writeln!(
+            &mut stdout,
+            "{}  {}",
+            output.display(),
+            summary(&opts.map, &counts)
+        )?;
+    }
+    writeln!(&mut stdout, "{} speaker labels renamed", total)?;
+    Ok(())
+}
+
+/// Path of the transcript relative to the input it was found in, or just
+/// its file name if the input was the transcript itself.
+fn mirrored(input: &Path, path: &Path) -> PathBuf {
+    match path.strip_prefix(input) {
+        Ok(relative) if !relative.as_os_str().is_empty() => relative.to_path_buf(),
+        _ => PathBuf::from(path.file_name().unwrap_or_default()),
+    }
+}
+
+/// Renames with their counts like `B -> P07: 12, Z -> P08: 3`.
+fn summary(renames: &[SpeakerRename], counts: &BTreeMap<String, usize>) -> String {
+    if counts.is_empty() {
+        return String::from("unchanged");
+    }
+    renames
+        .iter()
+        .filter_map(|rename| {
+            let count = counts.get(&rename.from)?;
+            Some(format!("{} -> {}: {}", rename.from, rename.to, count))
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("no transcripts found")]
+    NoTranscripts,
+    #[error("no speakers to rename, give them like --map B=P07")]
+    NoRenames,
+    #[error("give either --output-dir or --in-place")]
+    OutputOptions,
+    #[error("--orig only keeps originals with --in-place")]
+    OrigWithoutInPlace,
+    #[error("{0} exists, use --force to overwrite")]
+    OutputExists(PathBuf),
+    #[error("could not load transcript {0}, nothing was renamed: {1}")]
+    BrokenTranscript(PathBuf, TranscriptError),
+}
+
+impl Failure for Error {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Error::IO(_) => ExitCode::IO,
+            Error::NoTranscripts => ExitCode::NoInputs,
+            Error::NoRenames | Error::OutputOptions | Error::OrigWithoutInPlace => {
+                ExitCode::Failure
+            }
+            Error::OutputExists(_) => ExitCode::OutputExists,
+            Error::BrokenTranscript(_, err) => err.exit_code(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn mirrored_paths_are_relative_to_the_input() {
+        assert_eq!(
+            mirrored(
+                Path::new("project"),
+                Path::new("project/a/interview-001.rtf")
+            ),
+            PathBuf::from("a/interview-001.rtf")
+        );
+        assert_eq!(
+            mirrored(
+                Path::new("project/a/interview-001.rtf"),
+                Path::new("project/a/interview-001.rtf")
+            ),
+            PathBuf::from("interview-001.rtf")
+        );
+    }
+}
@@ -1,10 +1,24 @@
+use std::collections::BTreeMap;
+use std::ffi::{OsStr, OsString};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Stdio};
+use std::time::Duration;
 
-use crate::args::Split;
-use crate::find::collect_interviews;
-use crate::paths::path_as_str;
+use crate::args::{Resegment, Split};
+use crate::audio_order::Error as DecodeError;
+use crate::cues::{parse_cues, parse_time, segment_times, transcript_cues, Error as CuesError};
+use crate::exit::{ExitCode, Failure};
+use crate::find::{collect_interviews, PathFilter};
+use crate::incremental::{fingerprint, Entry, SplitState};
+use crate::logging::SPLIT;
+use crate::manifest::{Manifest, ManifestSegment};
+use crate::overlap::measure_overlap;
+use crate::pair::{probe_seconds, Error as PairError};
+use crate::paths::command_arg;
+use crate::timestamp::Timestamp;
+use crate::transcript::{Error as TranscriptError, Transcript};
 
+use log::{debug, info, warn};
 use thiserror::Error;
 
 type Result<T> = std::result::Result<T, Error>;
@@ -14,21 +28,429 @@ const SEGMENT_TIME: &str = "00:05:00";
 const SEGMENT_FILE_FORMAT: &str = "mp3";
 /// file extension to use for segments
 const SEGMENT_FILE_EXTENSION: &str = "mp3";
+/// ffmpeg placeholder for the segment number in the output pattern
+const SEGMENT_NUMBER_PLACEHOLDER: &str = "%03d";
 
 pub fn split(opts: Split) -> Result<()> {
-    let paths = collect_interviews(opts.input_files, opts.recursive)?;
+    let mut paths = collect_interviews(opts.input_files, opts.recursive)?;
+    PathFilter::new(opts.include, opts.exclude).apply(&mut paths);
     if paths.is_empty() {
         return Err(Error::NoInterviews);
     }
+    let segment_times = match (&opts.cues, &opts.cuts_from_transcript) {
+        (Some(_), Some(_)) => return Err(Error::CuesAndTranscript),
+        (Some(_), _) | (_, Some(_)) if paths.len() > 1 => return Err(Error::CuesForManyInterviews),
+        (Some(cues), None) => {
+            let source = std::fs::read_to_string(cues)
+                .map_err(|err| Error::CuesLoadFail(cues.clone(), err))?;
+            let cuts = parse_cues(&source).map_err(|err| Error::Cues(cues.clone(), err))?;
+            Some(segment_times(&cuts))
+        }
+        (None, Some(path)) => {
+            let transcript = Transcript::from_file(path)
+                .map_err(|err| Error::TranscriptLoadFail(path.clone(), err))?;
+            let cuts = transcript_cues(&transcript, opts.cut_at)
+                .map_err(|err| Error::Cues(path.clone(), err))?;
+            info!(
+                target: SPLIT,
+                "cutting at {} timestamps of {}",
+                cuts.len(),
+                path.display()
+            );
+            Some(segment_times(&cuts))
+        }
+        (None, None) => None,
+    };
 
     let preferred_output_dir: Option<&Path> = opts.output_directory.as_ref().map(AsRef::as_ref);
-    for path in paths {
-        let output_dir = output_directory_or_interview_parent(preferred_output_dir, &path)?;
-        split_interview(&path, output_dir)?;
+    // one entry for every set of segments to write, with --split-channels
+    // there are several for an interview
+    let mut interviews = Vec::with_capacity(paths.len());
+    let mut patterns = Vec::with_capacity(paths.len());
+    let mut tracks = Vec::with_capacity(paths.len());
+    for path in &paths {
+        let output_dir = output_directory_or_interview_parent(preferred_output_dir, path)?;
+        let pattern = if opts.per_interview_dirs {
+            segment_pattern(Some(&interview_directory(output_dir, path)), path)
+        } else {
+            segment_pattern(output_dir, path)
+        };
+        let channel_tracks = if opts.split_channels {
+            channel_tracks(&probe_channels(path)?)
+        } else {
+            vec![]
+        };
+        if opts.split_channels && channel_tracks.is_empty() {
+            warn!(
+                target: SPLIT,
+                "interview {} has a single audio channel, splitting it as a whole",
+                path.display()
+            );
+        }
+        if channel_tracks.is_empty() {
+            interviews.push(path.clone());
+            patterns.push(pattern);
+            tracks.push(None);
+        } else {
+            for (idx, track) in channel_tracks.into_iter().enumerate() {
+                interviews.push(path.clone());
+                patterns.push(channel_pattern(&pattern, path, idx + 1));
+                tracks.push(Some(track));
+            }
+        }
+    }
+
+    // with --incremental, the fingerprint of every set of segments that
+    // is not up to date yet, and None for those that are; segments that
+    // were recorded by an earlier run may be replaced
+    let mut states = BTreeMap::new();
+    let mut fingerprints = vec![];
+    let mut recorded = vec![false; patterns.len()];
+    if opts.incremental {
+        for (idx, ((path, pattern), track)) in
+            interviews.iter().zip(&patterns).zip(&tracks).enumerate()
+        {
+            let options = format!("{:?} {:?}", segment_times, track);
+            let fingerprint = fingerprint(path, &options)?;
+            let (state, key) = split_state(&mut states, pattern)?;
+            recorded[idx] = state.entry(&key).is_some();
+            if state.is_up_to_date(&key, fingerprint) {
+                info!(
+                    target: SPLIT,
+                    "skipping {}, segments are up to date",
+                    path.display()
+                );
+                fingerprints.push(None);
+            } else {
+                fingerprints.push(Some(fingerprint));
+            }
+        }
+    }
+
+    if !opts.continue_numbering && !opts.force {
+        ensure_no_colliding_patterns(&interviews, &patterns)?;
+        // check all of them before the first ffmpeg run, so nothing is
+        // half-done when we find existing segments later
+        for (pattern, _) in patterns.iter().zip(&recorded).filter(|(_, &r)| !r) {
+            ensure_no_existing_segments(pattern)?;
+        }
+    }
+
+    for (idx, ((path, pattern), track)) in interviews.iter().zip(&patterns).zip(&tracks).enumerate()
+    {
+        let fingerprint = match fingerprints.get(idx) {
+            Some(None) => continue,
+            Some(Some(fingerprint)) => Some(*fingerprint),
+            None => None,
+        };
+        if recorded[idx] {
+            // remove the old segments, there may be fewer new ones
+            let (state, key) = split_state(&mut states, pattern)?;
+            for segment in state.segment_paths(&key) {
+                match std::fs::remove_file(&segment) {
+                    Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                        return Err(err.into())
+                    }
+                    _ => (),
+                }
+            }
+        }
+        let start_number = if opts.continue_numbering {
+            next_segment_number(pattern)?
+        } else {
+            0
+        };
+        info!(
+            target: SPLIT,
+            "splitting {} into {}",
+            path.display(),
+            pattern.display()
+        );
+        if opts.per_interview_dirs {
+            std::fs::create_dir_all(state_dir(pattern))?;
+        }
+        split_interview(
+            path,
+            pattern,
+            start_number,
+            segment_times.as_deref(),
+            track.as_ref(),
+        )?;
+        write_manifest(path, pattern, start_number)?;
+        if let Some(fingerprint) = fingerprint {
+            let segments = produced_segments(pattern, start_number)?
+                .into_iter()
+                .filter_map(|(_, segment)| Some(segment.file_name()?.to_str()?.to_string()))
+                .collect();
+            let (state, key) = split_state(&mut states, pattern)?;
+            let entry = Entry {
+                fingerprint,
+                segments,
+            };
+            state.record(&key, entry)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the manifest listing the segments of the interview with their
+/// lengths and fingerprints, next to them, for merge to use.
+fn write_manifest(interview: &Path, pattern: &Path, start_number: u32) -> Result<()> {
+    let mut segments = vec![];
+    let mut start = 0.0;
+    for (number, segment) in produced_segments(pattern, start_number)? {
+        let duration =
+            probe_seconds(&segment).map_err(|err| Error::SegmentDuration(segment.clone(), err))?;
+        segments.push(ManifestSegment {
+            file: pattern_key(&segment),
+            number,
+            start,
+            duration,
+            hash: format!("{:016x}", fingerprint(&segment, "")?),
+        });
+        start += duration;
     }
+    let manifest = Manifest {
+        interview: pattern_key(interview),
+        segments,
+    };
+    let path = Manifest::path(&state_dir(pattern), &pattern_stem(pattern));
+    debug!(target: SPLIT, "writing segment manifest {}", path.display());
+    manifest.write(&path)?;
     Ok(())
 }
 
+/// Stem of the segments of the pattern, e.g. `interview` for
+/// `interview-%03d.mp3`.
+fn pattern_stem(pattern: &Path) -> String {
+    let key = pattern_key(pattern);
+    match key.split_once(SEGMENT_NUMBER_PLACEHOLDER) {
+        Some((prefix, _)) => prefix.trim_end_matches('-').to_string(),
+        None => key,
+    }
+}
+
+/// The state of the directory the pattern writes to, loaded on first use,
+/// and the key of the pattern in it.
+fn split_state<'a>(
+    states: &'a mut BTreeMap<PathBuf, SplitState>,
+    pattern: &Path,
+) -> Result<(&'a mut SplitState, String)> {
+    let dir = state_dir(pattern);
+    let state = match states.entry(dir) {
+        std::collections::btree_map::Entry::Occupied(entry) => entry.into_mut(),
+        std::collections::btree_map::Entry::Vacant(entry) => {
+            let state = SplitState::load(entry.key())?;
+            entry.insert(state)
+        }
+    };
+    Ok((state, pattern_key(pattern)))
+}
+
+fn state_dir(pattern: &Path) -> PathBuf {
+    pattern
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."))
+        .to_path_buf()
+}
+
+fn pattern_key(pattern: &Path) -> String {
+    pattern
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Audio of a single speaker in a multi-track recording.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Track {
+    /// Stream specifier for `-map`, e.g. `0:a:1`.
+    map: String,
+    /// Filter picking a single channel of the stream, if it has several.
+    pan: Option<String>,
+}
+
+/// Tracks for the audio streams with the given number of channels each.
+///
+/// Several streams are split by stream, a single stream with several
+/// channels by channel. A single mono stream has nothing to split and
+/// yields no tracks.
+fn channel_tracks(stream_channels: &[u32]) -> Vec<Track> {
+    match stream_channels {
+        [channels] => (0..*channels)
+            .filter(|_| *channels > 1)
+            .map(|channel| Track {
+                map: String::from("0:a:0"),
+                pan: Some(format!("pan=mono|c0=c{}", channel)),
+            })
+            .collect(),
+        streams => (0..streams.len())
+            .map(|stream| Track {
+                map: format!("0:a:{}", stream),
+                pan: None,
+            })
+            .collect(),
+    }
+}
+
+/// Asks ffprobe for the number of channels of every audio stream.
+fn probe_channels(interview: &Path) -> Result<Vec<u32>> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-select_streams",
+            "a",
+            "-show_entries",
+            "stream=channels",
+            "-of",
+            "csv=p=0",
+        ])
+        .arg(command_arg(interview))
+        .output()
+        .map_err(Error::FfprobeIo)?;
+    if !output.status.success() {
+        return Err(Error::FfprobeStatus(interview.into()));
+    }
+    audio_streams(interview, &String::from_utf8_lossy(&output.stdout))
+}
+
+/// The channels of every audio stream in the output of ffprobe, failing if
+/// there are none, since there is nothing to split then.
+fn audio_streams(interview: &Path, output: &str) -> Result<Vec<u32>> {
+    match parse_stream_channels(output) {
+        Some(channels) if channels.is_empty() => Err(Error::NoAudioStreams(interview.into())),
+        Some(channels) => Ok(channels),
+        None => Err(Error::FfprobeStatus(interview.into())),
+    }
+}
+
+/// Asks ffprobe for the creation time in the metadata of the recording and
+/// returns its time of day, e.g. `#14:30:00-0#` for
+/// `2021-03-04T14:30:00.000000Z`.
+pub fn probe_clock_start(recording: &Path) -> Result<Timestamp> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-show_entries",
+            "format_tags=creation_time",
+            "-of",
+            "csv=p=0",
+        ])
+        .arg(command_arg(recording))
+        .output()
+        .map_err(Error::FfprobeIo)?;
+    if !output.status.success() {
+        return Err(Error::FfprobeCreationTime(recording.into()));
+    }
+    parse_creation_time(&String::from_utf8_lossy(&output.stdout))
+        .ok_or_else(|| Error::FfprobeCreationTime(recording.into()))
+}
+
+/// Time of day of an ISO 8601 date and time like `2021-03-04T14:30:00Z`.
+fn parse_creation_time(creation_time: &str) -> Option<Timestamp> {
+    let (_, time) = creation_time.trim().split_once(['T', ' '])?;
+    let time = time.get(.."14:30:00".len())?;
+    let tenths = parse_time(time)?.as_millis() as u64 / 100;
+    Timestamp::from_tenths(tenths)
+}
+
+/// Parses one channel count per line, as printed by ffprobe.
+fn parse_stream_channels(output: &str) -> Option<Vec<u32>> {
+    output
+        .lines()
+        .map(|line| line.trim().trim_end_matches(','))
+        .filter(|line| !line.is_empty())
+        .map(|line| line.parse().ok())
+        .collect()
+}
+
+/// Fails if two interviews would write segments with the same names,
+/// e.g. because they have the same stem and the same output directory.
+fn ensure_no_colliding_patterns(interviews: &[PathBuf], patterns: &[PathBuf]) -> Result<()> {
+    for (idx, pattern) in patterns.iter().enumerate() {
+        if let Some(earlier) = patterns[..idx].iter().position(|p| p == pattern) {
+            return Err(Error::SegmentCollision(
+                interviews[earlier].clone(),
+                interviews[idx].clone(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Fails if ffmpeg would overwrite segments that already exist, when
+/// numbering starts at zero.
+fn ensure_no_existing_segments(pattern: &Path) -> Result<()> {
+    match existing_segments(pattern)?.into_iter().next() {
+        Some((_, existing)) => Err(Error::OutputSegmentExists(existing)),
+        None => Ok(()),
+    }
+}
+
+/// Number directly after the highest segment number that already exists
+/// for the given pattern, or zero if there are no segments yet.
+fn next_segment_number(pattern: &Path) -> Result<u32> {
+    let next = existing_segments(pattern)?
+        .into_iter()
+        .map(|(number, _)| number + 1)
+        .max()
+        .unwrap_or(0);
+    Ok(next)
+}
+
+/// Finds the segments of the pattern that splitting an interview starting
+/// at the given number wrote, leaving out those of interviews numbered
+/// before it with `--continue-numbering`.
+fn produced_segments(pattern: &Path, start_number: u32) -> Result<Vec<(u32, PathBuf)>> {
+    let mut segments = existing_segments(pattern)?;
+    segments.retain(|&(number, _)| number >= start_number);
+    Ok(segments)
+}
+
+/// Finds files that have been written with the given segment pattern,
+/// along with their segment number.
+fn existing_segments(pattern: &Path) -> Result<Vec<(u32, PathBuf)>> {
+    // compared lossily, the prefix and suffix are only ever used to find
+    // the digits between them
+    let pattern_name = pattern
+        .file_name()
+        .map(OsStr::to_string_lossy)
+        .unwrap_or_default();
+    let (prefix, suffix) = pattern_name
+        .split_once(SEGMENT_NUMBER_PLACEHOLDER)
+        .expect("segment pattern without placeholder");
+    let dir = pattern
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+
+    let entries = match dir.read_dir() {
+        Ok(entries) => entries,
+        // a directory for the interview that is not created yet
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err.into()),
+    };
+    let mut segments = vec![];
+    for entry in entries {
+        let path = entry?.path();
+        let name = path.file_name().map(OsStr::to_string_lossy);
+        let number = name
+            .as_deref()
+            .and_then(|name| name.strip_prefix(prefix))
+            .and_then(|name| name.strip_suffix(suffix))
+            .filter(|digits| digits.len() >= 3 && digits.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|digits| digits.parse().ok());
+        if let Some(number) = number {
+            segments.push((number, path));
+        }
+    }
+    segments.sort_unstable();
+    Ok(segments)
+}
+
 fn output_directory_or_interview_parent<'a>(
     preferred_output_directory: Option<&'a Path>,
     interview_path: &'a Path,
@@ -49,13 +471,38 @@ fn output_directory_or_interview_parent<'a>(
     Ok(output_dir)
 }
 
-fn split_interview(interview: &Path, output_dir: Option<&Path>) -> Result<()> {
-    let interview_str = path_as_str(&interview)?;
-    let pattern = segment_pattern(output_dir, interview)?;
-    let pattern = (&pattern).to_str().ok_or_else(|| Error::EncodingError)?;
-    let args = [
-        "-i",
-        interview_str,
+/// Splits the interview into segments of fixed length, or at the given
+/// comma-separated seconds, if any. With a track, only its audio is split.
+fn split_interview(
+    interview: &Path,
+    pattern: &Path,
+    start_number: u32,
+    segment_times: Option<&str>,
+    track: Option<&Track>,
+) -> Result<()> {
+    let interview = command_arg(interview);
+    let pattern = command_arg(pattern);
+    let start_number = start_number.to_string();
+    let (segment_option, segment_value) = match segment_times {
+        // `-segment_times 300.000,750.500`: cut at the given seconds
+        Some(times) => ("-segment_times", times),
+        None => ("-segment_time", SEGMENT_TIME),
+    };
+    let mut args: Vec<&OsStr> = vec![
+        // `-y`: overwrite without asking, we checked for existing segments before
+        OsStr::new("-y"),
+        OsStr::new("-i"),
+        &interview,
+    ];
+    if let Some(track) = track {
+        // `-map 0:a:1`: only the stream of the track
+        args.extend(&[OsStr::new("-map"), OsStr::new(&track.map)]);
+        if let Some(pan) = &track.pan {
+            // `-af pan=mono|c0=c1`: only one channel of the stream
+            args.extend(&[OsStr::new("-af"), OsStr::new(pan)]);
+        }
+    }
+    let options = [
         // `-acodec mp3`: convert to mp3, because the timecodes are off for mp4
         "-acodec",
         SEGMENT_FILE_FORMAT,
@@ -63,15 +510,158 @@ fn split_interview(interview: &Path, output_dir: Option<&Path>) -> Result<()> {
         //        have timecodes with offset that cause problems with F4
         "-vn",
         // `-segment_time 05:00:00`: split into 5min segemnts
+        segment_option,
+        segment_value,
+        // `-segment_start_number 0`: number of the first segment
+        "-segment_start_number",
+        &start_number,
+        "-f",
+        "segment",
+    ];
+    args.extend(options.iter().map(OsStr::new));
+    // pattern for segment filenames
+    args.push(&pattern);
+    run_ffmpeg(&args)
+}
+
+/// Joins already split segments and cuts them into segments with a
+/// different length, without needing the original recording.
+pub fn resegment(opts: Resegment) -> Result<()> {
+    let segments = collect_interviews(opts.input_segments, opts.recursive)?;
+    let first = segments.first().ok_or(Error::NoSegments)?;
+
+    let output_dir = output_directory_or_interview_parent(opts.output_directory.as_deref(), first)?;
+    let stem = match opts.name {
+        Some(name) => name,
+        None => interview_stem_of_segment(first)?,
+    };
+    let pattern = segment_pattern_for_stem(output_dir, OsStr::new(&stem));
+
+    // ffmpeg would be reading segments while overwriting them
+    for (_, existing) in existing_segments(&pattern)? {
+        if segments.iter().any(|s| same_file(s, &existing)) {
+            return Err(Error::OverwritesInput(existing));
+        }
+    }
+    if !opts.force {
+        ensure_no_existing_segments(&pattern)?;
+    }
+
+    let mut trims = vec![Duration::ZERO; segments.len()];
+    if opts.trim_overlap {
+        for (idx, pair) in segments.windows(2).enumerate() {
+            if let Some(overlap) = measure_overlap(&pair[0], &pair[1])? {
+                info!(
+                    target: SPLIT,
+                    "{} starts with the last {:.3}s of {}, trimming them",
+                    pair[1].display(),
+                    overlap.as_secs_f64(),
+                    pair[0].display()
+                );
+                trims[idx + 1] = overlap;
+            }
+        }
+    }
+    resegment_segments(&segments, &trims, &pattern, &opts.segment_time)
+}
+
+/// Joins the segments, trimming their starts by the given durations, and
+/// cuts them again.
+fn resegment_segments(
+    segments: &[PathBuf],
+    trims: &[Duration],
+    pattern: &Path,
+    segment_time: &str,
+) -> Result<()> {
+    let filter = concat_filter(trims);
+
+    let inputs: Vec<_> = segments
+        .iter()
+        .map(|segment| command_arg(segment))
+        .collect();
+    let mut args = vec![OsStr::new("-y")];
+    for input in &inputs {
+        args.push(OsStr::new("-i"));
+        args.push(input);
+    }
+    let options = [
+        // concatenate the audio of all inputs in one pass
+        "-filter_complex",
+        &filter,
+        "-map",
+        "[a]",
+        "-acodec",
+        SEGMENT_FILE_FORMAT,
         "-segment_time",
-        SEGMENT_TIME,
-        // pattern for segment filenames
+        segment_time,
         "-f",
         "segment",
-        pattern,
     ];
+    args.extend(options.iter().map(OsStr::new));
+    let pattern = command_arg(pattern);
+    args.push(&pattern);
+    run_ffmpeg(&args)
+}
+
+/// Filter graph that joins the first audio stream of the inputs into a
+/// single stream labelled `a`, after trimming the start of every input by
+/// its duration in `trims`.
+fn concat_filter(trims: &[Duration]) -> String {
+    let mut filter = String::new();
+    let mut inputs = String::new();
+    for (idx, trim) in trims.iter().enumerate() {
+        if trim.is_zero() {
+            inputs.push_str(&format!("[{}:a]", idx));
+        } else {
+            filter.push_str(&format!(
+                "[{idx}:a]atrim=start={:.3},asetpts=PTS-STARTPTS[t{idx}];",
+                trim.as_secs_f64(),
+                idx = idx
+            ));
+            inputs.push_str(&format!("[t{}]", idx));
+        }
+    }
+    format!("{}{}concat=n={}:v=0:a=1[a]", filter, inputs, trims.len())
+}
+
+/// Gets the stem of the interview a segment was split from, by removing
+/// the segment number, e.g. `interview` for `interview-003.mp3`.
+fn interview_stem_of_segment(segment: &Path) -> Result<String> {
+    let stem = segment
+        .file_stem()
+        .unwrap() // unwrap is safe, collect_interviews does not return empty filenames
+        .to_str()
+        .ok_or(Error::EncodingError)?;
+    let interview_stem = stem
+        .rsplit_once('-')
+        .filter(|(_, number)| number.len() >= 3 && number.bytes().all(|b| b.is_ascii_digit()))
+        .map(|(interview_stem, _)| interview_stem)
+        .unwrap_or(stem);
+    Ok(interview_stem.to_string())
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+fn run_ffmpeg(args: &[&OsStr]) -> Result<()> {
+    debug!(
+        target: SPLIT,
+        "running ffmpeg {}",
+        args.iter()
+            .map(|arg| arg.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+    // ffmpeg must not read commands from our stdin, which may be requests
+    // for `serve --stdio`
     let status = Command::new("ffmpeg")
-        .args(&args)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
         .status()
         .map_err(Error::FfmpegIo)?;
     if !status.success() {
@@ -80,24 +670,52 @@ fn split_interview(interview: &Path, output_dir: Option<&Path>) -> Result<()> {
     Ok(())
 }
 
+/// Directory named after the interview for its segments, in the given
+/// output directory, if any.
+fn interview_directory(output_directory: Option<&Path>, interview: &Path) -> PathBuf {
+    let interview_stem = interview.file_stem().unwrap(); // unwrap is safe, collect_interviews does not return empty filenames
+    output_directory
+        .map(|dir| dir.join(interview_stem))
+        .unwrap_or_else(|| PathBuf::from(interview_stem))
+}
+
 /// Output pattern for use with ffmpeg.
 ///
 /// Will use the give output directory, if any, otherwise the pattern will
 /// be for a relative path.
-fn segment_pattern(output_directory: Option<&Path>, interview: &Path) -> Result<PathBuf> {
-    let interview_stem = interview
+fn segment_pattern(output_directory: Option<&Path>, interview: &Path) -> PathBuf {
+    let interview_stem = interview.file_stem().unwrap(); // unwrap is safe, collect_interviews does not return empty filenames
+    segment_pattern_for_stem(output_directory, interview_stem)
+}
+
+/// Pattern for the segments of a single channel of the interview, e.g.
+/// `interview-ch1-%03d.mp3` next to `interview-%03d.mp3`.
+fn channel_pattern(pattern: &Path, interview: &Path, channel: usize) -> PathBuf {
+    let mut stem = interview
         .file_stem()
         .unwrap() // unwrap is safe, collect_interviews does not return empty filenames
-        .to_str()
-        .ok_or_else(|| Error::EncodingError)?;
+        .to_os_string();
+    stem.push(format!("-ch{}", channel));
+    pattern.with_file_name(segment_file_name(&stem))
+}
 
-    let mut pattern = PathBuf::new();
-    if let Some(output_directory) = output_directory {
-        pattern.push(output_directory);
+fn segment_pattern_for_stem(output_directory: Option<&Path>, interview_stem: &OsStr) -> PathBuf {
+    let name = segment_file_name(interview_stem);
+    match output_directory {
+        Some(output_directory) => output_directory.join(name),
+        None => PathBuf::from(name),
     }
-    pattern.push(format!("{}-%03d", interview_stem));
-    pattern.set_extension(SEGMENT_FILE_EXTENSION);
-    Ok(pattern)
+}
+
+/// File name pattern like `interview-%03d.mp3`, also for stems that are
+/// not UTF-8 or contain dots.
+fn segment_file_name(interview_stem: &OsStr) -> OsString {
+    let mut name = interview_stem.to_os_string();
+    name.push(format!(
+        "-{}.{}",
+        SEGMENT_NUMBER_PLACEHOLDER, SEGMENT_FILE_EXTENSION
+    ));
+    name
 }
 
 #[derive(Error, Debug)]
@@ -114,6 +732,67 @@ pub enum Error {
     FfmpegIo(std::io::Error),
     #[error("splitting interviews with ffmpeg failed")]
     FfmpegStatus,
+    #[error("failed to invoke ffprobe to find the audio channels, it comes with ffmpeg, install with your favorite package manager or on Windows download from https://ffmpeg.org/download.html#build-windows and add to your \"Path\" environment variable")]
+    FfprobeIo(std::io::Error),
+    #[error("ffprobe could not find the audio channels of {0}")]
+    FfprobeStatus(PathBuf),
+    #[error("{0} has no audio streams to split by channel")]
+    NoAudioStreams(PathBuf),
+    #[error("could not get the length of segment {0}: {1}")]
+    SegmentDuration(PathBuf, PairError),
+    #[error("ffprobe could not find a creation time in the metadata of {0}")]
+    FfprobeCreationTime(PathBuf),
+    #[error("interviews {0} and {1} would write the same segment files, use --continue-numbering or --force")]
+    SegmentCollision(PathBuf, PathBuf),
+    #[error("segment file {0} exists, use --force to overwrite")]
+    OutputSegmentExists(PathBuf),
+    #[error("no audio segments found")]
+    NoSegments,
+    #[error("new segment {0} would overwrite one of the input segments, use --name or --output-directory")]
+    OverwritesInput(PathBuf),
+    #[error("--cues and --cuts-from-transcript can only be used to split a single interview")]
+    CuesForManyInterviews,
+    #[error("give either --cues or --cuts-from-transcript")]
+    CuesAndTranscript,
+    #[error("failed to load transcript {0} to cut at: {1}")]
+    TranscriptLoadFail(PathBuf, TranscriptError),
+    #[error("failed to read cues from {0}: {1}")]
+    CuesLoadFail(PathBuf, std::io::Error),
+    #[error("failed to parse cues in {0}: {1}")]
+    Cues(PathBuf, CuesError),
+    #[error("could not compare segments to find their overlap: {0}")]
+    Overlap(#[from] DecodeError),
+}
+
+impl Failure for Error {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Error::IO(_) | Error::OutputDirectoryNotFound(_) | Error::CuesLoadFail(_, _) => {
+                ExitCode::IO
+            }
+            Error::CuesForManyInterviews | Error::CuesAndTranscript | Error::NoAudioStreams(_) => {
+                ExitCode::Failure
+            }
+            Error::TranscriptLoadFail(_, err) => err.exit_code(),
+            Error::Cues(_, err) => err.exit_code(),
+            Error::Overlap(err) => err.exit_code(),
+            Error::SegmentDuration(_, err) => err.exit_code(),
+            Error::NoInterviews | Error::NoSegments => ExitCode::NoInputs,
+            Error::EncodingError => ExitCode::Failure,
+            Error::FfmpegIo(err) | Error::FfprobeIo(err)
+                if err.kind() == std::io::ErrorKind::NotFound =>
+            {
+                ExitCode::FfmpegMissing
+            }
+            Error::FfmpegIo(_) | Error::FfprobeIo(_) => ExitCode::IO,
+            Error::FfmpegStatus | Error::FfprobeStatus(_) | Error::FfprobeCreationTime(_) => {
+                ExitCode::FfmpegFailed
+            }
+            Error::SegmentCollision(_, _)
+            | Error::OutputSegmentExists(_)
+            | Error::OverwritesInput(_) => ExitCode::OutputExists,
+        }
+    }
 }
 
 impl Error {
@@ -125,6 +804,7 @@ impl Error {
 #[cfg(test)]
 mod test {
     use super::*;
+    use argh::FromArgs;
 
     #[test]
     fn output_directory_for_interview_with_parent_dir() {
@@ -140,13 +820,14 @@ mod test {
 
     #[test]
     fn output_directory_for_interview_without_parent_dir_but_existing_preferred_dir() {
-        let preferred_output_dir = Some(Path::new("src"));
+        let preferred_output_dir = Path::new("src");
         let interview = Path::new("testdata/interview.mp3");
         assert!(
-            preferred_output_dir.unwrap().is_dir(),
+            preferred_output_dir.is_dir(),
             "Expected for test that \"{:?}\" is an existing directory",
             preferred_output_dir
         );
+        let preferred_output_dir = Some(preferred_output_dir);
         assert_eq!(
             output_directory_or_interview_parent(preferred_output_dir, interview)
                 .unwrap()
@@ -170,17 +851,227 @@ mod test {
         let output_dir = None;
         let interview = Path::new("interview.mp3");
         assert_eq!(
-            segment_pattern(output_dir, interview).unwrap(),
+            segment_pattern(output_dir, interview),
             PathBuf::from("interview-%03d.mp3")
         );
     }
 
+    #[test]
+    fn same_stem_in_same_directory_collides() {
+        let interviews = vec![
+            PathBuf::from("a/interview.mp3"),
+            PathBuf::from("b/interview.wav"),
+        ];
+        let output_dir = Some(Path::new("out"));
+        let patterns: Vec<PathBuf> = interviews
+            .iter()
+            .map(|i| segment_pattern(output_dir, i))
+            .collect();
+        match ensure_no_colliding_patterns(&interviews, &patterns) {
+            Err(Error::SegmentCollision(first, second)) => {
+                assert_eq!(first, interviews[0]);
+                assert_eq!(second, interviews[1]);
+            }
+            other => panic!("expected collision, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn same_stem_in_different_directories_does_not_collide() {
+        let interviews = vec![
+            PathBuf::from("a/interview.mp3"),
+            PathBuf::from("b/interview.mp3"),
+        ];
+        let patterns: Vec<PathBuf> = interviews
+            .iter()
+            .map(|i| segment_pattern(i.parent(), i))
+            .collect();
+        assert!(ensure_no_colliding_patterns(&interviews, &patterns).is_ok());
+    }
+
+    #[test]
+    fn existing_segments_are_found_by_pattern() {
+        let dir = std::env::temp_dir().join("f4tapir-existing-segments");
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in &["interview-000.mp3", "interview-001.mp3", "interview-x.mp3"] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+        let pattern = segment_pattern(Some(&dir), Path::new("interview.wav"));
+
+        let found: Vec<u32> = existing_segments(&pattern)
+            .unwrap()
+            .into_iter()
+            .map(|(number, _)| number)
+            .collect();
+        let next = next_segment_number(&pattern).unwrap();
+        let exists = ensure_no_existing_segments(&pattern);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(found, vec![0, 1]);
+        assert_eq!(next, 2);
+        assert!(matches!(exists, Err(Error::OutputSegmentExists(_))));
+    }
+
+    #[test]
+    fn continued_segments_are_told_apart_from_earlier_ones() {
+        let dir = std::env::temp_dir().join("f4tapir-produced-segments");
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in &[
+            "interview-000.mp3",
+            "interview-001.mp3",
+            "interview-002.mp3",
+        ] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+        let pattern = segment_pattern(Some(&dir), Path::new("interview.wav"));
+
+        let produced = |start_number| {
+            produced_segments(&pattern, start_number)
+                .unwrap()
+                .into_iter()
+                .map(|(number, _)| number)
+                .collect::<Vec<_>>()
+        };
+        let (all, continued) = (produced(0), produced(2));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(all, vec![0, 1, 2]);
+        assert_eq!(continued, vec![2]);
+    }
+
+    #[test]
+    fn no_existing_segments_start_at_zero() {
+        let pattern = segment_pattern(Some(Path::new("testdata")), Path::new("nothing.mp3"));
+        assert_eq!(next_segment_number(&pattern).unwrap(), 0);
+    }
+
+    #[test]
+    fn refused_split_creates_no_interview_directory() {
+        let dir = std::env::temp_dir().join("f4tapir-refused-split");
+        for sub in &["a", "b", "out"] {
+            std::fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        for sub in &["a", "b"] {
+            std::fs::write(dir.join(sub).join("interview.wav"), b"").unwrap();
+        }
+        let args: Vec<String> = vec![
+            "--per-interview-dirs".into(),
+            "-o".into(),
+            dir.join("out").display().to_string(),
+            dir.join("a").join("interview.wav").display().to_string(),
+            dir.join("b").join("interview.wav").display().to_string(),
+        ];
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let opts = Split::from_args(&["split"], &args).unwrap();
+
+        let result = split(opts);
+        let created = dir.join("out").join("interview").exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(result, Err(Error::SegmentCollision(_, _))));
+        assert!(!created);
+    }
+
+    #[test]
+    fn interview_stem_without_segment_number() {
+        assert_eq!(
+            interview_stem_of_segment(Path::new("dir/interview-03-002.mp3")).unwrap(),
+            "interview-03"
+        );
+        assert_eq!(
+            interview_stem_of_segment(Path::new("interview-03.mp3")).unwrap(),
+            "interview-03"
+        );
+    }
+
+    #[test]
+    fn channels_of_a_single_stream_become_tracks() {
+        assert_eq!(parse_stream_channels("2\n"), Some(vec![2]));
+        assert!(matches!(
+            audio_streams(Path::new("video.mp4"), "\n"),
+            Err(Error::NoAudioStreams(_))
+        ));
+        assert_eq!(
+            channel_tracks(&[2]),
+            vec![
+                Track {
+                    map: "0:a:0".into(),
+                    pan: Some("pan=mono|c0=c0".into())
+                },
+                Track {
+                    map: "0:a:0".into(),
+                    pan: Some("pan=mono|c0=c1".into())
+                },
+            ]
+        );
+        assert_eq!(channel_tracks(&[1]), vec![]);
+        assert_eq!(channel_tracks(&[1, 1])[1].map, "0:a:1");
+        assert_eq!(
+            channel_pattern(
+                Path::new("out/interview-%03d.mp3"),
+                Path::new("interview.wav"),
+                2
+            ),
+            PathBuf::from("out/interview-ch2-%03d.mp3")
+        );
+    }
+
+    #[test]
+    fn per_interview_directory_is_named_after_the_interview() {
+        let interview = Path::new("testdata/foo.mp3");
+        let dir = interview_directory(Some(Path::new("out")), interview);
+        assert_eq!(dir, PathBuf::from("out/foo"));
+        assert_eq!(
+            segment_pattern(Some(&dir), interview),
+            PathBuf::from("out/foo/foo-%03d.mp3")
+        );
+    }
+
+    #[test]
+    fn concat_filter_for_three_segments() {
+        assert_eq!(
+            concat_filter(&[Duration::ZERO; 3]),
+            "[0:a][1:a][2:a]concat=n=3:v=0:a=1[a]"
+        );
+        assert_eq!(
+            concat_filter(&[Duration::ZERO, Duration::from_millis(2013)]),
+            "[1:a]atrim=start=2.013,asetpts=PTS-STARTPTS[t1];[0:a][t1]concat=n=2:v=0:a=1[a]"
+        );
+    }
+
+    #[test]
+    fn time_of_day_of_creation_time() {
+        assert_eq!(
+            parse_creation_time("2021-03-04T14:30:05.000000Z\n"),
+            Timestamp::from_tenths((14 * 3600 + 30 * 60 + 5) * 10)
+        );
+        assert_eq!(parse_creation_time("\n"), None);
+    }
+
+    #[test]
+    fn manifest_is_named_after_the_pattern_stem() {
+        let pattern = segment_pattern(Some(Path::new("out")), Path::new("a/b.c.mp4"));
+        assert_eq!(pattern_stem(&pattern), "b.c");
+        assert_eq!(
+            Manifest::path(&state_dir(&pattern), &pattern_stem(&pattern)),
+            Path::new("out/b.c.f4tapir.toml")
+        );
+    }
+
+    #[test]
+    fn pattern_keeps_dots_in_the_stem() {
+        assert_eq!(
+            segment_pattern(None, Path::new("interview.v2.wav")),
+            PathBuf::from("interview.v2-%03d.mp3")
+        );
+    }
+
     #[test]
     fn pattern_for_interview_with_output_dir() {
         let output_dir = Some(Path::new("src"));
         let interview = Path::new("testdata/interview.mp3");
         assert_eq!(
-            segment_pattern(output_dir, interview).unwrap(),
+            segment_pattern(output_dir, interview),
             PathBuf::from("src/interview-%03d.mp3")
         );
     }
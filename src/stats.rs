@@ -0,0 +1,365 @@
+//! Pause profiles for conversation analysis, from the pause markers like
+//! `(...)` or `(3)` in the speech and from the gaps between consecutive
+//! timestamps, for every speaker and for everyone.
+//!
+//! Pauses are counted for the speaker whose utterance contains them, gaps
+//! for the speaker of the speech that ends at the later timestamp. Gaps
+//! never span two transcripts, as every segment starts its timestamps
+//! anew.
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use crate::args::Stats;
+use crate::exit::{ExitCode, Failure};
+use crate::find::collect_transcripts;
+use crate::store::{stored_path, stored_utterances, Error as StoreError};
+use crate::timestamp::Timestamp;
+use crate::transcript::{
+    annotations, AnnotationKind, SpeakerInference, Transcript, UtteranceRecord,
+};
+
+use log::warn;
+use serde_json::{json, Value};
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Upper bounds in tenths of a second and labels of the ranges that pause
+/// lengths are counted in.
+const PAUSE_RANGES: &[(u64, &str)] = &[(10, "<1s"), (30, "1-3s"), (u64::MAX, ">=3s")];
+/// Upper bounds and labels of the ranges of gaps between timestamps.
+const GAP_RANGES: &[(u64, &str)] = &[
+    (50, "<5s"),
+    (150, "5-15s"),
+    (600, "15-60s"),
+    (u64::MAX, ">=60s"),
+];
+
+pub fn stats(opts: Stats) -> Result<()> {
+    let mut stored = match &opts.store {
+        Some(_) if opts.infer_speakers.is_some() => return Err(Error::StoreInference),
+        Some(store) => stored_utterances(store)?,
+        None => BTreeMap::new(),
+    };
+    let paths = if opts.store.is_some() && opts.input_segments.is_empty() {
+        stored.keys().cloned().collect()
+    } else {
+        collect_transcripts(opts.input_segments, opts.recursive)?
+    };
+    if paths.is_empty() {
+        return Err(Error::NoTranscripts);
+    }
+    let mut transcripts = vec![];
+    for path in paths {
+        if let Some(records) = stored.remove(&stored_path(&path)) {
+            transcripts.push(records);
+            continue;
+        }
+        match Transcript::from_file(&path) {
+            Ok(transcript) => transcripts.push(
+                transcript
+                    .utterance_records()
+                    .infer_speakers(opts.infer_speakers)
+                    .collect(),
+            ),
+            Err(err) => warn!(
+                "failed to load transcript {}, skipping, cause: {}",
+                path.display(),
+                err
+            ),
+        }
+    }
+    let stats = PauseStats::of_records(transcripts);
+    let mut stdout = std::io::stdout().lock();
+    if opts.json {
+        serde_json::to_writer_pretty(&mut stdout, &stats.to_json())
+            .map_err(std::io::Error::from)?;
+        writeln!(&mut stdout)?;
+    } else {
+        stats.write_text(&mut stdout)?;
+    }
+    Ok(())
+}
+
+/// Lengths of pauses or gaps, counted in ranges.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Distribution {
+    pub count: usize,
+    /// Pauses like `(...)` whose length is not given.
+    pub untimed: usize,
+    /// Sum of the known lengths.
+    pub total: Timestamp,
+    pub longest: Timestamp,
+    /// Number of known lengths in each of the ranges.
+    pub ranges: Vec<usize>,
+    bounds: &'static [(u64, &'static str)],
+}
+
+impl Distribution {
+    fn new(bounds: &'static [(u64, &'static str)]) -> Distribution {
+        Distribution {
+            count: 0,
+            untimed: 0,
+            total: Timestamp::zero(),
+            longest: Timestamp::zero(),
+            ranges: vec![0; bounds.len()],
+            bounds,
+        }
+    }
+
+    fn add(&mut self, length: Option<Timestamp>) {
+        self.count += 1;
+        let length = match length {
+            Some(length) => length,
+            None => {
+                self.untimed += 1;
+                return;
+            }
+        };
+        self.total = Timestamp::from_tenths(self.total.tenths() + length.tenths())
+            .unwrap_or_else(Timestamp::max_value);
+        if length.tenths() > self.longest.tenths() {
+            self.longest = length;
+        }
+        if let Some(range) = self
+            .bounds
+            .iter()
+            .position(|&(bound, _)| length.tenths() < bound)
+        {
+            self.ranges[range] += 1;
+        }
+    }
+
+    /// Mean of the known lengths.
+    pub fn mean(&self) -> Timestamp {
+        let timed = (self.count - self.untimed) as u64;
+        Timestamp::from_tenths(self.total.tenths() / timed.max(1)).unwrap_or_default()
+    }
+
+    fn to_json(&self) -> Value {
+        let ranges: serde_json::Map<String, Value> = self
+            .bounds
+            .iter()
+            .zip(&self.ranges)
+            .map(|(&(_, label), &count)| (label.to_string(), json!(count)))
+            .collect();
+        json!({
+            "count": self.count,
+            "untimed": self.untimed,
+            "total_seconds": self.total.tenths() as f64 / 10.0,
+            "mean_seconds": self.mean().tenths() as f64 / 10.0,
+            "longest_seconds": self.longest.tenths() as f64 / 10.0,
+            "ranges": ranges,
+        })
+    }
+}
+
+/// Pauses and gaps of one speaker, or of everyone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpeakerPauses {
+    pub speaker: String,
+    pub pauses: Distribution,
+    pub gaps: Distribution,
+}
+
+impl SpeakerPauses {
+    fn new(speaker: &str) -> SpeakerPauses {
+        SpeakerPauses {
+            speaker: speaker.to_string(),
+            pauses: Distribution::new(PAUSE_RANGES),
+            gaps: Distribution::new(GAP_RANGES),
+        }
+    }
+}
+
+/// Pause profiles of the speakers of transcripts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PauseStats {
+    /// The speakers in the order they first speak.
+    pub speakers: Vec<SpeakerPauses>,
+    pub all: SpeakerPauses,
+}
+
+impl PauseStats {
+    pub fn of(transcripts: &[Transcript], inference: Option<SpeakerInference>) -> PauseStats {
+        PauseStats::of_records(transcripts.iter().map(|transcript| {
+            transcript
+                .utterance_records()
+                .infer_speakers(inference)
+                .collect()
+        }))
+    }
+
+    /// The pause profiles of transcripts given by their utterances, e.g.
+    /// from a [`crate::store`].
+    pub fn of_records<I>(transcripts: I) -> PauseStats
+    where
+        I: IntoIterator<Item = Vec<UtteranceRecord>>,
+    {
+        let mut stats = PauseStats {
+            speakers: vec![],
+            all: SpeakerPauses::new("all"),
+        };
+        for records in transcripts {
+            let mut previous: Option<Timestamp> = None;
+            for record in records {
+                let idx = match stats
+                    .speakers
+                    .iter()
+                    .position(|known| known.speaker == record.speaker)
+                {
+                    Some(idx) => idx,
+                    None => {
+                        stats.speakers.push(SpeakerPauses::new(&record.speaker));
+                        stats.speakers.len() - 1
+                    }
+                };
+                for annotation in annotations(&record.speech) {
+                    if let AnnotationKind::Pause(length) = annotation.kind {
+                        stats.speakers[idx].pauses.add(length);
+                        stats.all.pauses.add(length);
+                    }
+                }
+                for &timestamp in &record.timestamps {
+                    let previous_before =
+                        previous.filter(|previous| previous.tenths() <= timestamp.tenths());
+                    if let Some(previous) = previous_before {
+                        let gap = Timestamp::from_tenths(timestamp.tenths() - previous.tenths());
+                        stats.speakers[idx].gaps.add(gap);
+                        stats.all.gaps.add(gap);
+                    }
+                    previous = Some(timestamp);
+                }
+            }
+        }
+        stats
+    }
+
+    fn rows(&self) -> impl Iterator<Item = &SpeakerPauses> {
+        self.speakers.iter().chain(std::iter::once(&self.all))
+    }
+
+    fn write_text<W: Write>(&self, mut to: W) -> std::io::Result<()> {
+        let labels = |bounds: &[(u64, &str)]| {
+            let labels: Vec<String> = bounds
+                .iter()
+                .map(|(_, label)| format!("{:>7}", label))
+                .collect();
+            labels.join("")
+        };
+        let counts = |ranges: &[usize]| {
+            let counts: Vec<String> = ranges.iter().map(|count| format!("{:>7}", count)).collect();
+            counts.join("")
+        };
+        writeln!(&mut to, "pause markers")?;
+        writeln!(
+            &mut to,
+            "{:<10}{:>7}{:>9}  {:<14}{}",
+            "speaker",
+            "count",
+            "untimed",
+            "total",
+            labels(PAUSE_RANGES)
+        )?;
+        for row in self.rows() {
+            writeln!(
+                &mut to,
+                "{:<10}{:>7}{:>9}  {:<14}{}",
+                row.speaker,
+                row.pauses.count,
+                row.pauses.untimed,
+                row.pauses.total.to_string(),
+                counts(&row.pauses.ranges)
+            )?;
+        }
+        writeln!(&mut to)?;
+        writeln!(&mut to, "gaps between timestamps")?;
+        writeln!(
+            &mut to,
+            "{:<10}{:>7}  {:<14}{:<14}{:<14}{}",
+            "speaker",
+            "count",
+            "total",
+            "mean",
+            "longest",
+            labels(GAP_RANGES)
+        )?;
+        for row in self.rows() {
+            writeln!(
+                &mut to,
+                "{:<10}{:>7}  {:<14}{:<14}{:<14}{}",
+                row.speaker,
+                row.gaps.count,
+                row.gaps.total.to_string(),
+                row.gaps.mean().to_string(),
+                row.gaps.longest.to_string(),
+                counts(&row.gaps.ranges)
+            )?;
+        }
+        Ok(())
+    }
+
+    fn to_json(&self) -> Value {
+        let profile = |row: &SpeakerPauses| {
+            json!({
+                "speaker": row.speaker,
+                "pauses": row.pauses.to_json(),
+                "gaps": row.gaps.to_json(),
+            })
+        };
+        json!({
+            "speakers": self.speakers.iter().map(profile).collect::<Vec<_>>(),
+            "all": profile(&self.all),
+        })
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("no transcripts found")]
+    NoTranscripts,
+    #[error("--infer-speakers cannot be used with --store, which keeps only labelled utterances")]
+    StoreInference,
+    #[error("failed to read the project store: {0}")]
+    Store(#[from] StoreError),
+}
+
+impl Failure for Error {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Error::IO(_) => ExitCode::IO,
+            Error::NoTranscripts => ExitCode::NoInputs,
+            Error::StoreInference => ExitCode::Failure,
+            Error::Store(err) => err.exit_code(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::transcript::json::Document;
+
+    #[test]
+    fn pauses_and_gaps_are_counted_per_speaker() {
+        let document = Document::from_text(
+            "I: Und (...) dann? #00:00:04-0#\n\n\
+             B: Ja (2) also (5 Sek.) gut. #00:00:10-0# Na. #00:01:30-0#\n",
+        );
+        let transcript = document.to_transcript().unwrap();
+        let stats = PauseStats::of(&[transcript], None);
+
+        let speakers: Vec<&str> = stats.rows().map(|row| row.speaker.as_str()).collect();
+        assert_eq!(speakers, vec!["I", "B", "all"]);
+        let b = &stats.speakers[1];
+        assert_eq!((b.pauses.count, b.pauses.untimed), (2, 0));
+        assert_eq!(b.pauses.total.tenths(), 70);
+        assert_eq!(b.pauses.ranges, vec![0, 1, 1]);
+        assert_eq!(b.gaps.ranges, vec![0, 1, 0, 1]);
+        assert_eq!(b.gaps.longest.tenths(), 800);
+        assert_eq!(stats.all.pauses.untimed, 1);
+        assert_eq!(stats.all.gaps.count, 2);
+    }
+}
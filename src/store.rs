@@ -0,0 +1,545 @@
+//! SQLite database of a study, where transcripts are imported once, so
+//! that `stats` and `report` on large studies do not parse every RTF file
+//! again, and where merges are recorded.
+//!
+//! Imported transcripts are kept with their speaker list, utterances and
+//! timestamps, and what `report` needs to know about them. Importing a
+//! transcript again replaces it. `import` only parses transcripts whose
+//! files changed since they were imported, by the hash of their content.
+//!
+//! Needs the `sqlite` feature, without it the functions fail with
+//! [`Error::Unsupported`].
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::exit::{ExitCode, Failure};
+#[cfg(feature = "sqlite")]
+use crate::incremental::fingerprint;
+#[cfg(feature = "sqlite")]
+use crate::timestamp::Timestamp;
+#[cfg(feature = "sqlite")]
+use crate::transcript::Transcript;
+use crate::transcript::{Error as TranscriptError, SegmentShift, UtteranceRecord};
+
+#[cfg(feature = "sqlite")]
+use log::{debug, warn};
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Changes to the schema in order, the version of the schema in
+/// `PRAGMA user_version` is the number of them that were applied.
+#[cfg(feature = "sqlite")]
+const MIGRATIONS: &[&str] = &[
+    SCHEMA,
+    // FNV-1a hash of the file in hex, see crate::incremental::fingerprint
+    "ALTER TABLE transcripts ADD COLUMN hash TEXT;",
+];
+
+#[cfg(feature = "sqlite")]
+const SCHEMA: &str = "
+    CREATE TABLE transcripts (
+        id INTEGER PRIMARY KEY,
+        path TEXT NOT NULL UNIQUE,
+        -- seconds since the Unix epoch
+        imported_at INTEGER NOT NULL,
+        -- tenths of a second, see Transcript::interview_end_time
+        end_time INTEGER NOT NULL,
+        unparsed_lines INTEGER NOT NULL,
+        -- quality score from 0 to 100, see check
+        score INTEGER NOT NULL
+    );
+    CREATE TABLE speakers (
+        transcript INTEGER NOT NULL REFERENCES transcripts(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        code TEXT NOT NULL,
+        color TEXT
+    );
+    CREATE TABLE utterances (
+        id INTEGER PRIMARY KEY,
+        transcript INTEGER NOT NULL REFERENCES transcripts(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        line INTEGER NOT NULL,
+        byte_start INTEGER NOT NULL,
+        byte_end INTEGER NOT NULL,
+        speaker TEXT NOT NULL,
+        speech TEXT NOT NULL
+    );
+    CREATE TABLE timestamps (
+        utterance INTEGER NOT NULL REFERENCES utterances(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        tenths INTEGER NOT NULL
+    );
+    CREATE TABLE merges (
+        id INTEGER PRIMARY KEY,
+        output TEXT NOT NULL,
+        merged_at INTEGER NOT NULL
+    );
+    CREATE TABLE merged_segments (
+        merge INTEGER NOT NULL REFERENCES merges(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        path TEXT NOT NULL,
+        shift INTEGER NOT NULL,
+        end_time INTEGER NOT NULL
+    );
+    CREATE INDEX utterances_by_transcript ON utterances (transcript, position);
+    CREATE INDEX timestamps_by_utterance ON timestamps (utterance, position);
+";
+
+/// What is known about an imported transcript without its utterances.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredTranscript {
+    pub path: PathBuf,
+    pub end_time: crate::timestamp::Timestamp,
+    pub unparsed_lines: usize,
+    pub score: u8,
+}
+
+/// An open database.
+#[cfg(feature = "sqlite")]
+pub struct Store {
+    db: rusqlite::Connection,
+}
+
+#[cfg(feature = "sqlite")]
+impl Store {
+    /// Opens the database, creating it if it does not exist yet and
+    /// updating its schema if it is older.
+    pub fn open(path: &Path) -> Result<Store> {
+        let db = rusqlite::Connection::open(path)?;
+        db.pragma_update(None, "foreign_keys", true)?;
+        let version: usize = db.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version > MIGRATIONS.len() {
+            return Err(Error::Version(path.to_path_buf(), version));
+        }
+        for (applied, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            let tx = db.unchecked_transaction()?;
+            tx.execute_batch(migration)?;
+            tx.pragma_update(None, "user_version", applied + 1)?;
+            tx.commit()?;
+        }
+        Ok(Store { db })
+    }
+
+    /// Imports the transcript from the given path, replacing an earlier
+    /// import of it, under its [`stored_path`].
+    pub fn import(&mut self, path: &Path, transcript: &Transcript) -> Result<()> {
+        let hash = fingerprint(path, "").ok().map(hex);
+        self.import_hashed(path, transcript, hash)
+    }
+
+    /// Imports the transcript with the hash of its file.
+    fn import_hashed(
+        &mut self,
+        path: &Path,
+        transcript: &Transcript,
+        hash: Option<String>,
+    ) -> Result<()> {
+        use crate::check::Quality;
+        use crate::transcript::json::Document;
+        use rusqlite::params;
+
+        let quality = Quality::of_transcript(path, transcript);
+        let tx = self.db.transaction()?;
+        let path_text = stored_path(path).display().to_string();
+        tx.execute(
+            "DELETE FROM transcripts WHERE path = ?1",
+            params![path_text],
+        )?;
+        tx.execute(
+            "INSERT INTO transcripts (path, imported_at, end_time, unparsed_lines, score, hash)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                path_text,
+                now(),
+                transcript.interview_end_time().tenths(),
+                transcript.unparsed_lines().len(),
+                quality.score,
+                hash
+            ],
+        )?;
+        let id = tx.last_insert_rowid();
+        {
+            let mut insert = tx.prepare(
+                "INSERT INTO speakers (transcript, position, code, color) VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            let speakers = Document::of(transcript).metadata.speakers;
+            for (position, speaker) in speakers.iter().enumerate() {
+                insert.execute(params![id, position, speaker.code, speaker.color])?;
+            }
+            let mut insert_utterance = tx.prepare(
+                "INSERT INTO utterances (transcript, position, line, byte_start, byte_end, speaker, speech)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?;
+            let mut insert_timestamp = tx.prepare(
+                "INSERT INTO timestamps (utterance, position, tenths) VALUES (?1, ?2, ?3)",
+            )?;
+            for (position, record) in transcript.utterance_records().enumerate() {
+                insert_utterance.execute(params![
+                    id,
+                    position,
+                    record.line_index,
+                    record.byte_range.start,
+                    record.byte_range.end,
+                    record.speaker,
+                    record.speech
+                ])?;
+                let utterance = tx.last_insert_rowid();
+                for (position, timestamp) in record.timestamps.iter().enumerate() {
+                    insert_timestamp.execute(params![utterance, position, timestamp.tenths()])?;
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Records a merge of the transcripts at the given paths.
+    pub fn record_merge(
+        &mut self,
+        output: &str,
+        paths: &[PathBuf],
+        shifts: &[SegmentShift],
+    ) -> Result<()> {
+        use rusqlite::params;
+
+        let tx = self.db.transaction()?;
+        tx.execute(
+            "INSERT INTO merges (output, merged_at) VALUES (?1, ?2)",
+            params![output, now()],
+        )?;
+        let id = tx.last_insert_rowid();
+        {
+            let mut insert = tx.prepare(
+                "INSERT INTO merged_segments (merge, position, path, shift, end_time)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            for (position, (path, shift)) in paths.iter().zip(shifts).enumerate() {
+                insert.execute(params![
+                    id,
+                    position,
+                    path.display().to_string(),
+                    shift.shift.tenths(),
+                    shift.end_time.tenths()
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// The hashes of the files of the imported transcripts by their paths,
+    /// `None` if the file could not be read when it was imported.
+    pub fn hashes(&self) -> Result<BTreeMap<PathBuf, Option<String>>> {
+        let mut select = self.db.prepare("SELECT path, hash FROM transcripts")?;
+        let rows = select.query_map([], |row| {
+            Ok((PathBuf::from(row.get::<_, String>(0)?), row.get(1)?))
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Removes the transcript stored under the given path.
+    pub fn remove(&mut self, stored: &Path) -> Result<()> {
+        self.db.execute(
+            "DELETE FROM transcripts WHERE path = ?1",
+            [stored.display().to_string()],
+        )?;
+        Ok(())
+    }
+
+    /// The imported transcripts by their paths.
+    pub fn transcripts(&self) -> Result<BTreeMap<PathBuf, StoredTranscript>> {
+        let mut select = self.db.prepare(
+            "SELECT path, end_time, unparsed_lines, score FROM transcripts ORDER BY path",
+        )?;
+        let rows = select.query_map([], |row| {
+            Ok(StoredTranscript {
+                path: PathBuf::from(row.get::<_, String>(0)?),
+                end_time: tenths(row.get(1)?),
+                unparsed_lines: row.get(2)?,
+                score: row.get(3)?,
+            })
+        })?;
+        rows.map(|row| Ok(row.map(|stored| (stored.path.clone(), stored))?))
+            .collect()
+    }
+
+    /// The utterances of every imported transcript by its path, like
+    /// [`Transcript::utterance_records`].
+    pub fn utterance_records(&self) -> Result<BTreeMap<PathBuf, Vec<UtteranceRecord>>> {
+        let mut select = self.db.prepare(
+            "SELECT t.path, u.id, u.line, u.byte_start, u.byte_end, u.speaker, u.speech
+             FROM utterances u JOIN transcripts t ON t.id = u.transcript
+             ORDER BY t.path, u.position",
+        )?;
+        let mut select_timestamps = self
+            .db
+            .prepare("SELECT tenths FROM timestamps WHERE utterance = ?1 ORDER BY position")?;
+        let mut transcripts: BTreeMap<PathBuf, Vec<UtteranceRecord>> = BTreeMap::new();
+        let mut rows = select.query([])?;
+        while let Some(row) = rows.next()? {
+            let path = PathBuf::from(row.get::<_, String>(0)?);
+            let timestamps = select_timestamps
+                .query_map([row.get::<_, i64>(1)?], |row| Ok(tenths(row.get(0)?)))?
+                .collect::<rusqlite::Result<_>>()?;
+            let record = UtteranceRecord {
+                speaker: row.get(5)?,
+                speech: row.get(6)?,
+                timestamps,
+                line_index: row.get(2)?,
+                byte_range: row.get(3)?..row.get(4)?,
+                inferred: false,
+            };
+            transcripts.entry(path).or_default().push(record);
+        }
+        Ok(transcripts)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn hex(hash: u64) -> String {
+    format!("{:016x}", hash)
+}
+
+#[cfg(feature = "sqlite")]
+fn now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs() as i64)
+}
+
+#[cfg(feature = "sqlite")]
+fn tenths(tenths: u64) -> Timestamp {
+    Timestamp::from_tenths(tenths).unwrap_or_else(Timestamp::max_value)
+}
+
+/// Records a merge in the database at the given path and imports the
+/// merged transcripts, leaving out placeholders of missing segments.
+#[cfg(feature = "sqlite")]
+pub fn record_merge(
+    store: &Path,
+    output: &str,
+    paths: &[PathBuf],
+    shifts: &[SegmentShift],
+) -> Result<()> {
+    let mut store = Store::open(store)?;
+    store.record_merge(output, paths, shifts)?;
+    for path in paths.iter().filter(|path| path.is_file()) {
+        let transcript = Transcript::from_file(path)
+            .map_err(|err| Error::TranscriptLoadFail(path.clone(), err))?;
+        store.import(path, &transcript)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "sqlite"))]
+pub fn record_merge(
+    store: &Path,
+    _output: &str,
+    _paths: &[PathBuf],
+    _shifts: &[SegmentShift],
+) -> Result<()> {
+    Err(Error::Unsupported(store.to_path_buf()))
+}
+
+/// What [`import_transcripts`] did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Imported {
+    pub added: usize,
+    pub changed: usize,
+    pub unchanged: usize,
+    /// Transcripts whose files are gone.
+    pub removed: usize,
+    /// Transcripts that failed to load and were left as they were.
+    pub failed: usize,
+}
+
+impl fmt::Display for Imported {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} new, {} changed, {} unchanged, {} removed",
+            self.added, self.changed, self.unchanged, self.removed
+        )?;
+        if self.failed > 0 {
+            write!(f, ", {} failed to load", self.failed)?;
+        }
+        Ok(())
+    }
+}
+
+/// Imports the transcripts at the given paths into the database at the
+/// given path, parsing only those that are new or whose files changed
+/// since they were imported, and removes transcripts whose files are
+/// gone.
+///
+/// Transcripts that fail to load are skipped with a warning, and ones in
+/// archives are always parsed again.
+#[cfg(feature = "sqlite")]
+pub fn import_transcripts(store: &Path, paths: &[PathBuf]) -> Result<Imported> {
+    let mut store = Store::open(store)?;
+    let mut hashes = store.hashes()?;
+    let mut imported = Imported::default();
+    for path in paths {
+        let stored = stored_path(path);
+        // transcripts in archives have no file of their own to hash
+        let hash = fingerprint(path, "").ok().map(hex);
+        let known = hashes.remove(&stored);
+        if hash.is_some() && known.as_ref() == Some(&hash) {
+            imported.unchanged += 1;
+            continue;
+        }
+        let transcript = match Transcript::from_file(path) {
+            Ok(transcript) => transcript,
+            Err(err) => {
+                warn!(
+                    "failed to load transcript {}, skipping, cause: {}",
+                    path.display(),
+                    err
+                );
+                imported.failed += 1;
+                continue;
+            }
+        };
+        debug!("importing {}", path.display());
+        store.import_hashed(path, &transcript, hash)?;
+        match known {
+            Some(_) => imported.changed += 1,
+            None => imported.added += 1,
+        }
+    }
+    for gone in hashes.keys().filter(|stored| !stored.exists()) {
+        store.remove(gone)?;
+        imported.removed += 1;
+    }
+    Ok(imported)
+}
+
+#[cfg(not(feature = "sqlite"))]
+pub fn import_transcripts(store: &Path, _paths: &[PathBuf]) -> Result<Imported> {
+    Err(Error::Unsupported(store.to_path_buf()))
+}
+
+/// The transcripts imported into the database at the given path, by
+/// their [`stored_path`].
+#[cfg(feature = "sqlite")]
+pub fn stored_transcripts(store: &Path) -> Result<BTreeMap<PathBuf, StoredTranscript>> {
+    Store::open(store)?.transcripts()
+}
+
+#[cfg(not(feature = "sqlite"))]
+pub fn stored_transcripts(store: &Path) -> Result<BTreeMap<PathBuf, StoredTranscript>> {
+    Err(Error::Unsupported(store.to_path_buf()))
+}
+
+/// The utterances of the transcripts imported into the database at the
+/// given path, by the [`stored_path`] of their transcript.
+#[cfg(feature = "sqlite")]
+pub fn stored_utterances(store: &Path) -> Result<BTreeMap<PathBuf, Vec<UtteranceRecord>>> {
+    Store::open(store)?.utterance_records()
+}
+
+#[cfg(not(feature = "sqlite"))]
+pub fn stored_utterances(store: &Path) -> Result<BTreeMap<PathBuf, Vec<UtteranceRecord>>> {
+    Err(Error::Unsupported(store.to_path_buf()))
+}
+
+/// The path a transcript is stored under, which is the canonical one if
+/// it exists, so that it is found no matter how it is given.
+pub fn stored_path(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("cannot use the database {0}, f4tapir was built without the sqlite feature")]
+    Unsupported(PathBuf),
+    #[error("the database {0} was written by a newer f4tapir, schema version {1}")]
+    Version(PathBuf, usize),
+    #[error("failed to load transcript {0}: {1}")]
+    TranscriptLoadFail(PathBuf, TranscriptError),
+    #[cfg(feature = "sqlite")]
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+}
+
+impl Failure for Error {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Error::Unsupported(_) | Error::Version(_, _) => ExitCode::Failure,
+            Error::TranscriptLoadFail(_, err) => err.exit_code(),
+            #[cfg(feature = "sqlite")]
+            Error::Sqlite(_) => ExitCode::IO,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn imported_transcripts_can_be_read_back() {
+        let path = std::env::temp_dir().join("f4tapir-store.sqlite");
+        let _ = std::fs::remove_file(&path);
+        let segment = PathBuf::from("testdata/interview-01.rtf");
+        let transcript = Transcript::from_file(&segment).unwrap();
+        let mut store = Store::open(&path).unwrap();
+        store.import(&segment, &transcript).unwrap();
+        // importing again replaces it
+        store.import(&segment, &transcript).unwrap();
+        store
+            .record_merge("merged.rtf", std::slice::from_ref(&segment), &[])
+            .unwrap();
+
+        let store = Store::open(&path).unwrap();
+        let transcripts = store.transcripts().unwrap();
+        assert_eq!(transcripts.len(), 1);
+        let stored = &transcripts[&stored_path(&segment)];
+        assert_eq!(stored.end_time, transcript.interview_end_time());
+        assert_eq!(stored.score, 100);
+        let records: Vec<_> = transcript.utterance_records().collect();
+        assert_eq!(
+            store.utterance_records().unwrap()[&stored_path(&segment)],
+            records
+        );
+    }
+
+    #[test]
+    fn only_new_and_changed_transcripts_are_imported_again() {
+        let dir = std::env::temp_dir().join("f4tapir-store-import");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let store = dir.join("study.db");
+        let paths = vec![dir.join("iv-000.rtf"), dir.join("iv-001.rtf")];
+        std::fs::copy("testdata/interview-01.rtf", &paths[0]).unwrap();
+        std::fs::copy("testdata/interview-02.rtf", &paths[1]).unwrap();
+
+        let imported = import_transcripts(&store, &paths).unwrap();
+        assert_eq!((imported.added, imported.unchanged), (2, 0));
+        let imported = import_transcripts(&store, &paths).unwrap();
+        assert_eq!((imported.added, imported.unchanged), (0, 2));
+
+        std::fs::copy("testdata/interview-02.rtf", &paths[0]).unwrap();
+        std::fs::remove_file(&paths[1]).unwrap();
+        let imported = import_transcripts(&store, &paths[..1]).unwrap();
+        assert_eq!(
+            imported,
+            Imported {
+                changed: 1,
+                removed: 1,
+                ..Imported::default()
+            }
+        );
+        assert_eq!(
+            imported.to_string(),
+            "0 new, 1 changed, 0 unchanged, 1 removed"
+        );
+        let stored = stored_transcripts(&store).unwrap();
+        assert_eq!(
+            stored.keys().collect::<Vec<_>>(),
+            vec![&stored_path(&paths[0])]
+        );
+    }
+}
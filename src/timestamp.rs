@@ -2,15 +2,29 @@ use std::fmt::{self, Display, Formatter};
 use std::fs::File;
 use std::io::{Read, Result as IOResult, Write};
 use std::iter::Peekable;
+use std::ops::RangeInclusive;
 use std::path::Path;
 
+use memchr::{memchr, memrchr};
 use thiserror::Error;
 
-const F4_MAX_TIMESTAMP_LEN: usize = "#00:00:00-0#".len();
+const F4_MAX_TIMESTAMP_LEN: usize = "#0000:00:00-0#".len();
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Timestamp(u32);
 
+/// How strictly timestamps are recognized.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum TimestampSyntax {
+    /// Only timestamps like `#00:01:23-4#`, the way f4 writes them
+    /// by default.
+    #[default]
+    Strict,
+    /// Also dots instead of colons and dots or commas before the
+    /// subseconds, e.g. `#00.01.23-4#` or `#00:01:23,4#`.
+    Lenient,
+}
+
 impl Timestamp {
     const HOURS_LEN_MASK: u32 = 0b1100_0000_0000_0000_0000_0000_0000_0000;
     const HOURS_VAL_MASK: u32 = 0b0011_1111_1111_1100_0000_0000_0000_0000;
@@ -45,10 +59,24 @@ impl Timestamp {
     const SUBSECS_VAL_MAX: u32 = 9;
 
     fn new(hours: u32, minutes: u32, seconds: u32, subsecs: u32) -> Timestamp {
-        let hours_len = if hours < 100 { 2 } else { 3 };
+        let hours_len = match hours {
+            0..=99 => 2,
+            100..=999 => 3,
+            _ => 4,
+        };
         Self::new_with_input_len(hours, hours_len, minutes, 2, seconds, 2, subsecs, 1)
     }
 
+    /// The biggest timestamp that can be represented, `#4095:59:59-9#`.
+    pub fn max_value() -> Timestamp {
+        Self::new(
+            Self::HOURS_VAL_MAX,
+            Self::MINUTES_VAL_MAX,
+            Self::SECONDS_VAL_MAX,
+            Self::SUBSECS_VAL_MAX,
+        )
+    }
+
     pub fn zero() -> Timestamp {
         Self::new(0, 0, 0, 0)
     }
@@ -101,11 +129,127 @@ impl Timestamp {
     }
 
     pub fn extract_timestamps(buf: &[u8]) -> Vec<(usize, Timestamp)> {
-        buf.as_ref()
-            .windows(F4_MAX_TIMESTAMP_LEN)
-            .enumerate()
-            .filter_map(|(offset, window)| Timestamp::parse(window).map(|t| (offset, t)).ok())
-            .collect()
+        Self::extract_timestamps_with(buf, TimestampSyntax::Strict)
+    }
+
+    pub fn extract_timestamps_with(buf: &[u8], syntax: TimestampSyntax) -> Vec<(usize, Timestamp)> {
+        Self::timestamps_in_with(buf, syntax).collect()
+    }
+
+    /// Iterates over the timestamps in the text and their byte offsets,
+    /// without allocating.
+    pub fn timestamps_in(text: &str) -> TimestampsIn<'_> {
+        Self::timestamps_in_with(text.as_bytes(), TimestampSyntax::Strict)
+    }
+
+    pub fn timestamps_in_with(buf: &[u8], syntax: TimestampSyntax) -> TimestampsIn<'_> {
+        Self::timestamps_in_dialect(buf, syntax.dialect())
+    }
+
+    /// Iterates over the timestamps of the dialect in the buffer and their
+    /// byte offsets.
+    pub fn timestamps_in_dialect<'a>(
+        buf: &'a [u8],
+        dialect: &'a dyn TimestampDialect,
+    ) -> TimestampsIn<'a> {
+        TimestampsIn {
+            buf,
+            front: 0,
+            back: buf.len(),
+            dialect,
+        }
+    }
+
+    /// Rewrites timestamps that are only recognized with lenient syntax,
+    /// e.g. `#00.01.23,4#`, into the canonical form `#00:01:23-4#`.
+    ///
+    /// Timestamps that are already strict stay exactly as they are.
+    pub fn canonicalize_timestamps(content: &str) -> String {
+        Self::convert_timestamps(content, TimestampSyntax::Lenient.dialect())
+    }
+
+    /// Rewrites the timestamps of the dialect, e.g. `[00:01:23]` of
+    /// easytranscript, into F4 timestamps like `#00:01:23-0#`, which is
+    /// what the rest of f4tapir works with.
+    ///
+    /// Timestamps that are already F4 timestamps stay exactly as they are.
+    pub fn convert_timestamps(content: &str, from: &dyn TimestampDialect) -> String {
+        let bytes = content.as_bytes();
+        let mut converted = String::with_capacity(content.len());
+        let mut last_offset = 0;
+        let mut offset = 0;
+        while let Some(found) = memchr(from.opening(), &bytes[offset..]) {
+            let start = offset + found;
+            offset = start + 1;
+            let end = bytes.len().min(start + from.max_len());
+            let (timestamp, len) = match from.parse(&bytes[start..end]) {
+                Some(parsed) => parsed,
+                None => continue,
+            };
+            if Self::is_timestamp(&bytes[start..start + len]) {
+                continue;
+            }
+            converted.push_str(&content[last_offset..start]);
+            converted.push_str(&timestamp.to_string());
+            last_offset = start + len;
+            offset = last_offset;
+        }
+        converted.push_str(&content[last_offset..]);
+        converted
+    }
+
+    /// Rewrites clock times like `14:32:05` that some transcribers note
+    /// instead of timestamps into timestamps relative to the time of day
+    /// the recording started at, e.g. `#00:02:05-0#` for a start at
+    /// `14:30:00`. Clock times before the start are taken to be on the
+    /// next day.
+    ///
+    /// Only times with two-digit minutes and seconds that are not part of
+    /// a timestamp or a longer number are rewritten.
+    pub fn rebase_clock_times(content: &str, start: Timestamp) -> String {
+        const DAY_TENTHS: u64 = 24 * 60 * 60 * 10;
+        let bytes = content.as_bytes();
+        let is_part = |byte: u8| byte.is_ascii_digit() || b":#.,-".contains(&byte);
+        // a period ending a sentence is not a fraction of a second
+        let ends_in_part = |after: &[u8]| match after {
+            [b'.' | b',', digit, ..] => digit.is_ascii_digit(),
+            [b'.' | b',', ..] | [] => false,
+            [byte, ..] => is_part(*byte),
+        };
+        let mut rebased = String::with_capacity(content.len());
+        let mut last_offset = 0;
+        let mut offset = 0;
+        while let Some(found) = memchr(b':', &bytes[offset..]) {
+            let colon = offset + found;
+            offset = colon + 1;
+            let hours_len = bytes[..colon]
+                .iter()
+                .rev()
+                .take(3)
+                .take_while(|byte| byte.is_ascii_digit())
+                .count();
+            let start_offset = colon - hours_len;
+            let end = colon + ":mm:ss".len();
+            let standalone = (1..=2).contains(&hours_len)
+                && start_offset >= last_offset
+                && (start_offset == 0 || !is_part(bytes[start_offset - 1]))
+                && bytes.get(end..).is_some_and(|after| !ends_in_part(after));
+            let time = bytes
+                .get(start_offset..end)
+                .filter(|_| standalone)
+                .and_then(clock_time_tenths);
+            let tenths = match time {
+                Some(tenths) if tenths < DAY_TENTHS => tenths,
+                _ => continue,
+            };
+            let relative = (tenths + DAY_TENTHS - start.tenths() % DAY_TENTHS) % DAY_TENTHS;
+            rebased.push_str(&content[last_offset..start_offset]);
+            rebased.push_str(&Timestamp::from_tenths(relative).unwrap().to_string());
+            last_offset = end;
+            offset = end;
+        }
+        rebased.push_str(&content[last_offset..]);
+        rebased
     }
 
     /// Writes a version of the given input string slice with timestamps shifted
@@ -121,9 +265,14 @@ impl Timestamp {
         W: Write,
     {
         let mut last_offset = 0;
-        let timestamps = Timestamp::extract_timestamps(content_with_timestamps.as_ref());
         let mut last_adjusted = None;
-        for &(after_ts_offset, after_ts_timestamp) in &timestamps {
+        for (after_ts_offset, after_ts_timestamp) in
+            Timestamp::timestamps_in(content_with_timestamps)
+        {
+            if after_ts_offset < last_offset {
+                // starts with the closing # of the previous timestamp
+                continue;
+            }
             write!(
                 &mut to,
                 "{}",
@@ -141,25 +290,28 @@ impl Timestamp {
     }
 
     pub fn last_timestamp<B: AsRef<[u8]>>(buf: B) -> Option<Timestamp> {
-        buf.as_ref()
-            .windows(F4_MAX_TIMESTAMP_LEN)
-            .filter_map(|window| Timestamp::parse(window).ok())
+        Self::timestamps_in_with(buf.as_ref(), TimestampSyntax::Strict)
             .next_back()
+            .map(|(_, timestamp)| timestamp)
     }
 
+    /// Checks if the first 4KiB of the file contain something that looks
+    /// like a timestamp, even if it is only recognized by the lenient syntax
+    /// or is in another [`TimestampDialect`].
     pub fn contains_timestamps(candidate: &Path) -> IOResult<bool> {
         let mut file = File::open(candidate)?;
         let mut buf = [0_u8; 4096];
         let read_amount = file.read(&mut buf)?;
+        Ok(Self::looks_timestamped(&buf[0..read_amount]))
+    }
 
-        for ts_window in (&buf[0..read_amount]).windows(F4_MAX_TIMESTAMP_LEN) {
-            if Self::is_timestamp(ts_window) {
-                // found something that looks like an F4 timestamp
-                return Ok(true);
-            }
-        }
-
-        Ok(false) // no obvious timestamp found in the first 4096 bytes
+    /// Checks if there is something that looks like a timestamp in the
+    /// buffer, in any of the [`DIALECTS`] or the lenient F4 syntax.
+    pub fn looks_timestamped(buf: &[u8]) -> bool {
+        Self::timestamps_in_with(buf, TimestampSyntax::Lenient)
+            .next()
+            .is_some()
+            || detect_dialect(buf).is_some()
     }
 
     pub fn is_timestamp(timestamp_slice: &[u8]) -> bool {
@@ -167,23 +319,47 @@ impl Timestamp {
     }
 
     pub fn parse<S: AsRef<[u8]>>(timestamp: S) -> Result<Timestamp, Error> {
-        Self::try_parse_timestamp(timestamp.as_ref()).ok_or_else(|| Error::malformed(timestamp))
+        Self::parse_with(timestamp, TimestampSyntax::Strict)
+    }
+
+    pub fn parse_with<S: AsRef<[u8]>>(
+        timestamp: S,
+        syntax: TimestampSyntax,
+    ) -> Result<Timestamp, Error> {
+        Self::try_parse_timestamp(timestamp.as_ref(), syntax)
+            .ok_or_else(|| Error::malformed(timestamp))
     }
 
-    fn try_parse_timestamp<S: AsRef<[u8]>>(timestamp: S) -> Option<Timestamp> {
+    fn try_parse_timestamp<S: AsRef<[u8]>>(
+        timestamp: S,
+        syntax: TimestampSyntax,
+    ) -> Option<Timestamp> {
+        let (time_separators, subsecs_separators): (&[u8], &[u8]) = match syntax {
+            TimestampSyntax::Strict => (b":", b"-"),
+            TimestampSyntax::Lenient => (b":.", b"-.,"),
+        };
         let timestamp = timestamp.as_ref();
         let mut bytes = timestamp.iter().cloned().peekable();
 
         expect_byte(&mut bytes, b'#')?;
         let (hours, hours_len) = parse_number(&mut bytes, Self::HOURS_VAL_MAX)?;
-        expect_byte(&mut bytes, b':')?;
+        expect_one_of(&mut bytes, time_separators)?;
         let (minutes, minutes_len) = parse_number(&mut bytes, Self::MINUTES_VAL_MAX)?;
-        expect_byte(&mut bytes, b':')?;
+        expect_one_of(&mut bytes, time_separators)?;
         let (seconds, seconds_len) = parse_number(&mut bytes, Self::SECONDS_VAL_MAX)?;
-        expect_byte(&mut bytes, b'-')?;
+        expect_one_of(&mut bytes, subsecs_separators)?;
         let (subsecs, subsecs_len) = parse_number(&mut bytes, Self::SUBSECS_VAL_MAX)?;
         expect_byte(&mut bytes, b'#')?;
 
+        // reject instead of panicking when packing, e.g. for `#00:00:00-05#`
+        let lens_in_bounds = (Self::HOURS_LEN_MIN..=Self::HOURS_LEN_MAX).contains(&hours_len)
+            && (Self::MINUTES_LEN_MIN..=Self::MINUTES_LEN_MAX).contains(&minutes_len)
+            && (Self::SECONDS_LEN_MIN..=Self::SECONDS_LEN_MAX).contains(&seconds_len)
+            && subsecs_len == 1;
+        if !lens_in_bounds {
+            return None;
+        }
+
         Some(Timestamp::new_with_input_len(
             hours,
             hours_len,
@@ -201,9 +377,13 @@ impl Timestamp {
     ///
     /// Doing this on the last timestamp is likely the
     /// length of the interview segment.
+    ///
+    /// Timestamps that cannot be rounded up to the next hour
+    /// stay unchanged.
     pub fn round_up(self) -> Timestamp {
         if self.hours() > 0 {
-            if self.minutes() == 0 && self.seconds() == 0 && self.subsecs() == 0 {
+            let whole_hours = self.minutes() == 0 && self.seconds() == 0 && self.subsecs() == 0;
+            if whole_hours || self.hours() == Self::HOURS_VAL_MAX {
                 self
             } else {
                 Timestamp::new(self.hours() + 1, 0, 0, 0)
@@ -211,6 +391,8 @@ impl Timestamp {
         } else if self.minutes() > 0 {
             if self.seconds() == 0 && self.subsecs() == 0 {
                 self
+            } else if self.minutes() == 59 {
+                Timestamp::new(1, 0, 0, 0)
             } else {
                 Timestamp::new(0, self.minutes() + 1, 0, 0)
             }
@@ -223,6 +405,53 @@ impl Timestamp {
         }
     }
 
+    /// Adds the timestamps, or returns `None` if the sum has more
+    /// hours than can be represented.
+    pub fn checked_add(self, rhs: Timestamp) -> Option<Timestamp> {
+        let (subsecs, subsecs_carry) = carrying_add(self.subsecs(), rhs.subsecs(), 10);
+        let (seconds, seconds_carry) =
+            carrying_add(subsecs_carry + self.seconds(), rhs.seconds(), 60);
+        let (minutes, minutes_carry) =
+            carrying_add(seconds_carry + self.minutes(), rhs.minutes(), 60);
+        let hours = minutes_carry + self.hours() + rhs.hours();
+        if hours > Self::HOURS_VAL_MAX {
+            None
+        } else {
+            Some(Timestamp::new(hours, minutes, seconds, subsecs))
+        }
+    }
+
+    /// Adds the timestamps, or returns the biggest timestamp that can
+    /// be represented if the sum would be bigger.
+    pub fn saturating_add(self, rhs: Timestamp) -> Timestamp {
+        self.checked_add(rhs).unwrap_or_else(Timestamp::max_value)
+    }
+
+    /// Total length of the timestamp in tenths of a second.
+    pub fn tenths(self) -> u64 {
+        let seconds = u64::from(self.hours()) * 3600
+            + u64::from(self.minutes()) * 60
+            + u64::from(self.seconds());
+        seconds * 10 + u64::from(self.subsecs())
+    }
+
+    /// Timestamp for a length in tenths of a second, or `None` if it
+    /// has more hours than can be represented.
+    pub fn from_tenths(tenths: u64) -> Option<Timestamp> {
+        let subsecs = (tenths % 10) as u32;
+        let seconds = tenths / 10;
+        let hours = seconds / 3600;
+        if hours > u64::from(Self::HOURS_VAL_MAX) {
+            return None;
+        }
+        Some(Timestamp::new(
+            hours as u32,
+            (seconds / 60 % 60) as u32,
+            (seconds % 60) as u32,
+            subsecs,
+        ))
+    }
+
     pub fn hours(self) -> u32 {
         (self.0 & Self::HOURS_VAL_MASK) >> Self::HOURS_VAL_SHIFT
     }
@@ -286,6 +515,7 @@ impl Timestamp {
     ///
     /// For timestamps created in code, gets the canonical length from
     /// `formatted_len`.
+    #[allow(clippy::len_without_is_empty)] // timestamps are never empty
     pub fn len(self) -> usize {
         let len = 1 // #
         +
@@ -324,14 +554,14 @@ impl Default for Timestamp {
 impl std::ops::Add for Timestamp {
     type Output = Self;
 
+    /// Adds the timestamps.
+    ///
+    /// # Panics
+    /// Panics if the sum has more hours than can be represented,
+    /// use `checked_add` or `saturating_add` to avoid this.
     fn add(self, rhs: Self) -> Self::Output {
-        let (subsecs, subsecs_carry) = carrying_add(self.subsecs(), rhs.subsecs(), 10);
-        let (seconds, seconds_carry) =
-            carrying_add(subsecs_carry + self.seconds(), rhs.seconds(), 60);
-        let (minutes, minutes_carry) =
-            carrying_add(seconds_carry + self.minutes(), rhs.minutes(), 60);
-        let hours = minutes_carry + self.hours() + rhs.hours();
-        Timestamp::new(hours, minutes, seconds, subsecs)
+        self.checked_add(rhs)
+            .expect("timestamp overflow, sum exceeds the maximum hours")
     }
 }
 
@@ -365,6 +595,242 @@ impl fmt::Debug for Timestamp {
     }
 }
 
+impl TimestampSyntax {
+    /// The F4 dialect with this syntax.
+    pub fn dialect(self) -> &'static dyn TimestampDialect {
+        match self {
+            TimestampSyntax::Strict => &F4,
+            TimestampSyntax::Lenient => &F4Lenient,
+        }
+    }
+}
+
+/// How a transcription program writes timestamps into transcripts.
+///
+/// Transcripts with timestamps of other dialects than F4 are converted to
+/// F4 timestamps when they are loaded, see
+/// [`Timestamp::convert_timestamps`], so another dialect only needs an
+/// implementation here and an entry in [`DIALECTS`].
+pub trait TimestampDialect: fmt::Debug + Sync {
+    /// Name of the dialect for `--timestamp-dialect`, e.g. `f4`.
+    fn name(&self) -> &'static str;
+
+    /// The byte every timestamp starts with, e.g. `#`.
+    fn opening(&self) -> u8;
+
+    /// The most bytes a timestamp can take.
+    fn max_len(&self) -> usize;
+
+    /// Parses the timestamp at the start of the bytes, which may go on
+    /// after it, and returns it with the number of bytes it takes.
+    fn parse(&self, bytes: &[u8]) -> Option<(Timestamp, usize)>;
+
+    /// Writes the timestamp the way this dialect does.
+    fn format(&self, timestamp: Timestamp) -> String;
+}
+
+impl PartialEq for dyn TimestampDialect + '_ {
+    fn eq(&self, other: &Self) -> bool {
+        self.name() == other.name()
+    }
+}
+
+impl Eq for dyn TimestampDialect + '_ {}
+
+/// The known dialects, in the order they are tried when detecting them.
+pub static DIALECTS: &[&dyn TimestampDialect] = &[&F4, &Easytranscript, &HyperTranscribe];
+
+/// The dialect with the given name, or `None` if there is none.
+pub fn dialect_named(name: &str) -> Option<&'static dyn TimestampDialect> {
+    DIALECTS
+        .iter()
+        .copied()
+        .find(|dialect| dialect.name().eq_ignore_ascii_case(name))
+}
+
+/// The dialect with the given name, for the command line.
+pub fn parse_dialect(name: &str) -> Result<&'static dyn TimestampDialect, String> {
+    dialect_named(name).ok_or_else(|| {
+        let names: Vec<&str> = DIALECTS.iter().map(|dialect| dialect.name()).collect();
+        format!(
+            "unknown timestamp dialect {}, expected one of {}",
+            name,
+            names.join(", ")
+        )
+    })
+}
+
+/// The first of the [`DIALECTS`] that there are timestamps of in the
+/// buffer.
+pub fn detect_dialect(buf: &[u8]) -> Option<&'static dyn TimestampDialect> {
+    DIALECTS.iter().copied().find(|&dialect| {
+        Timestamp::timestamps_in_dialect(buf, dialect)
+            .next()
+            .is_some()
+    })
+}
+
+/// Timestamps like `#00:01:23-4#`, as written by F4 and f4tapir.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct F4;
+
+impl TimestampDialect for F4 {
+    fn name(&self) -> &'static str {
+        "f4"
+    }
+
+    fn opening(&self) -> u8 {
+        b'#'
+    }
+
+    fn max_len(&self) -> usize {
+        F4_MAX_TIMESTAMP_LEN
+    }
+
+    fn parse(&self, bytes: &[u8]) -> Option<(Timestamp, usize)> {
+        Timestamp::try_parse_timestamp(bytes, TimestampSyntax::Strict)
+            .map(|timestamp| (timestamp, timestamp.len()))
+    }
+
+    fn format(&self, timestamp: Timestamp) -> String {
+        timestamp.to_string()
+    }
+}
+
+/// F4 timestamps with the separators of [`TimestampSyntax::Lenient`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct F4Lenient;
+
+impl TimestampDialect for F4Lenient {
+    fn name(&self) -> &'static str {
+        "f4-lenient"
+    }
+
+    fn opening(&self) -> u8 {
+        b'#'
+    }
+
+    fn max_len(&self) -> usize {
+        F4_MAX_TIMESTAMP_LEN
+    }
+
+    fn parse(&self, bytes: &[u8]) -> Option<(Timestamp, usize)> {
+        Timestamp::try_parse_timestamp(bytes, TimestampSyntax::Lenient)
+            .map(|timestamp| (timestamp, timestamp.len()))
+    }
+
+    fn format(&self, timestamp: Timestamp) -> String {
+        timestamp.to_string()
+    }
+}
+
+/// Timestamps like `[00:01:23]` in whole seconds, as written by
+/// easytranscript.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Easytranscript;
+
+impl TimestampDialect for Easytranscript {
+    fn name(&self) -> &'static str {
+        "easytranscript"
+    }
+
+    fn opening(&self) -> u8 {
+        b'['
+    }
+
+    fn max_len(&self) -> usize {
+        "[0000:00:00]".len()
+    }
+
+    fn parse(&self, bytes: &[u8]) -> Option<(Timestamp, usize)> {
+        let (time, len) = bracketed(bytes)?;
+        let (hours, rest) = split_hours(time)?;
+        let tenths = minutes_and_seconds_tenths(rest)?;
+        Some((Timestamp::from_tenths(hours * 36_000 + tenths)?, len))
+    }
+
+    fn format(&self, timestamp: Timestamp) -> String {
+        format!(
+            "[{:02}:{:02}:{:02}]",
+            timestamp.hours(),
+            timestamp.minutes(),
+            timestamp.seconds()
+        )
+    }
+}
+
+/// Time codes like `[0:01:23.4]` with tenths or hundredths of a second,
+/// as written by HyperTRANSCRIBE. Hundredths are cut off to tenths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HyperTranscribe;
+
+impl TimestampDialect for HyperTranscribe {
+    fn name(&self) -> &'static str {
+        "hypertranscribe"
+    }
+
+    fn opening(&self) -> u8 {
+        b'['
+    }
+
+    fn max_len(&self) -> usize {
+        "[0000:00:00.00]".len()
+    }
+
+    fn parse(&self, bytes: &[u8]) -> Option<(Timestamp, usize)> {
+        let (time, len) = bracketed(bytes)?;
+        let (time, fraction) = split_once(time, b'.')?;
+        let (hours, rest) = split_hours(time)?;
+        let tenths = minutes_and_seconds_tenths(rest)?;
+        let subsecs = match fraction {
+            [tenth] => parse_digit(*tenth)?,
+            [tenth, hundredth] => parse_digit(*hundredth).and(parse_digit(*tenth))?,
+            _ => return None,
+        };
+        let tenths = hours * 36_000 + tenths + u64::from(subsecs);
+        Some((Timestamp::from_tenths(tenths)?, len))
+    }
+
+    fn format(&self, timestamp: Timestamp) -> String {
+        format!(
+            "[{}:{:02}:{:02}.{}]",
+            timestamp.hours(),
+            timestamp.minutes(),
+            timestamp.seconds(),
+            timestamp.subsecs()
+        )
+    }
+}
+
+/// What is between the `[` at the start of the bytes and the next `]`, and
+/// the number of bytes up to and including the `]`.
+fn bracketed(bytes: &[u8]) -> Option<(&[u8], usize)> {
+    let inner = bytes.strip_prefix(b"[")?;
+    let end = memchr(b']', inner)?;
+    Some((&inner[..end], end + 2))
+}
+
+fn split_once(bytes: &[u8], separator: u8) -> Option<(&[u8], &[u8])> {
+    let at = memchr(separator, bytes)?;
+    Some((&bytes[..at], &bytes[at + 1..]))
+}
+
+/// The hours before the first colon of a time like `0:01:23` and the rest
+/// after the colon.
+fn split_hours(time: &[u8]) -> Option<(u64, &[u8])> {
+    let (hours, rest) = split_once(time, b':')?;
+    let hours = parse_clock_part(hours, 1..=4, u64::from(Timestamp::HOURS_VAL_MAX))?;
+    Some((hours, rest))
+}
+
+/// Tenths of a second of minutes and seconds like `01:23`.
+fn minutes_and_seconds_tenths(time: &[u8]) -> Option<u64> {
+    let (minutes, seconds) = split_once(time, b':')?;
+    let minutes = parse_clock_part(minutes, 2..=2, 59)?;
+    let seconds = parse_clock_part(seconds, 2..=2, 59)?;
+    Some((minutes * 60 + seconds) * 10)
+}
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("{0} was not recognized as a timestamp")]
@@ -410,6 +876,64 @@ where
     }
 }
 
+/// Iterator over the timestamps in a buffer and their offsets, see
+/// [`Timestamp::timestamps_in`].
+///
+/// Only offsets with the opening byte of the dialect, like `#`, are tried,
+/// found with `memchr`. Timestamps sharing a `#`, like in
+/// `#00:00:01-0#00:00:02-0#`, are both found.
+#[derive(Debug, Clone)]
+pub struct TimestampsIn<'a> {
+    buf: &'a [u8],
+    /// Offset where the next timestamp from the front may start.
+    front: usize,
+    /// Offset before which the next timestamp from the back starts.
+    back: usize,
+    dialect: &'a dyn TimestampDialect,
+}
+
+impl<'a> TimestampsIn<'a> {
+    /// Parses a timestamp at the offset, which may be longer than the
+    /// remaining range, but not the buffer.
+    fn timestamp_at(&self, offset: usize) -> Option<Timestamp> {
+        let end = self.buf.len().min(offset + self.dialect.max_len());
+        self.dialect
+            .parse(&self.buf[offset..end])
+            .map(|(timestamp, _)| timestamp)
+    }
+}
+
+impl<'a> Iterator for TimestampsIn<'a> {
+    type Item = (usize, Timestamp);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(found) = memchr(self.dialect.opening(), &self.buf[self.front..self.back]) {
+            let offset = self.front + found;
+            self.front = offset + 1;
+            if let Some(timestamp) = self.timestamp_at(offset) {
+                return Some((offset, timestamp));
+            }
+        }
+        self.front = self.back;
+        None
+    }
+}
+
+impl<'a> DoubleEndedIterator for TimestampsIn<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        while let Some(offset) = memrchr(self.dialect.opening(), &self.buf[self.front..self.back])
+            .map(|found| self.front + found)
+        {
+            self.back = offset;
+            if let Some(timestamp) = self.timestamp_at(offset) {
+                return Some((offset, timestamp));
+            }
+        }
+        self.back = self.front;
+        None
+    }
+}
+
 fn expect_byte<I>(bytes: &mut Peekable<I>, expect: u8) -> Option<()>
 where
     I: Iterator<Item = u8>,
@@ -419,6 +943,15 @@ where
         .and_then(|b| if b == expect { Some(()) } else { None })
 }
 
+fn expect_one_of<I>(bytes: &mut Peekable<I>, expect: &[u8]) -> Option<()>
+where
+    I: Iterator<Item = u8>,
+{
+    bytes
+        .next()
+        .and_then(|b| if expect.contains(&b) { Some(()) } else { None })
+}
+
 fn parse_digit(byte: u8) -> Option<u8> {
     if !byte.is_ascii_digit() {
         return None;
@@ -427,6 +960,25 @@ fn parse_digit(byte: u8) -> Option<u8> {
     Some(digit)
 }
 
+/// Tenths of a second since midnight of a clock time like `14:32:05`.
+fn clock_time_tenths(time: &[u8]) -> Option<u64> {
+    let mut parts = time.split(|&byte| byte == b':');
+    let hours = parse_clock_part(parts.next()?, 1..=2, 23)?;
+    let minutes = parse_clock_part(parts.next()?, 2..=2, 59)?;
+    let seconds = parse_clock_part(parts.next()?, 2..=2, 59)?;
+    Some(((hours * 60 + minutes) * 60 + seconds) * 10)
+}
+
+fn parse_clock_part(part: &[u8], len: RangeInclusive<usize>, max: u64) -> Option<u64> {
+    if !len.contains(&part.len()) || !part.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    let value = part
+        .iter()
+        .fold(0, |value, &digit| value * 10 + u64::from(digit - b'0'));
+    Some(value).filter(|&value| value <= max)
+}
+
 fn carrying_add(lhs: u32, rhs: u32, wrap_at: u32) -> (u32, u32) {
     let unwrapped_sum = lhs + rhs;
     let carry = unwrapped_sum / wrap_at;
@@ -453,6 +1005,23 @@ mod test {
         assert!(!Timestamp::is_timestamp("#00:06:00.0#".as_bytes()))
     }
 
+    #[test]
+    fn timestamps_in_from_both_ends() {
+        let text = "a #00:00:01-0#00:00:02-0# b # #00:00:03-0#";
+        let found: Vec<usize> = Timestamp::timestamps_in(text)
+            .map(|(offset, _)| offset)
+            .collect();
+        assert_eq!(found, vec![2, 13, 30]);
+        let mut timestamps = Timestamp::timestamps_in(text);
+        assert_eq!(
+            timestamps.next_back().map(|(_, t)| t),
+            Timestamp::parse("#00:00:03-0#").ok()
+        );
+        assert_eq!(timestamps.next().map(|(offset, _)| offset), Some(2));
+        assert_eq!(timestamps.next_back().map(|(offset, _)| offset), Some(13));
+        assert_eq!(timestamps.next(), None);
+    }
+
     #[test]
     fn parsing() {
         // given
@@ -495,6 +1064,90 @@ mod test {
         )
     }
 
+    #[test]
+    fn checked_add_fails_on_overflow() {
+        let a = Timestamp::parse("#4000:30:00-0#").unwrap();
+        let b = Timestamp::parse("#95:30:00-0#").unwrap();
+        assert_eq!(a.checked_add(b), None);
+        assert_eq!(a.saturating_add(b), Timestamp::max_value());
+        assert_eq!(
+            a.checked_add(Timestamp::parse("#95:29:59-9#").unwrap()),
+            Some(Timestamp::max_value())
+        );
+    }
+
+    #[test]
+    fn max_value_is_formatted_with_all_hour_digits() {
+        assert_eq!(format!("{}", Timestamp::max_value()), "#4095:59:59-9#");
+        assert_eq!(Timestamp::max_value().len(), "#4095:59:59-9#".len());
+    }
+
+    #[test]
+    fn round_up_at_max_hours_does_not_overflow() {
+        let a = Timestamp::parse("#4095:00:01-0#").unwrap();
+        assert_eq!(a.round_up(), a);
+    }
+
+    #[test]
+    fn extract_timestamps_with_many_hour_digits() {
+        let content = b"a #4000:00:01-0# b #100:00:00-0#";
+        let extracted = Timestamp::extract_timestamps(content);
+        assert_eq!(
+            extracted,
+            vec![
+                (2, Timestamp::parse("#4000:00:01-0#").unwrap()),
+                (19, Timestamp::parse("#100:00:00-0#").unwrap()),
+            ]
+        );
+    }
+
+    #[test]
+    fn lenient_separators() {
+        let expected = Timestamp::parse("#00:01:23-4#").unwrap();
+        for lenient in &["#00.01.23-4#", "#00:01:23,4#", "#00.01.23.4#"] {
+            assert!(!Timestamp::is_timestamp(lenient.as_bytes()));
+            assert_eq!(
+                Timestamp::parse_with(lenient, TimestampSyntax::Lenient).unwrap(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn canonicalize_only_lenient_timestamps() {
+        assert_eq!(
+            Timestamp::canonicalize_timestamps("a #0:01:23-4# b #00.01.23,4# c"),
+            "a #0:01:23-4# b #00:01:23-4# c"
+        );
+    }
+
+    #[test]
+    fn clock_times_are_rebased_to_the_start() {
+        let start = Timestamp::from_tenths(14 * 36000 + 30 * 600).unwrap();
+        assert_eq!(
+            Timestamp::rebase_clock_times(
+                "a 14:32:05 b (9:05:00) c 0:10:00. #14:32:05-0# 14:32:051 1:2:03",
+                start
+            ),
+            "a #00:02:05-0# b (#18:35:00-0#) c #09:40:00-0#. #14:32:05-0# 14:32:051 1:2:03"
+        );
+    }
+
+    #[test]
+    fn tenths_round_trip() {
+        let ts = Timestamp::parse("#01:02:03-4#").unwrap();
+        assert_eq!(ts.tenths(), 37234);
+        assert_eq!(Timestamp::from_tenths(37234), Some(ts));
+        assert_eq!(Timestamp::from_tenths(u64::MAX), None);
+    }
+
+    #[test]
+    fn reject_too_many_digits() {
+        assert!(!Timestamp::is_timestamp("#00:00:00-05#".as_bytes()));
+        assert!(!Timestamp::is_timestamp("#00:000:00-0#".as_bytes()));
+        assert!(!Timestamp::is_timestamp("#00001:00:00-0#".as_bytes()));
+    }
+
     #[test]
     fn round_up_at_hours() {
         let a = Timestamp::parse("#58:58:57-9#").unwrap();
@@ -506,6 +1159,9 @@ mod test {
     fn round_up_at_minutes() {
         let a = Timestamp::parse("#00:14:57-9#").unwrap();
         let rounded_up = Timestamp::parse("#00:15:00-0#").unwrap();
+        assert_eq!(a.round_up(), rounded_up);
+        let a = Timestamp::parse("#00:59:00-1#").unwrap();
+        let rounded_up = Timestamp::parse("#01:00:00-0#").unwrap();
         assert_eq!(a.round_up(), rounded_up)
     }
 
@@ -561,4 +1217,44 @@ mod test {
             Timestamp::parse("#01:02:03-4#").unwrap()
         )
     }
+
+    #[test]
+    fn other_dialects_are_detected_and_converted_to_f4() {
+        let easy = "I: Hallo. [00:01:23] Und [1:00:00]";
+        let hyper = "I: Hallo. [0:01:23.45] Und [00:01:24.5]";
+        assert_eq!(
+            detect_dialect(easy.as_bytes()).unwrap().name(),
+            "easytranscript"
+        );
+        assert_eq!(
+            detect_dialect(hyper.as_bytes()).unwrap().name(),
+            "hypertranscribe"
+        );
+        assert_eq!(
+            detect_dialect(b"#00:01:23-4# [00:01:23]").unwrap().name(),
+            "f4"
+        );
+        assert!(detect_dialect(b"[unverst\\u228?ndlich] (3)").is_none());
+
+        assert_eq!(
+            Timestamp::convert_timestamps(easy, &Easytranscript),
+            "I: Hallo. #00:01:23-0# Und #01:00:00-0#"
+        );
+        assert_eq!(
+            Timestamp::convert_timestamps(hyper, &HyperTranscribe),
+            "I: Hallo. #00:01:23-4# Und #00:01:24-5#"
+        );
+        let timestamp = Timestamp::from_tenths(830).unwrap();
+        for &dialect in DIALECTS {
+            let formatted = dialect.format(timestamp);
+            assert_eq!(
+                dialect.parse(formatted.as_bytes()),
+                Some((timestamp, formatted.len())),
+                "{}",
+                dialect.name()
+            );
+        }
+        assert!(parse_dialect("HyperTRANSCRIBE").is_ok());
+        assert!(parse_dialect("express-scribe").is_err());
+    }
 }
@@ -0,0 +1,238 @@
+//! Inline annotations in speech, like `(lacht)`, `[unverständlich]` or
+//! the pause `(...)`, which transcribers put in round or square brackets.
+//!
+//! They stay in RTF and JSON outputs, which are complete, and can be left
+//! out of the other outputs with `merge --strip-annotations`.
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use super::formats::{OutputFormat, FORMATS};
+use crate::timestamp::Timestamp;
+
+/// Longest text in brackets that is taken for an annotation rather than a
+/// remark in parentheses that spans a sentence.
+const MAX_ANNOTATION_LEN: usize = 80;
+
+/// What an annotation marks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnnotationKind {
+    /// A pause, with its length if it is given in seconds like `(3)` or
+    /// `(2,5 Sek.)` rather than with dots like `(...)`.
+    Pause(Option<Timestamp>),
+    /// Speech that could not be understood, like `[unverständlich]` or
+    /// `(unv.)`.
+    Unintelligible,
+    /// Anything else, mostly nonverbal events like `(lacht)` or
+    /// `[Telefon klingelt]`.
+    Comment,
+}
+
+impl Display for AnnotationKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            AnnotationKind::Pause(_) => write!(f, "pause"),
+            AnnotationKind::Unintelligible => write!(f, "unintelligible"),
+            AnnotationKind::Comment => write!(f, "comment"),
+        }
+    }
+}
+
+/// An annotation in plain text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation<'a> {
+    pub kind: AnnotationKind,
+    /// The annotation with its brackets, e.g. `(lacht)`.
+    pub text: &'a str,
+    /// Byte offset of the opening bracket.
+    pub offset: usize,
+}
+
+/// The annotations in the plain text of speech, in order.
+pub fn annotations(text: &str) -> Vec<Annotation<'_>> {
+    let mut found = vec![];
+    let mut rest = 0;
+    while let Some(open) = text[rest..].find(['(', '[']).map(|at| rest + at) {
+        let close = if text[open..].starts_with('(') {
+            ')'
+        } else {
+            ']'
+        };
+        let content_start = open + 1;
+        let content = text[content_start..]
+            .find(['(', '[', ')', ']', '#', '\n'])
+            .map(|len| &text[content_start..content_start + len])
+            .filter(|content| text[content_start + content.len()..].starts_with(close));
+        match content {
+            Some(content) if !content.trim().is_empty() && content.len() <= MAX_ANNOTATION_LEN => {
+                let end = content_start + content.len() + 1;
+                found.push(Annotation {
+                    kind: kind_of(content),
+                    text: &text[open..end],
+                    offset: open,
+                });
+                rest = end;
+            }
+            _ => rest = content_start,
+        }
+    }
+    found
+}
+
+fn kind_of(content: &str) -> AnnotationKind {
+    let content = content.trim().to_lowercase();
+    if content.chars().all(|c| c == '.' || c == '…') {
+        return AnnotationKind::Pause(None);
+    }
+    let number_end = content
+        .find(|c: char| !c.is_ascii_digit() && c != ',' && c != '.')
+        .unwrap_or(content.len());
+    let unit = content[number_end..].trim().trim_end_matches('.');
+    if number_end > 0
+        && matches!(
+            unit,
+            "" | "s" | "sek" | "sec" | "sekunde" | "sekunden" | "second" | "seconds"
+        )
+    {
+        let seconds: Option<f64> = content[..number_end].replace(',', ".").parse().ok();
+        let tenths = seconds.and_then(|seconds| Timestamp::from_tenths((seconds * 10.0) as u64));
+        return AnnotationKind::Pause(tenths);
+    }
+    let unintelligible = content.contains("unverständlich")
+        || matches!(
+            content.trim_end_matches('.'),
+            "unv" | "unintelligible" | "inaudible"
+        )
+        || content.chars().all(|c| c == '?');
+    if unintelligible {
+        AnnotationKind::Unintelligible
+    } else {
+        AnnotationKind::Comment
+    }
+}
+
+/// The text without its annotations and the spaces before them.
+pub fn strip_annotations(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut rest = 0;
+    for annotation in annotations(text) {
+        stripped.push_str(&text[rest..annotation.offset]);
+        rest = annotation.offset + annotation.text.len();
+        let followed_by_word = text[rest..]
+            .chars()
+            .next()
+            .is_some_and(|c| !c.is_whitespace() && !c.is_ascii_punctuation());
+        if !followed_by_word {
+            stripped.truncate(stripped.trim_end().len());
+        }
+    }
+    stripped.push_str(&text[rest..]);
+    stripped.trim().to_string()
+}
+
+/// Outputs that annotations are left out of, see `merge
+/// --strip-annotations`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StripAnnotations {
+    /// Bits of the `FORMATS` by their index.
+    formats: u32,
+    template: bool,
+}
+
+impl StripAnnotations {
+    /// Checks if annotations are left out of outputs in the format.
+    pub fn strips<F: OutputFormat + ?Sized>(&self, format: &F) -> bool {
+        if format.name() == TEMPLATE {
+            return self.template;
+        }
+        strippable()
+            .any(|(index, known)| known.name() == format.name() && self.formats & 1 << index != 0)
+    }
+}
+
+/// Name of templates in `--strip-annotations`.
+const TEMPLATE: &str = "template";
+
+/// The `FORMATS` that annotations can be left out of, with their index.
+fn strippable() -> impl Iterator<Item = (usize, &'static dyn OutputFormat)> {
+    FORMATS
+        .iter()
+        .copied()
+        .enumerate()
+        .filter(|(_, format)| !format.made_from_rtf())
+}
+
+impl FromStr for StripAnnotations {
+    type Err = String;
+
+    /// Parses a comma-separated list of the names of `FORMATS` that are not
+    /// made from the merged RTF, like `txt`, `srt`, `csv` or `intervals`,
+    /// and `template`, or `all` for all of them.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut strip = StripAnnotations::default();
+        for name in s.split(',').map(str::trim) {
+            match name {
+                TEMPLATE => strip.template = true,
+                "all" => {
+                    strip = StripAnnotations {
+                        formats: strippable().fold(0, |bits, (index, _)| bits | 1 << index),
+                        template: true,
+                    }
+                }
+                _ => match strippable().find(|(_, format)| format.name() == name) {
+                    Some((index, _)) => strip.formats |= 1 << index,
+                    None => {
+                        let names: Vec<_> = strippable().map(|(_, format)| format.name()).collect();
+                        return Err(format!(
+                            "unknown output {}, expected {}, template or all",
+                            name,
+                            names.join(", ")
+                        ));
+                    }
+                },
+            }
+        }
+        Ok(strip)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::transcript::formats::{Html, Srt};
+
+    #[test]
+    fn annotations_are_recognized_and_stripped() {
+        let text = "Ja (lacht), das war [unverständlich] (...) so (3 Sek.) (siehe oben.";
+        let found: Vec<_> = annotations(text)
+            .into_iter()
+            .map(|annotation| (annotation.text, annotation.kind))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("(lacht)", AnnotationKind::Comment),
+                ("[unverständlich]", AnnotationKind::Unintelligible),
+                ("(...)", AnnotationKind::Pause(None)),
+                (
+                    "(3 Sek.)",
+                    AnnotationKind::Pause(Timestamp::from_tenths(30))
+                ),
+            ]
+        );
+        assert_eq!(strip_annotations(text), "Ja, das war so (siehe oben.");
+        assert_eq!(
+            "srt, csv"
+                .parse::<StripAnnotations>()
+                .map(|strip| strip.strips(&Srt)),
+            Ok(true)
+        );
+        assert_eq!(
+            "srt"
+                .parse::<StripAnnotations>()
+                .map(|strip| strip.strips(&Html)),
+            Ok(false)
+        );
+        assert!("rtf".parse::<StripAnnotations>().is_err());
+        assert!("json".parse::<StripAnnotations>().is_err());
+    }
+}
@@ -0,0 +1,174 @@
+//! Builds F4 transcripts from utterances, for tools that produce speech
+//! and timestamps rather than RTF, like speech recognition.
+//!
+//! The transcript is built through the JSON representation, so it is the
+//! same RTF that `convert` writes.
+use super::json::{Block, Document, Metadata, Speaker, Utterance, FORMAT, VERSION};
+use super::text::is_speaker_code;
+use crate::timestamp::Timestamp;
+use crate::transcript::{Error, Result, Transcript};
+
+/// Collects the paragraphs of a transcript one after the other.
+///
+/// Speakers are listed in the order they first speak, unless they are
+/// given a color before.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TranscriptBuilder {
+    metadata: Metadata,
+    blocks: Vec<Block>,
+}
+
+impl TranscriptBuilder {
+    pub fn new() -> TranscriptBuilder {
+        TranscriptBuilder::default()
+    }
+
+    /// Adds an utterance of the speaker, with timestamps like
+    /// `#00:01:50-6#` anywhere in the speech. Line breaks in the speech are
+    /// replaced with spaces.
+    pub fn utterance(mut self, speaker: &str, speech: &str) -> TranscriptBuilder {
+        self.add_speaker(speaker);
+        self.blocks.push(Block::Utterance(Utterance::new(
+            speaker,
+            &single_line(speech),
+        )));
+        self
+    }
+
+    /// Adds an utterance of the speaker that ends at the timestamp, which
+    /// is written after the speech.
+    pub fn timed_utterance(self, speaker: &str, speech: &str, end: Timestamp) -> TranscriptBuilder {
+        let speech = format!("{} {}", single_line(speech), end);
+        self.utterance(speaker, speech.trim_start())
+    }
+
+    /// Adds a paragraph that is not an utterance, e.g. a note of the
+    /// transcriber. Blank notes are left out.
+    pub fn note(mut self, text: &str) -> TranscriptBuilder {
+        let text = single_line(text);
+        if !text.is_empty() {
+            self.blocks.push(Block::Note { text });
+        }
+        self
+    }
+
+    /// Highlights the speaker with the color, like `#EF7C00`.
+    pub fn speaker_color(mut self, speaker: &str, color: &str) -> TranscriptBuilder {
+        self.add_speaker(speaker);
+        if let Some(known) = self
+            .metadata
+            .speakers
+            .iter_mut()
+            .find(|known| known.code == speaker)
+        {
+            known.color = Some(color.to_string());
+        }
+        self
+    }
+
+    /// Sets the comment of the document, `f4tapir` by default.
+    pub fn comment(mut self, comment: &str) -> TranscriptBuilder {
+        self.metadata.comment = Some(comment.to_string());
+        self
+    }
+
+    /// Sets the creation time, like `2021-03-04T10:05`.
+    pub fn created(mut self, created: &str) -> TranscriptBuilder {
+        self.metadata.created = Some(created.to_string());
+        self
+    }
+
+    /// The JSON representation of the transcript built so far.
+    pub fn document(&self) -> Document {
+        Document {
+            format: FORMAT.to_string(),
+            version: VERSION,
+            metadata: self.metadata.clone(),
+            blocks: self.blocks.clone(),
+        }
+    }
+
+    /// Builds the transcript, which is written as F4 RTF with `Display`.
+    ///
+    /// Fails if a speaker code would not be read back as one, or if there
+    /// are no timestamps, which every transcript needs.
+    pub fn build(&self) -> Result<Transcript> {
+        if let Some(speaker) = self
+            .metadata
+            .speakers
+            .iter()
+            .find(|speaker| !is_speaker_code(&speaker.code))
+        {
+            return Err(Error::MalformedSpeaker(speaker.code.clone()));
+        }
+        self.document().to_transcript()
+    }
+
+    fn add_speaker(&mut self, speaker: &str) {
+        let speakers = &mut self.metadata.speakers;
+        if !speakers.iter().any(|known| known.code == speaker) {
+            speakers.push(Speaker {
+                code: speaker.to_string(),
+                color: None,
+            });
+        }
+    }
+}
+
+/// The text with its line breaks and the space around them collapsed.
+fn single_line(text: &str) -> String {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn built_transcripts_read_back_as_their_utterances() {
+        let transcript = TranscriptBuilder::new()
+            .speaker_color("B", "#EF7C00")
+            .note("Interview mit B")
+            .utterance("I", "Was hat man \r\nfrüher so gehört? #00:00:27-8#")
+            .timed_utterance("B", "Punk {laut}.", Timestamp::from_tenths(586).unwrap())
+            .build()
+            .unwrap();
+
+        let document = Document::of(&transcript);
+        let codes: Vec<_> = document
+            .metadata
+            .speakers
+            .iter()
+            .map(|speaker| (speaker.code.as_str(), speaker.color.as_deref()))
+            .collect();
+        assert_eq!(codes, vec![("B", Some("#EF7C00")), ("I", Some("#000000"))]);
+        assert_eq!(
+            document.blocks,
+            vec![
+                Block::Note {
+                    text: "Interview mit B".to_string()
+                },
+                Block::Utterance(Utterance::new(
+                    "I",
+                    "Was hat man früher so gehört? #00:00:27-8#"
+                )),
+                Block::Utterance(Utterance::new("B", "Punk {laut}. #00:00:58-6#")),
+            ]
+        );
+        assert_eq!(
+            transcript.last_timestamp(),
+            Timestamp::from_tenths(586).unwrap()
+        );
+
+        assert!(matches!(
+            TranscriptBuilder::new()
+                .timed_utterance("Frau B", "Ja.", Timestamp::zero())
+                .build(),
+            Err(Error::MalformedSpeaker(speaker)) if speaker == "Frau B"
+        ));
+    }
+}
@@ -0,0 +1,159 @@
+//! Colored speaker labels in merged RTF, with `\cfN` referring to colors
+//! added to the color table of the preamble.
+//!
+//! Speakers in the speaker list of the first transcript get the colors
+//! f4transkript shows them in, others get colors of a palette in the
+//! order they first speak.
+use super::lines::Line;
+use super::speakers::speaker_list;
+
+/// Colors for speakers that are not in the speaker list, as red, green
+/// and blue.
+const PALETTE: &[(u8, u8, u8)] = &[
+    (0x1f, 0x77, 0xb4),
+    (0xd6, 0x27, 0x28),
+    (0x2c, 0xa0, 0x2c),
+    (0x94, 0x67, 0xbd),
+    (0xff, 0x7f, 0x0e),
+    (0x8c, 0x56, 0x4b),
+    (0xe3, 0x77, 0xc2),
+    (0x17, 0xbe, 0xcf),
+];
+const COLOR_TABLE_START: &str = "{\\colortbl";
+
+/// The colors of the speakers of a merge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct SpeakerColors {
+    /// Index of the first added color in the color table.
+    first: usize,
+    /// Number of speakers from the speaker list, whose colors come before
+    /// the palette.
+    listed: usize,
+    /// Speakers in the order of their colors.
+    speakers: Vec<String>,
+}
+
+impl SpeakerColors {
+    /// Colors for the speakers in the speaker list of the preamble and
+    /// the palette, with the preamble with them added to its color table,
+    /// or `None` if it has no color table.
+    pub(super) fn new(preamble: &str) -> Option<(SpeakerColors, String)> {
+        let table_start = preamble.find(COLOR_TABLE_START)?;
+        let table_end = table_start + preamble[table_start..].find('}')?;
+        let first = preamble[table_start..table_end].matches(';').count();
+
+        let mut speakers = vec![];
+        let mut added = String::new();
+        for (code, color) in speaker_list(preamble)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|entry| entry.split_once(":="))
+        {
+            if let Some((red, green, blue)) = parse_color(color) {
+                speakers.push(code.to_string());
+                added.push_str(&table_entry(red, green, blue));
+            }
+        }
+        let listed = speakers.len();
+        for &(red, green, blue) in PALETTE {
+            added.push_str(&table_entry(red, green, blue));
+        }
+
+        let preamble = format!(
+            "{}{}{}",
+            &preamble[..table_end],
+            added,
+            &preamble[table_end..]
+        );
+        Some((
+            SpeakerColors {
+                first,
+                listed,
+                speakers,
+            },
+            preamble,
+        ))
+    }
+
+    /// Gives the speakers of the lines that have none yet the next colors
+    /// of the palette.
+    pub(super) fn add_speakers<'a, I: IntoIterator<Item = Line<'a>>>(&mut self, lines: I) {
+        for line in lines {
+            if let Line::Utterance(utterance) = line {
+                if !self
+                    .speakers
+                    .iter()
+                    .any(|known| known == utterance.speaker())
+                {
+                    self.speakers.push(utterance.speaker().to_string());
+                }
+            }
+        }
+    }
+
+    /// Index of the color of the speaker in the color table, if it has
+    /// one.
+    pub(super) fn color_of(&self, speaker: &str) -> Option<usize> {
+        let position = self.speakers.iter().position(|known| known == speaker)?;
+        Some(if position < self.listed {
+            self.first + position
+        } else {
+            self.first + self.listed + (position - self.listed) % PALETTE.len()
+        })
+    }
+
+    /// The line with its speaker label colored, if it is an utterance.
+    pub(super) fn paint<'a>(&self, line: Line<'a>) -> Line<'a> {
+        match line {
+            Line::Utterance(utterance) => {
+                let color = self.color_of(utterance.speaker());
+                Line::Utterance(utterance.with_speaker_color(color))
+            }
+            other => other,
+        }
+    }
+}
+
+/// Parses a color like `#EF7C00`.
+fn parse_color(color: &str) -> Option<(u8, u8, u8)> {
+    let hex = color
+        .trim()
+        .strip_prefix('#')
+        .filter(|hex| hex.len() == 6)?;
+    let channel = |at: usize| u8::from_str_radix(hex.get(at..at + 2)?, 16).ok();
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+fn table_entry(red: u8, green: u8, blue: u8) -> String {
+    format!("\\red{}\\green{}\\blue{};", red, green, blue)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::transcript::json::Document;
+    use crate::transcript::Transcript;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn listed_speakers_keep_their_colors_and_others_get_the_palette() {
+        let transcript =
+            Transcript::try_from(std::fs::read_to_string("testdata/interview-01.rtf").unwrap())
+                .unwrap();
+        let (mut colors, preamble) = SpeakerColors::new(transcript.preamble()).unwrap();
+        assert!(preamble.contains(
+            "{\\colortbl\\red0\\green0\\blue0;\\red239\\green124\\blue0;\\red73\\green147\\blue199;\\red31\\green119\\blue180;"
+        ));
+        assert_eq!(colors.color_of("I"), Some(1));
+        assert_eq!(colors.color_of("Z"), Some(2));
+        assert_eq!(colors.color_of("B"), None);
+
+        colors.add_speakers(transcript.lines());
+        let other = Document::from_text("B: Hallo. #00:00:01-0#\nI: Ja. #00:00:02-0#\n")
+            .to_transcript()
+            .unwrap();
+        colors.add_speakers(other.lines());
+        assert_eq!(colors.color_of("B"), Some(3));
+        assert_eq!(colors.speakers.len(), 3);
+    }
+}
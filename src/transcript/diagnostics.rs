@@ -0,0 +1,212 @@
+//! Lines of a transcript that the parser did not understand, which are
+//! copied as they are and hence keep their timestamps unadjusted, and
+//! markup that the parser would not understand.
+use super::lines::Line;
+use super::rtf::{Rtf, TokenKind};
+use crate::timestamp::Timestamp;
+use crate::transcript::Transcript;
+
+/// Characters of an unparsed line to show in diagnostics.
+const SNIPPET_LEN: usize = 60;
+/// Control words for character and paragraph formatting and special
+/// characters that F4 and similar editors write into the content. Others,
+/// like fields or revision marks, are added by word processors and may be
+/// mangled when merging.
+const KNOWN_CONTROL_WORDS: &[&str] = &[
+    "ab",
+    "af",
+    "afs",
+    "ai",
+    "b",
+    "bullet",
+    "caps",
+    "cb",
+    "cf",
+    "charrsid",
+    "chcbpat",
+    "dbch",
+    "emdash",
+    "emspace",
+    "endash",
+    "enspace",
+    "expnd",
+    "expndtw",
+    "f",
+    "fi",
+    "fs",
+    "highlight",
+    "hich",
+    "i",
+    "insrsid",
+    "kerning",
+    "lang",
+    "langfe",
+    "langnp",
+    "ldblquote",
+    "li",
+    "line",
+    "loch",
+    "lquote",
+    "ltrch",
+    "nosupersub",
+    "par",
+    "pard",
+    "plain",
+    "qc",
+    "qj",
+    "ql",
+    "qr",
+    "rdblquote",
+    "ri",
+    "rquote",
+    "rtlch",
+    "sa",
+    "sb",
+    "scaps",
+    "sl",
+    "slmult",
+    "strike",
+    "sub",
+    "super",
+    "tab",
+    "u",
+    "uc",
+    "ul",
+    "ulnone",
+];
+
+/// A non-blank line that matched none of the expected paragraph forms.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnparsedLine {
+    /// Line in the transcript file, starting at one.
+    pub line: usize,
+    /// Start of the line, shortened to a few dozen characters.
+    pub snippet: String,
+    /// Number of timestamps in the line that will not be adjusted.
+    pub timestamps: usize,
+}
+
+impl Transcript {
+    /// Lines that are written as they are when merging, because they
+    /// could not be parsed.
+    pub fn unparsed_lines(&self) -> Vec<UnparsedLine> {
+        let first_content_line = self.first_content_line();
+        self.lines()
+            .enumerate()
+            .filter_map(|(idx, line)| match line {
+                Line::Other(other) if !other.trim().is_empty() => Some(UnparsedLine {
+                    line: first_content_line + idx,
+                    snippet: snippet(other),
+                    timestamps: Timestamp::extract_timestamps(other.as_bytes()).len(),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// A control word in the content that is not known to be harmless, or
+/// `\*` starting a destination that readers may skip.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownControlWord {
+    /// Line in the transcript file, starting at one.
+    pub line: usize,
+    /// Byte in the line where the control word starts, starting at one.
+    pub column: usize,
+    /// The control word with its backslash, e.g. `\fldinst`.
+    pub word: String,
+}
+
+impl Transcript {
+    /// Control words in the content that are not in the list of known
+    /// formatting, which hints at markup added by a word processor.
+    pub fn unknown_control_words(&self) -> Vec<UnknownControlWord> {
+        let first_content_line = self.first_content_line();
+        let mut unknown = vec![];
+        for (idx, line) in self.content().lines().enumerate() {
+            for token in Rtf::from(line) {
+                let word = token.as_str().trim_end();
+                let known = match token.kind() {
+                    TokenKind::ControlWord => KNOWN_CONTROL_WORDS.contains(&&word[1..]),
+                    TokenKind::ControlSym => word != "\\*",
+                    _ => true,
+                };
+                if !known {
+                    unknown.push(UnknownControlWord {
+                        line: first_content_line + idx,
+                        column: token.source().start() + 1,
+                        word: word.to_string(),
+                    });
+                }
+            }
+        }
+        unknown
+    }
+}
+
+fn snippet(line: &str) -> String {
+    let mut chars = line.chars();
+    let mut snippet: String = chars.by_ref().take(SNIPPET_LEN).collect();
+    if chars.next().is_some() {
+        snippet.push_str("...");
+    }
+    snippet
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn unbalanced_line_is_reported_with_its_line_number() {
+        let original = std::fs::read_to_string("testdata/interview-02.rtf").unwrap();
+        assert!(Transcript::try_from(original.clone())
+            .unwrap()
+            .unparsed_lines()
+            .is_empty());
+
+        let broken = original.replacen(
+            "{\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 \\par}",
+            "{\\b I: Versteh ich nicht #00:00:58-6#.}{\\par}",
+            1,
+        );
+        let transcript = Transcript::try_from(broken).unwrap();
+
+        assert_eq!(
+            transcript.unparsed_lines(),
+            vec![UnparsedLine {
+                line: 8,
+                snippet: "{\\b I: Versteh ich nicht #00:00:58-6#.}{\\par}".to_string(),
+                timestamps: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn fields_are_unknown_markup() {
+        let original = std::fs::read_to_string("testdata/interview-02.rtf").unwrap();
+        assert!(Transcript::try_from(original.clone())
+            .unwrap()
+            .unknown_control_words()
+            .is_empty());
+
+        let field = "{\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 {\\field{\\*\\fldinst PAGE}}\\par}";
+        let edited = original.replacen("{\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 \\par}", field, 1);
+        let transcript = Transcript::try_from(edited).unwrap();
+
+        let words: Vec<(usize, usize, String)> = transcript
+            .unknown_control_words()
+            .into_iter()
+            .map(|unknown| (unknown.line, unknown.column, unknown.word))
+            .collect();
+        assert_eq!(
+            words,
+            vec![
+                (8, 31, String::from("\\field")),
+                (8, 38, String::from("\\*")),
+                (8, 40, String::from("\\fldinst")),
+            ]
+        );
+    }
+}
@@ -0,0 +1,237 @@
+//! Document metadata in the `\info` group of the preamble, combined from
+//! all merged transcripts instead of keeping only that of the first.
+use super::rtf::{escape, plain_text};
+use super::speakers::{speaker_list, with_speaker_list};
+use crate::transcript::Transcript;
+
+const INFO_START: &str = "{\\info";
+
+/// A time in the document info like `{\creatim\yr2021\mo3\dy4\hr10\min5}`,
+/// ordered by when it was.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct InfoTime {
+    pub year: u32,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+}
+
+impl InfoTime {
+    /// Parses the time from the group with the given control word, e.g.
+    /// `\creatim`, in the info group.
+    fn parse(info: &str, word: &str) -> Option<InfoTime> {
+        let group = subgroup(info, word)?;
+        let group = &info[group.0..group.1];
+        let part = |name: &str| -> Option<u32> {
+            let at = group.find(&format!("\\{}", name))? + name.len() + 1;
+            let digits: String = group[at..]
+                .chars()
+                .take_while(char::is_ascii_digit)
+                .collect();
+            digits.parse().ok()
+        };
+        Some(InfoTime {
+            year: part("yr")?,
+            month: part("mo").unwrap_or(1),
+            day: part("dy").unwrap_or(1),
+            hour: part("hr").unwrap_or(0),
+            minute: part("min").unwrap_or(0),
+        })
+    }
+
+    fn to_rtf(self, word: &str) -> String {
+        format!(
+            "{{{}\\yr{}\\mo{}\\dy{}\\hr{}\\min{}}}",
+            word, self.year, self.month, self.day, self.hour, self.minute
+        )
+    }
+}
+
+/// Document info combined from several transcripts.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DocumentInfo {
+    /// Earliest creation time.
+    pub created: Option<InfoTime>,
+    /// Latest revision time.
+    pub revised: Option<InfoTime>,
+    /// Distinct comments, as plain text.
+    pub comments: Vec<String>,
+    /// Entries of the speaker lists like `I:=#EF7C00`, with every speaker
+    /// listed once with its first color.
+    pub speakers: Vec<String>,
+    /// Names of the transcripts the info was combined from.
+    pub sources: Vec<String>,
+}
+
+impl DocumentInfo {
+    /// Adds the info of the transcript, which is listed as the given source.
+    pub fn add(&mut self, source: &str, transcript: &Transcript) {
+        self.sources.push(source.to_string());
+        let info = match info_range(transcript.preamble()) {
+            Some((start, end)) => &transcript.preamble()[start..end],
+            None => return,
+        };
+        if let Some(created) = InfoTime::parse(info, "\\creatim") {
+            self.created = Some(self.created.map_or(created, |c| c.min(created)));
+        }
+        if let Some(revised) = InfoTime::parse(info, "\\revtim") {
+            self.revised = Some(self.revised.map_or(revised, |r| r.max(revised)));
+        }
+        if let Some((start, end)) = subgroup(info, "\\comment") {
+            let comment = plain_text(&info[start + "{\\comment".len()..end - 1]);
+            let comment = comment.trim();
+            if !comment.is_empty() && !self.comments.iter().any(|c| c == comment) {
+                self.comments.push(comment.to_string());
+            }
+        }
+        for entry in speaker_list(transcript.preamble())
+            .unwrap_or_default()
+            .split_inclusive('\n')
+        {
+            let code = entry.split_once(":=").map(|(code, _)| code);
+            let known = self
+                .speakers
+                .iter()
+                .any(|known| known.split_once(":=").map(|(code, _)| code) == code);
+            if code.is_some() && !known {
+                self.speakers.push(entry.to_string());
+            }
+        }
+    }
+
+    /// Comment listing the comments of the sources and the sources.
+    fn comment(&self) -> String {
+        let mut comment = self.comments.join("; ");
+        if !comment.is_empty() {
+            comment.push_str("; ");
+        }
+        comment.push_str("merged from ");
+        comment.push_str(&self.sources.join(", "));
+        comment
+    }
+}
+
+impl Transcript {
+    /// The transcript with the info group in its preamble replaced with
+    /// the combined info. Transcripts without an info group stay as they
+    /// are.
+    pub fn with_info(&self, info: &DocumentInfo) -> Transcript {
+        let preamble = self.preamble();
+        let (start, end) = match info_range(preamble) {
+            Some(range) => range,
+            None => return self.clone(),
+        };
+        let mut group = preamble[start..end].to_string();
+        if let Some(created) = info.created {
+            group = with_subgroup(&group, "\\creatim", &created.to_rtf("\\creatim"));
+        }
+        if let Some(revised) = info.revised {
+            group = with_subgroup(&group, "\\revtim", &revised.to_rtf("\\revtim"));
+        }
+        let comment = format!("{{\\comment {}}}", escape(&info.comment()));
+        group = with_subgroup(&group, "\\comment", &comment);
+        let mut preamble = format!("{}{}{}", &preamble[..start], group, &preamble[end..]);
+        if !info.speakers.is_empty() {
+            if let Some(with_speakers) = with_speaker_list(&preamble, &info.speakers.concat()) {
+                preamble = with_speakers;
+            }
+        }
+        self.with_parts(preamble, self.content().to_string())
+    }
+}
+
+/// Bytes of the info group in the preamble, including its braces.
+fn info_range(preamble: &str) -> Option<(usize, usize)> {
+    let start = preamble.find(INFO_START)?;
+    Some((start, group_end(preamble, start)?))
+}
+
+/// Bytes of the group in the info group that starts with the control
+/// word, including its braces.
+fn subgroup(info: &str, word: &str) -> Option<(usize, usize)> {
+    let pattern = format!("{{{}", word);
+    let start = info.match_indices(&pattern).map(|(at, _)| at).find(|&at| {
+        // `{\comment` but not `{\commentx`
+        !info[at + pattern.len()..].starts_with(|c: char| c.is_ascii_alphabetic())
+    })?;
+    Some((start, group_end(info, start)?))
+}
+
+/// The info group with the group that starts with the control word
+/// replaced, or added at its end if there is none.
+fn with_subgroup(info: &str, word: &str, replacement: &str) -> String {
+    match subgroup(info, word) {
+        Some((start, end)) => format!("{}{}{}", &info[..start], replacement, &info[end..]),
+        None => format!("{}{}}}", &info[..info.len() - 1], replacement),
+    }
+}
+
+/// End of the group starting at `start`, after its closing brace.
+fn group_end(source: &str, start: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut escaped = false;
+    for (at, c) in source[start..].char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(start + at + 1);
+                }
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::convert::TryFrom;
+
+    fn with_times(path: &str, created: &str, revised: &str, speaker_list: &str) -> Transcript {
+        let original = std::fs::read_to_string(path).unwrap();
+        let with_times = original
+            .replace(
+                "{\\comment f4transkript}",
+                &format!("{}{}{{\\comment f4transkript}}", created, revised),
+            )
+            .replace("STo9I0VGN0MwMApaOj0jNDk5M0M3Cg==", speaker_list);
+        Transcript::try_from(with_times).unwrap()
+    }
+
+    #[test]
+    fn info_of_segments_is_combined() {
+        let first = with_times(
+            "testdata/interview-01.rtf",
+            "{\\creatim\\yr2021\\mo3\\dy4\\hr10\\min5}",
+            "{\\revtim\\yr2021\\mo3\\dy5\\hr9\\min0}",
+            "STo9I0VGN0MwMApaOj0jNDk5M0M3Cg==",
+        );
+        // I:=#EF7C00 and B:=#000000
+        let second = with_times(
+            "testdata/interview-02.rtf",
+            "{\\creatim\\yr2021\\mo2\\dy28\\hr16\\min30}",
+            "{\\revtim\\yr2021\\mo4\\dy1\\hr8\\min15}",
+            "STo9I0VGN0MwMApCOj0jMDAwMDAwCg==",
+        );
+        let mut info = DocumentInfo::default();
+        info.add("a-000.rtf", &first);
+        info.add("a-001.rtf", &second);
+        let merged = first.with_info(&info);
+
+        let preamble = merged.preamble();
+        assert!(preamble.contains("{\\creatim\\yr2021\\mo2\\dy28\\hr16\\min30}"));
+        assert!(preamble.contains("{\\revtim\\yr2021\\mo4\\dy1\\hr8\\min15}"));
+        assert!(preamble.contains("{\\comment f4transkript; merged from a-000.rtf, a-001.rtf}"));
+        assert_eq!(
+            speaker_list(preamble).unwrap(),
+            "I:=#EF7C00\nZ:=#4993C7\nB:=#000000\n"
+        );
+        assert_eq!(merged.content(), first.content());
+    }
+}
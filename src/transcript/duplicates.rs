@@ -0,0 +1,149 @@
+//! Sentences that are transcribed twice at a segment boundary, because the
+//! audio of the next segment repeats the end of the one before or the
+//! transcriber of the next segment started a sentence early.
+//!
+//! The last sentence of one segment and the first sentence of the next
+//! one are compared as plain words, ignoring case, punctuation and
+//! timestamps, and count as duplicates if few letters differ.
+use super::formats::without_timestamps;
+use super::lines::Utterance;
+use super::rtf::plain_text;
+use super::sentences::{sentence_ends, Language};
+use crate::timestamp::Timestamp;
+
+/// Share of letters of the longer sentence that must be the same.
+const MIN_SIMILARITY: f64 = 0.85;
+/// Sentences shorter than this, like `Ja.`, are repeated too often to
+/// tell anything.
+const MIN_LETTERS: usize = 10;
+/// Sentences longer than this are not compared, to keep it quick.
+const MAX_LETTERS: usize = 500;
+
+/// The first sentence of an utterance that repeats the last sentence
+/// before it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoundaryDuplicate {
+    /// The repeated sentence as plain text.
+    pub sentence: String,
+    /// How similar the sentences are, from 0 to 1.
+    pub similarity: f64,
+    /// Bytes at the start of the raw speech of the utterance that make up
+    /// the sentence, if it can be dropped without losing timestamps.
+    pub drop_len: Option<usize>,
+}
+
+/// Checks if the first sentence of `first` repeats the last sentence of
+/// `last`.
+pub fn duplicate_at_boundary(
+    last: &Utterance,
+    first: &Utterance,
+    language: Language,
+) -> Option<BoundaryDuplicate> {
+    let last_text = without_timestamps(&plain_text(last.raw_speech()));
+    let last_sentence = last_sentence(&last_text, language);
+
+    let raw = first.raw_speech();
+    let end = sentence_ends(raw, language)
+        .first()
+        .copied()
+        .unwrap_or(raw.len());
+    let first_sentence = &raw[..end];
+    let first_text = without_timestamps(&plain_text(first_sentence));
+
+    let similarity = similarity(&words(last_sentence), &words(&first_text))?;
+    if similarity < MIN_SIMILARITY {
+        return None;
+    }
+    let has_timestamps = Timestamp::timestamps_in(first_sentence).next().is_some();
+    Some(BoundaryDuplicate {
+        sentence: first_text.trim().to_string(),
+        similarity,
+        drop_len: Some(end).filter(|_| !has_timestamps),
+    })
+}
+
+fn last_sentence(text: &str, language: Language) -> &str {
+    let text = text.trim_end();
+    let start = sentence_ends(text, language)
+        .into_iter()
+        .rev()
+        .find(|&end| end < text.len())
+        .unwrap_or(0);
+    &text[start..]
+}
+
+/// The letters and digits of the words, lowercase and separated by single
+/// spaces.
+fn words(text: &str) -> Vec<char> {
+    let mut words = vec![];
+    for word in text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+    {
+        if !words.is_empty() {
+            words.push(' ');
+        }
+        words.extend(word.chars().flat_map(char::to_lowercase));
+    }
+    words
+}
+
+/// One minus the edit distance relative to the longer text, or nothing if
+/// the texts are too short or too long to compare.
+fn similarity(a: &[char], b: &[char]) -> Option<f64> {
+    let longer = a.len().max(b.len());
+    if a.len().min(b.len()) < MIN_LETTERS || longer > MAX_LETTERS {
+        return None;
+    }
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, &ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    Some(1.0 - previous[b.len()] as f64 / longer as f64)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::convert::TryFrom;
+
+    fn utterance(content: &str) -> Utterance<'_> {
+        Utterance::try_from(content).expect("could not parse as utterance")
+    }
+
+    #[test]
+    fn repeated_sentences_are_found_despite_small_differences() {
+        let last = utterance("B: Wir sind dann umgezogen. Das war im Winter 1985. #00:04:58-2#");
+        let first = utterance("B: Das war im Winter 85! Und dann? #00:00:03-1#");
+        let duplicate = duplicate_at_boundary(&last, &first, Language::German).unwrap();
+        assert_eq!(duplicate.sentence, "Das war im Winter 85!");
+        assert!(duplicate.similarity > 0.85);
+        let drop_len = duplicate.drop_len.unwrap();
+        assert_eq!(&first.raw_speech()[drop_len..], " Und dann? #00:00:03-1#");
+
+        let stamped = utterance("B: Das war im #00:00:01-0# Winter 1985.");
+        assert_eq!(
+            duplicate_at_boundary(&last, &stamped, Language::German)
+                .unwrap()
+                .drop_len,
+            None
+        );
+        let other = utterance("B: Im Sommer sind wir dann weg.");
+        assert_eq!(duplicate_at_boundary(&last, &other, Language::German), None);
+        let short = utterance("B: Ja. Ja.");
+        assert_eq!(
+            duplicate_at_boundary(&short, &short, Language::German),
+            None
+        );
+        assert_eq!(
+            first.without_speech_start(drop_len).raw_speech(),
+            "Und dann? #00:00:03-1#"
+        );
+    }
+}
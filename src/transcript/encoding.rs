@@ -0,0 +1,243 @@
+//! Encodes merged transcripts for older F4 versions that expect
+//! Windows-1252 instead of UTF-8.
+//!
+//! RTF stays ASCII in Windows-1252: characters are written as `\'xx`
+//! escapes, or `\uN?` if Windows-1252 has no byte for them, and the header
+//! declares the code page with `\ansicpg1252`.
+use std::io::{Result as IOResult, Write};
+use std::str::FromStr;
+
+use super::rtf::windows_1252_byte;
+
+const BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// Character encoding of merged transcripts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputEncoding {
+    /// UTF-8, the transcript is written as merged.
+    Utf8,
+    /// Windows-1252, with characters it cannot represent as `\uN?` in RTF
+    /// and as `?` in other formats.
+    Cp1252,
+}
+
+impl FromStr for OutputEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Ok(OutputEncoding::Utf8),
+            "cp1252" | "windows-1252" => Ok(OutputEncoding::Cp1252),
+            _ => Err(format!(
+                "unknown output encoding {}, expected utf-8 or cp1252",
+                s
+            )),
+        }
+    }
+}
+
+/// Writer that encodes what is written to it before passing it on.
+///
+/// Incomplete characters, and the RTF header until its line is complete,
+/// are held back until more is written or [`Encoder::finish`] is called,
+/// which must be called after the last write.
+pub struct Encoder<W: Write> {
+    inner: W,
+    encoding: OutputEncoding,
+    rtf: bool,
+    bom: bool,
+    /// Written bytes that are not encoded yet.
+    pending: Vec<u8>,
+    /// If the RTF header still needs its code page.
+    in_header: bool,
+}
+
+impl<W: Write> Encoder<W> {
+    /// Encoder for RTF if `rtf` is set, or for plain text formats, which
+    /// start with a byte order mark if `bom` is set and the encoding is
+    /// UTF-8.
+    pub fn new(inner: W, encoding: OutputEncoding, rtf: bool, bom: bool) -> Encoder<W> {
+        Encoder {
+            inner,
+            encoding,
+            rtf,
+            bom: bom && !rtf && encoding == OutputEncoding::Utf8,
+            pending: vec![],
+            in_header: rtf && encoding == OutputEncoding::Cp1252,
+        }
+    }
+
+    /// Writes what was held back and returns the inner writer.
+    pub fn finish(mut self) -> IOResult<W> {
+        self.encode(true)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    fn encode(&mut self, last: bool) -> IOResult<()> {
+        if self.bom {
+            self.inner.write_all(BOM)?;
+            self.bom = false;
+        }
+        if self.encoding == OutputEncoding::Utf8 {
+            self.inner.write_all(&self.pending)?;
+            self.pending.clear();
+            return Ok(());
+        }
+        if self.in_header {
+            let header_end = match self.pending.iter().position(|&byte| byte == b'\n') {
+                Some(newline) => newline + 1,
+                None if last => self.pending.len(),
+                None => return Ok(()),
+            };
+            let header = String::from_utf8_lossy(&self.pending[..header_end]);
+            let header = with_code_page(&header, 1252);
+            self.inner.write_all(header.as_bytes())?;
+            self.pending.drain(..header_end);
+            self.in_header = false;
+        }
+
+        let (valid, invalid) = match std::str::from_utf8(&self.pending) {
+            Ok(text) => (text, None),
+            Err(err) => {
+                let text = std::str::from_utf8(&self.pending[..err.valid_up_to()])
+                    .expect("valid up to here");
+                (text, err.error_len())
+            }
+        };
+        let mut encoded = Vec::with_capacity(valid.len());
+        for c in valid.chars() {
+            match (windows_1252_byte(c), self.rtf) {
+                (Some(byte), false) => encoded.push(byte),
+                (Some(byte), true) if byte.is_ascii() => encoded.push(byte),
+                (Some(byte), true) => {
+                    encoded.extend_from_slice(format!("\\'{:02x}", byte).as_bytes())
+                }
+                (None, false) => encoded.push(b'?'),
+                (None, true) => {
+                    let mut units = [0; 2];
+                    for unit in c.encode_utf16(&mut units) {
+                        encoded.extend_from_slice(format!("\\u{}?", *unit as i16).as_bytes());
+                    }
+                }
+            }
+        }
+        self.inner.write_all(&encoded)?;
+        // bytes that are not UTF-8 are passed on as they are, incomplete
+        // characters at the end wait for the rest of their bytes
+        let consumed = valid.len() + invalid.unwrap_or(0);
+        if invalid.is_some() {
+            self.inner.write_all(&self.pending[valid.len()..consumed])?;
+        }
+        self.pending.drain(..consumed);
+        if last && !self.pending.is_empty() {
+            self.inner.write_all(&self.pending)?;
+            self.pending.clear();
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> IOResult<usize> {
+        self.pending.extend_from_slice(buf);
+        self.encode(false)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> IOResult<()> {
+        self.encode(false)?;
+        self.inner.flush()
+    }
+}
+
+/// The RTF header with the code page set, replacing the one it declares
+/// or declaring one after `\ansi`, or after `\rtf1` if there is no
+/// `\ansi`.
+fn with_code_page(header: &str, code_page: u32) -> String {
+    const ANSICPG: &str = "\\ansicpg";
+    if let Some(at) = header.find(ANSICPG) {
+        let digits_start = at + ANSICPG.len();
+        let digits_len = header[digits_start..]
+            .bytes()
+            .take_while(u8::is_ascii_digit)
+            .count();
+        return format!(
+            "{}{}{}",
+            &header[..digits_start],
+            code_page,
+            &header[digits_start + digits_len..]
+        );
+    }
+    let after_word = |word: &str| {
+        header
+            .match_indices(word)
+            .map(|(at, _)| at + word.len())
+            .find(|&end| {
+                !header[end..]
+                    .bytes()
+                    .next()
+                    .is_some_and(|byte| byte.is_ascii_alphanumeric())
+            })
+    };
+    match after_word("\\ansi").or_else(|| after_word("\\rtf1")) {
+        Some(end) => format!(
+            "{}{}{}{}",
+            &header[..end],
+            ANSICPG,
+            code_page,
+            &header[end..]
+        ),
+        None => header.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn encode(text: &[u8], encoding: OutputEncoding, rtf: bool, bom: bool) -> Vec<u8> {
+        let mut encoder = Encoder::new(vec![], encoding, rtf, bom);
+        // split in the middle of characters
+        for chunk in text.chunks(3) {
+            encoder.write_all(chunk).unwrap();
+        }
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn rtf_in_windows_1252_declares_its_code_page() {
+        let rtf = "{\\rtf1\\ansi{\\fonttbl}\r\n{Z: Größe – ok ☃\\par}\r\n}";
+        let encoded = encode(rtf.as_bytes(), OutputEncoding::Cp1252, true, false);
+        assert_eq!(
+            String::from_utf8(encoded).unwrap(),
+            "{\\rtf1\\ansi\\ansicpg1252{\\fonttbl}\r\n\
+             {Z: Gr\\'f6\\'dfe \\'96 ok \\u9731?\\par}\r\n}"
+        );
+        assert_eq!(
+            with_code_page("{\\rtf1\\ansi\\ansicpg65001\\deff0", 1252),
+            "{\\rtf1\\ansi\\ansicpg1252\\deff0"
+        );
+        assert_eq!(
+            with_code_page("{\\rtf1{\\fonttbl}", 1252),
+            "{\\rtf1\\ansicpg1252{\\fonttbl}"
+        );
+    }
+
+    #[test]
+    fn text_is_transcoded_or_marked() {
+        let text = "Größe – ☃\n";
+        assert_eq!(
+            encode(text.as_bytes(), OutputEncoding::Cp1252, false, false),
+            b"Gr\xf6\xdfe \x96 ?\n"
+        );
+        assert_eq!(
+            encode(text.as_bytes(), OutputEncoding::Utf8, false, true),
+            [BOM, text.as_bytes()].concat()
+        );
+        assert_eq!(
+            encode(b"{\\rtf1", OutputEncoding::Utf8, true, true),
+            b"{\\rtf1"
+        );
+    }
+}
@@ -1,5 +1,8 @@
 use thiserror::Error;
 
+use crate::exit::{ExitCode, Failure};
+use crate::timestamp::Timestamp;
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Error, Debug)]
@@ -8,6 +11,30 @@ pub enum Error {
     IO(#[from] std::io::Error),
     #[error("Corrupt transcript: {0}")]
     Format(FormatError),
+    /// Shifting a timestamp during merge would exceed the
+    /// biggest timestamp that can be represented.
+    #[error("shifting timestamp {0} by {1} exceeds the maximum of {max}", max = Timestamp::max_value())]
+    TimestampOverflow(Timestamp, Timestamp),
+    /// The start offset of the segment with the given number, starting
+    /// at 1, would shift it to before the start of the interview.
+    #[error("the offset of segment {0} moves it before the start of the interview")]
+    OffsetBeforeStart(usize),
+    /// The start offset of a segment moves its first timestamp before the
+    /// last one of the segment before it.
+    #[error("the offset of segment {0} moves its first timestamp to {1}, before the last timestamp {2} of the segment before it")]
+    OffsetNotMonotonic(usize, Timestamp, Timestamp),
+    /// A template for exporting has an unknown placeholder or
+    /// unbalanced braces, the offending part is included.
+    #[error("malformed template near {0}")]
+    MalformedTemplate(String),
+    /// A transcript that is built has a speaker code that would not be
+    /// read back as a speaker label, like an empty one or one with spaces.
+    #[error("invalid speaker code {0:?}")]
+    MalformedSpeaker(String),
+    /// The thread loading the transcript stopped before it was loaded,
+    /// because it panicked.
+    #[error("loading stopped unexpectedly")]
+    LoadingAborted,
 }
 
 #[derive(Error, Debug)]
@@ -40,3 +67,18 @@ impl Error {
         Self::Format(FormatError::MalformedEpilogue)
     }
 }
+
+impl Failure for Error {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Error::IO(_) => ExitCode::IO,
+            Error::Format(_) => ExitCode::MalformedTranscript,
+            Error::TimestampOverflow(_, _)
+            | Error::OffsetBeforeStart(_)
+            | Error::OffsetNotMonotonic(_, _, _)
+            | Error::MalformedTemplate(_)
+            | Error::MalformedSpeaker(_)
+            | Error::LoadingAborted => ExitCode::Failure,
+        }
+    }
+}
@@ -0,0 +1,543 @@
+//! Formats that merged transcripts are written in, the merged RTF itself,
+//! its JSON representation, or line by line from the merged utterances,
+//! like plain text, subtitles or CSV for NVivo.
+//!
+//! Every format is an [`OutputFormat`] in [`FORMATS`], where merging,
+//! `convert` and templates find it. Going through the utterances,
+//! buffering, leaving out annotations and splitting utterances into
+//! sentences is the same for all of them, so a new format only writes
+//! its header, lines and footer.
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{BufWriter, Result as IOResult, Write};
+
+use super::json::Document;
+use super::outputs::Merged;
+use super::template::Record;
+use crate::timestamp::Timestamp;
+use crate::transcript::{Result, Transcript};
+
+/// A format that merged utterances are written in, one after the other.
+pub trait OutputFormat: fmt::Debug + Sync {
+    /// Name of the format, e.g. `srt`, also in `--strip-annotations`.
+    fn name(&self) -> &'static str;
+
+    /// Endings of the names of files in this format, without the dot,
+    /// e.g. `srt` or `intervals.csv`.
+    fn extensions(&self) -> &'static [&'static str];
+
+    /// If there is a line per sentence rather than per utterance when the
+    /// language of the transcripts is known.
+    fn by_sentence(&self) -> bool {
+        false
+    }
+
+    /// If the format is made from the merged RTF rather than the merged
+    /// utterances, which keeps the merged RTF in memory. Annotations are
+    /// never left out of these formats.
+    fn made_from_rtf(&self) -> bool {
+        false
+    }
+
+    /// Writes the merge in this format, by default the header, a line for
+    /// every merged utterance and the footer.
+    fn write(&self, to: &mut dyn Write, merged: &Merged) -> Result<()> {
+        write_lines(to, &merged.records(self), self)?;
+        Ok(())
+    }
+
+    /// Writes what comes before the first line.
+    fn header(&self, _to: &mut dyn Write) -> IOResult<()> {
+        Ok(())
+    }
+
+    /// Writes the utterance with the given index, starting at zero, and
+    /// the utterance after it, if any. Formats made from the merged RTF
+    /// write nothing.
+    fn line(
+        &self,
+        _to: &mut dyn Write,
+        _index: usize,
+        _record: &Record,
+        _next: Option<&Record>,
+    ) -> IOResult<()> {
+        Ok(())
+    }
+
+    /// Writes what comes after the last line.
+    fn footer(&self, _to: &mut dyn Write) -> IOResult<()> {
+        Ok(())
+    }
+}
+
+impl PartialEq for dyn OutputFormat + '_ {
+    fn eq(&self, other: &Self) -> bool {
+        self.name() == other.name()
+    }
+}
+
+impl Eq for dyn OutputFormat + '_ {}
+
+/// The known formats, in the order their extensions are tried.
+pub static FORMATS: &[&dyn OutputFormat] = &[&Rtf, &Json, &Text, &Srt, &Intervals, &Nvivo, &Html];
+
+/// The format with the given name, or `None` if there is none.
+pub fn format_named(name: &str) -> Option<&'static dyn OutputFormat> {
+    FORMATS
+        .iter()
+        .copied()
+        .find(|format| format.name().eq_ignore_ascii_case(name))
+}
+
+/// The format of files with the given name, by their ending.
+pub(super) fn format_of_file(name: &str) -> Option<&'static dyn OutputFormat> {
+    let name = name.to_ascii_lowercase();
+    FORMATS.iter().copied().find(|format| {
+        format
+            .extensions()
+            .iter()
+            .any(|extension| name.ends_with(&format!(".{}", extension)))
+    })
+}
+
+/// Writes the records in the format, buffered.
+pub(super) fn write_lines<W, F>(to: W, records: &[Record], format: &F) -> IOResult<()>
+where
+    W: Write,
+    F: OutputFormat + ?Sized,
+{
+    let mut to = BufWriter::new(to);
+    format.header(&mut to)?;
+    for (index, record) in records.iter().enumerate() {
+        format.line(&mut to, index, record, records.get(index + 1))?;
+    }
+    format.footer(&mut to)?;
+    to.flush()
+}
+
+/// The merged RTF, like the transcripts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rtf;
+
+impl OutputFormat for Rtf {
+    fn name(&self) -> &'static str {
+        "rtf"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["rtf"]
+    }
+
+    fn made_from_rtf(&self) -> bool {
+        true
+    }
+
+    fn write(&self, to: &mut dyn Write, merged: &Merged) -> Result<()> {
+        to.write_all(merged.rtf())?;
+        Ok(())
+    }
+}
+
+/// The JSON representation of the merged transcript, pretty-printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Json;
+
+impl OutputFormat for Json {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["json"]
+    }
+
+    fn made_from_rtf(&self) -> bool {
+        true
+    }
+
+    fn write(&self, to: &mut dyn Write, merged: &Merged) -> Result<()> {
+        let rtf = String::from_utf8_lossy(merged.rtf()).into_owned();
+        let document = Document::of(&Transcript::try_from(rtf)?);
+        let mut to = BufWriter::new(to);
+        serde_json::to_writer_pretty(&mut to, &document).map_err(std::io::Error::from)?;
+        writeln!(&mut to)?;
+        to.flush()?;
+        Ok(())
+    }
+}
+
+/// Plain text with a paragraph per utterance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Text;
+
+impl OutputFormat for Text {
+    fn name(&self) -> &'static str {
+        "txt"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["txt"]
+    }
+
+    fn line(
+        &self,
+        to: &mut dyn Write,
+        _: usize,
+        record: &Record,
+        _: Option<&Record>,
+    ) -> IOResult<()> {
+        write!(to, "{}: {}\n\n", record.speaker, record.speech)
+    }
+}
+
+/// SubRip subtitles with an entry per utterance, from its start to its
+/// last timestamp, or the start of the next one if it has none.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Srt;
+
+impl OutputFormat for Srt {
+    fn name(&self) -> &'static str {
+        "srt"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["srt"]
+    }
+
+    fn by_sentence(&self) -> bool {
+        true
+    }
+
+    fn line(
+        &self,
+        to: &mut dyn Write,
+        index: usize,
+        record: &Record,
+        next: Option<&Record>,
+    ) -> IOResult<()> {
+        let end = end_time(record, next);
+        writeln!(to, "{}", index + 1)?;
+        writeln!(to, "{} --> {}", srt_time(record.start), srt_time(end))?;
+        writeln!(
+            to,
+            "{}: {}",
+            record.speaker,
+            without_timestamps(&record.speech)
+        )?;
+        writeln!(to)
+    }
+}
+
+/// CSV with a row per utterance that NVivo imports as transcript, with
+/// the columns Timestamp, Speaker and Content, and the span from its start
+/// to its end like in subtitles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Nvivo;
+
+impl OutputFormat for Nvivo {
+    fn name(&self) -> &'static str {
+        "csv"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["csv"]
+    }
+
+    fn by_sentence(&self) -> bool {
+        true
+    }
+
+    fn header(&self, to: &mut dyn Write) -> IOResult<()> {
+        write!(to, "Timestamp,Speaker,Content\r\n")
+    }
+
+    fn line(
+        &self,
+        to: &mut dyn Write,
+        _: usize,
+        record: &Record,
+        next: Option<&Record>,
+    ) -> IOResult<()> {
+        let end = end_time(record, next);
+        write!(
+            to,
+            "{} - {},{},{}\r\n",
+            nvivo_time(record.start),
+            nvivo_time(end),
+            csv_field(&record.speaker),
+            csv_field(&without_timestamps(&record.speech))
+        )
+    }
+}
+
+/// CSV with a row per stretch of speech that ends with a timestamp, from
+/// the timestamp before it, or the start of the utterance, to that
+/// timestamp, with the columns Speaker, Start, End and Text. Speech after
+/// the last timestamp of an utterance lasts until the next utterance
+/// starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Intervals;
+
+impl OutputFormat for Intervals {
+    fn name(&self) -> &'static str {
+        "intervals"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["intervals.csv"]
+    }
+
+    fn header(&self, to: &mut dyn Write) -> IOResult<()> {
+        write!(to, "Speaker,Start,End,Text\r\n")
+    }
+
+    fn line(
+        &self,
+        to: &mut dyn Write,
+        _: usize,
+        record: &Record,
+        next: Option<&Record>,
+    ) -> IOResult<()> {
+        let mut start = record.start;
+        let mut rest = 0;
+        let speech = &record.speech;
+        let mut chunks = vec![];
+        for (offset, timestamp) in Timestamp::timestamps_in(speech) {
+            if offset < rest {
+                continue;
+            }
+            chunks.push((&speech[rest..offset], start, timestamp));
+            start = timestamp;
+            rest = speech[offset + 1..]
+                .find('#')
+                .map_or(speech.len(), |end| offset + end + 2);
+        }
+        let next_start = next.map_or(start, |next| next.start);
+        chunks.push((&speech[rest..], start, next_start.max(start)));
+        for (text, start, end) in chunks {
+            let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            if text.is_empty() {
+                continue;
+            }
+            write!(
+                to,
+                "{},{},{},{}\r\n",
+                csv_field(&record.speaker),
+                nvivo_time(start),
+                nvivo_time(end),
+                csv_field(&text)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A web page with a paragraph per utterance, for reading a transcript
+/// without F4 or a word processor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Html;
+
+impl OutputFormat for Html {
+    fn name(&self) -> &'static str {
+        "html"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["html", "htm"]
+    }
+
+    fn header(&self, to: &mut dyn Write) -> IOResult<()> {
+        writeln!(to, "<!DOCTYPE html>")?;
+        writeln!(to, "<html>")?;
+        writeln!(to, "<head>")?;
+        writeln!(to, "<meta charset=\"utf-8\">")?;
+        writeln!(to, "<title>Transcript</title>")?;
+        writeln!(to, "</head>")?;
+        writeln!(to, "<body>")
+    }
+
+    fn line(
+        &self,
+        to: &mut dyn Write,
+        _: usize,
+        record: &Record,
+        _: Option<&Record>,
+    ) -> IOResult<()> {
+        writeln!(
+            to,
+            "<p><b>{}:</b> {}</p>",
+            html_escape(&record.speaker),
+            html_escape(&record.speech)
+        )
+    }
+
+    fn footer(&self, to: &mut dyn Write) -> IOResult<()> {
+        writeln!(to, "</body>")?;
+        writeln!(to, "</html>")
+    }
+}
+
+/// Formats the timestamp like `00:01:23.4`.
+fn nvivo_time(timestamp: Timestamp) -> String {
+    format!(
+        "{:02}:{:02}:{:02}.{}",
+        timestamp.hours(),
+        timestamp.minutes(),
+        timestamp.seconds(),
+        timestamp.subsecs()
+    )
+}
+
+/// Quotes the field if it contains a comma, quote or line break.
+pub(crate) fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\r', '\n']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Escapes the text for HTML.
+pub(crate) fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// End of the utterance, its last timestamp or the start of the next
+/// utterance if it has none.
+pub(super) fn end_time(record: &Record, next: Option<&Record>) -> Timestamp {
+    record
+        .end
+        .filter(|&end| end > record.start)
+        .or(next.map(|next| next.start))
+        .unwrap_or(record.start)
+}
+
+/// Formats the timestamp like `00:01:23,400`.
+fn srt_time(timestamp: Timestamp) -> String {
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        timestamp.hours(),
+        timestamp.minutes(),
+        timestamp.seconds(),
+        timestamp.subsecs() * 100
+    )
+}
+
+/// The text with its timestamps and the space around them collapsed.
+pub(super) fn without_timestamps(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut rest = 0;
+    for (offset, _) in Timestamp::timestamps_in(text) {
+        if offset < rest {
+            continue;
+        }
+        stripped.push_str(&text[rest..offset]);
+        // up to and including the closing #
+        rest = text[offset + 1..]
+            .find('#')
+            .map_or(text.len(), |end| offset + end + 2);
+    }
+    stripped.push_str(&text[rest..]);
+    stripped.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn record(speaker: &str, speech: &str, start: &str, end: Option<&str>) -> Record {
+        Record {
+            segment: 0,
+            speaker: speaker.to_string(),
+            speech: speech.to_string(),
+            start: Timestamp::parse(start).unwrap(),
+            end: end.map(|end| Timestamp::parse(end).unwrap()),
+            inferred: false,
+        }
+    }
+
+    fn written(records: &[Record], format: &dyn OutputFormat) -> String {
+        let mut buf = vec![];
+        write_lines(&mut buf, records, format).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn nvivo_rows_span_utterances() {
+        let records = vec![
+            record(
+                "I",
+                "Und dann? #00:00:04-2#",
+                "#00:00:00-0#",
+                Some("#00:00:04-2#"),
+            ),
+            record("B", "Ja, \"genau\".", "#00:00:04-2#", None),
+            record(
+                "I",
+                "Gut. #01:02:03-4#",
+                "#00:00:09-0#",
+                Some("#01:02:03-4#"),
+            ),
+        ];
+        assert_eq!(
+            written(&records, &Nvivo),
+            "Timestamp,Speaker,Content\r\n\
+             00:00:00.0 - 00:00:04.2,I,Und dann?\r\n\
+             00:00:04.2 - 00:00:09.0,B,\"Ja, \"\"genau\"\".\"\r\n\
+             00:00:09.0 - 01:02:03.4,I,Gut.\r\n"
+        );
+    }
+
+    #[test]
+    fn interval_rows_end_at_timestamps() {
+        let records = vec![
+            record(
+                "I",
+                "Und dann? #00:00:04-2# Was, dann? #00:00:06-0#",
+                "#00:00:00-0#",
+                None,
+            ),
+            record("B", "Ja. #00:00:08-1# Genau", "#00:00:06-0#", None),
+            record("I", "Gut.", "#00:00:09-0#", None),
+        ];
+        assert_eq!(
+            written(&records, &Intervals),
+            "Speaker,Start,End,Text\r\n\
+             I,00:00:00.0,00:00:04.2,Und dann?\r\n\
+             I,00:00:04.2,00:00:06.0,\"Was, dann?\"\r\n\
+             B,00:00:06.0,00:00:08.1,Ja.\r\n\
+             B,00:00:08.1,00:00:09.0,Genau\r\n\
+             I,00:00:09.0,00:00:09.0,Gut.\r\n"
+        );
+    }
+
+    #[test]
+    fn html_paragraphs_are_escaped() {
+        let records = vec![record("I", "<Lachen> & dann?", "#00:00:00-0#", None)];
+        let html = written(&records, &Html);
+        assert!(html.starts_with("<!DOCTYPE html>\n"), "{}", html);
+        assert!(
+            html.contains("<p><b>I:</b> &lt;Lachen&gt; &amp; dann?</p>\n"),
+            "{}",
+            html
+        );
+        assert!(html.ends_with("</body>\n</html>\n"), "{}", html);
+        assert_eq!(format_named("HTML"), Some(&Html as &dyn OutputFormat));
+        assert_eq!(
+            format_of_file("a.intervals.csv"),
+            Some(&Intervals as &dyn OutputFormat)
+        );
+    }
+
+    #[test]
+    fn timestamps_are_removed_from_subtitles() {
+        assert_eq!(
+            without_timestamps("Ja. #00:04:50-3# Genau.  #00:05:27-8#"),
+            "Ja. Genau."
+        );
+    }
+}
@@ -0,0 +1,64 @@
+//! Metadata blocks before the first utterance of a transcript, like the
+//! date, pseudonym and segment number, which are repeated in the middle
+//! of a merged transcript unless they are left out for later segments.
+use super::trailer::{has_notes, is_speech};
+use crate::transcript::Transcript;
+
+impl Transcript {
+    /// Lines of the content before the first utterance or timestamp, if
+    /// at least one of them has text, e.g. `Interview B07, Teil 2`. Blank
+    /// lines alone are not a header.
+    pub fn header(&self) -> &str {
+        let content = self.content();
+        let mut speech_start = content.len();
+        let mut offset = 0;
+        for raw in content.split_inclusive('\n') {
+            if is_speech(self.dialect(), raw.trim_end_matches(['\r', '\n'])) {
+                speech_start = offset;
+                break;
+            }
+            offset += raw.len();
+        }
+        let header = &content[..speech_start];
+        if has_notes(self.dialect(), header) {
+            header
+        } else {
+            ""
+        }
+    }
+
+    /// The transcript without its header.
+    pub fn without_header(&self) -> Transcript {
+        let content = self.content();
+        self.with_parts(
+            self.preamble().to_string(),
+            content[self.header().len()..].to_string(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryFrom;
+
+    use crate::transcript::Transcript;
+
+    #[test]
+    fn notes_before_the_first_utterance_are_a_header() {
+        const HEADER: &str = "{\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 Interview B07, Teil 2\\par}\r\n\
+            {\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 \\par}\r\n";
+        let original = std::fs::read_to_string("testdata/interview-02.rtf").unwrap();
+        let transcript = Transcript::try_from(original.clone()).unwrap();
+        assert_eq!(transcript.header(), "");
+
+        let start = transcript.preamble().len();
+        let with_header = format!("{}{}{}", &original[..start], HEADER, &original[start..]);
+        let transcript = Transcript::try_from(with_header).unwrap();
+        assert_eq!(transcript.header(), HEADER);
+        assert_eq!(transcript.without_header().header(), "");
+        assert_eq!(
+            transcript.without_header().content().len(),
+            transcript.content().len() - HEADER.len()
+        );
+    }
+}
@@ -0,0 +1,340 @@
+//! A JSON representation of transcripts, for exporting them and for
+//! building F4 transcripts from what other tools produce.
+//!
+//! A document looks like this:
+//!
+//! ```json
+//! {
+//!   "format": "f4tapir-transcript",
+//!   "version": 1,
+//!   "metadata": {
+//!     "speakers": [{ "code": "I", "color": "#EF7C00" }],
+//!     "comment": "f4transkript",
+//!     "created": "2021-03-04T10:05"
+//!   },
+//!   "blocks": [
+//!     {
+//!       "type": "utterance",
+//!       "speaker": "I",
+//!       "speech": "Was soll das heißen? #00:01:50-6#",
+//!       "timestamps": [{ "offset": 21, "timestamp": "#00:01:50-6#", "tenths": 1106 }],
+//!       "runs": [{ "text": "Was soll das heißen? ", "end": "#00:01:50-6#" }]
+//!     },
+//!     { "type": "note", "text": "Aufnahme endet." }
+//!   ]
+//! }
+//! ```
+//!
+//! All text is plain text. Offsets count bytes in the speech. The speech is
+//! what is written when building RTF, the timestamps and runs into which
+//! the timestamps divide it are only there for consumers and may be left
+//! out of input documents. Blank paragraphs are not represented, they are
+//! put between all blocks when building RTF like F4 does.
+use std::convert::TryFrom;
+
+use serde::{Deserialize, Serialize};
+
+use super::docinfo::DocumentInfo;
+use super::lines::Line;
+use super::rtf::{escape, plain_text};
+use super::speakers::{encode_base64, speaker_list};
+use crate::timestamp::Timestamp;
+use crate::transcript::{Result, Transcript};
+
+/// Value of `format` in every document.
+pub const FORMAT: &str = "f4tapir-transcript";
+/// Version of the representation, increased on incompatible changes.
+pub const VERSION: u32 = 1;
+
+/// Start of every paragraph that F4 writes.
+const PARAGRAPH_START: &str = "{\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 ";
+/// Color given to speakers without a color.
+const DEFAULT_COLOR: &str = "#000000";
+
+/// A whole transcript.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Document {
+    pub format: String,
+    pub version: u32,
+    #[serde(default)]
+    pub metadata: Metadata,
+    pub blocks: Vec<Block>,
+}
+
+/// Information about the transcript from its preamble.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Metadata {
+    /// Speakers in the order F4 lists them.
+    #[serde(default)]
+    pub speakers: Vec<Speaker>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    /// Creation time like `2021-03-04T10:05`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+}
+
+/// A speaker code like `I` with the color F4 highlights it with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Speaker {
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+}
+
+/// A paragraph of the transcript.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Block {
+    Utterance(Utterance),
+    /// A paragraph that is not an utterance, e.g. a note of the
+    /// transcriber.
+    Note {
+        text: String,
+    },
+}
+
+/// What a speaker said.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Utterance {
+    pub speaker: String,
+    /// The speech with its timestamps like `#00:01:50-6#`.
+    pub speech: String,
+    #[serde(default)]
+    pub timestamps: Vec<TimestampAt>,
+    #[serde(default)]
+    pub runs: Vec<Run>,
+}
+
+/// A timestamp in the speech.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimestampAt {
+    /// Byte offset of the timestamp in the speech.
+    pub offset: usize,
+    pub timestamp: String,
+    /// The timestamp in tenths of a second.
+    pub tenths: u64,
+}
+
+/// Speech up to the next timestamp, or to the end of the utterance.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Run {
+    pub text: String,
+    /// The timestamp after the text, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
+}
+
+impl Document {
+    /// The document for the transcript.
+    pub fn of(transcript: &Transcript) -> Document {
+        let mut info = DocumentInfo::default();
+        info.add("", transcript);
+        let speakers = speaker_list(transcript.preamble())
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|entry| entry.split_once(":="))
+            .map(|(code, color)| Speaker {
+                code: code.to_string(),
+                color: Some(color.to_string()).filter(|color| !color.is_empty()),
+            })
+            .collect();
+        let metadata = Metadata {
+            speakers,
+            comment: info.comments.first().cloned(),
+            created: info.created.map(|created| {
+                format!(
+                    "{:04}-{:02}-{:02}T{:02}:{:02}",
+                    created.year, created.month, created.day, created.hour, created.minute
+                )
+            }),
+        };
+        let blocks = transcript
+            .lines()
+            .filter_map(|line| match line {
+                Line::Utterance(utterance) => Some(Block::Utterance(Utterance::new(
+                    &utterance.speaker_text(),
+                    &utterance.speech_text(),
+                ))),
+                Line::Paragraph(paragraph) => note(paragraph.content()),
+                Line::Formatted(formatted) => note(formatted),
+                Line::Other(_) => None,
+            })
+            .collect();
+        Document {
+            format: FORMAT.to_string(),
+            version: VERSION,
+            metadata,
+            blocks,
+        }
+    }
+
+    /// Builds F4 RTF with a paragraph per block.
+    pub fn to_rtf(&self) -> String {
+        let list: String = self
+            .metadata
+            .speakers
+            .iter()
+            .map(|speaker| {
+                format!(
+                    "{}:={}\n",
+                    speaker.code,
+                    speaker.color.as_deref().unwrap_or(DEFAULT_COLOR)
+                )
+            })
+            .collect();
+        let comment = self.metadata.comment.as_deref().unwrap_or("f4tapir");
+        let created = self
+            .metadata
+            .created
+            .as_deref()
+            .and_then(created_to_rtf)
+            .unwrap_or_default();
+        let mut rtf = format!(
+            "{{\\rtf1\\ansi{{\\fonttbl{{\\f0\\fnil Arial;}}}}\r\n\
+             {{\\colortbl\\red0\\green0\\blue0;}}\r\n\
+             {{\\*\\generator f4tapir}}{{\\info{{\\doccomm TS:#HH:MM:SS-m# \
+             #SPEAKER_LIST_START#{}\r\n#SPEAKER_LIST_END#\\'0A \\'0D\r\n}}\
+             {}{{\\comment {}}}}}\
+             \\paperw11906\\paperh16838\\margl1417\\margr1417\\margt1417\\margb1134\r\n\
+             \\jexpand\r\n",
+            encode_base64(list.as_bytes()),
+            created,
+            escape(comment)
+        );
+        let blank = format!("{}\\par}}\r\n", PARAGRAPH_START);
+        rtf.push_str(&blank);
+        for block in &self.blocks {
+            match block {
+                Block::Utterance(utterance) => rtf.push_str(&format!(
+                    "{p}{p}{}:}}{p} {}}}\\par}}\r\n",
+                    escape(&utterance.speaker),
+                    escape(&utterance.speech),
+                    p = PARAGRAPH_START
+                )),
+                Block::Note { text } => {
+                    rtf.push_str(&format!("{}{}\\par}}\r\n", PARAGRAPH_START, escape(text)))
+                }
+            }
+            rtf.push_str(&blank);
+        }
+        rtf.push_str("\r\n}");
+        rtf
+    }
+
+    /// Builds the F4 transcript with a paragraph per block.
+    pub fn to_transcript(&self) -> Result<Transcript> {
+        Transcript::try_from(self.to_rtf())
+    }
+}
+
+impl Utterance {
+    /// The utterance with the timestamps and runs of the speech.
+    pub fn new(speaker: &str, speech: &str) -> Utterance {
+        let mut timestamps = vec![];
+        let mut runs = vec![];
+        let mut run_start = 0;
+        for (offset, timestamp) in Timestamp::extract_timestamps(speech.as_bytes()) {
+            let end = speech[offset + 1..]
+                .find('#')
+                .map_or(speech.len(), |end| offset + end + 2);
+            let text = &speech[offset..end];
+            timestamps.push(TimestampAt {
+                offset,
+                timestamp: text.to_string(),
+                tenths: timestamp.tenths(),
+            });
+            runs.push(Run {
+                text: speech[run_start..offset].to_string(),
+                end: Some(text.to_string()),
+            });
+            run_start = end;
+        }
+        if run_start < speech.len() {
+            runs.push(Run {
+                text: speech[run_start..].to_string(),
+                end: None,
+            });
+        }
+        Utterance {
+            speaker: speaker.to_string(),
+            speech: speech.to_string(),
+            timestamps,
+            runs,
+        }
+    }
+}
+
+/// A note with the text of the paragraph, unless it is blank.
+fn note(paragraph: &str) -> Option<Block> {
+    let text = plain_text(paragraph);
+    let text = text.trim();
+    Some(Block::Note {
+        text: text.to_string(),
+    })
+    .filter(|_| !text.is_empty())
+}
+
+/// `{\creatim…}` for a time like `2021-03-04T10:05`.
+fn created_to_rtf(created: &str) -> Option<String> {
+    let number = |range: std::ops::Range<usize>| created.get(range)?.parse::<u32>().ok();
+    Some(format!(
+        "{{\\creatim\\yr{}\\mo{}\\dy{}\\hr{}\\min{}}}",
+        number(0..4)?,
+        number(5..7)?,
+        number(8..10)?,
+        number(11..13).unwrap_or(0),
+        number(14..16).unwrap_or(0)
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn transcripts_round_trip_through_json() {
+        let transcript = Transcript::from_file("testdata/interview-01.rtf").unwrap();
+        let document = Document::of(&transcript);
+        assert_eq!(document.metadata.speakers[0].code, "I");
+        assert_eq!(
+            document.metadata.speakers[1].color.as_deref(),
+            Some("#4993C7")
+        );
+        let first = match &document.blocks[0] {
+            Block::Utterance(utterance) => utterance,
+            block => panic!("expected an utterance, got {:?}", block),
+        };
+        assert_eq!(first.speaker, "I");
+        assert!(first.speech.starts_with("Was hat man früher"));
+        assert_eq!(first.timestamps[0].timestamp, "#00:00:27-8#");
+        assert_eq!(first.timestamps[0].tenths, 278);
+        assert_eq!(first.runs[0].end.as_deref(), Some("#00:00:27-8#"));
+        assert_eq!(
+            first.runs[1].text,
+            "? So daheim und beim fortgehen meine ich. "
+        );
+
+        let json = serde_json::to_string(&document).unwrap();
+        let parsed: Document = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, document);
+        let rebuilt = parsed.to_transcript().unwrap();
+        assert_eq!(Document::of(&rebuilt), document);
+        assert_eq!(rebuilt.last_timestamp(), transcript.last_timestamp());
+    }
+
+    #[test]
+    fn documents_without_derived_fields_are_read() {
+        let document: Document = serde_json::from_str(
+            r#"{"format": "f4tapir-transcript", "version": 1, "blocks": [
+                {"type": "utterance", "speaker": "I", "speech": "Na {gut} #00:00:01-0#"},
+                {"type": "note", "text": "Ende"}
+            ]}"#,
+        )
+        .unwrap();
+        let rtf = document.to_rtf();
+        assert!(rtf.contains(" Na \\{gut\\} #00:00:01-0#}\\par}"));
+        let rebuilt = Document::of(&document.to_transcript().unwrap());
+        assert_eq!(rebuilt.blocks[1], document.blocks[1]);
+    }
+}
@@ -0,0 +1,125 @@
+//! Line endings of transcript files.
+//!
+//! F4 writes `\r\n`, but transcripts resaved on Linux or macOS may use
+//! `\n`. Transcripts are normalized to `\r\n` when loading and written
+//! with the line ending they were loaded with.
+use std::io::{Result, Write};
+
+/// Line ending convention of a transcript file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    /// `\r\n`, as written by F4.
+    #[default]
+    CrLf,
+    /// `\n` only.
+    Lf,
+}
+
+impl LineEnding {
+    /// `Lf` if the text has line feeds but no `\r\n` at all, `CrLf`
+    /// otherwise.
+    pub fn detect(text: &str) -> LineEnding {
+        if text.contains('\n') && !text.contains("\r\n") {
+            LineEnding::Lf
+        } else {
+            LineEnding::CrLf
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::CrLf => "\r\n",
+            LineEnding::Lf => "\n",
+        }
+    }
+
+    /// Converts every line ending in the text to `\r\n`, if it has any
+    /// other ones.
+    pub(super) fn normalize(text: String) -> String {
+        if text.matches('\n').count() == text.matches("\r\n").count() {
+            text
+        } else {
+            text.replace("\r\n", "\n").replace('\n', "\r\n")
+        }
+    }
+}
+
+/// Writes `\r\n` as the given line ending.
+pub(super) struct LineEndingWriter<W> {
+    inner: W,
+    line_ending: LineEnding,
+    /// A `\r` at the end of the last write, which is dropped if the next
+    /// write starts with `\n`.
+    pending_cr: bool,
+}
+
+impl<W: Write> LineEndingWriter<W> {
+    pub(super) fn new(inner: W, line_ending: LineEnding) -> Self {
+        LineEndingWriter {
+            inner,
+            line_ending,
+            pending_cr: false,
+        }
+    }
+}
+
+impl<W: Write> Write for LineEndingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if self.line_ending == LineEnding::CrLf {
+            return self.inner.write(buf);
+        }
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if std::mem::take(&mut self.pending_cr) && buf[0] != b'\n' {
+            self.inner.write_all(b"\r")?;
+        }
+        let (complete, pending_cr) = match buf.strip_suffix(b"\r") {
+            Some(complete) => (complete, true),
+            None => (buf, false),
+        };
+        let mut rest = complete;
+        while let Some(at) = rest.windows(2).position(|pair| pair == b"\r\n") {
+            self.inner.write_all(&rest[..at])?;
+            rest = &rest[at + 1..];
+        }
+        self.inner.write_all(rest)?;
+        self.pending_cr = pending_cr;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if std::mem::take(&mut self.pending_cr) {
+            self.inner.write_all(b"\r")?;
+        }
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn crlf_is_written_as_lf_across_writes() {
+        let mut buf = vec![];
+        {
+            let mut to = LineEndingWriter::new(&mut buf, LineEnding::Lf);
+            to.write_all(b"a\r\nb\r").unwrap();
+            to.write_all(b"\nc\r").unwrap();
+            to.write_all(b"d\r").unwrap();
+            to.flush().unwrap();
+        }
+        assert_eq!(buf, b"a\nb\nc\rd\r");
+    }
+
+    #[test]
+    fn line_endings_are_detected_and_normalized() {
+        assert_eq!(LineEnding::detect("a\nb\n"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("a\r\nb\n"), LineEnding::CrLf);
+        assert_eq!(
+            LineEnding::normalize("a\nb\r\nc\n".into()),
+            "a\r\nb\r\nc\r\n"
+        );
+    }
+}
@@ -5,7 +5,7 @@
 use std::convert::TryFrom;
 use std::io::{Result, Write};
 
-use super::rtf::{Rtf, TokenKind};
+use super::rtf::{plain_text, Rtf, TokenKind};
 
 use crate::timestamp::Timestamp;
 use crate::transcript::Transcript;
@@ -14,107 +14,248 @@ pub use paragraph::Paragraph;
 pub use utterance::Utterance;
 
 /// The canonical preamble to be used at the beginning of
-/// every line, when it is written.
+/// every line that was not parsed from a transcript, when it
+/// is written. Parsed lines keep their original preamble.
 const LINE_PREAMBLE: &str = "{\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 ";
 const LINE_EPILOGUE: &str = "\\par}";
+/// Start of a line in the `\pard` dialect.
+const PARD_LINE_START: &str = "\\pard";
+/// End of a line in the `\pard` dialect.
+const PARD_LINE_EPILOGUE: &str = "\\par";
+
+/// The way paragraphs are written in a transcript.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    /// Every paragraph is a group, e.g. `{\f0 \fs24 ... \par}`,
+    /// the way f4transkript writes them.
+    Group,
+    /// Paragraphs start with `\pard` and end with `\par` without
+    /// a surrounding group, the way some other editors write them.
+    Pard,
+}
+
+impl Dialect {
+    /// Writes a paragraph with the given RTF, e.g. text escaped with
+    /// `escape`, the way paragraphs are written in the dialect.
+    pub fn write_paragraph<W: Write>(self, mut to: W, text: &str) -> Result<()> {
+        match self {
+            Dialect::Group => write!(&mut to, "{}{}{}\r\n", LINE_PREAMBLE, text, LINE_EPILOGUE),
+            Dialect::Pard => write!(
+                &mut to,
+                "{} {}{}\r\n",
+                PARD_LINE_START, text, PARD_LINE_EPILOGUE
+            ),
+        }
+    }
+
+    /// Guesses the dialect by checking how most of the lines in
+    /// the given transcript content start.
+    pub fn detect(content: &str) -> Dialect {
+        let pard_lines = content
+            .lines()
+            .filter(|line| line.starts_with(PARD_LINE_START))
+            .count();
+        let group_lines = content.lines().filter(|line| line.starts_with('{')).count();
+        if pard_lines > group_lines {
+            Dialect::Pard
+        } else {
+            Dialect::Group
+        }
+    }
+}
+
+/// How utterances are written, which is as they are by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Style {
+    /// Writes speaker labels uniformly as `Z: `, whether they were typed
+    /// `Z :`, `Z:` or `Z:  `, and collapses repeated spaces in speech.
+    pub tidy: bool,
+    /// Breaks utterances into lines of at most this many characters with
+    /// `\line`, for software that cannot handle long paragraphs.
+    pub wrap: Option<usize>,
+}
 
 /// Iterates over the utterances in a borrowed string
 /// slice.
-pub struct Lines<'a>(std::str::Lines<'a>);
+pub struct Lines<'a> {
+    lines: std::str::Lines<'a>,
+    dialect: Dialect,
+    style: Style,
+}
 
 impl<'a> Lines<'a> {
     /// Creates an iterator over the lines in the given
     /// transcript.
-    pub fn new(source: &Transcript) -> Lines {
-        Lines(source.content().lines())
+    pub fn new(source: &Transcript) -> Lines<'_> {
+        Lines {
+            lines: source.content().lines(),
+            dialect: source.dialect(),
+            style: Style::default(),
+        }
+    }
+
+    /// Makes utterances write themselves in the given style.
+    pub fn style(self, style: Style) -> Self {
+        Lines { style, ..self }
+    }
+
+    pub(super) fn parse_line_in_dialect(dialect: Dialect, line: &'a str) -> Line<'a> {
+        match dialect {
+            Dialect::Group => Self::parse_line(line),
+            Dialect::Pard => Self::parse_pard_line(line),
+        }
     }
 
     fn parse_line(line: &'a str) -> Line<'a> {
         Self::trim_preamble_and_epilogue(line)
-            .map(|line| match Utterance::try_from(line) {
-                // ok, valid utterance
-                Ok(utterance) => Line::Utterance(utterance),
-                // also ok, a generic non-empty paragraph
-                _ => Line::Paragraph(line.into()),
+            .map(|(preamble, content)| Self::parse_content(preamble, content, LINE_EPILOGUE))
+            .unwrap_or_else(|| {
+                if Self::is_paragraph_group(line) {
+                    Line::Formatted(line)
+                } else {
+                    Line::Other(line)
+                }
             })
-            .unwrap_or_else(|| Line::Other(line))
     }
 
-    fn trim_preamble_and_epilogue(line: &str) -> Option<&str> {
-        let mut rtf = Rtf::from(line);
-
-        rtf.next()
-            .filter(|token| token.kind() == TokenKind::GroupStart)?;
+    fn parse_pard_line(line: &'a str) -> Line<'a> {
+        Self::trim_pard_preamble_and_epilogue(line)
+            .map(|(preamble, content)| Self::parse_content(preamble, content, PARD_LINE_EPILOGUE))
+            .unwrap_or_else(|| {
+                if line.starts_with(PARD_LINE_START) {
+                    Line::Formatted(line)
+                } else {
+                    Line::Other(line)
+                }
+            })
+    }
 
-        // \f0 or \f1
-        rtf.next()
-            .filter(|token| token.kind() == TokenKind::ControlWord)
-            .filter(|token| token.as_str() == "\\f")?;
-        rtf.next()
-            .filter(|token| token.kind() == TokenKind::Parameter)?;
-        rtf.next()
-            .filter(|token| token.kind() == TokenKind::Delimiter)?;
+    fn parse_content(preamble: &'a str, content: &'a str, epilogue: &'a str) -> Line<'a> {
+        match Utterance::try_from(content) {
+            // ok, valid utterance
+            Ok(utterance) => Line::Utterance(utterance.with_line_frame(preamble, epilogue)),
+            // also ok, a generic non-empty paragraph
+            _ => Line::Paragraph(Paragraph::new(preamble, content, epilogue)),
+        }
+    }
 
-        // \fs24
-        rtf.next()
-            .filter(|token| token.kind() == TokenKind::ControlWord)
-            .filter(|token| token.as_str() == "\\fs")?;
-        rtf.next()
-            .filter(|token| token.kind() == TokenKind::Parameter)?;
-        rtf.next()
-            .filter(|token| token.kind() == TokenKind::Delimiter)?;
+    /// Splits a line like `\pard\plain \f0\fs24 I: Hello.\par` into
+    /// the leading control words, the content and the `\par` at the end.
+    fn trim_pard_preamble_and_epilogue(line: &str) -> Option<(&str, &str)> {
+        if !line.starts_with(PARD_LINE_START) {
+            return None;
+        }
+        let without_epilogue = line.strip_suffix(PARD_LINE_EPILOGUE)?;
 
-        // \ul0
-        rtf.next()
-            .filter(|token| token.kind() == TokenKind::ControlWord)
-            .filter(|token| token.as_str() == "\\ul")?;
-        rtf.next()
-            .filter(|token| token.kind() == TokenKind::Parameter)?;
-        rtf.next()
-            .filter(|token| token.kind() == TokenKind::Delimiter)?;
+        let mut content_start = 0;
+        for token in Rtf::from(without_epilogue) {
+            match token.kind() {
+                TokenKind::ControlWord
+                | TokenKind::ControlSym
+                | TokenKind::Parameter
+                | TokenKind::Delimiter => content_start = token.source().end(),
+                _ => break,
+            }
+        }
+        Some(without_epilogue.split_at(content_start))
+    }
 
-        // \b0
-        rtf.next()
-            .filter(|token| token.kind() == TokenKind::ControlWord)
-            .filter(|token| token.as_str() == "\\b")?;
-        rtf.next()
-            .filter(|token| token.kind() == TokenKind::Parameter)?;
-        rtf.next()
-            .filter(|token| token.kind() == TokenKind::Delimiter)?;
+    /// Checks if the line is a single RTF group that ends a paragraph,
+    /// even if the formatting at the start of the line is not the one
+    /// we expect for utterances.
+    fn is_paragraph_group(line: &str) -> bool {
+        if !line.starts_with('{') || !line.ends_with(LINE_EPILOGUE) {
+            return false;
+        }
 
-        // \i0
-        rtf.next()
-            .filter(|token| token.kind() == TokenKind::ControlWord)
-            .filter(|token| token.as_str() == "\\i")?;
-        rtf.next()
-            .filter(|token| token.kind() == TokenKind::Parameter)?;
-        rtf.next()
-            .filter(|token| token.kind() == TokenKind::Delimiter)?;
+        let mut depth = 0_usize;
+        let mut tokens = Rtf::from(line).peekable();
+        while let Some(token) = tokens.next() {
+            match token.kind() {
+                TokenKind::GroupStart => depth += 1,
+                TokenKind::GroupEnd => {
+                    depth = match depth.checked_sub(1) {
+                        Some(depth) => depth,
+                        None => return false,
+                    };
+                    // the outermost group must only be closed at the very end
+                    if depth == 0 && tokens.peek().is_some() {
+                        return false;
+                    }
+                }
+                _ => (),
+            }
+        }
+        depth == 0
+    }
 
-        // \cf0
-        rtf.next()
-            .filter(|token| token.kind() == TokenKind::ControlWord)
-            .filter(|token| token.as_str() == "\\cf")?;
+    /// Splits the line into the opening group with the formatting
+    /// of the line and the content after it, excluding the epilogue.
+    ///
+    /// The formatting is any run of control words with or without
+    /// parameters, e.g. `\f0 \fs24 \ul0 \b0 \i0 \cf0` the way
+    /// f4transkript writes it, but also `\f2`, other orders or extra words
+    /// like `\lang1031`. It is kept as it is for writing the line.
+    fn trim_preamble_and_epilogue(line: &str) -> Option<(&str, &str)> {
+        if !Self::is_paragraph_group(line) {
+            return None;
+        }
+        let without_epilogue = line.strip_suffix(LINE_EPILOGUE)?;
+        let mut rtf = Rtf::from(without_epilogue);
         rtf.next()
-            .filter(|token| token.kind() == TokenKind::Parameter)?;
-        let last_token_of_preamble = rtf
-            .next()
-            .filter(|token| token.kind() == TokenKind::Delimiter)?;
+            .filter(|token| token.kind() == TokenKind::GroupStart)?;
 
-        let content_start = last_token_of_preamble.source().end();
-        let without_preamble = &line[content_start..];
-        without_preamble.strip_suffix(LINE_EPILOGUE)
+        let mut content_start = None;
+        for token in rtf {
+            match token.kind() {
+                TokenKind::ControlWord if !TEXT_CONTROL_WORDS.contains(&token.as_str()) => {
+                    content_start = Some(token.source().end())
+                }
+                TokenKind::Parameter | TokenKind::Delimiter if content_start.is_some() => {
+                    content_start = Some(token.source().end())
+                }
+                _ => break,
+            }
+        }
+        Some(without_epilogue.split_at(content_start?))
     }
 }
 
+/// Control words that stand for text rather than format it, which start
+/// the content of a line instead of ending its preamble.
+const TEXT_CONTROL_WORDS: &[&str] = &[
+    "\\u",
+    "\\tab",
+    "\\line",
+    "\\emdash",
+    "\\endash",
+    "\\lquote",
+    "\\rquote",
+    "\\ldblquote",
+    "\\rdblquote",
+    "\\bullet",
+];
+
 #[derive(Debug)]
 pub enum Line<'a> {
     Paragraph(Paragraph<'a>),
     Utterance(Utterance<'a>),
+    /// A paragraph group with inline formatting we do not understand,
+    /// e.g. bold or italic runs. The markup is kept as-is, only the
+    /// timestamps are adjusted.
+    Formatted(&'a str),
     Other(&'a str),
 }
 
 impl<'a> Line<'a> {
+    /// Writes the line in the given style, if it is an utterance.
+    fn with_style(self, style: Style) -> Self {
+        match self {
+            Self::Utterance(utterance) => Self::Utterance(utterance.with_style(style)),
+            other => other,
+        }
+    }
+
     pub fn write_adjusted<W>(&self, mut to: W, adjust_by: Timestamp) -> Result<()>
     where
         W: Write,
@@ -123,11 +264,27 @@ impl<'a> Line<'a> {
             // adjust timestamps in utterances and other paragraphs
             Self::Utterance(utterance) => utterance.write_adjusted(&mut to, adjust_by),
             Self::Paragraph(paragraph) => paragraph.write_adjusted(&mut to, adjust_by),
+            Self::Formatted(formatted) => {
+                Timestamp::write_with_adjusted_timestamps(&mut to, formatted, adjust_by)?;
+                write!(&mut to, "\r\n")
+            }
             // unrecognized RTF content, write as-is
             Self::Other(other) => write!(&mut to, "{}\r\n", other),
         }
     }
 
+    /// Number of timestamps in this line that are adjusted when
+    /// writing it with `write_adjusted`.
+    pub fn adjustable_timestamps(&self) -> usize {
+        let adjustable = match self {
+            Self::Utterance(utterance) => utterance.raw_speech(),
+            Self::Paragraph(paragraph) => paragraph.content(),
+            Self::Formatted(formatted) => formatted,
+            Self::Other(_) => return 0,
+        };
+        Timestamp::extract_timestamps(adjustable.as_bytes()).len()
+    }
+
     pub fn utterance(&'a self) -> Option<&'a Utterance<'a>> {
         match self {
             Line::Utterance(utterance) => Some(utterance),
@@ -135,6 +292,24 @@ impl<'a> Line<'a> {
         }
     }
 
+    /// Content of a paragraph without a speaker label that looks like it
+    /// continues the speech of an utterance, i.e. has some letters and is
+    /// not a segment boundary marker.
+    pub fn continuation(&'a self) -> Option<&'a str> {
+        let content = match self {
+            Line::Paragraph(paragraph) => paragraph.content(),
+            _ => return None,
+        };
+        let text = plain_text(content);
+        let text = text.trim();
+        let marker = text.starts_with("---") && text.ends_with("---");
+        if marker || !text.chars().any(char::is_alphabetic) {
+            None
+        } else {
+            Some(content)
+        }
+    }
+
     #[cfg(test)]
     pub fn paragraph(&'a self) -> Option<&'a Paragraph<'a>> {
         match self {
@@ -150,61 +325,70 @@ impl<'a> Iterator for Lines<'a> {
     type Item = Line<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.0.next().map(Self::parse_line)
+        let (dialect, style) = (self.dialect, self.style);
+        self.lines
+            .next()
+            .map(|line| Self::parse_line_in_dialect(dialect, line).with_style(style))
     }
 }
 
 impl<'a> DoubleEndedIterator for Lines<'a> {
     fn next_back(&mut self) -> Option<Self::Item> {
-        self.0.next_back().map(Self::parse_line)
+        let (dialect, style) = (self.dialect, self.style);
+        self.lines
+            .next_back()
+            .map(|line| Self::parse_line_in_dialect(dialect, line).with_style(style))
     }
 }
 
 mod paragraph {
-    use super::{LINE_EPILOGUE, LINE_PREAMBLE};
     use crate::timestamp::Timestamp;
     use std::io::{Result, Write};
 
     /// A paragraph with non-utterance and non-blank content.
     ///
-    /// The reference excludes a surrounding `{` on the
-    /// left and a `\par}` on the right, holding only the
-    /// actual content of the paragraph.
+    /// The content excludes the opening group with the line
+    /// formatting on the left, which is kept in `preamble`,
+    /// and a `\par}` on the right, which is kept in `epilogue`,
+    /// holding only the actual content of the paragraph.
     #[derive(Debug)]
     pub struct Paragraph<'a> {
+        preamble: &'a str,
         content: &'a str,
+        epilogue: &'a str,
     }
 
     impl<'a> Paragraph<'a> {
+        pub fn new(preamble: &'a str, content: &'a str, epilogue: &'a str) -> Self {
+            Paragraph {
+                preamble,
+                content,
+                epilogue,
+            }
+        }
+
         pub fn write_adjusted<W>(&self, mut to: W, adjust_by: Timestamp) -> Result<()>
         where
             W: Write,
         {
-            write!(&mut to, "{}", LINE_PREAMBLE)?;
+            write!(&mut to, "{}", self.preamble)?;
             Timestamp::write_with_adjusted_timestamps(&mut to, self.content, adjust_by)?;
-            write!(&mut to, "{}\r\n", LINE_EPILOGUE)?;
+            write!(&mut to, "{}\r\n", self.epilogue)?;
             Ok(())
         }
 
-        #[cfg(test)]
         pub fn content(&self) -> &str {
             self.content
         }
     }
-
-    impl<'a> From<&'a str> for Paragraph<'a> {
-        fn from(content: &'a str) -> Self {
-            Paragraph { content }
-        }
-    }
 }
 
 mod utterance {
     use std::convert::TryFrom;
     use std::io::{Result, Write};
 
-    use super::{Rtf, LINE_EPILOGUE, LINE_PREAMBLE};
-    use crate::timestamp::Timestamp;
+    use super::{plain_text, Rtf, Style, LINE_EPILOGUE, LINE_PREAMBLE};
+    use crate::timestamp::{Timestamp, TimestampsIn};
 
     /// A paragraph that contains an utterance.
     ///
@@ -213,11 +397,11 @@ mod utterance {
     /// RTF code that occurs in slightly different variations at
     /// the beginning of every utterance, e.g. `Z` says something
     /// in this:
-    /// ```
+    /// ```text
     /// {\f0 \fs24 \ul0 \b0 \i0 \cf0 {\f1 \fs24 \ul0 \b0 \i0 \cf0 Z:}{\f0 \fs24 \ul0 \b0 \i0 \cf0
     /// ```
     /// Sometimes transcripts use this form, with the colon at the end:
-    /// ```
+    /// ```text
     /// {\f0 \fs24 \ul0 \b0 \i0 \cf0 {\f0 \fs24 \ul0 \b0 \i0 \cf0 Z}{\f0 \fs24 \ul0 \b0 \i0 \cf0 :
     /// ```
     /// Either way, the contained `speaker` reference contains only
@@ -231,17 +415,74 @@ mod utterance {
     /// The utterance ends with `\par}\r\n` and athis is not included
     /// in any of the contained strings.
     ///
+    /// The opening group of the line, e.g. `{\f0 \fs24 \ul0 \b0 \i0 \cf0 `,
+    /// is kept in `line_preamble` and re-emitted as-is when writing, and so
+    /// is the `\par}` at the end, which is kept in `line_epilogue`.
+    ///
     /// All the strings are non-overlapping.
     #[derive(Debug)]
     pub struct Utterance<'a> {
+        line_preamble: &'a str,
+        line_epilogue: &'a str,
         speaker_before: &'a str,
         speaker: &'a str,
         speaker_after: &'a str,
         speech: &'a str,
         speech_after: &'a str,
+        /// How to write the speaker label and speech.
+        style: Style,
+        /// Index in the color table to write the speaker label in.
+        speaker_color: Option<usize>,
+        /// Number to write before the speaker label.
+        line_number: Option<u64>,
     }
 
     impl<'a> Utterance<'a> {
+        /// Uses the given start and end of the line when writing,
+        /// instead of the canonical ones.
+        pub fn with_line_frame(self, line_preamble: &'a str, line_epilogue: &'a str) -> Self {
+            Utterance {
+                line_preamble,
+                line_epilogue,
+                ..self
+            }
+        }
+
+        /// Writes the speaker label and speech in the given style.
+        pub fn with_style(self, style: Style) -> Self {
+            Utterance { style, ..self }
+        }
+
+        /// Writes the speaker in the color with the given index in the
+        /// color table, like `{\cf3 Z}`.
+        pub fn with_speaker_color(self, speaker_color: Option<usize>) -> Self {
+            Utterance {
+                speaker_color,
+                ..self
+            }
+        }
+
+        /// Writes the given number and a tab before the speaker label.
+        pub fn with_line_number(self, line_number: Option<u64>) -> Self {
+            Utterance {
+                line_number,
+                ..self
+            }
+        }
+
+        /// Leaves out the given number of bytes at the start of the raw
+        /// speech, e.g. a sentence that was already transcribed before,
+        /// keeping as much whitespace before the rest as before it all.
+        pub fn without_speech_start(self, len: usize) -> Self {
+            let leading = self.speech.len() - self.speech.trim_start().len();
+            let rest = &self.speech[len..];
+            let rest_leading = rest.len() - rest.trim_start().len();
+            Utterance {
+                speech: &rest[rest_leading.saturating_sub(leading)..],
+                ..self
+            }
+        }
+
         pub fn speaker(&self) -> &str {
             self.speaker.trim()
         }
@@ -250,6 +491,29 @@ mod utterance {
             self.speech.trim()
         }
 
+        /// The speaker as plain text, with escapes like `\'fc` decoded and
+        /// formatting dropped, for anything but writing RTF.
+        pub fn speaker_text(&self) -> String {
+            plain_text(self.speaker).trim().to_string()
+        }
+
+        /// The speech as plain text, with escapes like `\'fc` decoded and
+        /// formatting dropped, for anything but writing RTF.
+        pub fn speech_text(&self) -> String {
+            plain_text(self.speech).trim().to_string()
+        }
+
+        /// Timestamps in the speech with their byte offsets in `speech()`,
+        /// from the front or the back.
+        pub fn timestamps(&self) -> TimestampsIn<'a> {
+            Timestamp::timestamps_in(self.speech.trim())
+        }
+
+        /// The speech including surrounding whitespace.
+        pub fn raw_speech(&self) -> &str {
+            self.speech
+        }
+
         pub fn write_adjusted<W>(&self, to: W, adjust_by: Timestamp) -> Result<()>
         where
             W: Write,
@@ -268,13 +532,64 @@ mod utterance {
         where
             W: Write,
         {
-            write!(&mut to, "{}", LINE_PREAMBLE)?;
-            write!(
-                &mut to,
-                "{}{}{}",
-                self.speaker_before, self.speaker, self.speaker_after,
-            )?;
-            Timestamp::write_with_adjusted_timestamps(&mut to, self.speech.trim(), adjust_by)?;
+            write!(&mut to, "{}", self.line_preamble)?;
+            if let Some(number) = self.line_number {
+                write!(&mut to, "{{{:04}\\tab}}", number)?;
+            }
+            if self.style.tidy {
+                self.write_tidy_label(&mut to)?;
+            } else {
+                write!(&mut to, "{}", self.speaker_before)?;
+                self.write_speaker(&mut to, self.speaker)?;
+                write!(&mut to, "{}", self.speaker_after)?;
+            }
+            let speech = (adjust_by, extra_speech, extra_speech_adjust);
+            match self.style.wrap {
+                Some(width) => {
+                    let mut buf = vec![];
+                    self.write_speech(&mut buf, speech)?;
+                    // the label is on the first line too, with `: `
+                    let label_len = plain_text(self.speaker.trim()).chars().count() + 2;
+                    let wrapped = wrap(&String::from_utf8_lossy(&buf), width, label_len);
+                    write!(&mut to, "{}", wrapped)?;
+                }
+                None => self.write_speech(&mut to, speech)?,
+            }
+            write!(&mut to, "{}", self.speech_after)?;
+            write!(&mut to, "{}\r\n", self.line_epilogue)?;
+            Ok(())
+        }
+
+        /// Writes the speech and extra speech with their adjustments.
+        fn write_speech<W>(
+            &self,
+            mut to: W,
+            (adjust_by, extra_speech, extra_speech_adjust): (Timestamp, &str, Timestamp),
+        ) -> Result<()>
+        where
+            W: Write,
+        {
+            if self.style.tidy {
+                let mut speech = collapse_spaces(self.speech);
+                if !extra_speech.is_empty() {
+                    speech.push(' ');
+                }
+                Timestamp::write_with_adjusted_timestamps(&mut to, &speech, adjust_by)?;
+                Timestamp::write_with_adjusted_timestamps(
+                    &mut to,
+                    &collapse_spaces(extra_speech),
+                    extra_speech_adjust,
+                )?;
+                return Ok(());
+            }
+            // keep the speech as-is, unless there is extra speech and
+            // we need exactly one space in between
+            let speech = if extra_speech.is_empty() {
+                self.speech
+            } else {
+                self.speech.trim_end()
+            };
+            Timestamp::write_with_adjusted_timestamps(&mut to, speech, adjust_by)?;
             if !extra_speech.is_empty() {
                 write!(&mut to, " ")?;
             }
@@ -283,10 +598,99 @@ mod utterance {
                 extra_speech.trim(),
                 extra_speech_adjust,
             )?;
-            write!(&mut to, "{}", self.speech_after)?;
-            write!(&mut to, "{}\r\n", LINE_EPILOGUE)?;
             Ok(())
         }
+
+        /// Writes the speaker, in its color if it has one.
+        fn write_speaker<W>(&self, mut to: W, speaker: &str) -> Result<()>
+        where
+            W: Write,
+        {
+            match self.speaker_color {
+                Some(color) => write!(&mut to, "{{\\cf{} {}}}", color, speaker),
+                None => write!(&mut to, "{}", speaker),
+            }
+        }
+
+        /// Writes the speaker label trimmed, with no space before the
+        /// colon and exactly one after it, given that the speech is
+        /// trimmed when tidying.
+        fn write_tidy_label<W>(&self, mut to: W) -> Result<()>
+        where
+            W: Write,
+        {
+            write!(&mut to, "{}", self.speaker_before)?;
+            self.write_speaker(&mut to, self.speaker.trim())?;
+            write!(&mut to, "{}", self.speaker_after)?;
+            let label_has_space = Rtf::from(self.speaker_after)
+                .rfind(|token| token.kind().is_text())
+                .map(|text| text.as_str().ends_with(' '))
+                .unwrap_or(false);
+            if !label_has_space {
+                write!(&mut to, " ")?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Replaces spaces in the text of the RTF code with `\line ` so that
+    /// lines are at most `width` characters long, unless a single word is
+    /// longer. The first line already has `first_line_used` characters.
+    fn wrap(speech: &str, width: usize, first_line_used: usize) -> String {
+        let mut wrapped = String::with_capacity(speech.len());
+        let mut line_len = first_line_used;
+        // position of the last space on the current line and the line
+        // length up to and including it
+        let mut last_space = None;
+        for token in Rtf::from(speech) {
+            if !token.kind().is_text() {
+                wrapped.push_str(token.as_str());
+                continue;
+            }
+            for c in token.as_str().chars() {
+                if c == ' ' {
+                    last_space = Some((wrapped.len(), line_len + 1));
+                }
+                wrapped.push(c);
+                line_len += 1;
+                if line_len > width {
+                    if let Some((at, len_up_to_space)) = last_space.take() {
+                        wrapped.replace_range(at..at + 1, "\\line ");
+                        line_len -= len_up_to_space;
+                    }
+                }
+            }
+        }
+        wrapped
+    }
+
+    /// Collapses runs of spaces in the text of the RTF code to a single
+    /// space and trims them at both ends, leaving delimiters of control
+    /// words alone.
+    fn collapse_spaces(speech: &str) -> String {
+        let mut collapsed = String::with_capacity(speech.len());
+        let mut any_text = false;
+        // spaces are only written once more text follows
+        let mut pending_space = false;
+        for token in Rtf::from(speech) {
+            if !token.kind().is_text() {
+                collapsed.push_str(token.as_str());
+                continue;
+            }
+            for c in token.as_str().chars() {
+                if c == ' ' {
+                    pending_space = any_text;
+                } else {
+                    if pending_space {
+                        collapsed.push(' ');
+                        pending_space = false;
+                    }
+                    collapsed.push(c);
+                    any_text = true;
+                }
+            }
+        }
+        collapsed
     }
 
     impl<'a> TryFrom<&'a str> for Utterance<'a> {
@@ -333,8 +737,8 @@ mod utterance {
                 }
             }
 
-            // consume up to the last text block and extend speech_end
-            if let Some(last_text) = text_content.last() {
+            // extend speech_end to the last text block, found from the back
+            if let Some(last_text) = text_content.next_back() {
                 speech_end = Some(last_text.source().end());
             }
 
@@ -349,11 +753,16 @@ mod utterance {
             let speech_after = &par[speech_end..par.len()];
 
             Ok(Utterance {
+                line_preamble: LINE_PREAMBLE,
+                line_epilogue: LINE_EPILOGUE,
                 speaker_before,
                 speaker,
                 speaker_after,
                 speech,
                 speech_after,
+                style: Style::default(),
+                speaker_color: None,
+                line_number: None,
             })
         }
     }
@@ -378,6 +787,45 @@ mod utterance {
             assert_eq!(speaker, "Z");
             assert_eq!(speech, "Ich glaube auch, dass es nicht stimmt");
         }
+
+        #[test]
+        fn long_speech_is_wrapped() {
+            assert_eq!(
+                wrap("eins zwei drei #00:00:01-0# vier", 12, 3),
+                "eins zwei\\line drei\\line #00:00:01-0#\\line vier"
+            );
+            assert_eq!(
+                wrap("a{\\b fett}\\b0  b c", 6, 0),
+                "a{\\b fett}\\b0 \\line b c"
+            );
+            assert_eq!(wrap("kurz", 12, 3), "kurz");
+        }
+
+        #[test]
+        fn tidy_labels_and_spaces() {
+            let write_tidy = |content: &str| {
+                let mut buf = vec![];
+                Utterance::try_from(content)
+                    .expect("could not parse as utterance")
+                    .with_style(Style {
+                        tidy: true,
+                        wrap: None,
+                    })
+                    .write_adjusted(&mut buf, Timestamp::zero())
+                    .unwrap();
+                String::from_utf8(buf).unwrap()
+            };
+            let expected = format!("{}Z: Ja, so war es. #00:00:01-0#\\par}}\r\n", LINE_PREAMBLE);
+            assert_eq!(write_tidy("Z : Ja,  so   war es. #00:00:01-0#  "), expected);
+            assert_eq!(write_tidy("Z:  Ja, so war es.  #00:00:01-0#"), expected);
+            assert_eq!(
+                write_tidy("{\\f1 Z:}{\\f0 \\cf0 Ja, so war\\cf1  es. #00:00:01-0#}"),
+                format!(
+                    "{}{{\\f1 Z:}}{{\\f0 \\cf0  Ja, so war\\cf1  es. #00:00:01-0#}}\\par}}\r\n",
+                    LINE_PREAMBLE
+                )
+            );
+        }
     }
 }
 
@@ -585,6 +1033,137 @@ mod test {
         hash
     }
 
+    #[test]
+    fn formatted_paragraph_keeps_markup_and_adjusts_timestamps() {
+        const LINE: &str = "{{\\i Z:} Ganz {\\b1 fett} #00:03:10-1#\\par}";
+        let line = Lines::parse_line(LINE);
+        assert!(
+            matches!(line, Line::Formatted(_)),
+            "Not a formatted paragraph: {:?}",
+            line
+        );
+
+        let mut buf = vec![];
+        line.write_adjusted(&mut buf, Timestamp::parse("#00:05:00-0#").unwrap())
+            .unwrap();
+        assert_eq!(
+            std::str::from_utf8(&buf).unwrap(),
+            "{{\\i Z:} Ganz {\\b1 fett} #00:08:10-1#\\par}\r\n"
+        );
+    }
+
+    #[test]
+    fn any_control_words_are_a_line_preamble() {
+        for (line, speaker) in &[
+            (
+                "{\\b \\f0 \\fs24 {\\i Z:} Ganz {\\b1 fett} #00:03:10-1#\\par}",
+                "Z",
+            ),
+            (
+                "{\\f2 \\lang1031 \\fs24 \\cf0 \\b0 I: Mhm. #00:03:10-1#\\par}",
+                "I",
+            ),
+            ("{\\f0\\fs20\\i0 B: Mhm. #00:03:10-1#\\par}", "B"),
+        ] {
+            let parsed = Lines::parse_line(line);
+            let utterance = parsed.utterance().expect("not an utterance");
+            assert_eq!(utterance.speaker(), *speaker);
+
+            let mut buf = vec![];
+            parsed
+                .write_adjusted(&mut buf, Timestamp::parse("#00:05:00-0#").unwrap())
+                .unwrap();
+            assert_eq!(
+                std::str::from_utf8(&buf).unwrap(),
+                format!("{}\r\n", line.replace("#00:03:10-1#", "#00:08:10-1#"))
+            );
+        }
+        let quoted = Lines::parse_line("{\\f0 \\u8222?Ja\\u8220?, sagte er.\\par}");
+        assert_eq!(
+            quoted.paragraph().map(Paragraph::content),
+            Some("\\u8222?Ja\\u8220?, sagte er.")
+        );
+    }
+
+    #[test]
+    fn unbalanced_line_is_not_a_paragraph() {
+        const LINE: &str = "{\\b Z: Hallo #00:03:10-1#}{\\par}";
+        assert!(matches!(Lines::parse_line(LINE), Line::Other(_)));
+    }
+
+    #[test]
+    fn original_line_preamble_is_written() {
+        const LINE: &str = "{\\f1 \\fs24 \\ul0 \\b0 \\i0 \\cf0 {\\f1 \\fs24 \\ul0 \\b0 \\i0 \\cf0 I: Mhm. #00:03:10-1#}\\par}";
+        const EMPTY: &str = "{\\f1 \\fs20 \\ul0 \\b0 \\i0 \\cf0 \\par}";
+        for line in &[LINE, EMPTY] {
+            let mut buf = vec![];
+            Lines::parse_line(line)
+                .write_adjusted(&mut buf, Timestamp::zero())
+                .unwrap();
+            assert_eq!(std::str::from_utf8(&buf).unwrap(), format!("{}\r\n", line));
+        }
+    }
+
+    #[test]
+    fn pard_dialect_is_detected() {
+        const GROUPS: &str = "{\\f0 \\fs24 \\par}\r\n{\\f0 \\fs24 I: Hallo. #00:00:01-0#\\par}\r\n";
+        const PARDS: &str = "\\pard\\plain \\f0\\fs24 \\par\r\n\\pard\\plain \\f0\\fs24 I: Hallo. #00:00:01-0#\\par\r\n";
+        assert_eq!(Dialect::detect(GROUPS), Dialect::Group);
+        assert_eq!(Dialect::detect(PARDS), Dialect::Pard);
+    }
+
+    #[test]
+    fn text_of_utterance_is_unescaped() {
+        let raw = format!(
+            "{p}{p}Z\\'fc:}}{p} Zur\\'fcck zu {{\\b den}} \\u8222?Methoden\\u8220? #00:00:17-5#}}\\par}}",
+            p = LINE_PREAMBLE
+        );
+        let line = Lines::parse_line_in_dialect(Dialect::Group, &raw);
+        let utterance = line.utterance().expect("not an utterance");
+        assert_eq!(utterance.speaker_text(), "Zü");
+        assert_eq!(
+            utterance.speech_text(),
+            "Zurück zu den „Methoden“ #00:00:17-5#"
+        );
+        assert!(utterance.speech().starts_with("Zur\\'fcck"));
+        let timestamps: Vec<_> = utterance.timestamps().collect();
+        assert_eq!(
+            timestamps,
+            vec![(
+                utterance.speech().find('#').unwrap(),
+                Timestamp::parse("#00:00:17-5#").unwrap()
+            )]
+        );
+    }
+
+    #[test]
+    fn pard_utterance_is_parsed_and_adjusted() {
+        const LINE: &str = "\\pard\\plain \\f0\\fs24 Z: Ich glaub schon. #00:03:10-1#\\par";
+        let line = Lines::parse_line_in_dialect(Dialect::Pard, LINE);
+        let utterance = line.utterance().expect("not an utterance");
+        assert_eq!(utterance.speaker(), "Z");
+        assert_eq!(utterance.speech(), "Ich glaub schon. #00:03:10-1#");
+
+        let mut buf = vec![];
+        line.write_adjusted(&mut buf, Timestamp::parse("#00:05:00-0#").unwrap())
+            .unwrap();
+        assert_eq!(
+            std::str::from_utf8(&buf).unwrap(),
+            "\\pard\\plain \\f0\\fs24 Z: Ich glaub schon. #00:08:10-1#\\par\r\n"
+        );
+    }
+
+    #[test]
+    fn empty_pard_paragraph_is_written_as_is() {
+        const LINE: &str = "\\pard\\plain \\f0\\fs24 \\par";
+        let line = Lines::parse_line_in_dialect(Dialect::Pard, LINE);
+        assert_eq!(line.paragraph().map(Paragraph::content), Some(""));
+
+        let mut buf = vec![];
+        line.write_adjusted(&mut buf, Timestamp::zero()).unwrap();
+        assert_eq!(std::str::from_utf8(&buf).unwrap(), format!("{}\r\n", LINE));
+    }
+
     #[test]
     fn alternative_preamble_is_accepted() {
         const LINE: &str = "{\\f1 \\fs24 \\ul0 \\b0 \\i0 \\cf0 {\\f1 \\fs24 \\ul0 \\b0 \\i0 \\cf0 I: Mhm. #00:03:10-1#}\\par}";
@@ -3,11 +3,177 @@
 //! stitching together the last utterance of one transcript
 //! with the first utterance of the next, if the speaker is
 //! the same.
-use super::lines::Line;
-use std::io::Write;
+use super::annotations::StripAnnotations;
+use super::colors::SpeakerColors;
+use super::duplicates::duplicate_at_boundary;
+use super::line_ending::LineEndingWriter;
+use super::lines::{Dialect, Line, Style, Utterance};
+use super::numbering::{LineNumbering, LineNumbers, NumberedLine};
+use super::rtf::escape;
+use super::sentences::Language;
+use super::template::{Record, Records};
+use super::toc::{bookmark, HoldBack, Toc};
+use super::unadjusted::{UnadjustedCheck, UnadjustedTimestamp};
+use std::convert::TryFrom;
+use std::io::{BufWriter, Write};
 
+use log::{debug, warn};
+
+use crate::logging::MERGE;
 use crate::timestamp::Timestamp;
-use crate::transcript::{Result, Transcript};
+use crate::transcript::{Error, Result, SpeakerInference, Trailers, Transcript};
+
+/// How the timestamps of one of the merged transcripts were
+/// adjusted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentShift {
+    /// Suspected length of the segment, see
+    /// [`Transcript::interview_end_time`].
+    pub end_time: Timestamp,
+    /// Cumulative shift that was added to the timestamps of
+    /// the segment.
+    pub shift: Timestamp,
+    /// Number of timestamps in the segment that were adjusted.
+    pub adjusted_timestamps: usize,
+    /// Timestamps of the segment in the merged output that are earlier
+    /// than the shift, which means they were missed when adjusting.
+    pub unadjusted: Vec<UnadjustedTimestamp>,
+    /// Utterances of the segment with their line numbers, if numbered.
+    /// An utterance stitched with the next segment belongs to this one.
+    pub numbered_lines: Vec<NumberedLine>,
+}
+
+/// Options for how transcripts are merged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MergeOptions {
+    /// If utterances of the same speaker at a segment boundary are
+    /// stitched together at all.
+    pub stitch: bool,
+    /// Utterances of the same speaker at a segment boundary are only
+    /// stitched together if the first timestamp of the next segment
+    /// is at most this long after the last timestamp of the previous
+    /// one, after adjusting them.
+    pub stitch_max_gap: Timestamp,
+    /// Writes a paragraph like `--- Segment 2 (shift #00:05:00-0#) ---`
+    /// where two segments were joined. If utterances were stitched at
+    /// the junction, it comes after the stitched utterance.
+    pub mark_boundaries: bool,
+    /// Writes speaker labels uniformly as `Z: ` and collapses repeated
+    /// spaces in speech.
+    pub tidy: bool,
+    /// Breaks utterances into lines of at most this many characters.
+    pub wrap: Option<usize>,
+    /// Also exports paragraphs without a speaker label, attributed to a
+    /// speaker like this. RTF output is not affected.
+    pub infer_speakers: Option<SpeakerInference>,
+    /// What happens to notes after the last utterance of every segment,
+    /// see [`Transcript::trailer`]. Outputs other than RTF only have the
+    /// paragraphs of trailers attributed to speakers by `infer_speakers`.
+    pub trailers: Trailers,
+    /// Leaves out the notes before the first utterance of every segment
+    /// but the first, see [`Transcript::header`].
+    pub strip_segment_headers: bool,
+    /// Timestamps of all segments are already relative to the start of the
+    /// interview, e.g. rebased clock times, so no segment is shifted.
+    pub absolute_timestamps: bool,
+    /// Leaves out the first sentence of a segment if it repeats the last
+    /// sentence of the segment before. It is warned about either way.
+    pub drop_boundary_duplicates: bool,
+    /// Language of the speech, for telling where sentences end. If given,
+    /// SRT and NVivo outputs have an entry per sentence instead of one
+    /// per utterance.
+    pub language: Option<Language>,
+    /// Outputs that annotations like `(lacht)` are left out of.
+    pub strip_annotations: StripAnnotations,
+    /// Writes the speaker labels of the merged RTF in a color per speaker,
+    /// see [`SpeakerColors`].
+    pub color_speakers: bool,
+    /// Writes a bookmark paragraph at the start of every segment of the
+    /// merged RTF and a table of contents linking to them after the
+    /// preamble.
+    pub toc: bool,
+    /// Writes a line number before every utterance of the merged RTF.
+    pub line_numbers: Option<LineNumbering>,
+}
+
+impl MergeOptions {
+    /// How utterances are written.
+    fn style(&self) -> Style {
+        Style {
+            tidy: self.tidy,
+            wrap: self.wrap,
+        }
+    }
+
+    /// Default for `stitch_max_gap`, in seconds.
+    ///
+    /// Generous, because the first timestamp of a segment usually marks
+    /// the end of its first utterance rather than its start.
+    pub const DEFAULT_STITCH_MAX_GAP_SECS: u64 = 60;
+
+    /// Checks if utterances of the same speaker should be stitched, given
+    /// the last adjusted timestamp before the boundary and the first one
+    /// after it. Without timestamps to compare, they are stitched unless
+    /// stitching is off.
+    fn should_stitch(
+        &self,
+        last_before: Option<Timestamp>,
+        first_after: Option<Timestamp>,
+    ) -> bool {
+        if !self.stitch {
+            return false;
+        }
+        match (last_before, first_after) {
+            (Some(before), Some(after)) => {
+                after.tenths().saturating_sub(before.tenths()) <= self.stitch_max_gap.tenths()
+            }
+            _ => true,
+        }
+    }
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        MergeOptions {
+            stitch: true,
+            stitch_max_gap: Timestamp::from_tenths(Self::DEFAULT_STITCH_MAX_GAP_SECS * 10).unwrap(),
+            mark_boundaries: false,
+            tidy: false,
+            wrap: None,
+            infer_speakers: None,
+            trailers: Trailers::Keep,
+            strip_segment_headers: false,
+            absolute_timestamps: false,
+            drop_boundary_duplicates: false,
+            language: None,
+            strip_annotations: StripAnnotations::default(),
+            color_speakers: false,
+            toc: false,
+            line_numbers: None,
+        }
+    }
+}
+
+/// Shift of every transcript in a merge of the transcripts with the given
+/// options, without merging them.
+pub fn segment_shifts<'a, I>(transcripts: I, options: &MergeOptions) -> Result<Vec<Timestamp>>
+where
+    I: IntoIterator<Item = &'a Transcript>,
+{
+    let mut shifts: Vec<Timestamp> = vec![];
+    let mut previous_end_time = Timestamp::zero();
+    for (index, transcript) in transcripts.into_iter().enumerate() {
+        let shift = shifts.last().copied().unwrap_or_default();
+        shifts.push(next_shift(
+            options,
+            shift,
+            previous_end_time,
+            (index, transcript),
+        )?);
+        previous_end_time = transcript.interview_end_time();
+    }
+    Ok(shifts)
+}
 
 /// Writes a merged version of the transcripts given with an
 /// iterator to the given writable thing.
@@ -15,45 +181,423 @@ use crate::transcript::{Result, Transcript};
 /// We try to stitch together adjacent transcripts if the
 /// speakers are the same. We also adjust the timestamps.
 ///
+/// Returns the applied shift for each of the transcripts, in
+/// the order they were written.
+///
 /// If the transcript iterator is empty, does nothing and returns
 /// an Ok result.
-pub fn write_merged_transcript<W, I>(mut to: W, transcripts: I) -> Result<()>
+///
+/// Fails with `Error::TimestampOverflow` before writing a transcript
+/// whose shifted timestamps could not be represented.
+///
+/// The output is checked for timestamps that ended up earlier than the
+/// shift of their segment, they are reported in `SegmentShift::unadjusted`.
+///
+/// Writes are buffered and flushed before returning, so there is no
+/// need to pass a buffered writer.
+///
+/// Lines end like in the first transcript, with `\r\n` or just `\n`.
+pub fn write_merged_transcript<W, I>(
+    to: W,
+    transcripts: I,
+    options: &MergeOptions,
+) -> Result<Vec<SegmentShift>>
 where
     W: Write,
     I: IntoIterator<Item = Transcript>,
 {
-    let mut transcripts = transcripts.into_iter().peekable();
-    let first_epilogue = {
-        let first = match transcripts.peek() {
-            Some(first) => first,
-            None => return Ok(()),
-        };
-        write!(&mut to, "{}", first.preamble())?;
-        first.epilogue().to_string()
-    };
+    merge(to, transcripts, options, false).map(|(shifts, _)| shifts)
+}
+
+/// Merges the transcripts like `write_merged_transcript`, and also returns
+/// the merged utterances as records for the outputs other than RTF.
+pub(super) fn write_recorded_merge<W, I>(
+    to: W,
+    transcripts: I,
+    options: &MergeOptions,
+) -> Result<(Vec<SegmentShift>, Vec<Record>)>
+where
+    W: Write,
+    I: IntoIterator<Item = Transcript>,
+{
+    merge(to, transcripts, options, true)
+}
 
-    let mut last_transcript = None;
-    let mut shift = Timestamp::zero();
+fn merge<W, I>(
+    to: W,
+    transcripts: I,
+    options: &MergeOptions,
+    recorded: bool,
+) -> Result<(Vec<SegmentShift>, Vec<Record>)>
+where
+    W: Write,
+    I: IntoIterator<Item = Transcript>,
+{
+    let mut transcripts = transcripts.into_iter();
+    let first = match transcripts.next() {
+        Some(first) => first,
+        None => return Ok((vec![], vec![])),
+    };
+    let mut merger = Merger::new(to, &first, options)?;
+    if recorded {
+        merger.records = Some(Records::new(*options));
+    }
+    merger.add(first)?;
     for transcript in transcripts {
-        let previous = last_transcript.as_ref().map(|t| (t, shift));
-        let next_shift = shift
-            + last_transcript
-                .as_ref()
-                .map(Transcript::interview_end_time)
-                .unwrap_or_default();
+        merger.add(transcript)?;
+    }
+    merger.finish_with_records()
+}
+
+/// How far a merge got, to resume it later with `Merger::resume`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// Shifts of the transcripts merged so far.
+    pub shifts: Vec<SegmentShift>,
+    /// Number of lines written so far.
+    pub lines: usize,
+}
+
+/// Merges transcripts one after the other like `write_merged_transcript`,
+/// so that the progress can be saved in between.
+///
+/// The last line of the last added transcript is only written when the
+/// next one is added or the merge is finished, since it may be stitched
+/// with the first line of the next transcript.
+pub struct Merger<W: Write> {
+    to: UnadjustedCheck<HoldBack<LineEndingWriter<BufWriter<W>>>>,
+    /// Epilogue of the first transcript, which ends the merged one.
+    epilogue: String,
+    options: MergeOptions,
+    last_transcript: Option<Transcript>,
+    shifts: Vec<SegmentShift>,
+    /// Trailers to write at the end, with `Trailers::Move`.
+    moved_trailers: Vec<String>,
+    /// The merged utterances, for the outputs other than RTF.
+    records: Option<Records>,
+    /// Colors of the speaker labels, with `color_speakers`.
+    colors: Option<SpeakerColors>,
+    /// Starts of the segments, with `toc`.
+    toc: Option<Toc>,
+    /// Numbers of the utterances, with `line_numbers`.
+    numbers: Option<LineNumbers>,
+}
+
+impl<W: Write> Merger<W> {
+    /// Starts a merge by writing the preamble of the first transcript,
+    /// which still has to be added.
+    ///
+    /// With `color_speakers`, the preamble gets the colors of the speakers,
+    /// with `toc`, the rest is held back until the merge is finished.
+    pub fn new(to: W, first: &Transcript, options: &MergeOptions) -> Result<Self> {
+        let mut merger = Self::resume(to, first, None, Checkpoint::default(), options);
+        let colored = if options.color_speakers {
+            let colored = SpeakerColors::new(first.preamble());
+            if colored.is_none() {
+                warn!(
+                    target: MERGE,
+                    "the first transcript has no color table, speakers are not colored"
+                );
+            }
+            colored
+        } else {
+            None
+        };
+        match colored {
+            Some((colors, preamble)) => {
+                write!(&mut merger.to, "{}{}", first.prefix(), preamble)?;
+                merger.colors = Some(colors);
+            }
+            None => write!(&mut merger.to, "{}{}", first.prefix(), first.preamble())?,
+        }
+        if options.toc {
+            merger.to.get_mut().hold();
+            merger.toc = Some(Toc::new(first.dialect()));
+        }
+        Ok(merger)
+    }
+
+    /// Continues a merge that was written up to the checkpoint, with
+    /// `last` being the transcript that was added last, if any.
+    ///
+    /// Trailers that were moved before the checkpoint, the colors of
+    /// speakers, the table of contents and the line numbers are lost, so
+    /// merges that move trailers, color speakers, have a table of contents
+    /// or line numbers cannot be resumed.
+    pub fn resume(
+        to: W,
+        first: &Transcript,
+        last: Option<Transcript>,
+        checkpoint: Checkpoint,
+        options: &MergeOptions,
+    ) -> Self {
+        let to = HoldBack::new(LineEndingWriter::new(
+            BufWriter::new(to),
+            first.line_ending(),
+        ));
+        Merger {
+            to: UnadjustedCheck::resume(to, checkpoint.lines),
+            epilogue: first.epilogue().to_string(),
+            options: *options,
+            last_transcript: last.map(|last| match options.trailers {
+                Trailers::Keep => last,
+                Trailers::Move | Trailers::Strip => last.without_trailer(),
+            }),
+            shifts: checkpoint.shifts,
+            moved_trailers: vec![],
+            records: None,
+            colors: None,
+            toc: None,
+            numbers: options.line_numbers.map(LineNumbers::new),
+        }
+    }
+
+    /// Adds the next transcript, writing all but its last line.
+    pub fn add(&mut self, transcript: Transcript) -> Result<()> {
+        let index = self.shifts.len();
+        let transcript = self.take_trailer(index, transcript);
+        let transcript = if self.options.strip_segment_headers && index > 0 {
+            strip_header(index, transcript)
+        } else {
+            transcript
+        };
+        let shift = self.shift();
+        let previous = self.last_transcript.as_ref().map(|t| (t, shift));
+        let previous_end_time = self
+            .last_transcript
+            .as_ref()
+            .map(Transcript::interview_end_time)
+            .unwrap_or_default();
+        let next_shift = next_shift(
+            &self.options,
+            shift,
+            previous_end_time,
+            (index, &transcript),
+        )?;
+        ensure_shiftable(&transcript, next_shift)?;
+        let previous_last = self
+            .last_transcript
+            .as_ref()
+            .map(|last| last.last_timestamp().saturating_add(shift));
+        ensure_monotonic(previous_last, (index, &transcript), next_shift)?;
+        if let Some(colors) = &mut self.colors {
+            colors.add_speakers(transcript.lines());
+        }
         let next = (&transcript, next_shift);
-        write_next_except_last_line(&mut to, previous, next)?;
-        last_transcript = Some(transcript);
-        shift = next_shift;
+        let marker = Some(boundary_marker(index, next_shift))
+            .filter(|_| self.options.mark_boundaries && previous.is_some());
+        let marker = match &mut self.toc {
+            Some(toc) => {
+                toc.add(if self.options.absolute_timestamps {
+                    first_timestamp(transcript.content()).unwrap_or_default()
+                } else {
+                    next_shift
+                });
+                Some(bookmark(index) + marker.as_deref().unwrap_or_default())
+            }
+            None => marker,
+        };
+        write_next_except_last_line(
+            &mut self.to,
+            previous,
+            (index, next),
+            marker.as_deref(),
+            &self.options,
+            (self.colors.as_ref(), &mut self.numbers, &mut self.records),
+        )?;
+        self.shifts.push(SegmentShift {
+            end_time: transcript.interview_end_time(),
+            shift: next_shift,
+            adjusted_timestamps: transcript
+                .lines()
+                .map(|line| line.adjustable_timestamps())
+                .sum(),
+            unadjusted: vec![],
+            numbered_lines: vec![],
+        });
+        self.last_transcript = Some(transcript);
+        Ok(())
+    }
+
+    /// Flushes what was written so far and returns how far the merge got.
+    ///
+    /// Unadjusted timestamps are only reported when finishing.
+    pub fn checkpoint(&mut self) -> Result<Checkpoint> {
+        self.to.flush()?;
+        Ok(Checkpoint {
+            shifts: self.shifts.clone(),
+            lines: self.to.lines(),
+        })
+    }
+
+    /// Writes the last line and the epilogue, and returns the applied
+    /// shift for each of the transcripts.
+    pub fn finish(self) -> Result<Vec<SegmentShift>> {
+        self.finish_with_records().map(|(shifts, _)| shifts)
     }
-    if let Some(last_transcript) = last_transcript {
-        if let Some(last_line) = last_transcript.lines().next_back() {
-            // write the excluded line from the last iteration
-            last_line.write_adjusted(&mut to, shift)?;
+
+    /// Finishes the merge like `finish`, and also returns the merged
+    /// utterances if they were recorded.
+    fn finish_with_records(mut self) -> Result<(Vec<SegmentShift>, Vec<Record>)> {
+        let shift = self.shift();
+        if let Some(last_transcript) = &self.last_transcript {
+            let style = self.options.style();
+            if let Some(last_line) = last_transcript.lines().style(style).next_back() {
+                // write the excluded line from the last iteration
+                let last_line = paint(last_line, self.colors.as_ref());
+                let segment = self.shifts.len() - 1;
+                record(&last_line, &mut self.records, (segment, shift))?;
+                number(last_line, &mut self.numbers, (segment, shift))
+                    .write_adjusted(&mut self.to, shift)?;
+            }
+        }
+        for trailer in &self.moved_trailers {
+            write!(&mut self.to, "{}", trailer)?;
+        }
+        // the table of contents comes before every line that was checked
+        let toc_lines = match &self.toc {
+            Some(toc) => {
+                let paragraphs = toc.paragraphs()?;
+                self.to.get_mut().release(&paragraphs)?;
+                paragraphs.iter().filter(|&&b| b == b'\n').count()
+            }
+            None => 0,
+        };
+        self.to.enter_segment(0, Timestamp::zero());
+        write!(&mut self.to, "{}", self.epilogue)?;
+        let (mut to, unadjusted) = self.to.finish();
+        to.flush()?;
+        for (segment, mut timestamp) in unadjusted {
+            timestamp.line += toc_lines;
+            self.shifts[segment].unadjusted.push(timestamp);
         }
+        for (segment, numbered) in self.numbers.map(LineNumbers::finish).unwrap_or_default() {
+            self.shifts[segment].numbered_lines.push(numbered);
+        }
+        let records = self.records.map(Records::finish).unwrap_or_default();
+        Ok((self.shifts, records))
+    }
+
+    /// The transcript without its trailer, unless trailers are kept,
+    /// remembering it for the end if it is moved.
+    fn take_trailer(&mut self, index: usize, transcript: Transcript) -> Transcript {
+        let trailer = transcript.trailer();
+        if self.options.trailers == Trailers::Keep || trailer.is_empty() {
+            return transcript;
+        }
+        let lines = trailer.lines().count();
+        if self.options.trailers == Trailers::Move {
+            debug!(
+                target: MERGE,
+                "segment {}: moving {} lines after the last utterance to the end",
+                index + 1,
+                lines
+            );
+            self.moved_trailers.push(trailer.to_string());
+            if let Some(records) = &mut self.records {
+                records.move_trailer(index, transcript.dialect(), trailer);
+            }
+        } else {
+            debug!(
+                target: MERGE,
+                "segment {}: leaving out {} lines after the last utterance",
+                index + 1,
+                lines
+            );
+        }
+        transcript.without_trailer()
+    }
+
+    /// Shift of the transcript that was added last.
+    fn shift(&self) -> Timestamp {
+        self.shifts
+            .last()
+            .map(|shift| shift.shift)
+            .unwrap_or_default()
+    }
+}
+
+/// The transcript of the segment with the given index without its
+/// header.
+fn strip_header(index: usize, transcript: Transcript) -> Transcript {
+    let header = transcript.header();
+    if header.is_empty() {
+        return transcript;
+    }
+    debug!(
+        target: MERGE,
+        "segment {}: leaving out {} lines before the first utterance",
+        index + 1,
+        header.lines().count()
+    );
+    transcript.without_header()
+}
+
+/// RTF of the paragraph marking the start of the segment with the given
+/// zero-based index.
+fn boundary_marker(index: usize, shift: Timestamp) -> String {
+    escape(&format!("--- Segment {} (shift {}) ---", index + 1, shift))
+}
+
+/// Shift for the next transcript with the given index after one that was
+/// shifted by `shift` and ends at `previous_end_time`, corrected by the
+/// start offset of the next transcript.
+fn next_shift(
+    options: &MergeOptions,
+    shift: Timestamp,
+    previous_end_time: Timestamp,
+    (index, next): (usize, &Transcript),
+) -> Result<Timestamp> {
+    let shift = if options.absolute_timestamps {
+        Timestamp::zero()
+    } else {
+        shift
+            .checked_add(previous_end_time)
+            .ok_or(Error::TimestampOverflow(previous_end_time, shift))?
+    };
+    if next.start_offset() == 0 {
+        return Ok(shift);
+    }
+    i64::try_from(shift.tenths())
+        .ok()
+        .and_then(|tenths| tenths.checked_add(next.start_offset()))
+        .and_then(|tenths| u64::try_from(tenths).ok())
+        .and_then(Timestamp::from_tenths)
+        .ok_or(Error::OffsetBeforeStart(index + 1))
+}
+
+/// Checks that the first timestamp of a transcript with a start offset
+/// does not come before the last one of the transcript before it, after
+/// shifting them.
+fn ensure_monotonic(
+    previous_last: Option<Timestamp>,
+    (index, next): (usize, &Transcript),
+    shift: Timestamp,
+) -> Result<()> {
+    if next.start_offset() == 0 {
+        return Ok(());
+    }
+    match (previous_last, first_timestamp(next.content())) {
+        (Some(previous_last), Some(first)) if first.saturating_add(shift) < previous_last => Err(
+            Error::OffsetNotMonotonic(index + 1, first.saturating_add(shift), previous_last),
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Checks that all timestamps in the transcript can be shifted by the
+/// given amount without exceeding the biggest possible timestamp.
+fn ensure_shiftable(transcript: &Transcript, shift: Timestamp) -> Result<()> {
+    let latest = Timestamp::extract_timestamps(transcript.content().as_bytes())
+        .into_iter()
+        .map(|(_, timestamp)| timestamp)
+        .max();
+    match latest {
+        Some(latest) if latest.checked_add(shift).is_none() => {
+            Err(Error::TimestampOverflow(latest, shift))
+        }
+        _ => Ok(()),
     }
-    write!(&mut to, "{}", first_epilogue)?;
-    Ok(())
 }
 
 /// Writes the lines of the first given transcript, assuming that the
@@ -62,41 +606,80 @@ where
 /// If the last transcript ended with a line of the same speaker as the
 /// frist line in the current transcript, we attempt to write these
 /// lines in a merged way, that is, without the initial speaker label.
+///
+/// The marker, if any, is written as a paragraph at the junction.
 fn write_next_except_last_line<'a, W>(
-    mut to: W,
+    to: &mut UnadjustedCheck<W>,
     previous: Option<(&'a Transcript, Timestamp)>,
-    current: (&'a Transcript, Timestamp),
+    current: (usize, (&'a Transcript, Timestamp)),
+    marker: Option<&str>,
+    options: &MergeOptions,
+    (colors, numbers, records): (
+        Option<&SpeakerColors>,
+        &mut Option<LineNumbers>,
+        &mut Option<Records>,
+    ),
 ) -> Result<()>
 where
     W: Write,
 {
-    let (current_transcript, current_shift) = current;
-    let mut lines = current_transcript.lines();
+    let (index, (current_transcript, current_shift)) = current;
+    // the first utterance is checked for repeating the last one before,
+    // wherever they are, as empty paragraphs often end segments
+    let mut last_utterance = previous.and_then(|(t, _)| {
+        t.lines().rev().find_map(|line| match line {
+            Line::Utterance(utterance) => Some(utterance),
+            _ => None,
+        })
+    });
+    let mut lines = current_transcript
+        .lines()
+        .style(options.style())
+        .filter_map(move |line| match (line, last_utterance.take()) {
+            (Line::Utterance(first), Some(last)) => {
+                check_boundary_duplicate(index, &last, first, options).map(Line::Utterance)
+            }
+            (line, last) => {
+                last_utterance = last;
+                Some(line)
+            }
+        })
+        .map(|line| paint(line, colors));
 
     // handle stitching with last transcript
-    let previous_last_line_and_shift =
-        previous
-            .and_then(|(t, _)| t.lines().next_back())
-            .map(|last_line| {
-                let previous_shift = previous.map(|(_, ts)| ts).unwrap_or_default();
-                (last_line, previous_shift)
-            });
+    let previous_last_line_and_shift = previous
+        .and_then(|(t, _)| t.lines().style(options.style()).next_back())
+        .map(|last_line| paint(last_line, colors))
+        .map(|last_line| {
+            let previous_shift = previous.map(|(_, ts)| ts).unwrap_or_default();
+            (last_line, previous_shift)
+        });
 
     match lines.next() {
         // we have a first line and maybe a last line too, try stitching
         Some(first_line) => {
             write_last_and_first_line(
-                &mut to,
+                to,
                 previous_last_line_and_shift,
                 first_line,
-                current_shift,
+                (index, current_shift),
+                marker.map(|marker| (current_transcript.dialect(), marker)),
+                options,
+                (numbers, records),
             )?;
         }
         // not a single line in this transcript, write last line of last transcript and stop
         None => {
             if let Some((last_line, shift)) = previous_last_line_and_shift {
-                last_line.write_adjusted(&mut to, shift)?;
+                record(&last_line, records, (index - 1, shift))?;
+                number(last_line, numbers, (index - 1, shift)).write_adjusted(&mut *to, shift)?;
             }
+            if let Some(marker) = marker {
+                current_transcript
+                    .dialect()
+                    .write_paragraph(&mut *to, marker)?;
+            }
+            to.enter_segment(index, current_shift);
             return Ok(());
         }
     };
@@ -108,49 +691,431 @@ where
             // last line, do not write and stop
             break;
         } else {
-            line.write_adjusted(&mut to, current_shift)?;
+            record(&line, records, (index, current_shift))?;
+            number(line, numbers, (index, current_shift))
+                .write_adjusted(&mut *to, current_shift)?;
         }
     }
 
     Ok(())
 }
 
+/// Writes the junction of two segments. Lines written before the first
+/// line that belongs only to the new segment are checked against the
+/// shift of the previous one.
 fn write_last_and_first_line<'a, W>(
-    mut to: W,
+    to: &mut UnadjustedCheck<W>,
     last_line_and_shift: Option<(Line<'a>, Timestamp)>,
     first_line: Line<'a>,
-    shift: Timestamp,
+    (index, shift): (usize, Timestamp),
+    marker: Option<(Dialect, &str)>,
+    options: &MergeOptions,
+    (numbers, records): (&mut Option<LineNumbers>, &mut Option<Records>),
 ) -> Result<()>
 where
     W: Write,
 {
+    // the last line is written either way, stitched or not
+    let last_line_and_shift = match last_line_and_shift {
+        Some((last_line, last_shift)) => {
+            record(&last_line, records, (index - 1, last_shift))?;
+            Some((
+                number(last_line, numbers, (index - 1, last_shift)),
+                last_shift,
+            ))
+        }
+        None => None,
+    };
     let previous_utterance_and_shift = last_line_and_shift
         .as_ref()
         .and_then(|(last_line, shift)| last_line.utterance().map(|u| (u, shift)));
     let first_utterance = first_line.utterance();
+    if let (Some((last, &last_shift)), Some(first)) =
+        (previous_utterance_and_shift, first_utterance)
+    {
+        log_stitching(index, (last, last_shift), (first, shift), options);
+    }
     match (previous_utterance_and_shift, first_utterance) {
-        (Some((last, &last_shift)), Some(first)) if last.speaker() == first.speaker() => {
+        (Some((last, &last_shift)), Some(first))
+            if last.speaker() == first.speaker()
+                && options.should_stitch(
+                    shifted(
+                        last.timestamps()
+                            .next_back()
+                            .map(|(_, timestamp)| timestamp),
+                        last_shift,
+                    ),
+                    shifted(
+                        first.timestamps().next().map(|(_, timestamp)| timestamp),
+                        shift,
+                    ),
+                ) =>
+        {
             // the last speaker from the last transcript and the first of this
             // transcripts are the same => do not duplicate the speaker label,
             // but merge the content of the utterances.
-            last.write_adjusted_with_extra_speech(&mut to, last_shift, first.speech(), shift)?;
+            last.write_adjusted_with_extra_speech(&mut *to, last_shift, first.speech(), shift)?;
+            if let Some(records) = records {
+                records.stitch(first, (index, shift))?;
+            }
+            if let Some((dialect, marker)) = marker {
+                dialect.write_paragraph(&mut *to, marker)?;
+            }
+            to.enter_segment(index, shift);
         }
         _ => {
             // different speakers or nothing to merge, one after the other or just one
             if let Some((last_line, last_shift)) = last_line_and_shift {
-                last_line.write_adjusted(&mut to, last_shift)?;
+                last_line.write_adjusted(&mut *to, last_shift)?;
             }
-            first_line.write_adjusted(&mut to, shift)?;
+            if let Some((dialect, marker)) = marker {
+                dialect.write_paragraph(&mut *to, marker)?;
+            }
+            to.enter_segment(index, shift);
+            record(&first_line, records, (index, shift))?;
+            number(first_line, numbers, (index, shift)).write_adjusted(&mut *to, shift)?;
         }
     };
     Ok(())
 }
 
+/// Warns if the first sentence of the segment with the given index repeats
+/// the last one of the previous segment, and leaves it out if configured,
+/// along with the utterance if nothing else is said in it.
+fn check_boundary_duplicate<'a>(
+    index: usize,
+    last: &Utterance,
+    first: Utterance<'a>,
+    options: &MergeOptions,
+) -> Option<Utterance<'a>> {
+    let duplicate = match duplicate_at_boundary(last, &first, options.language.unwrap_or_default())
+    {
+        Some(duplicate) => duplicate,
+        None => return Some(first),
+    };
+    let segment = index + 1;
+    match duplicate.drop_len {
+        Some(len) if options.drop_boundary_duplicates => {
+            warn!(
+                target: MERGE,
+                "segment {}: leaving out \"{}\", it repeats the end of the previous segment",
+                segment,
+                duplicate.sentence
+            );
+            Some(first.without_speech_start(len)).filter(|rest| !rest.speech_text().is_empty())
+        }
+        Some(_) => {
+            warn!(
+                target: MERGE,
+                "segment {}: \"{}\" repeats the end of the previous segment, \
+                 use --drop-boundary-duplicates to leave it out",
+                segment,
+                duplicate.sentence
+            );
+            Some(first)
+        }
+        None => {
+            warn!(
+                target: MERGE,
+                "segment {}: \"{}\" repeats the end of the previous segment, \
+                 but contains timestamps and is kept",
+                segment,
+                duplicate.sentence
+            );
+            Some(first)
+        }
+    }
+}
+
+/// Logs if and why the utterances at the start of the segment with the
+/// given index and at the end of the previous one are stitched.
+fn log_stitching(
+    index: usize,
+    (last, last_shift): (&Utterance, Timestamp),
+    (first, shift): (&Utterance, Timestamp),
+    options: &MergeOptions,
+) {
+    let before = shifted(
+        last.timestamps()
+            .next_back()
+            .map(|(_, timestamp)| timestamp),
+        last_shift,
+    );
+    let after = shifted(
+        first.timestamps().next().map(|(_, timestamp)| timestamp),
+        shift,
+    );
+    let or_none = |timestamp: Option<Timestamp>| {
+        timestamp
+            .map(|timestamp| timestamp.to_string())
+            .unwrap_or_else(|| String::from("no timestamp"))
+    };
+    let segment = index + 1;
+    if last.speaker() != first.speaker() {
+        debug!(
+            target: MERGE,
+            "segment {}: not stitching, {} ends the previous segment and {} starts this one",
+            segment,
+            last.speaker_text(),
+            first.speaker()
+        );
+    } else if !options.stitch {
+        debug!(
+            target: MERGE,
+            "segment {}: not stitching utterances of {}, stitching is off",
+            segment,
+            first.speaker_text()
+        );
+    } else if options.should_stitch(before, after) {
+        debug!(
+            target: MERGE,
+            "segment {}: stitching utterances of {} ending at {} and starting at {}",
+            segment,
+            first.speaker_text(),
+            or_none(before),
+            or_none(after)
+        );
+    } else {
+        debug!(
+            target: MERGE,
+            "segment {}: not stitching utterances of {}, {} is more than {} after {}",
+            segment,
+            first.speaker_text(),
+            or_none(after),
+            options.stitch_max_gap,
+            or_none(before)
+        );
+    }
+}
+
+/// The line with its speaker label colored, if speakers are colored.
+fn paint<'a>(line: Line<'a>, colors: Option<&SpeakerColors>) -> Line<'a> {
+    match colors {
+        Some(colors) => colors.paint(line),
+        None => line,
+    }
+}
+
+/// The line with its line number, if utterances are numbered.
+fn number<'a>(
+    line: Line<'a>,
+    numbers: &mut Option<LineNumbers>,
+    at: (usize, Timestamp),
+) -> Line<'a> {
+    match numbers {
+        Some(numbers) => numbers.number(line, at),
+        None => line,
+    }
+}
+
+/// Records the line for the outputs other than RTF, if they are written.
+fn record(line: &Line, records: &mut Option<Records>, at: (usize, Timestamp)) -> Result<()> {
+    match records {
+        Some(records) => records.line(line, at),
+        None => Ok(()),
+    }
+}
+
+/// The first timestamp in the given text, if any.
+fn first_timestamp(text: &str) -> Option<Timestamp> {
+    Timestamp::extract_timestamps(text.as_bytes())
+        .first()
+        .map(|&(_, timestamp)| timestamp)
+}
+
+fn shifted(timestamp: Option<Timestamp>, by: Timestamp) -> Option<Timestamp> {
+    timestamp.map(|timestamp| timestamp.saturating_add(by))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::convert::TryFrom;
     use std::str;
 
+    #[test]
+    fn resumed_merge_is_the_same() {
+        let transcripts = vec![
+            Transcript::from_file("testdata/interview-01.rtf").unwrap(),
+            Transcript::from_file("testdata/interview-02.rtf").unwrap(),
+        ];
+        let options = MergeOptions::default();
+        let mut expected = vec![];
+        let expected_shifts =
+            write_merged_transcript(&mut expected, transcripts.clone(), &options).unwrap();
+
+        let (first, second) = (transcripts[0].clone(), transcripts[1].clone());
+        let mut written = vec![];
+        let mut merger = Merger::new(&mut written, &first, &options).unwrap();
+        merger.add(first.clone()).unwrap();
+        let checkpoint = merger.checkpoint().unwrap();
+        // the merge stops here and continues in another run
+        drop(merger);
+        let mut merger = Merger::resume(
+            &mut written,
+            &first,
+            Some(first.clone()),
+            checkpoint,
+            &options,
+        );
+        merger.add(second).unwrap();
+        let shifts = merger.finish().unwrap();
+
+        assert_eq!(str::from_utf8(&written), str::from_utf8(&expected));
+        assert_eq!(shifts, expected_shifts);
+    }
+
+    #[test]
+    fn merging_a_single_transcript_reproduces_it() {
+        for path in &["testdata/interview-01.rtf", "testdata/interview-02.rtf"] {
+            let original = std::fs::read_to_string(path).unwrap();
+            let transcript = Transcript::from_file(path).unwrap();
+
+            let mut buf = vec![];
+            write_merged_transcript(&mut buf, vec![transcript], &MergeOptions::default()).unwrap();
+
+            assert_eq!(str::from_utf8(&buf).unwrap(), original);
+        }
+    }
+
+    #[test]
+    fn line_ending_of_first_transcript_is_kept() {
+        let lf = std::fs::read_to_string("testdata/interview-01.rtf")
+            .unwrap()
+            .replace("\r\n", "\n");
+        let transcript001 = Transcript::try_from(lf).unwrap();
+        let transcript002 = Transcript::from_file("testdata/interview-02.rtf").unwrap();
+
+        let mut buf = vec![];
+        write_merged_transcript(
+            &mut buf,
+            vec![transcript001, transcript002],
+            &MergeOptions::default(),
+        )
+        .unwrap();
+
+        assert!(!buf.contains(&b'\r'));
+        assert!(str::from_utf8(&buf).unwrap().ends_with("\n}"));
+    }
+
+    #[test]
+    fn start_offsets_correct_shifts_unless_timestamps_go_back() {
+        let first = Transcript::from_file("testdata/interview-01.rtf").unwrap();
+        let second = Transcript::from_file("testdata/interview-02.rtf").unwrap();
+        let merged = |offset| {
+            let transcripts = vec![first.clone(), second.clone().with_start_offset(offset)];
+            write_merged_transcript(vec![], transcripts, &MergeOptions::default())
+        };
+
+        let shifts = merged(120).unwrap();
+        assert_eq!(shifts[1].shift, Timestamp::parse("#00:05:12-0#").unwrap());
+        assert!(matches!(
+            merged(-2999),
+            Err(Error::OffsetNotMonotonic(2, _, _))
+        ));
+        assert!(matches!(merged(-3001), Err(Error::OffsetBeforeStart(2))));
+    }
+
+    #[test]
+    fn shifts_are_reported_per_segment() {
+        let transcript001 = Transcript::from_file("testdata/interview-01.rtf").unwrap();
+        let transcript002 = Transcript::from_file("testdata/interview-02.rtf").unwrap();
+
+        let shifts = write_merged_transcript(
+            vec![],
+            vec![transcript001, transcript002],
+            &MergeOptions::default(),
+        )
+        .unwrap();
+
+        let five_minutes = Timestamp::parse("#00:05:00-0#").unwrap();
+        assert_eq!(
+            shifts,
+            vec![
+                SegmentShift {
+                    end_time: five_minutes,
+                    shift: Timestamp::zero(),
+                    adjusted_timestamps: 8,
+                    unadjusted: vec![],
+                    numbered_lines: vec![],
+                },
+                SegmentShift {
+                    end_time: Timestamp::parse("#00:02:00-0#").unwrap(),
+                    shift: five_minutes,
+                    adjusted_timestamps: 6,
+                    unadjusted: vec![],
+                    numbered_lines: vec![],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn segment_shifts_are_the_shifts_of_the_merge() {
+        let transcripts = vec![
+            Transcript::from_file("testdata/interview-01.rtf").unwrap(),
+            Transcript::from_file("testdata/interview-02.rtf")
+                .unwrap()
+                .with_start_offset(-20),
+        ];
+        let options = MergeOptions::default();
+        let merged = write_merged_transcript(std::io::sink(), transcripts.clone(), &options)
+            .unwrap()
+            .into_iter()
+            .map(|shift| shift.shift)
+            .collect::<Vec<_>>();
+        assert_eq!(segment_shifts(&transcripts, &options).unwrap(), merged);
+    }
+
+    #[test]
+    fn overflowing_shift_is_an_error() {
+        let original = std::fs::read_to_string("testdata/interview-01.rtf").unwrap();
+        let huge = original.replace("#00:04:50-3#", "#3000:04:50-3#");
+        let huge = Transcript::try_from(huge).unwrap();
+
+        let result =
+            write_merged_transcript(vec![], vec![huge.clone(), huge], &MergeOptions::default());
+
+        assert!(
+            matches!(result, Err(Error::TimestampOverflow(_, _))),
+            "expected overflow, got {:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn unadjusted_timestamps_are_reported() {
+        const MISSED: &str = "{\\b I: Versteh ich nicht #00:00:58-6#.}{\\par}";
+        let transcript001 = Transcript::from_file("testdata/interview-01.rtf").unwrap();
+        let original = std::fs::read_to_string("testdata/interview-02.rtf").unwrap();
+        let broken = original.replace(
+            "{\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 {\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 I:}\
+             {\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0  Versteh ich nicht #00:00:58-6#.}\\par}",
+            MISSED,
+        );
+        let transcript002 = Transcript::try_from(broken).unwrap();
+
+        let mut buf = vec![];
+        let shifts = write_merged_transcript(
+            &mut buf,
+            vec![transcript001, transcript002],
+            &MergeOptions::default(),
+        )
+        .unwrap();
+        let merged = str::from_utf8(&buf).unwrap();
+        let line = merged.lines().position(|l| l == MISSED).unwrap() + 1;
+
+        assert!(shifts[0].unadjusted.is_empty());
+        assert_eq!(
+            shifts[1].unadjusted,
+            vec![UnadjustedTimestamp {
+                line,
+                column: MISSED.find('#').unwrap() + 1,
+                timestamp: Timestamp::parse("#00:00:58-6#").unwrap(),
+            }]
+        );
+    }
+
     #[test]
     fn merge_001_and_002() {
         // given: transcripts that can be stitched
@@ -161,8 +1126,12 @@ mod test {
 
         // when: writing a stitched version to memory and getting the interesting line
         let mut buf = vec![];
-        write_merged_transcript(&mut buf, vec![transcript001, transcript002])
-            .expect("could not write merged transcipt");
+        write_merged_transcript(
+            &mut buf,
+            vec![transcript001, transcript002],
+            &MergeOptions::default(),
+        )
+        .expect("could not write merged transcipt");
         let merged = str::from_utf8(&buf[..]).expect("not valid utf-8");
         let mut merged_lines = merged.lines();
         let merged_line = merged_lines.nth(17).expect("could not get stitched line");
@@ -172,14 +1141,104 @@ mod test {
         assert_eq!(
             merged_line,
             "{\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 {\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 Z:}\
-            {\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 Ich glaub jetzt wei\\'df ich, worauf sie \
+            {\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0  Ich glaub jetzt wei\\'df ich, worauf sie \
             hinauswollen. #00:04:50-3# Zunächst einmal ist der Punk nicht tot, ja? \
             #00:05:27-8# So auditiv meine ich. #00:05:31-6#}\\par}"
         );
         assert_eq!(
             last_line,
             "{\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 {\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 Z:}\
-            {\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 Ja, ja. #00:06:56-9#}\\par}"
+            {\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0  Ja, ja. #00:06:56-9#}\\par}"
         )
     }
+
+    #[test]
+    fn no_stitching_across_long_gaps() {
+        let transcript001 = Transcript::from_file("testdata/interview-01.rtf").unwrap();
+        let transcript002 = Transcript::from_file("testdata/interview-02.rtf").unwrap();
+        // last timestamp #00:04:50-3#, first after boundary #00:05:27-8#
+        let options = MergeOptions {
+            stitch_max_gap: Timestamp::parse("#00:00:30-0#").unwrap(),
+            ..MergeOptions::default()
+        };
+
+        let mut buf = vec![];
+        write_merged_transcript(&mut buf, vec![transcript001, transcript002], &options).unwrap();
+        let merged = str::from_utf8(&buf).unwrap();
+        let mut merged_lines = merged.lines();
+
+        assert!(merged_lines
+            .nth(17)
+            .unwrap()
+            .ends_with("hinauswollen. #00:04:50-3#}\\par}"));
+        assert!(merged_lines.next().unwrap().contains("Z: Zunächst einmal"));
+    }
+
+    #[test]
+    fn stitching_can_be_turned_off() {
+        let options = MergeOptions {
+            stitch: false,
+            ..MergeOptions::default()
+        };
+        assert!(!options.should_stitch(None, None));
+        assert!(MergeOptions::default().should_stitch(None, None));
+    }
+
+    #[test]
+    fn trailers_are_moved_to_the_end() {
+        const NOTE: &str = "{\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 Aufnahme endet.\\par}";
+        let original = std::fs::read_to_string("testdata/interview-01.rtf").unwrap();
+        let end = original.rfind("\r\n}").unwrap();
+        let with_note = format!("{}{}\r\n{}", &original[..end], NOTE, &original[end..]);
+        let transcript001 = Transcript::try_from(with_note).unwrap();
+        let transcript002 = Transcript::from_file("testdata/interview-02.rtf").unwrap();
+        let merge = |trailers| {
+            let options = MergeOptions {
+                trailers,
+                ..MergeOptions::default()
+            };
+            let mut buf = vec![];
+            write_merged_transcript(
+                &mut buf,
+                vec![transcript001.clone(), transcript002.clone()],
+                &options,
+            )
+            .unwrap();
+            String::from_utf8(buf).unwrap()
+        };
+
+        let kept = merge(Trailers::Keep);
+        assert_eq!(kept.lines().nth(18), Some(NOTE));
+        let moved = merge(Trailers::Move);
+        // without the note in between, the utterances of Z are stitched
+        assert!(moved
+            .lines()
+            .nth(17)
+            .unwrap()
+            .contains("Zun\u{e4}chst einmal"));
+        assert_eq!(moved.lines().rev().nth(2), Some(NOTE));
+        let stripped = merge(Trailers::Strip);
+        assert!(!stripped.contains(NOTE));
+        assert_eq!(stripped.lines().count(), moved.lines().count() - 1);
+    }
+
+    #[test]
+    fn boundaries_are_marked_with_paragraphs() {
+        let transcript001 = Transcript::from_file("testdata/interview-01.rtf").unwrap();
+        let transcript002 = Transcript::from_file("testdata/interview-02.rtf").unwrap();
+        let options = MergeOptions {
+            mark_boundaries: true,
+            ..MergeOptions::default()
+        };
+
+        let mut buf = vec![];
+        write_merged_transcript(&mut buf, vec![transcript001, transcript002], &options).unwrap();
+        let merged = str::from_utf8(&buf).unwrap();
+
+        assert_eq!(
+            merged.lines().nth(18).unwrap(),
+            "{\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 --- Segment 2 (shift #00:05:00-0#) ---\\par}"
+        );
+        assert_eq!(merged.matches("--- Segment").count(), 1);
+    }
 }
@@ -1,9 +1,56 @@
+mod annotations;
+mod builder;
+mod colors;
+mod diagnostics;
+mod docinfo;
+mod duplicates;
+mod encoding;
 mod err;
+mod formats;
+mod header;
+pub mod json;
+mod line_ending;
 mod lines;
 mod merge;
+mod numbering;
+mod outputs;
+mod records;
+mod revisions;
 mod rtf;
+mod sentences;
+mod speakers;
+mod template;
+mod text;
+mod toc;
+mod trailer;
 mod transcript;
+mod unadjusted;
+mod word_index;
 
+pub use annotations::{
+    annotations, strip_annotations, Annotation, AnnotationKind, StripAnnotations,
+};
+pub use builder::TranscriptBuilder;
+pub use diagnostics::{UnknownControlWord, UnparsedLine};
+pub use docinfo::{DocumentInfo, InfoTime};
+pub use encoding::{Encoder, OutputEncoding};
 pub use err::*;
-pub use merge::write_merged_transcript;
+pub(crate) use formats::{csv_field, html_escape};
+pub use formats::{format_named, Html, Intervals, Nvivo, OutputFormat, Srt, Text, FORMATS};
+pub use line_ending::LineEnding;
+pub use lines::{Dialect, Utterance};
+pub use merge::{
+    segment_shifts, write_merged_transcript, Checkpoint, MergeOptions, Merger, SegmentShift,
+};
+pub use numbering::{LineNumbering, NumberedLine};
+pub use outputs::{write_merged_outputs, write_transcript_as, Merged, OutputKind};
+pub use records::{SpeakerInference, UtteranceRecord, UtteranceRecords};
+pub use revisions::strip_revisions;
+pub use rtf::{escape, plain_text, unescape, Rtf, Token, TokenKind};
+pub use sentences::{sentence_ends, Language};
+pub use speakers::SpeakerRename;
+pub use template::{Record, Template};
+pub use trailer::Trailers;
 pub use transcript::*;
+pub use unadjusted::UnadjustedTimestamp;
+pub use word_index::{index_words, IndexedWord};
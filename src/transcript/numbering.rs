@@ -0,0 +1,127 @@
+//! Line numbers of the utterances in merged RTF, for citing transcripts
+//! the way qualitative coding does.
+//!
+//! Numbers are written before the speaker label, with at least four
+//! digits and a tab, e.g. `{0042\tab}I: ...`.
+//!
+//! Utterances that are stitched together at a segment boundary get a
+//! single number, since they are a single paragraph.
+use super::lines::Line;
+use crate::timestamp::Timestamp;
+
+/// How utterances are numbered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineNumbering {
+    /// Number of the first utterance.
+    pub start: u64,
+    /// Difference between the numbers of consecutive utterances.
+    pub step: u64,
+}
+
+impl Default for LineNumbering {
+    fn default() -> Self {
+        LineNumbering { start: 1, step: 1 }
+    }
+}
+
+/// An utterance of the merged transcript with its line number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NumberedLine {
+    pub number: u64,
+    /// First timestamp of the utterance, adjusted, if it has any.
+    pub timestamp: Option<Timestamp>,
+}
+
+/// Numbers utterances in the order they are written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct LineNumbers {
+    next: u64,
+    step: u64,
+    /// Numbered lines with the indexes of their segments.
+    numbered: Vec<(usize, NumberedLine)>,
+}
+
+impl LineNumbers {
+    pub(super) fn new(numbering: LineNumbering) -> Self {
+        LineNumbers {
+            next: numbering.start,
+            step: numbering.step,
+            numbered: vec![],
+        }
+    }
+
+    /// The line with the next number if it is an utterance of the segment
+    /// with the given index and shift.
+    pub(super) fn number<'a>(
+        &mut self,
+        line: Line<'a>,
+        (segment, shift): (usize, Timestamp),
+    ) -> Line<'a> {
+        match line {
+            Line::Utterance(utterance) => {
+                let number = self.next;
+                self.next = self.next.saturating_add(self.step);
+                let timestamp = utterance
+                    .timestamps()
+                    .next()
+                    .map(|(_, timestamp)| timestamp.saturating_add(shift));
+                self.numbered
+                    .push((segment, NumberedLine { number, timestamp }));
+                Line::Utterance(utterance.with_line_number(Some(number)))
+            }
+            other => other,
+        }
+    }
+
+    /// The numbered lines with the indexes of their segments.
+    pub(super) fn finish(self) -> Vec<(usize, NumberedLine)> {
+        self.numbered
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::transcript::json::Document;
+
+    #[test]
+    fn utterances_are_numbered_by_step_with_their_first_timestamp() {
+        let transcript = Document::from_text(
+            "I: Und dann? #00:00:01-0#\nZ: Dann kam er #00:00:03-0# nach Hause. #00:00:05-0#\n",
+        )
+        .to_transcript()
+        .unwrap();
+        let mut numbers = LineNumbers::new(LineNumbering { start: 10, step: 5 });
+        let shift = Timestamp::from_tenths(600).unwrap();
+        let mut written = vec![];
+        for line in transcript.lines() {
+            numbers
+                .number(line, (1, shift))
+                .write_adjusted(&mut written, shift)
+                .unwrap();
+        }
+
+        let written = String::from_utf8(written).unwrap();
+        assert!(written.contains("{0010\\tab}{\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 I:}"));
+        assert!(written.contains("{0015\\tab}{\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 Z:}"));
+        assert_eq!(
+            numbers.finish(),
+            vec![
+                (
+                    1,
+                    NumberedLine {
+                        number: 10,
+                        timestamp: Timestamp::from_tenths(610),
+                    }
+                ),
+                (
+                    1,
+                    NumberedLine {
+                        number: 15,
+                        timestamp: Timestamp::from_tenths(630),
+                    }
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,469 @@
+//! Writes one merge to several outputs in different formats at once, so
+//! that the transcripts are only loaded and merged a single time.
+use std::borrow::Cow;
+use std::io::{sink, Result as IOResult, Write};
+use std::iter::once;
+use std::path::Path;
+
+use super::annotations::strip_annotations;
+use super::formats::{end_time, format_of_file, without_timestamps, OutputFormat, Rtf};
+use super::merge::write_recorded_merge;
+use super::sentences::{sentence_ends, Language};
+use super::template::Record;
+use crate::timestamp::Timestamp;
+use crate::transcript::{
+    write_merged_transcript, MergeOptions, Result, SegmentShift, Template, Transcript,
+};
+
+/// How a merged transcript is written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputKind {
+    /// One of the `FORMATS`.
+    Format(&'static dyn OutputFormat),
+    /// User-provided template.
+    Template(Template),
+}
+
+impl OutputKind {
+    /// RTF like the merged transcripts.
+    pub const RTF: OutputKind = OutputKind::Format(&Rtf);
+
+    /// Infers the format from the extension of the path, that of one of
+    /// the `FORMATS`, e.g. `intervals.csv` for timestamp intervals or `csv`
+    /// for NVivo.
+    /// The template, if any, is used for anything but the formats made
+    /// from the merged RTF, and paths with other extensions are written
+    /// as RTF.
+    pub fn for_path(path: &Path, template: Option<&Template>) -> OutputKind {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        match (format_of_file(&name), template) {
+            (Some(format), _) if format.made_from_rtf() => OutputKind::Format(format),
+            (_, Some(template)) => OutputKind::Template(template.clone()),
+            (format, None) => format.map_or(OutputKind::RTF, OutputKind::Format),
+        }
+    }
+
+    /// The format that outputs of this kind are written in.
+    pub fn format(&self) -> &dyn OutputFormat {
+        match self {
+            OutputKind::Format(format) => *format,
+            OutputKind::Template(template) => template,
+        }
+    }
+}
+
+/// A finished merge, which outputs are written from.
+pub struct Merged {
+    /// The merged RTF, if an output is made from it.
+    rtf: Vec<u8>,
+    records: Vec<Record>,
+    options: MergeOptions,
+}
+
+impl Merged {
+    /// The merged RTF, which is empty unless an output is made from it.
+    pub fn rtf(&self) -> &[u8] {
+        &self.rtf
+    }
+
+    /// The merged utterances, without annotations if they are left out of
+    /// the format, and split into sentences if the format has a line per
+    /// sentence and the language is known.
+    pub fn records<F: OutputFormat + ?Sized>(&self, format: &F) -> Cow<'_, [Record]> {
+        let records = if self.options.strip_annotations.strips(format) {
+            Cow::Owned(without_annotations(&self.records))
+        } else {
+            Cow::Borrowed(&self.records[..])
+        };
+        match self.options.language {
+            Some(language) if format.by_sentence() => {
+                Cow::Owned(sentence_records(&records, language))
+            }
+            _ => records,
+        }
+    }
+}
+
+/// Merges the transcripts like `write_merged_transcript` and writes the
+/// result to every output in its format, reading every transcript once.
+///
+/// If every output is RTF, they are written while merging. Otherwise, the
+/// merged utterances are recorded while merging, and the outputs are
+/// written from them and the merged RTF, if one needs it, after the last
+/// transcript was merged. Unadjusted timestamps in the shifts refer to
+/// the RTF version and are only reported if there is an RTF output.
+pub fn write_merged_outputs<W, I>(
+    outputs: Vec<(W, &OutputKind)>,
+    transcripts: I,
+    options: &MergeOptions,
+) -> Result<Vec<SegmentShift>>
+where
+    W: Write,
+    I: IntoIterator<Item = Transcript>,
+{
+    if outputs.iter().all(|(_, kind)| **kind == OutputKind::RTF) {
+        let rtf = outputs.into_iter().map(|(to, _)| to).collect();
+        return write_merged_transcript(Tee(rtf), transcripts, options);
+    }
+
+    let mut rtf = vec![];
+    let (mut shifts, records) = if outputs
+        .iter()
+        .any(|(_, kind)| kind.format().made_from_rtf())
+    {
+        write_recorded_merge(&mut rtf, transcripts, options)?
+    } else {
+        write_recorded_merge(sink(), transcripts, options)?
+    };
+    if !outputs.iter().any(|(_, kind)| **kind == OutputKind::RTF) {
+        for shift in &mut shifts {
+            shift.unadjusted.clear();
+        }
+    }
+    let merged = Merged {
+        rtf,
+        records,
+        options: *options,
+    };
+    for (mut to, kind) in outputs {
+        kind.format().write(&mut to, &merged)?;
+    }
+    Ok(shifts)
+}
+
+/// Writes a single transcript in the format, like a merge of only that
+/// transcript.
+pub fn write_transcript_as<W: Write>(
+    to: W,
+    transcript: Transcript,
+    format: &'static dyn OutputFormat,
+) -> Result<()> {
+    let kind = OutputKind::Format(format);
+    let outputs = vec![(to, &kind)];
+    write_merged_outputs(outputs, once(transcript), &MergeOptions::default())?;
+    Ok(())
+}
+
+/// Writes everything to all of the writers.
+struct Tee<W>(Vec<W>);
+
+impl<W: Write> Write for Tee<W> {
+    fn write(&mut self, buf: &[u8]) -> IOResult<usize> {
+        for to in &mut self.0 {
+            to.write_all(buf)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> IOResult<()> {
+        self.0.iter_mut().try_for_each(Write::flush)
+    }
+}
+
+/// The records with the annotations left out of their speech.
+fn without_annotations(records: &[Record]) -> Vec<Record> {
+    records
+        .iter()
+        .map(|record| Record {
+            speech: strip_annotations(&record.speech),
+            ..record.clone()
+        })
+        .collect()
+}
+
+/// Splits the utterances into a record per sentence. Sentences that end
+/// with a timestamp end at it, and the times of the others are
+/// interpolated by their position between the timestamps around them.
+fn sentence_records(records: &[Record], language: Language) -> Vec<Record> {
+    let mut sentences = vec![];
+    for (idx, record) in records.iter().enumerate() {
+        let speech = &record.speech;
+        let mut known = vec![(0, record.start)];
+        for (offset, timestamp) in Timestamp::timestamps_in(speech) {
+            known.push((offset, timestamp));
+            known.push((offset + timestamp.len(), timestamp));
+        }
+        known.push((speech.len(), end_time(record, records.get(idx + 1))));
+        let mut start = 0;
+        let mut ends = sentence_ends(speech, language);
+        ends.push(speech.len());
+        for end in ends {
+            let end = after_timestamps(speech, end);
+            if without_timestamps(&speech[start..end]).is_empty() {
+                continue;
+            }
+            sentences.push(Record {
+                speech: speech[start..end].trim().to_string(),
+                start: interpolated(&known, start),
+                end: Some(interpolated(&known, end)),
+                segment: record.segment,
+                speaker: record.speaker.clone(),
+                inferred: record.inferred,
+            });
+            start = end;
+        }
+    }
+    sentences
+}
+
+/// The offset after the timestamps following the offset, if any.
+fn after_timestamps(text: &str, mut offset: usize) -> usize {
+    loop {
+        let rest = text[offset..].trim_start();
+        match Timestamp::timestamps_in(rest).next() {
+            Some((0, timestamp)) => offset = text.len() - rest.len() + timestamp.len(),
+            _ => return offset,
+        }
+    }
+}
+
+/// Time at the offset, between the known times at offsets before and
+/// after it.
+fn interpolated(known: &[(usize, Timestamp)], offset: usize) -> Timestamp {
+    let after = known
+        .iter()
+        .position(|&(known_offset, _)| known_offset >= offset)
+        .unwrap_or(known.len() - 1);
+    let (end_offset, end) = known[after];
+    let (start_offset, start) = known[after.saturating_sub(1)];
+    if end_offset <= start_offset || end <= start {
+        return start.max(end);
+    }
+    let tenths = (end.tenths() - start.tenths()) * (offset - start_offset) as u64
+        / (end_offset - start_offset) as u64;
+    Timestamp::from_tenths(start.tenths() + tenths).unwrap_or(end)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::transcript::formats::{Html, Intervals, Json, Nvivo, Srt, Text};
+    use crate::transcript::json::Document;
+    use crate::transcript::{SpeakerInference, Trailers, TranscriptBuilder};
+    use std::convert::TryFrom;
+
+    #[test]
+    fn formats_are_inferred_from_extensions() {
+        let template = Template::parse("{speech}").unwrap();
+        assert_eq!(
+            OutputKind::for_path(Path::new("a.RTF"), None),
+            OutputKind::RTF
+        );
+        assert_eq!(
+            OutputKind::for_path(Path::new("a.txt"), None),
+            OutputKind::Format(&Text)
+        );
+        assert_eq!(
+            OutputKind::for_path(Path::new("a.srt"), None),
+            OutputKind::Format(&Srt)
+        );
+        assert_eq!(
+            OutputKind::for_path(Path::new("a.csv"), None),
+            OutputKind::Format(&Nvivo)
+        );
+        assert_eq!(
+            OutputKind::for_path(Path::new("a.json"), Some(&template)),
+            OutputKind::Format(&Json)
+        );
+        assert_eq!(
+            OutputKind::for_path(Path::new("a.Intervals.csv"), None),
+            OutputKind::Format(&Intervals)
+        );
+        assert_eq!(
+            OutputKind::for_path(Path::new("a.tex"), Some(&template)),
+            OutputKind::Template(template)
+        );
+        assert_eq!(
+            OutputKind::for_path(Path::new("a.htm"), None),
+            OutputKind::Format(&Html)
+        );
+        assert_eq!(OutputKind::for_path(Path::new("a"), None), OutputKind::RTF);
+    }
+
+    #[test]
+    fn all_formats_in_one_pass() {
+        let transcripts = vec![
+            Transcript::from_file("testdata/interview-01.rtf").unwrap(),
+            Transcript::from_file("testdata/interview-02.rtf").unwrap(),
+        ];
+        let mut expected_rtf = vec![];
+        write_merged_transcript(
+            &mut expected_rtf,
+            transcripts.clone(),
+            &MergeOptions::default(),
+        )
+        .unwrap();
+
+        let (mut rtf, mut text, mut srt, mut json) = (vec![], vec![], vec![], vec![]);
+        let outputs = vec![
+            (&mut rtf, &OutputKind::RTF),
+            (&mut text, &OutputKind::Format(&Text)),
+            (&mut srt, &OutputKind::Format(&Srt)),
+            (&mut json, &OutputKind::Format(&Json)),
+        ];
+        let shifts = write_merged_outputs(outputs, transcripts, &MergeOptions::default()).unwrap();
+
+        assert_eq!(shifts.len(), 2);
+        assert_eq!(rtf, expected_rtf);
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.starts_with("I: "), "{}", text);
+        let srt = String::from_utf8(srt).unwrap();
+        assert!(
+            srt.starts_with("1\n00:00:00,000 --> 00:00:31,600\nI: "),
+            "{}",
+            srt
+        );
+        assert!(!srt.contains('#'), "{}", srt);
+        // the other outputs have the utterances of the RTF, stitched alike
+        let merged = Transcript::try_from(String::from_utf8(rtf).unwrap()).unwrap();
+        let utterances = merged.lines().filter(|line| line.utterance().is_some());
+        assert_eq!(text.matches("\n\n").count(), utterances.count());
+        let json: Document = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json, Document::of(&merged));
+    }
+
+    #[test]
+    fn boundary_duplicates_are_left_out_of_every_output() {
+        let ts = |text| Timestamp::parse(text).unwrap();
+        let transcripts = vec![
+            TranscriptBuilder::new()
+                .timed_utterance("I", "Wann war das?", ts("#00:04:40-0#"))
+                .utterance(
+                    "B",
+                    "Wir sind dann umgezogen. Das war im Winter 1985. #00:04:58-2#",
+                )
+                .build()
+                .unwrap(),
+            TranscriptBuilder::new()
+                .utterance("B", "Das war im Winter 85! Und dann? #00:00:03-1#")
+                .timed_utterance("I", "Genau.", ts("#00:00:09-0#"))
+                .build()
+                .unwrap(),
+        ];
+        let options = MergeOptions {
+            drop_boundary_duplicates: true,
+            ..MergeOptions::default()
+        };
+
+        let (mut rtf, mut text) = (vec![], vec![]);
+        let outputs = vec![
+            (&mut rtf, &OutputKind::RTF),
+            (&mut text, &OutputKind::Format(&Text)),
+        ];
+        write_merged_outputs(outputs, transcripts, &options).unwrap();
+
+        let rtf = String::from_utf8(rtf).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(rtf.contains(" Und dann? #00:05:03-1#}"), "{}", rtf);
+        assert!(!rtf.contains("Winter 85!"), "{}", rtf);
+        assert!(text.contains("B: Und dann? #00:05:03-1#"), "{}", text);
+        assert!(!text.contains("Winter 85!"), "{}", text);
+    }
+
+    #[test]
+    fn utterances_that_only_repeat_the_boundary_are_left_out() {
+        let ts = |text| Timestamp::parse(text).unwrap();
+        let transcripts = vec![
+            TranscriptBuilder::new()
+                .utterance(
+                    "B",
+                    "Wir sind dann umgezogen. Das war im Winter 1985. #00:04:58-2#",
+                )
+                .build()
+                .unwrap(),
+            TranscriptBuilder::new()
+                .utterance("B", "Das war im Winter 85!")
+                .timed_utterance("I", "Genau.", ts("#00:00:09-0#"))
+                .build()
+                .unwrap(),
+        ];
+        let options = MergeOptions {
+            drop_boundary_duplicates: true,
+            ..MergeOptions::default()
+        };
+
+        let (mut rtf, mut text) = (vec![], vec![]);
+        let outputs = vec![
+            (&mut rtf, &OutputKind::RTF),
+            (&mut text, &OutputKind::Format(&Text)),
+        ];
+        write_merged_outputs(outputs, transcripts, &options).unwrap();
+
+        let rtf = Transcript::try_from(String::from_utf8(rtf).unwrap()).unwrap();
+        let speakers: Vec<_> = rtf
+            .lines()
+            .filter_map(|line| line.utterance().map(|u| u.speaker().to_string()))
+            .collect();
+        assert_eq!(speakers, ["B", "I"]);
+        let text = String::from_utf8(text).unwrap();
+        assert_eq!(text.matches("B:").count(), 1, "{}", text);
+        assert!(!text.contains("Winter 85!"), "{}", text);
+    }
+
+    #[test]
+    fn moved_trailers_end_every_output() {
+        const NOTE: &str = "{\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 Aufnahme endet.\\par}";
+        let original = std::fs::read_to_string("testdata/interview-01.rtf").unwrap();
+        let end = original.rfind("\r\n}").unwrap();
+        let with_note = format!("{}{}\r\n{}", &original[..end], NOTE, &original[end..]);
+        let transcripts = vec![
+            Transcript::try_from(with_note).unwrap(),
+            Transcript::from_file("testdata/interview-02.rtf").unwrap(),
+        ];
+        let merge = |trailers| {
+            let options = MergeOptions {
+                trailers,
+                infer_speakers: Some(SpeakerInference::Previous),
+                ..MergeOptions::default()
+            };
+            let (mut rtf, mut text) = (vec![], vec![]);
+            let outputs = vec![
+                (&mut rtf, &OutputKind::RTF),
+                (&mut text, &OutputKind::Format(&Text)),
+            ];
+            write_merged_outputs(outputs, transcripts.clone(), &options).unwrap();
+            (
+                String::from_utf8(rtf).unwrap(),
+                String::from_utf8(text).unwrap(),
+            )
+        };
+
+        let (rtf, text) = merge(Trailers::Move);
+        assert_eq!(rtf.lines().rev().nth(2), Some(NOTE));
+        assert!(text.trim_end().ends_with(": Aufnahme endet."), "{}", text);
+        assert_eq!(text.matches("Aufnahme endet.").count(), 1, "{}", text);
+        let (rtf, text) = merge(Trailers::Strip);
+        assert!(!rtf.contains("Aufnahme endet."));
+        assert!(!text.contains("Aufnahme endet."), "{}", text);
+    }
+
+    #[test]
+    fn sentences_end_at_timestamps_or_in_between() {
+        let records = vec![Record {
+            segment: 0,
+            speaker: "B".to_string(),
+            speech: "Ja, z.B. Punk. #00:00:04-0# Und dann? Wir sind los. #00:00:10-0#".to_string(),
+            start: Timestamp::parse("#00:00:00-0#").unwrap(),
+            end: Some(Timestamp::parse("#00:00:10-0#").unwrap()),
+            inferred: false,
+        }];
+        let rows: Vec<_> = sentence_records(&records, Language::German)
+            .into_iter()
+            .map(|record| {
+                let end = record.end.unwrap();
+                (record.speech, record.start.tenths(), end.tenths())
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("Ja, z.B. Punk. #00:00:04-0#".to_string(), 0, 40),
+                ("Und dann?".to_string(), 40, 64),
+                ("Wir sind los. #00:00:10-0#".to_string(), 64, 100),
+            ]
+        );
+    }
+}
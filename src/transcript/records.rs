@@ -0,0 +1,308 @@
+//! Utterances of a transcript as owned values, for exporters and
+//! statistics that do not want to deal with RTF lines.
+use std::collections::VecDeque;
+use std::iter::once;
+use std::ops::Range;
+use std::str::FromStr;
+
+use super::lines::Lines;
+use super::rtf::plain_text;
+use crate::timestamp::Timestamp;
+use crate::transcript::Transcript;
+
+/// An utterance with its text as plain text, independent of the
+/// transcript it was read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UtteranceRecord {
+    /// Code of the speaker, e.g. `I`.
+    pub speaker: String,
+    /// Plain text of the speech, including its timestamps.
+    pub speech: String,
+    /// Timestamps in the speech, in order of appearance.
+    pub timestamps: Vec<Timestamp>,
+    /// Index of the line in [`Transcript::content`], starting at zero.
+    pub line_index: usize,
+    /// Bytes of the line in [`Transcript::content`], without the line
+    /// break.
+    pub byte_range: Range<usize>,
+    /// If the line is a paragraph without a speaker label that was
+    /// attributed to a speaker by [`SpeakerInference`].
+    pub inferred: bool,
+}
+
+/// Whom paragraphs without a speaker label are attributed to, for
+/// transcripts where continuation paragraphs of long utterances have no
+/// label of their own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpeakerInference {
+    /// The speaker of the utterance before the paragraph.
+    Previous,
+    /// The speaker of the utterance after the paragraph, or the one
+    /// before if there is none.
+    Next,
+}
+
+impl FromStr for SpeakerInference {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "previous" => Ok(SpeakerInference::Previous),
+            "next" => Ok(SpeakerInference::Next),
+            _ => Err(format!(
+                "unknown speaker inference {}, expected previous or next",
+                s
+            )),
+        }
+    }
+}
+
+/// A record of an utterance that paragraphs without a speaker label can
+/// be attributed to the speaker of.
+pub(super) trait Spoken {
+    fn speaker(&self) -> &str;
+    fn set_speaker(&mut self, speaker: String);
+}
+
+impl Spoken for UtteranceRecord {
+    fn speaker(&self) -> &str {
+        &self.speaker
+    }
+
+    fn set_speaker(&mut self, speaker: String) {
+        self.speaker = speaker;
+    }
+}
+
+/// Attributes paragraphs without a speaker label to speakers as the
+/// records come one after the other, the one step of speaker inference
+/// for every kind of record.
+pub(super) struct Attribution<T> {
+    inference: Option<SpeakerInference>,
+    /// Speaker of the last utterance.
+    speaker: Option<String>,
+    /// Paragraphs waiting for the speaker of the next utterance.
+    waiting: Vec<T>,
+}
+
+impl<T: Spoken> Attribution<T> {
+    pub(super) fn new(inference: Option<SpeakerInference>) -> Self {
+        Attribution {
+            inference,
+            speaker: None,
+            waiting: vec![],
+        }
+    }
+
+    /// If paragraphs without a speaker label are attributed at all.
+    pub(super) fn infers(&self) -> bool {
+        self.inference.is_some()
+    }
+
+    /// Adds the utterance to the records, after the waiting paragraphs,
+    /// which go to its speaker.
+    pub(super) fn utterance<E: Extend<T>>(&mut self, utterance: T, to: &mut E) {
+        let speaker = utterance.speaker().to_string();
+        to.extend(self.waiting.drain(..).map(|mut paragraph| {
+            paragraph.set_speaker(speaker.clone());
+            paragraph
+        }));
+        self.speaker = Some(speaker);
+        to.extend(once(utterance));
+    }
+
+    /// Adds a paragraph without a speaker label to the records if it goes
+    /// to the speaker before it, or keeps it for the speaker of the next
+    /// utterance. Paragraphs that cannot be attributed are left out.
+    pub(super) fn paragraph<E: Extend<T>>(&mut self, mut paragraph: T, to: &mut E) {
+        match (self.inference, &self.speaker) {
+            (Some(SpeakerInference::Next), _) => self.waiting.push(paragraph),
+            (Some(SpeakerInference::Previous), Some(speaker)) => {
+                paragraph.set_speaker(speaker.clone());
+                to.extend(once(paragraph));
+            }
+            (Some(SpeakerInference::Previous), None) | (None, _) => (),
+        }
+    }
+
+    /// Adds the waiting paragraphs to the records, attributed to the last
+    /// speaker, since there is no utterance after them, e.g. at the end of
+    /// a transcript. They keep waiting if there was no utterance yet.
+    pub(super) fn flush<E: Extend<T>>(&mut self, to: &mut E) {
+        if let Some(speaker) = &self.speaker {
+            to.extend(self.waiting.drain(..).map(|mut paragraph| {
+                paragraph.set_speaker(speaker.clone());
+                paragraph
+            }));
+        }
+    }
+
+    /// Speaker of the last utterance, if any.
+    pub(super) fn last_speaker(&self) -> Option<&str> {
+        self.speaker.as_deref()
+    }
+}
+
+/// Iterator over the utterances of a transcript, see
+/// [`Transcript::utterance_records`].
+pub struct UtteranceRecords<'a> {
+    content: &'a str,
+    lines: std::iter::Enumerate<std::str::Lines<'a>>,
+    transcript: &'a Transcript,
+    /// Records to return before reading more lines.
+    ready: VecDeque<UtteranceRecord>,
+    attribution: Attribution<UtteranceRecord>,
+}
+
+impl Transcript {
+    /// Iterates over the utterances, skipping other paragraphs.
+    pub fn utterance_records(&self) -> UtteranceRecords<'_> {
+        UtteranceRecords {
+            content: self.content(),
+            lines: self.content().lines().enumerate(),
+            transcript: self,
+            ready: VecDeque::new(),
+            attribution: Attribution::new(None),
+        }
+    }
+}
+
+impl<'a> UtteranceRecords<'a> {
+    /// Also yields paragraphs without a speaker label that continue an
+    /// utterance, attributed to a speaker as given, and marked as
+    /// inferred. Paragraphs that cannot be attributed are skipped.
+    pub fn infer_speakers(self, inference: Option<SpeakerInference>) -> Self {
+        UtteranceRecords {
+            attribution: Attribution::new(inference),
+            ..self
+        }
+    }
+
+    fn record(&self, line_index: usize, raw: &str, speaker: &str, speech: &str) -> UtteranceRecord {
+        let start = raw.as_ptr() as usize - self.content.as_ptr() as usize;
+        UtteranceRecord {
+            speaker: plain_text(speaker).trim().to_string(),
+            speech: plain_text(speech).trim().to_string(),
+            timestamps: Timestamp::extract_timestamps(speech.as_bytes())
+                .into_iter()
+                .map(|(_, timestamp)| timestamp)
+                .collect(),
+            line_index,
+            byte_range: start..start + raw.len(),
+            inferred: false,
+        }
+    }
+}
+
+impl<'a> Iterator for UtteranceRecords<'a> {
+    type Item = UtteranceRecord;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(record) = self.ready.pop_front() {
+            return Some(record);
+        }
+        let dialect = self.transcript.dialect();
+        while let Some((line_index, raw)) = self.lines.next() {
+            let line = Lines::parse_line_in_dialect(dialect, raw);
+            if let Some(utterance) = line.utterance() {
+                let record = self.record(line_index, raw, utterance.speaker(), utterance.speech());
+                self.attribution.utterance(record, &mut self.ready);
+                return self.ready.pop_front();
+            }
+            let content = match line.continuation() {
+                Some(content) if self.attribution.infers() => content,
+                _ => continue,
+            };
+            let mut record = self.record(line_index, raw, "", content);
+            record.inferred = true;
+            self.attribution.paragraph(record, &mut self.ready);
+            if let Some(record) = self.ready.pop_front() {
+                return Some(record);
+            }
+        }
+        // paragraphs after the last utterance go to its speaker
+        self.attribution.flush(&mut self.ready);
+        self.ready.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::transcript::merge::write_recorded_merge;
+    use crate::transcript::MergeOptions;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn records_of_reference_transcript() {
+        let transcript = Transcript::from_file("testdata/interview-02.rtf").unwrap();
+        let records: Vec<UtteranceRecord> = transcript.utterance_records().collect();
+
+        assert_eq!(records.len(), 5);
+        let second = &records[1];
+        assert_eq!(second.speaker, "I");
+        assert_eq!(second.speech, "Versteh ich nicht #00:00:58-6#.");
+        assert_eq!(
+            second.timestamps,
+            vec![Timestamp::parse("#00:00:58-6#").unwrap()]
+        );
+        assert_eq!(second.line_index, 2);
+        assert!(transcript.content()[second.byte_range.clone()].ends_with("\\par}"));
+    }
+
+    #[test]
+    fn unlabeled_paragraphs_are_attributed_to_inferred_speakers() {
+        let original = std::fs::read_to_string("testdata/interview-02.rtf").unwrap();
+        let transcript = Transcript::try_from(original.replacen(
+            "{\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 \\par}",
+            "{\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 Das meine ich ernst. #00:00:40-0#\\par}",
+            1,
+        ))
+        .unwrap();
+        let speakers = |inference| {
+            transcript
+                .utterance_records()
+                .infer_speakers(inference)
+                .map(|record| (record.speaker, record.inferred))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(speakers(None).len(), 5);
+        let previous = speakers(Some(SpeakerInference::Previous));
+        assert_eq!(previous.len(), 6);
+        assert_eq!(previous[1], ("Z".to_string(), true));
+        assert_eq!(
+            speakers(Some(SpeakerInference::Next))[1],
+            ("I".to_string(), true)
+        );
+    }
+
+    #[test]
+    fn merged_records_attribute_paragraphs_like_utterance_records() {
+        let original = std::fs::read_to_string("testdata/interview-02.rtf").unwrap();
+        let transcript = Transcript::try_from(original.replacen(
+            "{\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 \\par}",
+            "{\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 Das meine ich ernst.\\par}",
+            1,
+        ))
+        .unwrap();
+        for inference in [SpeakerInference::Previous, SpeakerInference::Next] {
+            let records: Vec<_> = transcript
+                .utterance_records()
+                .infer_speakers(Some(inference))
+                .map(|record| (record.speaker, record.speech, record.inferred))
+                .collect();
+            let options = MergeOptions {
+                infer_speakers: Some(inference),
+                ..MergeOptions::default()
+            };
+            let (_, merged) =
+                write_recorded_merge(std::io::sink(), vec![transcript.clone()], &options).unwrap();
+            let merged: Vec<_> = merged
+                .into_iter()
+                .map(|record| (record.speaker, record.speech, record.inferred))
+                .collect();
+            assert_eq!(merged, records);
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! Tracked changes and comments that word processors leave in transcripts
+//! that were edited in them, removed so that the text as it reads after
+//! accepting all changes is merged.
+use super::rtf::{Rtf, Token, TokenKind};
+
+/// Control words marking revisions or referencing comments, which are
+/// dropped with their parameters.
+const REVISION_CONTROL_WORDS: &[&str] = &[
+    "\\revised",
+    "\\revauth",
+    "\\revdttm",
+    "\\deleted",
+    "\\revauthdel",
+    "\\revdttmdel",
+    "\\crauth",
+    "\\crdate",
+    "\\chatn",
+];
+/// Destinations of comments, which are dropped with everything in them.
+const COMMENT_DESTINATIONS: &[&str] = &["\\annotation", "\\atn", "\\atrf"];
+
+/// The RTF source with deleted text, revision marks and comments removed,
+/// keeping inserted text.
+///
+/// Deleted text is text in a group after `\deleted`, up to the end of the
+/// group or `\deleted0`. Only the text is removed, the groups and other
+/// control words stay, so that paragraphs stay where they are.
+pub fn strip_revisions(source: &str) -> String {
+    let mut stripped = String::with_capacity(source.len());
+    // for every open group, if text in it is deleted
+    let mut deleted = vec![false];
+    // depth of the comment being skipped, if any
+    let mut comment: Option<usize> = None;
+    // the group start that was just read, held back until it is known if
+    // it starts a comment
+    let mut pending_group: Option<&str> = None;
+    let mut dropping_word = false;
+
+    let mut tokens = Rtf::from(source).peekable();
+    while let Some(token) = tokens.next() {
+        let text = source_of(source, &token);
+        let kind = token.kind();
+        if let Some(depth) = comment {
+            match kind {
+                TokenKind::GroupStart => comment = Some(depth + 1),
+                TokenKind::GroupEnd if depth == 0 => comment = None,
+                TokenKind::GroupEnd => comment = Some(depth - 1),
+                _ => (),
+            }
+            continue;
+        }
+        if kind == TokenKind::ControlSym && text == "\\*" {
+            let starts_comment = tokens.peek().is_some_and(|next| {
+                let word = source_of(source, next).trim_end();
+                next.kind() == TokenKind::ControlWord
+                    && COMMENT_DESTINATIONS
+                        .iter()
+                        .any(|destination| word.starts_with(destination))
+            });
+            if starts_comment && pending_group.is_some() {
+                pending_group = None;
+                deleted.pop();
+                comment = Some(0);
+                continue;
+            }
+        }
+        if let Some(group) = pending_group.take() {
+            stripped.push_str(group);
+        }
+        match kind {
+            TokenKind::GroupStart => {
+                let outer = deleted.last().copied().unwrap_or_default();
+                deleted.push(outer);
+                pending_group = Some(text);
+                continue;
+            }
+            TokenKind::GroupEnd if deleted.len() > 1 => {
+                deleted.pop();
+            }
+            TokenKind::ControlWord => {
+                let word = text.trim_end();
+                dropping_word = REVISION_CONTROL_WORDS.contains(&word);
+                if word == "\\deleted" {
+                    let parameter = tokens
+                        .peek()
+                        .filter(|next| next.kind() == TokenKind::Parameter)
+                        .map(|next| source_of(source, next));
+                    if let Some(current) = deleted.last_mut() {
+                        *current = parameter != Some("0");
+                    }
+                }
+                if dropping_word {
+                    continue;
+                }
+            }
+            // keep what only looks like a delimiter but is text
+            TokenKind::Parameter | TokenKind::Delimiter
+                if dropping_word && (kind == TokenKind::Parameter || text.trim().is_empty()) =>
+            {
+                continue;
+            }
+            TokenKind::Text if deleted.last().copied().unwrap_or_default() => {
+                dropping_word = false;
+                // keep line breaks, which are not text in RTF
+                stripped.extend(text.chars().filter(|&c| c == '\r' || c == '\n'));
+                continue;
+            }
+            _ => (),
+        }
+        if kind != TokenKind::Parameter {
+            dropping_word = false;
+        }
+        stripped.push_str(text);
+    }
+    if let Some(group) = pending_group {
+        stripped.push_str(group);
+    }
+    stripped
+}
+
+/// The source of the token, borrowed from the whole source rather than the
+/// token.
+fn source_of<'a>(source: &'a str, token: &Token) -> &'a str {
+    let extent = token.source();
+    &source[extent.start()..extent.end()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn deleted_text_and_comments_are_removed() {
+        assert_eq!(
+            strip_revisions(
+                "{\\f0 I: Ich war {\\deleted\\revauthdel1 nie }{\\revised\\revauth1 oft }dort.\
+                 {\\*\\atnid A}{\\*\\annotation {\\*\\atnref 1}Stimmt das?}\\par}\r\n"
+            ),
+            "{\\f0 I: Ich war {}{oft }dort.\\par}\r\n"
+        );
+        assert_eq!(
+            strip_revisions("{\\b \\deleted a\\deleted0 b}{c}"),
+            "{\\b b}{c}"
+        );
+        let original = std::fs::read_to_string("testdata/interview-01.rtf").unwrap();
+        assert_eq!(strip_revisions(&original), original);
+    }
+}
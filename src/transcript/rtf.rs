@@ -1,3 +1,5 @@
+use memchr::memrchr3;
+
 pub use token::{Token, TokenKind};
 
 /// Double-ended iterator over RTF tokens in a string slice.
@@ -18,14 +20,70 @@ impl<'a> Iterator for Rtf<'a> {
             self.back_pos,
             self.front_last_consumed,
         )
-        .map(|token| {
+        .inspect(|token| {
             self.front_pos = token.source().end();
             self.front_last_consumed = Some(token.kind());
-            token
         })
     }
 }
 
+impl<'a> DoubleEndedIterator for Rtf<'a> {
+    /// Tokenizes from the last token start before the end that does not
+    /// depend on what came before, which is at most a control word with
+    /// its parameter, delimiter and a text run away.
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let (mut pos, mut last_consumed) = match self.last_token_start() {
+            Some(start) => (start, None),
+            None => (self.front_pos, self.front_last_consumed),
+        };
+        let mut last = None;
+        while let Some(token) = Token::parse(self.source, pos, self.back_pos, last_consumed) {
+            pos = token.source().end();
+            last_consumed = Some(token.kind());
+            last = Some(token);
+        }
+        let token = last?;
+        self.back_pos = token.source().start();
+        Some(token)
+    }
+}
+
+impl<'a> Rtf<'a> {
+    /// Offset of the last backslash or brace before the end that starts a
+    /// control word, control symbol or group, if it is after the front.
+    ///
+    /// Escapes like `\'fc` and braces followed by `'` are skipped, they
+    /// may be in the middle of a text run.
+    fn last_token_start(&self) -> Option<usize> {
+        let bytes = self.source.as_bytes();
+        let mut end = self.back_pos;
+        while let Some(found) = memrchr3(b'\\', b'{', b'}', &bytes[self.front_pos..end]) {
+            let found = self.front_pos + found;
+            let backslashes = bytes[..found]
+                .iter()
+                .rev()
+                .take_while(|&&byte| byte == b'\\')
+                .count();
+            let start = if bytes[found] == b'\\' {
+                // pairs of backslashes from the start of the run are
+                // control symbols, the odd one out starts a token itself
+                found - backslashes % 2
+            } else if backslashes % 2 == 1 {
+                // escaped brace
+                found - 1
+            } else {
+                found
+            };
+            let escape = start == found && bytes.get(found + 1) == Some(&b'\'');
+            if !escape {
+                return Some(start).filter(|&start| start >= self.front_pos);
+            }
+            end = found;
+        }
+        None
+    }
+}
+
 impl<'a> From<&'a str> for Rtf<'a> {
     fn from(source: &'a str) -> Self {
         Rtf {
@@ -37,6 +95,190 @@ impl<'a> From<&'a str> for Rtf<'a> {
     }
 }
 
+/// Plain text in the given RTF, with escape sequences like `\'fc`
+/// decoded as Windows-1252, `\line` and `\tab` as line break and tab,
+/// and all other control words and groups dropped.
+pub fn plain_text(source: &str) -> String {
+    let mut text = String::with_capacity(source.len());
+    let mut after_unicode_word = false;
+    let mut skip_unicode_fallback = false;
+    let mut after_control_symbol = false;
+    let mut high_surrogate = None;
+    for token in Rtf::from(source) {
+        let extent = token.source();
+        let token_str = &source[extent.start()..extent.end()];
+        match token.kind() {
+            TokenKind::Text => decode_escapes(token_str, &mut text),
+            TokenKind::ControlSym => match token_str.as_bytes()[1] {
+                b'~' => text.push('\u{a0}'),
+                b'_' => text.push('-'),
+                sym @ (b'\\' | b'{' | b'}') => text.push(char::from(sym)),
+                _ => (),
+            },
+            TokenKind::Parameter if after_unicode_word => {
+                let code = token_str.parse::<i32>().unwrap_or_default();
+                push_unicode(code, &mut high_surrogate, &mut text);
+                skip_unicode_fallback = true;
+            }
+            TokenKind::ControlWord if token_str == "\\line" => text.push('\n'),
+            TokenKind::ControlWord if token_str == "\\tab" => text.push('\t'),
+            // control symbols have no delimiter, what follows them is text
+            TokenKind::Delimiter if after_control_symbol => text.push_str(token_str),
+            TokenKind::Delimiter if skip_unicode_fallback || token_str == " " => (),
+            TokenKind::Delimiter => text.push_str(token_str),
+            _ => (),
+        }
+        if token.kind() != TokenKind::Parameter {
+            skip_unicode_fallback = false;
+        }
+        after_unicode_word = token.kind() == TokenKind::ControlWord && token_str == "\\u";
+        after_control_symbol = token.kind() == TokenKind::ControlSym;
+    }
+    text
+}
+
+/// Escapes the text for RTF: backslashes and braces with a backslash,
+/// characters in Windows-1252 as `\'xx` like F4 does, other non-ASCII
+/// characters as `\uN?`, and line breaks and tabs as `\line` and `\tab`.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\line "),
+            '\t' => escaped.push_str("\\tab "),
+            '\r' => (),
+            c if c.is_ascii() => escaped.push(c),
+            c => match windows_1252_byte(c) {
+                Some(byte) => escaped.push_str(&format!("\\'{:02x}", byte)),
+                None => {
+                    let mut units = [0; 2];
+                    for unit in c.encode_utf16(&mut units) {
+                        escaped.push_str(&format!("\\u{}?", *unit as i16));
+                    }
+                }
+            },
+        }
+    }
+    escaped
+}
+
+/// Decodes the escapes that [`escape`] writes, `\'xx` as Windows-1252,
+/// `\uN` with its fallback character, `\{`, `\}`, `\\`, `\line` and
+/// `\tab`, and keeps all other RTF as it is.
+pub fn unescape(source: &str) -> String {
+    let mut text = String::with_capacity(source.len());
+    let mut tokens = Rtf::from(source).peekable();
+    let source_of = |token: &Token| {
+        let extent = token.source();
+        &source[extent.start()..extent.end()]
+    };
+    // if the first character of the text after `\uN ` is its fallback
+    let mut skip_fallback = false;
+    let mut high_surrogate = None;
+    while let Some(token) = tokens.next() {
+        let token_str = source_of(&token);
+        match token.kind() {
+            TokenKind::Text if skip_fallback => {
+                let mut chars = token_str.chars();
+                chars.next();
+                decode_escapes(chars.as_str(), &mut text);
+            }
+            TokenKind::Text => decode_escapes(token_str, &mut text),
+            TokenKind::ControlSym if matches!(token_str.as_bytes()[1], b'\\' | b'{' | b'}') => {
+                text.push(char::from(token_str.as_bytes()[1]))
+            }
+            TokenKind::ControlWord if token_str == "\\u" => {
+                let parameter = tokens
+                    .next_if(|next| next.kind() == TokenKind::Parameter)
+                    .map(|parameter| source_of(&parameter).parse::<i32>().unwrap_or_default());
+                let code = match parameter {
+                    Some(code) => code,
+                    None => {
+                        text.push_str(token_str);
+                        continue;
+                    }
+                };
+                push_unicode(code, &mut high_surrogate, &mut text);
+                // the fallback is the delimiter, or the character after a
+                // space as delimiter
+                let delimiter = tokens.next_if(|next| next.kind() == TokenKind::Delimiter);
+                skip_fallback = delimiter.is_some_and(|delimiter| source_of(&delimiter) == " ");
+                continue;
+            }
+            TokenKind::ControlWord if token_str == "\\line" || token_str == "\\tab" => {
+                text.push(if token_str == "\\line" { '\n' } else { '\t' });
+                tokens
+                    .next_if(|next| next.kind() == TokenKind::Delimiter && source_of(next) == " ");
+            }
+            _ => text.push_str(token_str),
+        }
+        skip_fallback = false;
+    }
+    text
+}
+
+/// Appends the character of the parameter of `\uN`. Characters beyond 16
+/// bit are written as two UTF-16 surrogates, the first one is held back in
+/// `high_surrogate` until the second one follows.
+fn push_unicode(code: i32, high_surrogate: &mut Option<u16>, into: &mut String) {
+    // negative values are used for code points above 32767
+    let unit = if code < 0 { code + 65536 } else { code } as u16;
+    match high_surrogate.take() {
+        Some(high) => into.extend(char::decode_utf16([high, unit]).flatten()),
+        None if (0xd800..0xdc00).contains(&unit) => *high_surrogate = Some(unit),
+        None => into.extend(char::decode_utf16([unit]).flatten()),
+    }
+}
+
+/// Appends text with `\'xx` escapes decoded as Windows-1252.
+fn decode_escapes(escaped: &str, into: &mut String) {
+    let mut rest = escaped;
+    while let Some(at) = rest.find("\\'") {
+        into.push_str(&rest[..at]);
+        let hex = rest.get(at + 2..at + 4).unwrap_or_default();
+        match u8::from_str_radix(hex, 16) {
+            Ok(byte) => {
+                into.push(windows_1252_char(byte));
+                rest = &rest[at + 4..];
+            }
+            Err(_) => {
+                into.push_str("\\'");
+                rest = &rest[at + 2..];
+            }
+        }
+    }
+    into.push_str(rest);
+}
+
+/// Windows-1252 characters for the bytes from 0x80 to 0x9f, where it
+/// differs from Latin-1.
+const HIGH_CONTROL_RANGE: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8d}', 'Ž', '\u{8f}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9d}', 'ž', 'Ÿ',
+];
+
+pub(super) fn windows_1252_char(byte: u8) -> char {
+    match byte {
+        0x80..=0x9f => HIGH_CONTROL_RANGE[usize::from(byte - 0x80)],
+        _ => char::from(byte),
+    }
+}
+
+/// The Windows-1252 byte for the character, if it has one.
+pub(super) fn windows_1252_byte(c: char) -> Option<u8> {
+    match c {
+        '\0'..='\u{7f}' | '\u{a0}'..='\u{ff}' => Some(c as u8),
+        c => HIGH_CONTROL_RANGE
+            .iter()
+            .position(|&high| high == c)
+            .map(|idx| 0x80 + idx as u8),
+    }
+}
+
 mod token {
     use std::fmt;
 
@@ -95,11 +337,7 @@ mod token {
 
     impl TokenKind {
         pub fn is_text(&self) -> bool {
-            if let Self::Text = self {
-                true
-            } else {
-                false
-            }
+            matches!(self, Self::Text)
         }
     }
 
@@ -120,6 +358,10 @@ mod token {
             self.source.len()
         }
 
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+
         pub fn parse(
             source: &'a str,
             from: usize,
@@ -211,7 +453,7 @@ mod token {
         }
 
         fn parse_control_word_or_symbol(source: &'a str, from: usize) -> Self {
-            match source.bytes().nth(from + 1) {
+            match source.as_bytes().get(from + 1).copied() {
                 // starts with character ecape sequence, treat as text and read on
                 Some(b'\'') => {
                     Self::new_text(source, from, Self::consume_plain_text(source, from + 2))
@@ -235,7 +477,7 @@ mod token {
                 .skip_while(|(_, ch)| ch.is_ascii_digit())
                 .map(|(idx, _)| idx)
                 .next()
-                .unwrap_or_else(|| source.len());
+                .unwrap_or(source.len());
             Self::new_parameter(source, from, param_end)
         }
 
@@ -248,7 +490,7 @@ mod token {
                 .find(|&(_, ch)| !ch.is_ascii_lowercase())
                 .map(|(idx, _)| idx);
 
-            Self::new_control_word(source, from, delimiter_pos.unwrap_or_else(|| source.len()))
+            Self::new_control_word(source, from, delimiter_pos.unwrap_or(source.len()))
         }
 
         /// Consumes plain text including escape sequences until the next
@@ -299,30 +541,10 @@ mod token {
     }
 
     impl<'a> Extent<'a> {
-        pub fn new(source: &str, start: usize, end: usize) -> Extent {
+        pub fn new(source: &str, start: usize, end: usize) -> Extent<'_> {
             Extent { source, start, end }
         }
 
-        /// Gets an extent for a given surrounding string and a
-        /// portion of that string.
-        ///
-        /// # Panics
-        /// Panics if the inner string is not really contained in
-        /// the outer string.
-        /*pub fn for_substring(outer: &'a str, inner: &'a str) -> Extent<'a> {
-            let start = (inner.as_ptr() as usize) - (outer.as_ptr() as usize);
-            let end = start + inner.len();
-            assert!(
-                start <= outer.len() && end <= outer.len(),
-                "substring is not fully contained in outer string"
-            );
-            Extent {
-                source: outer,
-                start,
-                end,
-            }
-        }*/
-
         /// Source code portion represented with this struct.
         pub fn as_str(&'a self) -> &'a str {
             &self.source[self.start..self.end]
@@ -411,6 +633,74 @@ mod test {
         assert_eq!(rtf_text_only, vec!["I", ": Mhm, genau. #00:00:19-0#"]);
     }
 
+    #[test]
+    fn tokens_from_the_back_are_those_from_the_front() {
+        let transcript = std::fs::read_to_string("testdata/interview-01.rtf").unwrap();
+        let sources = [
+            transcript.as_str(),
+            "{\\b fett\\b0}\\{nicht\\} \\\\{x} \\u9731?a\\'fc\\par",
+            "\\cf1 \\\\\\{ a",
+            "\\par{'a}' \\\\\\'e4 b{\\'fc}",
+            "",
+        ];
+        let extent = |token: Token| (token.source().start(), token.source().end());
+        for source in sources {
+            let forward: Vec<_> = Rtf::from(source).map(extent).collect();
+            let mut backward: Vec<_> = Rtf::from(source).rev().map(extent).collect();
+            backward.reverse();
+            assert_eq!(backward, forward);
+
+            // from both ends at once
+            let mut rtf = Rtf::from(source);
+            let mut front = vec![];
+            let mut back = vec![];
+            while let Some(token) = rtf.next() {
+                front.push(extent(token));
+                match rtf.next_back() {
+                    Some(token) => back.push(extent(token)),
+                    None => break,
+                }
+            }
+            back.reverse();
+            front.extend(back);
+            assert_eq!(front, forward);
+        }
+    }
+
+    #[test]
+    fn tokens_of_a_long_line_from_the_back_are_those_from_the_front() {
+        let line =
+            "{\\f0 \\fs24 I}{: Gr\\'fc\\'dfe, \\{so\\} \\\\ \\u9731?\\~und{\\b0 so}. #00:00:19-0#}"
+                .repeat(5000);
+        let token = |token: Token| (token.kind(), token.source().start(), token.source().end());
+        let forward: Vec<_> = Rtf::from(line.as_str()).map(token).collect();
+        let mut backward: Vec<_> = Rtf::from(line.as_str()).rev().map(token).collect();
+        backward.reverse();
+        assert_eq!(backward, forward);
+    }
+
+    #[test]
+    fn escapes_round_trip() {
+        let text = "Zurück zu „den“ {Methoden} \\ ☃ 𝄞\nund\tso";
+        let escaped = escape(text);
+        assert_eq!(
+            escaped,
+            "Zur\\'fcck zu \\'84den\\'93 \\{Methoden\\} \\\\ \\u9731? \\u-10188?\\u-8930?\\line und\\tab so"
+        );
+        assert_eq!(unescape(&escaped), text);
+        assert_eq!(unescape("{\\b Gr\\u246 o\\'dfe}\\{"), "{\\b Größe}{");
+    }
+
+    #[test]
+    fn plain_text_decodes_escapes() {
+        const RTF_WITH_TEXT: &str = "{\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 {\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 Z}{\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 : Zur\\'fcck zu \\'84den\\'93 Methoden #00:00:17-5#}\\par}";
+        assert_eq!(
+            plain_text(RTF_WITH_TEXT),
+            "Z: Zurück zu „den“ Methoden #00:00:17-5#"
+        );
+        assert_eq!(plain_text("Gr\\u246?\\'dfe"), "Größe");
+    }
+
     #[test]
     fn zurueck_zu_den_methoden() {
         const RTF_WITH_TEXT: &str = "{\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 {\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 Z}{\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 : Zur\\'fcck zu den Methoden #00:00:17-5#}\\par}";
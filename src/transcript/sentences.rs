@@ -0,0 +1,137 @@
+//! Where sentences end in speech, for exports with a row per sentence.
+//!
+//! A period only ends a sentence if it does not end an abbreviation like
+//! `z.B.`, `bzw.` or `e.g.` of the language, a single letter like in
+//! `Peter M.`, or in German a day or century like `am 3. Mai`, and if the
+//! next word does not start in lowercase. Question and exclamation marks
+//! always end sentences.
+use std::str::FromStr;
+
+/// Language of the speech, for its abbreviations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Language {
+    #[default]
+    German,
+    English,
+}
+
+impl Language {
+    /// Lowercase abbreviations without their last period.
+    fn abbreviations(self) -> &'static [&'static str] {
+        match self {
+            Language::German => &[
+                "abs", "allg", "bspw", "bzgl", "bzw", "ca", "d.h", "dr", "ehem", "evtl", "fr",
+                "geb", "ggf", "hr", "i.d.r", "inkl", "insb", "jh", "max", "min", "mio", "mrd",
+                "nr", "o.ä", "prof", "s.o", "s.u", "sog", "str", "tel", "u.a", "u.ä", "u.u", "usw",
+                "v.a", "vgl", "z.b", "z.t", "zb",
+            ],
+            Language::English => &[
+                "a.m", "approx", "ca", "cf", "co", "dept", "dr", "e.g", "etc", "fig", "i.e", "inc",
+                "jr", "ltd", "mr", "mrs", "ms", "no", "p.m", "prof", "sr", "st", "u.k", "u.s",
+                "vol", "vs",
+            ],
+        }
+    }
+
+    /// Checks if the word before a period is an abbreviation, an initial
+    /// or an ordinal number.
+    fn is_abbreviation(self, word: &str) -> bool {
+        let word = word.trim_start_matches(|c: char| !c.is_alphanumeric());
+        let lowercase = word.to_lowercase();
+        let mut chars = word.chars();
+        let initial = matches!((chars.next(), chars.next()), (Some(c), None) if c.is_alphabetic());
+        let ordinal = self == Language::German
+            && (1..=2).contains(&word.len())
+            && word.bytes().all(|b| b.is_ascii_digit());
+        initial || ordinal || self.abbreviations().contains(&lowercase.as_str())
+    }
+}
+
+impl FromStr for Language {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "de" | "german" | "deutsch" => Ok(Language::German),
+            "en" | "english" => Ok(Language::English),
+            _ => Err(format!("unknown language {}, expected de or en", s)),
+        }
+    }
+}
+
+/// Byte offsets right after the punctuation, and closing quotes, that end
+/// the sentences in the text. Text after the last one is an unfinished
+/// sentence.
+pub fn sentence_ends(text: &str, language: Language) -> Vec<usize> {
+    let mut ends = vec![];
+    for (idx, c) in text.char_indices() {
+        if !matches!(c, '.' | '?' | '!' | '…') {
+            continue;
+        }
+        let after = idx + c.len_utf8();
+        let end = text[after..]
+            .find(|c: char| !is_closing(c))
+            .map_or(text.len(), |len| after + len);
+        let next = text[end..].chars().next();
+        if next.is_some_and(|next| !next.is_whitespace()) {
+            // more punctuation, or a period inside a word like `z.B.`
+            continue;
+        }
+        if c == '.' || c == '…' {
+            let word_start = text[..idx]
+                .rfind(char::is_whitespace)
+                .map_or(0, |space| space + 1);
+            let next_word = text[end..].trim_start().chars().next();
+            if (c == '.' && language.is_abbreviation(&text[word_start..idx]))
+                || next_word.is_some_and(char::is_lowercase)
+            {
+                continue;
+            }
+        }
+        ends.push(end);
+    }
+    ends
+}
+
+fn is_closing(c: char) -> bool {
+    matches!(c, '"' | '\'' | '“' | '”' | '’' | '»' | '«' | ')')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sentences(text: &str, language: Language) -> Vec<&str> {
+        let mut start = 0;
+        let mut sentences = vec![];
+        for end in sentence_ends(text, language) {
+            sentences.push(text[start..end].trim());
+            start = end;
+        }
+        sentences.push(text[start..].trim());
+        sentences
+    }
+
+    #[test]
+    fn abbreviations_do_not_end_sentences() {
+        assert_eq!(
+            sentences(
+                "Also z.B. Bücher, bzw. Platten. Am 3. Mai 1985 kam Peter M. zurück! \
+                 Wirklich? „Ja.“ Und dann... naja",
+                Language::German
+            ),
+            vec![
+                "Also z.B. Bücher, bzw. Platten.",
+                "Am 3. Mai 1985 kam Peter M. zurück!",
+                "Wirklich?",
+                "„Ja.“",
+                "Und dann... naja"
+            ]
+        );
+        assert_eq!(
+            sentences("Records, e.g. Vinyl. It was 3. Dr. Who", Language::English),
+            vec!["Records, e.g. Vinyl.", "It was 3.", "Dr. Who"]
+        );
+        assert_eq!(sentences("Das war 1985. Dann", Language::German).len(), 2);
+    }
+}
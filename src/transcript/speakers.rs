@@ -0,0 +1,208 @@
+//! Renames speakers in transcripts, e.g. when the pseudonym of a
+//! participant changes in the middle of a project.
+//!
+//! Speaker labels of utterances are renamed, and so are the speakers in
+//! the speaker list that F4 keeps base64-encoded in the document comment.
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use super::lines::Lines;
+use super::rtf::escape;
+use crate::transcript::Transcript;
+
+const SPEAKER_LIST_START: &str = "#SPEAKER_LIST_START#";
+const SPEAKER_LIST_END: &str = "#SPEAKER_LIST_END#";
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Renames the speaker with the code `from` to `to`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpeakerRename {
+    pub from: String,
+    pub to: String,
+}
+
+impl FromStr for SpeakerRename {
+    type Err = String;
+
+    /// Parses `FROM=TO`, e.g. `B=P07`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((from, to)) if !from.trim().is_empty() && !to.trim().is_empty() => {
+                Ok(SpeakerRename {
+                    from: from.trim().to_string(),
+                    to: to.trim().to_string(),
+                })
+            }
+            _ => Err(format!("expected a speaker rename like B=P07, got {}", s)),
+        }
+    }
+}
+
+impl Transcript {
+    /// The transcript with its speakers renamed, and how many utterances
+    /// were renamed for every speaker code that was renamed at least once.
+    pub fn with_renamed_speakers(
+        &self,
+        renames: &[SpeakerRename],
+    ) -> (Transcript, BTreeMap<String, usize>) {
+        let rename = |speaker: &str| {
+            renames
+                .iter()
+                .find(|rename| rename.from == speaker)
+                .map(|rename| rename.to.as_str())
+        };
+        let mut counts = BTreeMap::new();
+        let mut content = String::with_capacity(self.content().len());
+        for raw in self.content().split_inclusive('\n') {
+            let line = raw.trim_end_matches(['\r', '\n']);
+            let parsed = Lines::parse_line_in_dialect(self.dialect(), line);
+            let renamed = parsed.utterance().and_then(|utterance| {
+                let speaker = utterance.speaker_text();
+                let to = rename(&speaker)?;
+                let start = utterance.speaker().as_ptr() as usize - line.as_ptr() as usize;
+                *counts.entry(speaker).or_insert(0) += 1;
+                Some((start..start + utterance.speaker().len(), to))
+            });
+            match renamed {
+                Some((range, to)) => {
+                    content.push_str(&raw[..range.start]);
+                    content.push_str(&escape(to));
+                    content.push_str(&raw[range.end..]);
+                }
+                None => content.push_str(raw),
+            }
+        }
+        let preamble = rename_in_speaker_list(self.preamble(), &rename)
+            .unwrap_or_else(|| self.preamble().to_string());
+        (self.with_parts(preamble, content), counts)
+    }
+}
+
+/// The preamble with the speakers renamed in the speaker list, or `None`
+/// if there is no speaker list or nothing to rename in it.
+fn rename_in_speaker_list<'a, F>(preamble: &str, rename: &F) -> Option<String>
+where
+    F: Fn(&str) -> Option<&'a str>,
+{
+    let list = speaker_list(preamble)?;
+
+    // every entry looks like `I:=#EF7C00`, with the color of the speaker
+    let mut renamed_any = false;
+    let list: String = list
+        .split_inclusive('\n')
+        .map(|entry| match entry.split_once(":=") {
+            Some((speaker, rest)) => match rename(speaker) {
+                Some(to) => {
+                    renamed_any = true;
+                    format!("{}:={}", to, rest)
+                }
+                None => entry.to_string(),
+            },
+            None => entry.to_string(),
+        })
+        .collect();
+    if !renamed_any {
+        return None;
+    }
+    with_speaker_list(preamble, &list)
+}
+
+/// The decoded speaker list in the preamble, with an entry like
+/// `I:=#EF7C00` per line, if there is one.
+pub(super) fn speaker_list(preamble: &str) -> Option<String> {
+    let (start, end) = speaker_list_range(preamble)?;
+    String::from_utf8(decode_base64(&preamble[start..end])?).ok()
+}
+
+/// The preamble with its speaker list replaced, if it has one.
+pub(super) fn with_speaker_list(preamble: &str, list: &str) -> Option<String> {
+    let (start, end) = speaker_list_range(preamble)?;
+    Some(format!(
+        "{}{}{}",
+        &preamble[..start],
+        encode_base64(list.as_bytes()),
+        &preamble[end..]
+    ))
+}
+
+/// Bytes of the base64-encoded speaker list in the preamble.
+fn speaker_list_range(preamble: &str) -> Option<(usize, usize)> {
+    let start = preamble.find(SPEAKER_LIST_START)? + SPEAKER_LIST_START.len();
+    let end = start + preamble[start..].find(SPEAKER_LIST_END)?;
+    Some((start, start + preamble[start..end].trim_end().len()))
+}
+
+fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+    let mut decoded = vec![];
+    let mut bits = 0u32;
+    let mut bit_count = 0;
+    for byte in encoded.bytes().filter(|&byte| byte != b'=') {
+        let value = BASE64.iter().position(|&b| b == byte)? as u32;
+        bits = bits << 6 | value;
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            decoded.push((bits >> bit_count) as u8);
+        }
+    }
+    Some(decoded)
+}
+
+pub(super) fn encode_base64(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (idx, &byte)| {
+            bits | u32::from(byte) << (16 - 8 * idx)
+        });
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                encoded.push(BASE64[(bits >> (18 - 6 * idx) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn labels_and_speaker_list_are_renamed() {
+        let transcript = Transcript::from_file("testdata/interview-02.rtf").unwrap();
+        let renames = vec!["Z=P07".parse().unwrap(), "X=Y".parse().unwrap()];
+        assert_eq!(escape("Ö{1}"), "\\'d6\\{1\\}");
+        let (renamed, counts) = transcript.with_renamed_speakers(&renames);
+
+        assert_eq!(
+            counts.into_iter().collect::<Vec<_>>(),
+            vec![("Z".to_string(), 3)]
+        );
+        let speakers: Vec<String> = renamed
+            .utterance_records()
+            .map(|record| record.speaker)
+            .collect();
+        assert_eq!(speakers, vec!["P07", "I", "P07", "I", "P07"]);
+        assert!(renamed
+            .preamble()
+            .contains(&encode_base64(b"I:=#EF7C00\nP07:=#4993C7\n")));
+        assert_eq!(
+            renamed.content().len(),
+            transcript.content().len() + 3 * "07".len()
+        );
+    }
+
+    #[test]
+    fn base64_round_trip() {
+        for text in ["", "I", "I:", "I:=#EF7C00\nZ:=#4993C7\n"] {
+            let encoded = encode_base64(text.as_bytes());
+            assert_eq!(decode_base64(&encoded).unwrap(), text.as_bytes());
+        }
+        assert_eq!(
+            encode_base64(b"I:=#EF7C00\nZ:=#4993C7\n"),
+            "STo9I0VGN0MwMApaOj0jNDk5M0M3Cg=="
+        );
+    }
+}
@@ -0,0 +1,432 @@
+//! Writes merged transcripts in a format described by a user-provided
+//! template, e.g. LaTeX, a Markdown table or custom CSV.
+//!
+//! The part of the template between `{each}` and `{end}` is written once
+//! for every utterance, the rest only once before and after them. If there
+//! is no `{each}`, the whole template is written for every utterance.
+//!
+//! These placeholders are replaced for every utterance:
+//! * `{speaker}`: the code of the speaker, e.g. `I`,
+//! * `{speech}`: the plain text of the utterance, with adjusted timestamps,
+//! * `{start}`: the adjusted timestamp when the utterance starts,
+//! * `{segment}`: the index of the segment it is from, starting at zero,
+//! * `{inferred}`: `inferred` if the utterance is a paragraph without a
+//!   speaker label that `--infer-speakers` attributed to a speaker.
+//!
+//! Write `{{` and `}}` for literal curly braces.
+use std::io::{Result as IOResult, Write};
+
+use super::formats::OutputFormat;
+use super::lines::{Dialect, Line, Lines, Utterance};
+use super::records::{Attribution, Spoken};
+use super::rtf::plain_text;
+use crate::timestamp::Timestamp;
+use crate::transcript::{Error, MergeOptions, Result};
+
+const EACH: &str = "each";
+const END: &str = "end";
+
+/// A parsed template, ready to be rendered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    header: Vec<Piece>,
+    each: Vec<Piece>,
+    footer: Vec<Piece>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Literal(String),
+    Speaker,
+    Speech,
+    Start,
+    Segment,
+    Inferred,
+}
+
+/// An utterance after merging, as seen by templates and output formats.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    /// Index of the segment the utterance is from, starting at zero.
+    pub segment: usize,
+    pub speaker: String,
+    /// Plain text of the utterance, with adjusted timestamps.
+    pub speech: String,
+    /// Adjusted timestamp when the utterance starts.
+    pub start: Timestamp,
+    /// Last adjusted timestamp in the speech.
+    pub end: Option<Timestamp>,
+    /// If the record is a paragraph without a speaker label, attributed to
+    /// a speaker with `--infer-speakers`.
+    pub inferred: bool,
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Template> {
+        let mut sections = vec![vec![]];
+        let mut literal = String::new();
+        let mut rest = source;
+        while let Some(at) = rest.find(['{', '}']) {
+            literal.push_str(&rest[..at]);
+            rest = &rest[at..];
+            if rest.starts_with("{{") || rest.starts_with("}}") {
+                literal.push_str(&rest[..1]);
+                rest = &rest[2..];
+                continue;
+            }
+            let name_end = match (rest.starts_with('{'), rest.find('}')) {
+                (true, Some(end)) => end,
+                _ => return Err(Error::MalformedTemplate(rest.chars().take(20).collect())),
+            };
+            let name = &rest[1..name_end];
+            rest = &rest[name_end + 1..];
+
+            let section = sections.last_mut().unwrap();
+            if !literal.is_empty() {
+                section.push(Piece::Literal(std::mem::take(&mut literal)));
+            }
+            match name {
+                "speaker" => section.push(Piece::Speaker),
+                "speech" => section.push(Piece::Speech),
+                "start" => section.push(Piece::Start),
+                "segment" => section.push(Piece::Segment),
+                "inferred" => section.push(Piece::Inferred),
+                EACH if sections.len() == 1 => sections.push(vec![]),
+                END if sections.len() == 2 => sections.push(vec![]),
+                _ => return Err(Error::MalformedTemplate(format!("{{{}}}", name))),
+            }
+        }
+        literal.push_str(rest);
+        if !literal.is_empty() {
+            sections.last_mut().unwrap().push(Piece::Literal(literal));
+        }
+
+        let mut sections = sections.into_iter();
+        let first = sections.next().unwrap_or_default();
+        Ok(match (sections.next(), sections.next()) {
+            (Some(each), Some(footer)) => Template {
+                header: first,
+                each,
+                footer,
+            },
+            (Some(_), None) => return Err(Error::MalformedTemplate(format!("{{{}}}", EACH))),
+            _ => Template {
+                header: vec![],
+                each: first,
+                footer: vec![],
+            },
+        })
+    }
+
+    fn write_section(to: &mut dyn Write, section: &[Piece], record: &Record) -> IOResult<()> {
+        for piece in section {
+            match piece {
+                Piece::Literal(literal) => write!(to, "{}", literal)?,
+                Piece::Speaker => write!(to, "{}", record.speaker)?,
+                Piece::Speech => write!(to, "{}", record.speech)?,
+                Piece::Start => write!(to, "{}", record.start)?,
+                Piece::Segment => write!(to, "{}", record.segment)?,
+                Piece::Inferred if record.inferred => write!(to, "inferred")?,
+                Piece::Inferred => (),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl OutputFormat for Template {
+    fn name(&self) -> &'static str {
+        "template"
+    }
+
+    /// Templates are chosen with `--template`, not by extension.
+    fn extensions(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn header(&self, to: &mut dyn Write) -> IOResult<()> {
+        Template::write_section(to, &self.header, &Record::empty())
+    }
+
+    fn line(
+        &self,
+        to: &mut dyn Write,
+        _: usize,
+        record: &Record,
+        _: Option<&Record>,
+    ) -> IOResult<()> {
+        Template::write_section(to, &self.each, record)
+    }
+
+    fn footer(&self, to: &mut dyn Write) -> IOResult<()> {
+        Template::write_section(to, &self.footer, &Record::empty())
+    }
+}
+
+impl Spoken for Record {
+    fn speaker(&self) -> &str {
+        &self.speaker
+    }
+
+    fn set_speaker(&mut self, speaker: String) {
+        self.speaker = speaker;
+    }
+}
+
+impl Record {
+    /// The record the header and footer of templates are written with.
+    fn empty() -> Record {
+        Record {
+            segment: 0,
+            speaker: String::new(),
+            speech: String::new(),
+            start: Timestamp::zero(),
+            end: None,
+            inferred: false,
+        }
+    }
+}
+
+/// Records the utterances of a merge as they are written, for the outputs
+/// that are written from records rather than RTF.
+pub(super) struct Records {
+    options: MergeOptions,
+    records: Vec<Record>,
+    /// Index of the segment of the line recorded last.
+    segment: Option<usize>,
+    /// Start of the next record, the last adjusted timestamp before it in
+    /// its segment or the shift of the segment.
+    start: Timestamp,
+    /// Attributes continuation paragraphs to speakers.
+    attribution: Attribution<Record>,
+    /// Speech of the paragraphs of trailers that are moved to the end, with
+    /// the index of their segment.
+    moved: Vec<(usize, String)>,
+}
+
+impl Records {
+    pub(super) fn new(options: MergeOptions) -> Self {
+        Records {
+            options,
+            records: vec![],
+            segment: None,
+            start: Timestamp::zero(),
+            attribution: Attribution::new(options.infer_speakers),
+            moved: vec![],
+        }
+    }
+
+    /// Records the line, written with the shift of the segment with the
+    /// given index.
+    pub(super) fn line(&mut self, line: &Line, (segment, shift): (usize, Timestamp)) -> Result<()> {
+        self.enter(segment, shift);
+        let (raw_speech, speaker) = match (line.utterance(), line.continuation()) {
+            (Some(utterance), _) => (utterance.speech(), Some(utterance.speaker_text())),
+            (None, Some(content)) if self.attribution.infers() => (content, None),
+            _ => return Ok(()),
+        };
+        let (speech, end) = adjusted_text(raw_speech, shift, self.options.tidy)?;
+        let record = Record {
+            segment,
+            speaker: speaker.clone().unwrap_or_default(),
+            speech,
+            start: self.start,
+            end,
+            inferred: speaker.is_none(),
+        };
+        self.start = end.unwrap_or(self.start);
+        match speaker {
+            Some(_) => self.attribution.utterance(record, &mut self.records),
+            None => self.attribution.paragraph(record, &mut self.records),
+        }
+        Ok(())
+    }
+
+    /// Adds the speech of the first utterance of the segment with the
+    /// given index to the last record, which it was stitched to.
+    pub(super) fn stitch(
+        &mut self,
+        first: &Utterance,
+        (segment, shift): (usize, Timestamp),
+    ) -> Result<()> {
+        self.enter(segment, shift);
+        let (speech, end) = adjusted_text(first.speech(), shift, self.options.tidy)?;
+        if let Some(last) = self.records.last_mut() {
+            last.speech.push(' ');
+            last.speech.push_str(&speech);
+            last.end = end.or(last.end);
+        }
+        self.start = end.unwrap_or(self.start);
+        Ok(())
+    }
+
+    /// Keeps the paragraphs of the trailer of the segment with the given
+    /// index for the end, where they are attributed to the last speaker if
+    /// speakers are inferred.
+    pub(super) fn move_trailer(&mut self, segment: usize, dialect: Dialect, trailer: &str) {
+        if !self.attribution.infers() {
+            return;
+        }
+        for line in trailer.lines() {
+            let line = Lines::parse_line_in_dialect(dialect, line);
+            if let Some(content) = line.continuation() {
+                let speech = speech_text(content, self.options.tidy);
+                self.moved.push((segment, speech));
+            }
+        }
+    }
+
+    /// The recorded utterances, followed by the paragraphs of the moved
+    /// trailers.
+    pub(super) fn finish(mut self) -> Vec<Record> {
+        self.attribution.flush(&mut self.records);
+        for (segment, speech) in std::mem::take(&mut self.moved) {
+            let (Some(speaker), Some(last)) =
+                (self.attribution.last_speaker(), self.records.last())
+            else {
+                continue;
+            };
+            let record = Record {
+                segment,
+                speaker: speaker.to_string(),
+                speech,
+                start: last.end.unwrap_or(last.start),
+                end: None,
+                inferred: true,
+            };
+            self.records.push(record);
+        }
+        self.records
+    }
+
+    /// Starts the records of the segment with the given index and shift,
+    /// unless its lines are recorded already.
+    fn enter(&mut self, segment: usize, shift: Timestamp) {
+        if self.segment != Some(segment) {
+            // paragraphs after the last utterance of a segment go to its
+            // speaker
+            self.attribution.flush(&mut self.records);
+            self.segment = Some(segment);
+            self.start = shift;
+        }
+    }
+}
+
+/// Plain text of the speech with adjusted timestamps, and its last
+/// adjusted timestamp.
+fn adjusted_text(
+    speech: &str,
+    shift: Timestamp,
+    tidy: bool,
+) -> Result<(String, Option<Timestamp>)> {
+    let mut adjusted = vec![];
+    let last = Timestamp::write_with_adjusted_timestamps(&mut adjusted, speech, shift)?;
+    Ok((speech_text(&String::from_utf8_lossy(&adjusted), tidy), last))
+}
+
+/// Plain text of the speech, with spaces collapsed if `tidy` is set.
+fn speech_text(speech: &str, tidy: bool) -> String {
+    let speech = plain_text(speech);
+    if tidy {
+        speech
+            .split(' ')
+            .filter(|word| !word.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    } else {
+        speech.trim().to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::transcript::{write_merged_outputs, OutputKind, SpeakerInference, Transcript};
+    use std::convert::TryFrom;
+
+    #[test]
+    fn parse_sections_and_placeholders() {
+        let template =
+            Template::parse("| a | b |\n{each}| {speaker} | {{{speech}}} |\n{end}.").unwrap();
+        assert_eq!(
+            template,
+            Template {
+                header: vec![Piece::Literal("| a | b |\n".into())],
+                each: vec![
+                    Piece::Literal("| ".into()),
+                    Piece::Speaker,
+                    Piece::Literal(" | {".into()),
+                    Piece::Speech,
+                    Piece::Literal("} |\n".into()),
+                ],
+                footer: vec![Piece::Literal(".".into())],
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_placeholders_are_rejected() {
+        assert!(matches!(
+            Template::parse("{speeker}"),
+            Err(Error::MalformedTemplate(_))
+        ));
+        assert!(Template::parse("{each}{speech}").is_err());
+    }
+
+    #[test]
+    fn render_merged_utterances() {
+        let transcript001 = Transcript::from_file("testdata/interview-01.rtf").unwrap();
+        let transcript002 = Transcript::from_file("testdata/interview-02.rtf").unwrap();
+        let template = Template::parse("{segment};{start};{speaker};{speech}\n").unwrap();
+
+        let mut buf = vec![];
+        let template = OutputKind::Template(template);
+        write_merged_outputs(
+            vec![(&mut buf, &template)],
+            vec![transcript001, transcript002],
+            &MergeOptions::default(),
+        )
+        .unwrap();
+        let rendered = String::from_utf8(buf).unwrap();
+
+        assert!(rendered.starts_with("0;#00:00:00-0#;"), "{}", rendered);
+        assert!(
+            rendered.contains(
+                "0;#00:01:56-9#;Z;Ich glaub jetzt weiß ich, worauf sie hinauswollen. \
+                 #00:04:50-3# Zunächst einmal ist der Punk nicht tot, ja? \
+                 #00:05:27-8# So auditiv meine ich. #00:05:31-6#\n"
+            ),
+            "{}",
+            rendered
+        );
+    }
+
+    #[test]
+    fn inferred_speakers_are_marked() {
+        let original = std::fs::read_to_string("testdata/interview-02.rtf").unwrap();
+        let transcript = Transcript::try_from(original.replacen(
+            "{\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 \\par}",
+            "{\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 Das meine ich ernst. #00:00:40-0#\\par}",
+            1,
+        ))
+        .unwrap();
+        let template = Template::parse("{speaker};{inferred};{speech}\n").unwrap();
+        let options = MergeOptions {
+            infer_speakers: Some(SpeakerInference::Previous),
+            ..MergeOptions::default()
+        };
+
+        let mut buf = vec![];
+        let template = OutputKind::Template(template);
+        let outputs = vec![(&mut buf, &template)];
+        write_merged_outputs(outputs, vec![transcript], &options).unwrap();
+        let rendered = String::from_utf8(buf).unwrap();
+
+        let mut lines = rendered.lines().skip(1);
+        assert_eq!(
+            lines.next(),
+            Some("Z;inferred;Das meine ich ernst. #00:00:40-0#")
+        );
+        assert_eq!(lines.next(), Some("I;;Versteh ich nicht #00:00:58-6#."));
+    }
+}
@@ -0,0 +1,124 @@
+//! Plain text transcripts like those written with a text editor, with a
+//! paragraph per utterance like `I: something #00:01:23-4#`, read into the
+//! JSON representation to build F4 RTF from them.
+use super::json::{Block, Document, Metadata, Speaker, Utterance, FORMAT, VERSION};
+
+/// Longest label that is taken for a speaker rather than text with a colon.
+const MAX_LABEL_LEN: usize = 24;
+
+impl Document {
+    /// Reads plain text with an utterance per paragraph that starts with a
+    /// speaker label like `I:`. Lines without a label continue the
+    /// utterance on the line before, or are notes if they follow a blank
+    /// line.
+    pub fn from_text(text: &str) -> Document {
+        let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+        let mut blocks: Vec<Block> = vec![];
+        let mut speakers: Vec<Speaker> = vec![];
+        let mut continues = false;
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() {
+                continues = false;
+                continue;
+            }
+            if let Some((speaker, speech)) = label(line) {
+                if !speakers.iter().any(|known| known.code == speaker) {
+                    speakers.push(Speaker {
+                        code: speaker.to_string(),
+                        color: None,
+                    });
+                }
+                blocks.push(Block::Utterance(Utterance::new(speaker, speech)));
+                continues = true;
+                continue;
+            }
+            match blocks.last_mut() {
+                Some(Block::Utterance(utterance)) if continues => {
+                    let speech = format!("{} {}", utterance.speech, line);
+                    *utterance = Utterance::new(&utterance.speaker, speech.trim());
+                }
+                Some(Block::Note { text }) if continues => {
+                    text.push(' ');
+                    text.push_str(line);
+                }
+                _ => blocks.push(Block::Note {
+                    text: line.to_string(),
+                }),
+            }
+            continues = true;
+        }
+        Document {
+            format: FORMAT.to_string(),
+            version: VERSION,
+            metadata: Metadata {
+                speakers,
+                ..Metadata::default()
+            },
+            blocks,
+        }
+    }
+}
+
+/// Checks if the text is short and plain enough to be read as a speaker
+/// label.
+pub(super) fn is_speaker_code(speaker: &str) -> bool {
+    !speaker.is_empty()
+        && speaker.chars().count() <= MAX_LABEL_LEN
+        && speaker
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.')
+}
+
+/// Speaker and speech of a line starting with a label like `I:`.
+fn label(line: &str) -> Option<(&str, &str)> {
+    let (speaker, speech) = line.split_once(':')?;
+    if is_speaker_code(speaker) {
+        Some((speaker, speech.trim()))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn text_is_read_as_utterances_and_notes() {
+        let document = Document::from_text(
+            "\u{feff}Interview mit Z\r\n\r\nI: Was hat man früher so gehört #00:00:27-8#?\r\n\
+             Z: Punk {laut} #00:00:58-6#\r\nund so #00:01:02-0#\r\n\r\n#00:02:00-0# Ende\r\n",
+        );
+        let codes: Vec<_> = document
+            .metadata
+            .speakers
+            .iter()
+            .map(|speaker| speaker.code.as_str())
+            .collect();
+        assert_eq!(codes, ["I", "Z"]);
+        assert_eq!(document.blocks.len(), 4);
+        match &document.blocks[2] {
+            Block::Utterance(utterance) => {
+                assert_eq!(
+                    utterance.speech,
+                    "Punk {laut} #00:00:58-6# und so #00:01:02-0#"
+                );
+                assert_eq!(utterance.timestamps.len(), 2);
+            }
+            block => panic!("expected an utterance, got {:?}", block),
+        }
+        assert_eq!(
+            document.blocks[3],
+            Block::Note {
+                text: "#00:02:00-0# Ende".to_string()
+            }
+        );
+
+        let rtf = document.to_rtf();
+        assert!(rtf.contains(" Was hat man fr\\'fcher so geh\\'f6rt #00:00:27-8#?}\\par}"));
+        assert!(rtf.contains("Punk \\{laut\\}"));
+        let transcript = document.to_transcript().unwrap();
+        assert_eq!(transcript.last_timestamp().tenths(), 1200);
+    }
+}
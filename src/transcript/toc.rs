@@ -0,0 +1,135 @@
+//! Table of contents of a merged RTF transcript, linking to a bookmark at
+//! the start of every segment, so long interviews can be navigated in
+//! Word.
+//!
+//! The table of contents comes right after the preamble, but the starts
+//! of the segments are only known once all of them are merged, so the
+//! merged segments are held back until then.
+use std::io::{Result, Write};
+
+use super::lines::Dialect;
+use crate::timestamp::Timestamp;
+
+/// The starts of the merged segments so far.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Toc {
+    dialect: Dialect,
+    starts: Vec<Timestamp>,
+}
+
+impl Toc {
+    pub(super) fn new(dialect: Dialect) -> Self {
+        Toc {
+            dialect,
+            starts: vec![],
+        }
+    }
+
+    /// Adds the next segment, starting at the given time of the interview.
+    pub(super) fn add(&mut self, start: Timestamp) {
+        self.starts.push(start);
+    }
+
+    /// The paragraphs of the table of contents, one per segment with a
+    /// link to its bookmark and its start, and an empty one after them.
+    pub(super) fn paragraphs(&self) -> Result<Vec<u8>> {
+        let mut paragraphs = vec![];
+        self.dialect.write_paragraph(&mut paragraphs, "Contents")?;
+        for (index, start) in self.starts.iter().enumerate() {
+            let entry = format!(
+                "{{\\field{{\\*\\fldinst HYPERLINK \\\\l \"{}\"}}{{\\fldrslt Segment {}}}}} {}",
+                bookmark_name(index),
+                index + 1,
+                start
+            );
+            self.dialect.write_paragraph(&mut paragraphs, &entry)?;
+        }
+        self.dialect.write_paragraph(&mut paragraphs, "")?;
+        Ok(paragraphs)
+    }
+}
+
+/// RTF of an empty bookmark for the start of the segment with the given
+/// zero-based index.
+pub(super) fn bookmark(index: usize) -> String {
+    let name = bookmark_name(index);
+    format!("{{\\*\\bkmkstart {0}}}{{\\*\\bkmkend {0}}}", name)
+}
+
+fn bookmark_name(index: usize) -> String {
+    format!("segment{}", index + 1)
+}
+
+/// Passes writes through until told to hold them back, and writes what
+/// was held back when released.
+pub(super) struct HoldBack<W> {
+    inner: W,
+    held: Option<Vec<u8>>,
+}
+
+impl<W: Write> HoldBack<W> {
+    pub(super) fn new(inner: W) -> Self {
+        HoldBack { inner, held: None }
+    }
+
+    /// Holds back everything written from now on.
+    pub(super) fn hold(&mut self) {
+        self.held.get_or_insert_with(Vec::new);
+    }
+
+    /// Writes the given bytes and then what was held back, and passes
+    /// writes through again.
+    pub(super) fn release(&mut self, before: &[u8]) -> Result<()> {
+        if let Some(held) = self.held.take() {
+            self.inner.write_all(before)?;
+            self.inner.write_all(&held)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    pub(super) fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for HoldBack<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        match &mut self.held {
+            Some(held) => held.write(buf),
+            None => self.inner.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn contents_come_before_what_was_held_back() {
+        let mut toc = Toc::new(Dialect::Group);
+        toc.add(Timestamp::zero());
+        toc.add(Timestamp::from_tenths(3000).unwrap());
+        let mut to = HoldBack::new(vec![]);
+        write!(&mut to, "preamble\r\n").unwrap();
+        to.hold();
+        write!(&mut to, "{}\r\nbody\r\n", bookmark(1)).unwrap();
+        to.release(&toc.paragraphs().unwrap()).unwrap();
+        write!(&mut to, "}}").unwrap();
+
+        let written = String::from_utf8(to.into_inner()).unwrap();
+        let lines: Vec<&str> = written.lines().collect();
+        assert_eq!(lines[0], "preamble");
+        assert!(lines[1].contains("Contents"));
+        assert!(lines[3].contains(
+            "{\\field{\\*\\fldinst HYPERLINK \\\\l \"segment2\"}{\\fldrslt Segment 2}} #00:05:00-0#"
+        ));
+        assert_eq!(lines[5], "{\\*\\bkmkstart segment2}{\\*\\bkmkend segment2}");
+        assert_eq!(lines[6..], ["body", "}"]);
+    }
+}
@@ -0,0 +1,102 @@
+//! Notes after the last utterance of a transcript, like "Aufnahme endet"
+//! or field notes, which end up in the middle of a merged transcript
+//! unless they are moved to its end or dropped.
+use super::lines::{Dialect, Line, Lines};
+use super::rtf::plain_text;
+use crate::timestamp::Timestamp;
+use crate::transcript::Transcript;
+
+/// What happens to the trailers of merged transcripts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Trailers {
+    /// Trailers stay where they are, after the last utterance of their
+    /// segment.
+    #[default]
+    Keep,
+    /// Trailers of all segments are collected at the end of the merged
+    /// transcript.
+    Move,
+    /// Trailers are left out.
+    Strip,
+}
+
+impl Transcript {
+    /// Lines of the content after the last utterance or timestamp, if at
+    /// least one of them has text, e.g. `Aufnahme endet`. Blank lines
+    /// alone are not a trailer.
+    pub fn trailer(&self) -> &str {
+        let content = self.content();
+        let mut speech_end = 0;
+        let mut offset = 0;
+        for raw in content.split_inclusive('\n') {
+            offset += raw.len();
+            if is_speech(self.dialect(), raw.trim_end_matches(['\r', '\n'])) {
+                speech_end = offset;
+            }
+        }
+        let trailer = &content[speech_end..];
+        if has_notes(self.dialect(), trailer) {
+            trailer
+        } else {
+            ""
+        }
+    }
+
+    /// The transcript without its trailer.
+    pub fn without_trailer(&self) -> Transcript {
+        let content = self.content();
+        let speech_end = content.len() - self.trailer().len();
+        self.with_parts(
+            self.preamble().to_string(),
+            content[..speech_end].to_string(),
+        )
+    }
+}
+
+/// Checks if the line is an utterance or has timestamps.
+pub(super) fn is_speech(dialect: Dialect, line: &str) -> bool {
+    matches!(
+        Lines::parse_line_in_dialect(dialect, line),
+        Line::Utterance(_)
+    ) || !Timestamp::extract_timestamps(line.as_bytes()).is_empty()
+}
+
+/// Checks if any of the lines is a paragraph with text rather than blank.
+pub(super) fn has_notes(dialect: Dialect, lines: &str) -> bool {
+    lines
+        .lines()
+        .any(|line| match Lines::parse_line_in_dialect(dialect, line) {
+            Line::Paragraph(_) | Line::Formatted(_) => {
+                plain_text(line).chars().any(char::is_alphabetic)
+            }
+            _ => false,
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::convert::TryFrom;
+
+    const NOTE: &str = "{\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 Aufnahme endet.\\par}\r\n";
+    const BLANK: &str = "{\\f0 \\fs24 \\ul0 \\b0 \\i0 \\cf0 \\par}\r\n";
+
+    fn with_appended(lines: &str) -> Transcript {
+        let original = std::fs::read_to_string("testdata/interview-01.rtf").unwrap();
+        let end = original.rfind("\r\n}").unwrap();
+        Transcript::try_from(format!("{}{}{}", &original[..end], lines, &original[end..])).unwrap()
+    }
+
+    #[test]
+    fn notes_after_the_last_utterance_are_a_trailer() {
+        let transcript = with_appended(&format!("{}{}{}", BLANK, NOTE, BLANK));
+        assert_eq!(transcript.trailer(), format!("{}{}{}", BLANK, NOTE, BLANK));
+        let without = transcript.without_trailer();
+        assert_eq!(without.trailer(), "");
+        assert!(without.content().ends_with("#00:04:50-3#}\\par}\r\n"));
+
+        let original = Transcript::from_file("testdata/interview-01.rtf").unwrap();
+        assert_eq!(original.trailer(), "");
+        assert_eq!(with_appended(BLANK).trailer(), "");
+    }
+}
@@ -1,19 +1,52 @@
-use super::lines::Lines;
+use super::line_ending::{LineEnding, LineEndingWriter};
+use super::lines::{Dialect, Lines};
 
 use std::convert::{TryFrom, TryInto};
 use std::fmt::{self, Display, Formatter};
-use std::fs::read_to_string;
+use std::io::Write;
 use std::path::Path;
 
-use crate::timestamp::Timestamp;
-use crate::transcript::{Error, Result};
+use crate::archive::read_to_string;
+use crate::timestamp::{detect_dialect, Timestamp, TimestampDialect, TimestampSyntax, F4};
+use crate::transcript::{strip_revisions, Error, Result};
 
 const PREAMBLE_END_PATTERN: &str = "\\jexpand\r\n";
+/// Groups that belong to the RTF header rather than to the content.
+const HEADER_GROUPS: [&str; 8] = [
+    "{\\fonttbl",
+    "{\\colortbl",
+    "{\\stylesheet",
+    "{\\info",
+    "{\\*",
+    "{\\listtable",
+    "{\\listoverridetable",
+    "{\\rsidtbl",
+];
 const EPILOGUE: &str = "\r\n}";
 
+/// How a transcript file is read before it is parsed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LoadOptions {
+    /// Lenient syntax rewrites timestamps that are only recognized with it
+    /// into canonical ones.
+    pub syntax: TimestampSyntax,
+    /// Removes deleted text, revision marks and comments left by word
+    /// processors, see [`strip_revisions`].
+    pub strip_revisions: bool,
+    /// Time of day the recording started at, to rewrite clock times like
+    /// `14:32:05` into timestamps, see [`Timestamp::rebase_clock_times`].
+    pub clock_start: Option<Timestamp>,
+    /// Dialect of the timestamps, which are converted into F4 timestamps,
+    /// or `None` to detect it, see [`detect_dialect`].
+    pub timestamps: Option<&'static dyn TimestampDialect>,
+}
+
 #[derive(Clone)]
 pub struct Transcript {
     // TODO just remember the offsets and read on demand for merging
+    /// Byte order mark and whitespace that some editors put before
+    /// `{\rtf1`, kept apart from the preamble and written back as-is.
+    prefix: String,
     /// The preamble with RTF setup before the actual interview.
     preamble: String,
     /// The part of the transcript files that contains the actual
@@ -24,19 +57,73 @@ pub struct Transcript {
     /// Suspected length of the interview segment, based on
     /// rounding up the last encountered timestamp.
     interview_end_time: Timestamp,
+    /// Correction of the shift of the segment when merging, in tenths of
+    /// a second, e.g. when its recording started late.
+    start_offset: i64,
+    /// How paragraphs are written in the content.
+    dialect: Dialect,
+    /// Line ending of the file, preamble and content are normalized
+    /// to `\r\n` regardless.
+    line_ending: LineEnding,
 }
 
 impl Transcript {
+    /// Loads the transcript, converting timestamps of other dialects than
+    /// F4 if it has no F4 timestamps.
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Transcript> {
-        read_to_string(path)?.try_into()
+        Self::load(path, LoadOptions::default())
+    }
+
+    /// Loads the transcript, rewriting timestamps that are only recognized
+    /// with the lenient syntax into canonical ones if `syntax` is lenient.
+    pub fn from_file_with<P: AsRef<Path>>(path: P, syntax: TimestampSyntax) -> Result<Transcript> {
+        Self::load(
+            path,
+            LoadOptions {
+                syntax,
+                ..LoadOptions::default()
+            },
+        )
+    }
+
+    /// Loads the transcript, preparing the file as given before parsing it.
+    pub fn load<P: AsRef<Path>>(path: P, options: LoadOptions) -> Result<Transcript> {
+        let mut buf = read_to_string(path.as_ref())?;
+        if options.strip_revisions {
+            buf = strip_revisions(&buf);
+        }
+        if let Some(clock_start) = options.clock_start {
+            buf = Timestamp::rebase_clock_times(&buf, clock_start);
+        }
+        let dialect = options
+            .timestamps
+            .or_else(|| detect_dialect(buf.as_bytes()))
+            .unwrap_or(&F4);
+        if dialect.name() != F4.name() {
+            buf = Timestamp::convert_timestamps(&buf, dialect);
+        }
+        match options.syntax {
+            TimestampSyntax::Strict => buf.try_into(),
+            TimestampSyntax::Lenient => Timestamp::canonicalize_timestamps(&buf).try_into(),
+        }
+    }
+
+    /// Byte order mark and whitespace before the RTF, usually empty.
+    pub fn prefix(&self) -> &str {
+        &self.prefix
     }
 
     /// The part of the transcript file before the main content,
-    /// including the RTF header.
+    /// including the RTF header, but not the prefix.
     pub fn preamble(&self) -> &str {
         &self.preamble
     }
 
+    /// Number of the line in the file that the content starts on.
+    pub fn first_content_line(&self) -> usize {
+        self.prefix.matches('\n').count() + self.preamble.matches('\n').count() + 1
+    }
+
     pub fn content(&self) -> &str {
         &self.content
     }
@@ -47,52 +134,194 @@ impl Transcript {
         EPILOGUE
     }
 
-    pub fn lines(&self) -> Lines {
+    pub fn lines(&self) -> Lines<'_> {
         Lines::new(self)
     }
 
     pub fn interview_end_time(&self) -> Timestamp {
         self.interview_end_time
     }
+
+    /// The transcript with a known end time, e.g. the exact length of its
+    /// audio segment, which the next segment is shifted by when merging.
+    pub fn with_interview_end_time(mut self, end_time: Timestamp) -> Transcript {
+        self.interview_end_time = end_time;
+        self
+    }
+
+    /// Correction added to the shift of the transcript when merging, in
+    /// tenths of a second, zero unless set with [`Self::with_start_offset`].
+    pub fn start_offset(&self) -> i64 {
+        self.start_offset
+    }
+
+    /// The transcript starting later, or earlier for negative offsets, than
+    /// the end of the segment before it when merging, which also moves the
+    /// segments after it.
+    pub fn with_start_offset(mut self, tenths: i64) -> Transcript {
+        self.start_offset = tenths;
+        self
+    }
+
+    /// The last timestamp in the content, before rounding it up
+    /// to the suspected end time.
+    pub fn last_timestamp(&self) -> Timestamp {
+        Timestamp::last_timestamp(&self.content).unwrap_or_default()
+    }
+
+    /// Checks for timestamps like `#00.01.23-4#` that are only recognized
+    /// with the lenient syntax and hence would not be adjusted.
+    pub fn has_lenient_timestamps(&self) -> bool {
+        let content = self.content.as_bytes();
+        Timestamp::extract_timestamps_with(content, TimestampSyntax::Lenient).len()
+            > Timestamp::extract_timestamps(content).len()
+    }
+
+    /// How paragraphs are written in this transcript, detected
+    /// when loading it.
+    pub fn dialect(&self) -> Dialect {
+        self.dialect
+    }
+
+    /// Line ending of the file the transcript was loaded from.
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    /// Writes the transcript with every timestamp shifted, line by line
+    /// like merging writes segments. Without a shift, this writes the
+    /// transcript as it was loaded.
+    pub fn write_shifted<W: Write>(&self, to: W, shift: Timestamp) -> Result<()> {
+        let mut to = LineEndingWriter::new(to, self.line_ending);
+        write!(&mut to, "{}{}", self.prefix, self.preamble)?;
+        for line in self.lines() {
+            line.write_adjusted(&mut to, shift)?;
+        }
+        write!(&mut to, "{}", EPILOGUE)?;
+        to.flush()?;
+        Ok(())
+    }
+
+    /// The same transcript with an edited preamble and content, which
+    /// must not change its timestamps or dialect.
+    pub(super) fn with_parts(&self, preamble: String, content: String) -> Transcript {
+        Transcript {
+            preamble,
+            content,
+            ..self.clone()
+        }
+    }
 }
 
 impl Display for Transcript {
+    /// Writes the transcript with its original line ending.
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{preamble}{content}{epilogue}",
+        let transcript = format!(
+            "{prefix}{preamble}{content}{epilogue}",
+            prefix = self.prefix(),
             preamble = self.preamble(),
             content = self.content(),
             epilogue = EPILOGUE
-        )
+        );
+        match self.line_ending {
+            LineEnding::CrLf => f.write_str(&transcript),
+            LineEnding::Lf => f.write_str(&transcript.replace("\r\n", "\n")),
+        }
     }
 }
 
 impl TryFrom<String> for Transcript {
     type Error = Error;
     fn try_from(buf: String) -> Result<Transcript> {
-        let content_start = find_content_start(&buf)?;
-        let content_end = find_content_end(&buf)?;
+        Transcript::parse(buf, true)
+    }
+}
+
+impl Transcript {
+    /// Parses a transcript that may not have any timestamps yet, which
+    /// then ends at zero, e.g. to insert timestamps into it.
+    pub fn parse_untimed(buf: String) -> Result<Transcript> {
+        Transcript::parse(buf, false)
+    }
+
+    fn parse(buf: String, require_timestamps: bool) -> Result<Transcript> {
+        let line_ending = LineEnding::detect(&buf);
+        let buf = LineEnding::normalize(buf);
+        let (prefix, buf) = buf.split_at(prefix_len(&buf));
+        let content_start = find_content_start(buf)?;
+        let content_end = find_content_end(buf)?;
         let preamble = String::from(&buf[0..content_start]);
         let content = String::from(&buf[content_start..content_end]);
-        let interview_end_time = Timestamp::last_timestamp(&buf)
-            .ok_or_else(Error::no_timestamps_found)?
-            .round_up();
+        let dialect = Dialect::detect(&content);
+        let interview_end_time = match Timestamp::last_timestamp(buf) {
+            Some(last) => last.round_up(),
+            None if require_timestamps => return Err(Error::no_timestamps_found()),
+            None => Timestamp::zero(),
+        };
         Ok(Transcript {
+            prefix: prefix.to_string(),
             preamble,
             content,
             interview_end_time,
+            start_offset: 0,
+            dialect,
+            line_ending,
         })
     }
 }
 
+/// Length of the byte order mark and whitespace before the first `{`, if
+/// there is nothing else before it.
+fn prefix_len(transcript: &str) -> usize {
+    transcript
+        .find(|c: char| c != '\u{feff}' && !c.is_whitespace())
+        .filter(|&start| transcript[start..].starts_with('{'))
+        .unwrap_or(0)
+}
+
+/// Finds where the content starts, directly after the `\jexpand` line F4
+/// ends its preamble with or, for transcripts by f5transkript or resaved
+/// with other software, where the first paragraph starts.
 fn find_content_start(transcript: &str) -> Result<usize> {
     transcript
         .find(PREAMBLE_END_PATTERN)
         .map(|offset| offset + PREAMBLE_END_PATTERN.len())
+        .or_else(|| find_first_paragraph(transcript))
         .ok_or_else(Error::malformed_preamble)
 }
 
+/// Start of the first paragraph on the top level of the document, that is
+/// a `\pard` or a line starting with a group that is not a header group
+/// like the font table.
+fn find_first_paragraph(transcript: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut escaped = false;
+    let mut line_start = false;
+    for (at, c) in transcript.char_indices() {
+        if depth == 1 && !escaped {
+            let rest = &transcript[at..];
+            let is_pard = rest
+                .strip_prefix("\\pard")
+                .map(|after| !after.starts_with(|c: char| c.is_ascii_alphabetic()))
+                .unwrap_or(false);
+            let is_paragraph_group =
+                line_start && c == '{' && !HEADER_GROUPS.iter().any(|g| rest.starts_with(g));
+            if is_pard || is_paragraph_group {
+                return Some(at);
+            }
+        }
+        line_start = c == '\n';
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            _ => (),
+        }
+    }
+    None
+}
+
 fn find_content_end(transcript: &str) -> Result<usize> {
     if transcript.ends_with(EPILOGUE) {
         Ok(transcript.len() - EPILOGUE.len())
@@ -104,6 +333,7 @@ fn find_content_end(transcript: &str) -> Result<usize> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::fs::read_to_string;
 
     #[test]
     fn reference_transcript_01_is_wellformed() {
@@ -111,6 +341,62 @@ mod test {
         assert!(Transcript::from_file(path).is_ok());
     }
 
+    #[test]
+    fn lenient_timestamps_are_canonicalized() {
+        let original = read_to_string("testdata/interview-01.rtf").unwrap();
+        let lenient = original.replace("#00:04:50-3#", "#00.04.50,3#");
+        let transcript = Transcript::try_from(lenient.clone()).unwrap();
+        assert!(transcript.has_lenient_timestamps());
+
+        let canonical = Transcript::try_from(Timestamp::canonicalize_timestamps(&lenient)).unwrap();
+        assert!(!canonical.has_lenient_timestamps());
+        assert_eq!(canonical.to_string(), original);
+    }
+
+    #[test]
+    fn transcripts_with_lf_line_endings_keep_them() {
+        let original = read_to_string("testdata/interview-01.rtf").unwrap();
+        let lf = original.replace("\r\n", "\n");
+        let transcript = Transcript::try_from(lf.clone()).unwrap();
+        assert_eq!(transcript.line_ending(), LineEnding::Lf);
+        assert_eq!(
+            transcript.lines().count(),
+            Transcript::try_from(original).unwrap().lines().count()
+        );
+        assert_eq!(transcript.to_string(), lf);
+    }
+
+    #[test]
+    fn byte_order_mark_and_whitespace_are_kept_apart() {
+        let original = read_to_string("testdata/interview-01.rtf").unwrap();
+        let prefixed = format!("\u{feff} \r\n{}", original);
+        let transcript = Transcript::try_from(prefixed.clone()).unwrap();
+        assert_eq!(transcript.prefix(), "\u{feff} \r\n");
+        assert!(transcript.preamble().starts_with("{\\rtf1"));
+        assert_eq!(transcript.first_content_line(), 8);
+        assert_eq!(transcript.to_string(), prefixed);
+    }
+
+    #[test]
+    fn content_start_without_jexpand() {
+        const PREAMBLE: &str = "{\\rtf1\\ansi\\deff0\r\n{\\fonttbl{\\f0 Arial;}}\r\n\
+                                {\\*\\generator f5transkript}\\paperw11906\r\n";
+        const CONTENT: &str = "{\\f0 \\fs24 I: Hallo #00:00:01-0#\\par}\r\n";
+        let transcript = Transcript::try_from(format!("{}{}\r\n}}", PREAMBLE, CONTENT)).unwrap();
+        assert_eq!(transcript.preamble(), PREAMBLE);
+        assert_eq!(transcript.content(), CONTENT);
+
+        let pard = format!("{}\\pard\\plain I: Hallo #00:00:01-0#\\par\r\n}}", PREAMBLE);
+        let transcript = Transcript::try_from(pard).unwrap();
+        assert_eq!(transcript.preamble(), PREAMBLE);
+
+        let f4 = read_to_string("testdata/interview-01.rtf").unwrap();
+        assert_eq!(
+            find_first_paragraph(&f4).unwrap(),
+            find_content_start(&f4).unwrap()
+        );
+    }
+
     #[test]
     fn reference_transcript_02_is_wellformed() {
         let path = &Path::new("testdata/interview-02.rtf");
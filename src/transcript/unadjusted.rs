@@ -0,0 +1,140 @@
+//! Detection of timestamps that were left unadjusted in merged output.
+//!
+//! After merging, every timestamp of a segment is at least the shift of
+//! that segment. A smaller one was missed when adjusting, usually because
+//! the RTF parser did not recognize its surroundings.
+use std::io::{Result, Write};
+
+use crate::timestamp::{Timestamp, TimestampSyntax};
+
+/// A timestamp in the merged output that is earlier than the shift of
+/// the segment it belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnadjustedTimestamp {
+    /// Line in the merged output, starting at one.
+    pub line: usize,
+    /// Byte position of the timestamp in the line, starting at one.
+    pub column: usize,
+    pub timestamp: Timestamp,
+}
+
+/// Passes writes through and checks every completed line for timestamps
+/// below the shift of the segment that is currently written.
+pub(super) struct UnadjustedCheck<W> {
+    inner: W,
+    /// Unfinished line written so far.
+    line: Vec<u8>,
+    /// Number of completed lines.
+    line_number: usize,
+    segment: usize,
+    shift: Timestamp,
+    found: Vec<(usize, UnadjustedTimestamp)>,
+}
+
+impl<W: Write> UnadjustedCheck<W> {
+    #[cfg(test)]
+    pub(super) fn new(inner: W) -> Self {
+        Self::resume(inner, 0)
+    }
+
+    /// Continues checking output that already has the given number of
+    /// completed lines.
+    pub(super) fn resume(inner: W, lines: usize) -> Self {
+        UnadjustedCheck {
+            inner,
+            line: vec![],
+            line_number: lines,
+            segment: 0,
+            shift: Timestamp::zero(),
+            found: vec![],
+        }
+    }
+
+    /// Number of completed lines.
+    pub(super) fn lines(&self) -> usize {
+        self.line_number
+    }
+
+    pub(super) fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Lines completed from now on belong to the segment with the given
+    /// index and shift.
+    pub(super) fn enter_segment(&mut self, segment: usize, shift: Timestamp) {
+        self.segment = segment;
+        self.shift = shift;
+    }
+
+    /// Checks the last unfinished line, if any, and returns the inner
+    /// writer together with the found timestamps and the indexes of
+    /// their segments.
+    pub(super) fn finish(mut self) -> (W, Vec<(usize, UnadjustedTimestamp)>) {
+        self.check_line();
+        (self.inner, self.found)
+    }
+
+    fn check_line(&mut self) {
+        self.line_number += 1;
+        for (offset, timestamp) in
+            Timestamp::timestamps_in_with(&self.line, TimestampSyntax::Strict)
+        {
+            if timestamp < self.shift {
+                let unadjusted = UnadjustedTimestamp {
+                    line: self.line_number,
+                    column: offset + 1,
+                    timestamp,
+                };
+                self.found.push((self.segment, unadjusted));
+            }
+        }
+        self.line.clear();
+    }
+}
+
+impl<W: Write> Write for UnadjustedCheck<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let written = self.inner.write(buf)?;
+        let mut rest = &buf[..written];
+        while let Some(newline) = rest.iter().position(|&b| b == b'\n') {
+            self.line.extend_from_slice(&rest[..=newline]);
+            self.check_line();
+            rest = &rest[newline + 1..];
+        }
+        self.line.extend_from_slice(rest);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn timestamps_below_the_shift_are_found_with_their_position() {
+        let five_minutes = Timestamp::parse("#00:05:00-0#").unwrap();
+        let mut check = UnadjustedCheck::new(vec![]);
+        write!(&mut check, "early #00:01:00-0#\r\n").unwrap();
+        check.enter_segment(1, five_minutes);
+        write!(&mut check, "fine #00:06:00-0#\r\nmissed ").unwrap();
+        write!(&mut check, "#00:01:00-0#").unwrap();
+
+        let (written, found) = check.finish();
+        assert_eq!(written.len(), 58);
+        assert_eq!(
+            found,
+            vec![(
+                1,
+                UnadjustedTimestamp {
+                    line: 3,
+                    column: 8,
+                    timestamp: Timestamp::parse("#00:01:00-0#").unwrap(),
+                }
+            )]
+        );
+    }
+}
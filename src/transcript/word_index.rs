@@ -0,0 +1,161 @@
+//! Maps the words of the plain text export of a merge back to the audio,
+//! for looking up quotes that papers cite by their position in the text.
+//!
+//! Every word gets the timestamp before it, which is the end of the
+//! utterance or part of it before the word, since F4 timestamps mark
+//! where speech ends, and the segment it is from.
+use std::io::sink;
+
+use super::formats::{write_lines, Text};
+use super::merge::write_recorded_merge;
+use crate::timestamp::Timestamp;
+use crate::transcript::{MergeOptions, Result, Transcript};
+
+/// A word of the plain text export of a merge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedWord {
+    /// Offset of the first character of the word in the text, in
+    /// characters rather than bytes.
+    pub offset: usize,
+    pub word: String,
+    /// The nearest timestamp before the word, adjusted, or the start of
+    /// its utterance if there is none.
+    pub timestamp: Timestamp,
+    /// Index of the segment the word is from, starting at zero.
+    pub segment: usize,
+}
+
+/// The plain text export of the merged transcripts, like a merge into a
+/// `.txt` file, and an index of its words.
+///
+/// Timestamps are not words, and punctuation around words is left out.
+pub fn index_words<I>(transcripts: I, options: &MergeOptions) -> Result<(String, Vec<IndexedWord>)>
+where
+    I: IntoIterator<Item = Transcript>,
+{
+    let (_, records) = write_recorded_merge(sink(), transcripts, options)?;
+    let mut text = String::new();
+    let mut text_len = 0;
+    let mut words = vec![];
+    for record in &records {
+        let mut rendered = vec![];
+        write_lines(&mut rendered, std::slice::from_ref(record), &Text)?;
+        let rendered = String::from_utf8_lossy(&rendered);
+        // the speech comes last, before the line breaks
+        let speech_start = rendered.rfind(&record.speech).unwrap_or(rendered.len());
+        let mut offset = text_len + rendered[..speech_start].chars().count();
+
+        let mut timestamps = Timestamp::timestamps_in(&record.speech).peekable();
+        let mut timestamp = record.start;
+        let mut timestamp_end = 0;
+        let mut word = String::new();
+        let mut word_offset = offset;
+        for (at, c) in record.speech.char_indices() {
+            if at < timestamp_end {
+                offset += 1;
+                continue;
+            }
+            match timestamps.peek() {
+                Some(&(found_at, found)) if found_at == at => {
+                    push_word(
+                        &mut words,
+                        &mut word,
+                        word_offset,
+                        timestamp,
+                        record.segment,
+                    );
+                    timestamps.next();
+                    timestamp = found;
+                    // skip the rest of the timestamp up to its closing `#`
+                    timestamp_end = record.speech[at + 1..]
+                        .find('#')
+                        .map_or(record.speech.len(), |end| at + end + 2);
+                }
+                _ if c.is_whitespace() => {
+                    push_word(
+                        &mut words,
+                        &mut word,
+                        word_offset,
+                        timestamp,
+                        record.segment,
+                    );
+                }
+                _ => {
+                    if word.is_empty() {
+                        word_offset = offset;
+                    }
+                    word.push(c);
+                }
+            }
+            offset += 1;
+        }
+        push_word(
+            &mut words,
+            &mut word,
+            word_offset,
+            timestamp,
+            record.segment,
+        );
+
+        text_len += rendered.chars().count();
+        text.push_str(&rendered);
+    }
+    Ok((text, words))
+}
+
+/// Adds the word without the punctuation around it, if anything is left,
+/// and starts the next one.
+fn push_word(
+    words: &mut Vec<IndexedWord>,
+    word: &mut String,
+    offset: usize,
+    timestamp: Timestamp,
+    segment: usize,
+) {
+    let raw = std::mem::take(word);
+    let trimmed = raw.trim_start_matches(|c: char| !c.is_alphanumeric());
+    let leading = raw.chars().count() - trimmed.chars().count();
+    let trimmed = trimmed.trim_end_matches(|c: char| !c.is_alphanumeric());
+    if !trimmed.is_empty() {
+        words.push(IndexedWord {
+            offset: offset + leading,
+            word: trimmed.to_string(),
+            timestamp,
+            segment,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn words_point_to_the_timestamp_before_them_and_their_segment() {
+        let paths = ["testdata/interview-01.rtf", "testdata/interview-02.rtf"];
+        let transcripts = paths
+            .iter()
+            .map(|path| Transcript::from_file(path).unwrap());
+        let (text, words) = index_words(transcripts, &MergeOptions::default()).unwrap();
+
+        let chars: Vec<char> = text.chars().collect();
+        for word in &words {
+            let at: String = chars[word.offset..word.offset + word.word.chars().count()]
+                .iter()
+                .collect();
+            assert_eq!(at, word.word);
+        }
+        let word = |word: &str| words.iter().find(|w| w.word == word).unwrap();
+        assert_eq!(word("Was").timestamp, Timestamp::zero());
+        assert_eq!(word("Was").segment, 0);
+        // after #00:00:27-8#? in the same utterance
+        assert_eq!(
+            word("daheim").timestamp,
+            Timestamp::from_tenths(278).unwrap()
+        );
+        assert!(words.iter().all(|w| !w.word.contains('#')));
+        let last = words.last().unwrap();
+        assert_eq!(last.segment, 1);
+        assert!(last.timestamp > Timestamp::from_tenths(3000).unwrap());
+    }
+}
@@ -0,0 +1,87 @@
+//! Property tests checking that transcripts are written exactly as they
+//! were read when nothing about them changes.
+use std::convert::TryFrom;
+
+use f4tapir::generate::{Fixture, Quirk};
+use f4tapir::timestamp::Timestamp;
+use f4tapir::transcript::json::{Block, Document, Metadata, Utterance, FORMAT, VERSION};
+use f4tapir::transcript::{write_merged_transcript, MergeOptions, Transcript};
+use proptest::prelude::*;
+
+/// Fixtures with any combination of quirks.
+fn fixtures() -> impl Strategy<Value = Fixture> {
+    (
+        1..5_usize,
+        1..1200_u64,
+        1..120_u64,
+        proptest::sample::subsequence(Quirk::ALL.to_vec(), 0..=Quirk::ALL.len()),
+        any::<u64>(),
+    )
+        .prop_map(|(speakers, seconds, every, quirks, seed)| Fixture {
+            speakers,
+            duration: Timestamp::from_tenths(seconds * 10).unwrap(),
+            timestamp_every: every,
+            quirks,
+            seed,
+        })
+}
+
+/// Documents with arbitrary text, including characters that need escaping.
+fn documents() -> impl Strategy<Value = Document> {
+    let utterance =
+        ("[A-Z][0-9]?", "[^\\r\\n]{0,40}", 0..36000_u64).prop_map(|(speaker, speech, tenths)| {
+            let timestamp = Timestamp::from_tenths(tenths).unwrap();
+            let speech = format!("{} {}", speech, timestamp);
+            Block::Utterance(Utterance::new(&speaker, speech.trim()))
+        });
+    let note = "[^\\r\\n#:]{1,40}".prop_filter_map("blank note", |text| {
+        let text = text.trim();
+        Some(Block::Note {
+            text: text.to_string(),
+        })
+        .filter(|_| !text.is_empty())
+    });
+    // transcripts need at least one timestamp
+    let blocks = proptest::collection::vec(prop_oneof![4 => utterance.clone(), 1 => note], 0..20);
+    (blocks, utterance).prop_map(|(mut blocks, utterance)| {
+        blocks.push(utterance);
+        Document {
+            format: FORMAT.to_string(),
+            version: VERSION,
+            metadata: Metadata::default(),
+            blocks,
+        }
+    })
+}
+
+fn unshifted(transcript: &Transcript) -> String {
+    let mut written = vec![];
+    transcript
+        .write_shifted(&mut written, Timestamp::zero())
+        .unwrap();
+    String::from_utf8(written).unwrap()
+}
+
+proptest! {
+    #[test]
+    fn generated_transcripts_are_written_unchanged(fixture in fixtures()) {
+        let source = fixture.segment(0);
+        // all timestamps may be lenient ones, which are not loaded
+        let transcript = Transcript::try_from(source.clone());
+        prop_assume!(transcript.is_ok());
+        let transcript = transcript.unwrap();
+        prop_assert_eq!(unshifted(&transcript), source.clone());
+
+        let mut merged = vec![];
+        write_merged_transcript(&mut merged, vec![transcript], &MergeOptions::default()).unwrap();
+        prop_assert_eq!(String::from_utf8(merged).unwrap(), source);
+    }
+
+    #[test]
+    fn built_transcripts_are_written_unchanged(document in documents()) {
+        let source = document.to_rtf();
+        let transcript = Transcript::try_from(source.clone()).unwrap();
+        prop_assert_eq!(unshifted(&transcript), source);
+        prop_assert_eq!(Document::of(&transcript).blocks, document.blocks);
+    }
+}